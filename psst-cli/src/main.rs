@@ -10,6 +10,7 @@ use psst_core::{
     item_id::{ItemId, ItemIdType},
     player::{item::PlaybackItem, PlaybackConfig, Player, PlayerCommand, PlayerEvent},
     session::{SessionConfig, SessionService},
+    util::NetTimeouts,
 };
 use std::{env, io, io::BufRead, path::PathBuf, thread};
 
@@ -27,13 +28,14 @@ fn main() {
     let session = SessionService::with_config(SessionConfig {
         login_creds,
         proxy_url: None,
+        timeouts: NetTimeouts::default(),
     });
 
     start(track_id, session).unwrap();
 }
 
 fn start(track_id: &str, session: SessionService) -> Result<(), Error> {
-    let cdn = Cdn::new(session.clone(), None)?;
+    let cdn = Cdn::new(session.clone(), None, NetTimeouts::default())?;
     let cache = Cache::new(PathBuf::from("cache"))?;
     let item_id = ItemId::from_base62(track_id, ItemIdType::Track).unwrap();
     play_item(
@@ -65,6 +67,7 @@ fn play_item(
             .send(PlayerEvent::Command(PlayerCommand::LoadQueue {
                 items: vec![item, item, item],
                 position: 0,
+                shuffle_weights: None,
             }))
             .unwrap();
 
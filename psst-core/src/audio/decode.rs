@@ -105,6 +105,24 @@ impl AudioDecoder {
     /// Read a next packet of audio from this decoder.  Returns `None` in case
     /// of EOF or internal error.
     pub fn read_packet<S>(&mut self, samples: &mut SampleBuffer<S>) -> Option<TimeStamp>
+    where
+        S: ConvertibleSample,
+    {
+        match self.try_read_packet(samples) {
+            Ok(ts) => ts,
+            Err(err) => {
+                log::error!("failed to read packet: {}", err);
+                None
+            }
+        }
+    }
+
+    /// Like `read_packet`, but tells EOF, `Ok(None)`, apart from the errors
+    /// we cannot recover from.  Packets failing to decode are skipped.
+    pub fn try_read_packet<S>(
+        &mut self,
+        samples: &mut SampleBuffer<S>,
+    ) -> Result<Option<TimeStamp>, Error>
     where
         S: ConvertibleSample,
     {
@@ -113,11 +131,11 @@ impl AudioDecoder {
             let packet = match self.format.next_packet() {
                 Ok(packet) => packet,
                 Err(SymphoniaError::IoError(io)) if io.kind() == io::ErrorKind::UnexpectedEof => {
-                    return None; // End of this stream.
+                    return Ok(None); // End of this stream.
                 }
                 Err(err) => {
-                    log::error!("format error: {}", err);
-                    return None; // We cannot recover from format errors, quit.
+                    // We cannot recover from format errors, quit.
+                    return Err(err.into());
                 }
             };
             while !self.format.metadata().is_latest() {
@@ -133,7 +151,7 @@ impl AudioDecoder {
                 Ok(decoded) => {
                     // Interleave the samples into the buffer.
                     samples.copy_interleaved_ref(decoded);
-                    return Some(packet.ts());
+                    return Ok(Some(packet.ts()));
                 }
                 Err(SymphoniaError::IoError(err)) => {
                     // The packet failed to decode due to an IO error, skip the packet.
@@ -146,8 +164,7 @@ impl AudioDecoder {
                     continue;
                 }
                 Err(err) => {
-                    log::error!("fatal decode error: {}", err);
-                    return None;
+                    return Err(err.into());
                 }
             };
         }
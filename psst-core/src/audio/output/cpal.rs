@@ -1,5 +1,8 @@
+use std::sync::Arc;
+
 use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
 use crossbeam_channel::{bounded, Receiver, Sender};
+use parking_lot::Mutex;
 
 use crate::{
     actor::{Act, Actor, ActorHandle},
@@ -133,14 +136,23 @@ impl AudioSink for CpalSink {
         self.pause();
     }
 
+    fn release(&self) {
+        self.send_to_stream(StreamMsg::Release);
+    }
+
     fn close(&self) {
         self.send_to_stream(StreamMsg::Close);
     }
 }
 
 struct Stream {
-    stream: cpal::Stream,
-    _device: cpal::Device,
+    /// `None` while released.
+    stream: Option<cpal::Stream>,
+    device: cpal::Device,
+    config: cpal::StreamConfig,
+    /// Shared with the stream, so the playing source and volume survive
+    /// releasing it.
+    callback: Arc<Mutex<StreamCallback>>,
 }
 
 impl Stream {
@@ -150,29 +162,64 @@ impl Stream {
         callback_recv: Receiver<CallbackMsg>,
         stream_send: Sender<StreamMsg>,
     ) -> Result<Self, Error> {
-        let mut callback = StreamCallback {
+        let callback = Arc::new(Mutex::new(StreamCallback {
             callback_recv,
             stream_send,
             source: Box::new(Empty),
             volume: 1.0, // We start with the full volume.
             state: CallbackState::Paused,
-        };
+        }));
+        let stream = Self::build(&device, &config, &callback)?;
 
+        Ok(Self {
+            stream: Some(stream),
+            device,
+            config,
+            callback,
+        })
+    }
+
+    fn build(
+        device: &cpal::Device,
+        config: &cpal::StreamConfig,
+        callback: &Arc<Mutex<StreamCallback>>,
+    ) -> Result<cpal::Stream, Error> {
         log::info!("opening output stream: {:?}", config);
+        let callback = callback.clone();
         let stream = device.build_output_stream(
-            &config,
+            config,
             move |output, _| {
-                callback.write_samples(output);
+                callback.lock().write_samples(output);
             },
             |err| {
                 log::error!("audio output error: {}", err);
             },
         )?;
+        Ok(stream)
+    }
 
-        Ok(Self {
-            _device: device,
-            stream,
-        })
+    /// Open the stream again after a release.  The default device might have
+    /// changed in the meantime, the sink keeps its sample rate and channel
+    /// count though, so if the new device doesn't support them, we stay with
+    /// the previous device.
+    fn reopen(&mut self) -> Result<(), Error> {
+        if let Some(device) = cpal::default_host().default_output_device() {
+            match Self::build(&device, &self.config, &self.callback) {
+                Ok(stream) => {
+                    if let Ok(name) = device.name() {
+                        log::info!("using audio device: {:?}", name);
+                    }
+                    self.stream = Some(stream);
+                    self.device = device;
+                    return Ok(());
+                }
+                Err(err) => {
+                    log::warn!("failed to open the default audio device: {}", err);
+                }
+            }
+        }
+        self.stream = Some(Self::build(&self.device, &self.config, &self.callback)?);
+        Ok(())
     }
 }
 
@@ -184,21 +231,40 @@ impl Actor for Stream {
         match msg {
             StreamMsg::Pause => {
                 log::debug!("pausing audio output stream");
-                if let Err(err) = self.stream.pause() {
-                    log::error!("failed to stop stream: {}", err);
+                if let Some(stream) = &self.stream {
+                    if let Err(err) = stream.pause() {
+                        log::error!("failed to stop stream: {}", err);
+                    }
                 }
                 Ok(Act::Continue)
             }
             StreamMsg::Resume => {
                 log::debug!("resuming audio output stream");
-                if let Err(err) = self.stream.play() {
-                    log::error!("failed to start stream: {}", err);
+                if self.stream.is_none() {
+                    if let Err(err) = self.reopen() {
+                        log::error!("failed to reopen stream: {}", err);
+                        return Ok(Act::Continue);
+                    }
+                }
+                if let Some(stream) = &self.stream {
+                    if let Err(err) = stream.play() {
+                        log::error!("failed to start stream: {}", err);
+                    }
+                }
+                Ok(Act::Continue)
+            }
+            StreamMsg::Release => {
+                log::debug!("releasing audio output stream");
+                if let Some(stream) = self.stream.take() {
+                    let _ = stream.pause();
                 }
                 Ok(Act::Continue)
             }
             StreamMsg::Close => {
                 log::debug!("closing audio output stream");
-                let _ = self.stream.pause();
+                if let Some(stream) = &self.stream {
+                    let _ = stream.pause();
+                }
                 Ok(Act::Shutdown)
             }
         }
@@ -208,6 +274,7 @@ impl Actor for Stream {
 enum StreamMsg {
     Pause,
     Resume,
+    Release,
     Close,
 }
 
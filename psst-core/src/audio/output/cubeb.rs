@@ -1,6 +1,7 @@
-use std::{env, ffi::CString, ops::Deref};
+use std::{env, ffi::CString, ops::Deref, sync::Arc};
 
 use crossbeam_channel::{bounded, Receiver, Sender};
+use parking_lot::Mutex;
 
 use crate::{
     actor::{Act, Actor, ActorHandle},
@@ -50,7 +51,12 @@ const STREAM_LATENCY: u32 = 0x1000;
 struct Stream {
     #[allow(unused)]
     ctx: cubeb::Context,
-    stream: cubeb::Stream<Frame>,
+    /// `None` while released.
+    stream: Option<cubeb::Stream<Frame>>,
+    /// Shared with the stream, so the playing source survives releasing it.
+    callback: Arc<Mutex<StreamCallback>>,
+    /// Volume to apply to a reopened stream.
+    volume: f32,
 }
 
 impl Stream {
@@ -67,13 +73,28 @@ impl Stream {
         let ctx_name = CString::new("Psst").ok();
         let ctx = cubeb::Context::init(ctx_name.as_deref(), backend_name.as_deref())?;
 
-        let mut callback = StreamCallback {
+        let callback = Arc::new(Mutex::new(StreamCallback {
             callback_recv,
             source: Box::new(Empty),
             state: CallbackState::Paused,
             buffer: vec![0.0; 1024 * 1024],
-        };
+        }));
+        let stream = Self::build(&ctx, &callback)?;
+
+        Ok(Self {
+            ctx,
+            stream: Some(stream),
+            callback,
+            volume: 1.0,
+        })
+    }
 
+    /// Open a stream on the default output device, which can be a different
+    /// one each time.
+    fn build(
+        ctx: &cubeb::Context,
+        callback: &Arc<Mutex<StreamCallback>>,
+    ) -> Result<cubeb::Stream<Frame>, Error> {
         let params = cubeb::StreamParamsBuilder::new()
             .format(cubeb::SampleFormat::Float32NE)
             .rate(SAMPLE_RATE)
@@ -81,27 +102,35 @@ impl Stream {
             .layout(cubeb::ChannelLayout::STEREO)
             .take();
 
+        let callback = callback.clone();
         let mut builder = cubeb::StreamBuilder::new();
         builder
             .name("Psst")
             .default_output(&params)
             .latency(STREAM_LATENCY)
             .data_callback(move |_, output| {
-                callback.write_samples(output);
+                callback.lock().write_samples(output);
                 output.len() as isize
             })
             .state_callback(|state| {
                 log::debug!("stream state: {:?}", state);
             });
-        let stream = builder.init(&ctx)?;
+        let stream = builder.init(ctx)?;
+        Ok(stream)
+    }
 
-        Ok(Self { ctx, stream })
+    fn reopen(&mut self) -> Result<(), Error> {
+        let stream = Self::build(&self.ctx, &self.callback)?;
+        stream.set_volume(self.volume)?;
+        self.stream = Some(stream);
+        Ok(())
     }
 }
 
 enum StreamMsg {
     Pause,
     Resume,
+    Release,
     Close,
     SetVolume(f32),
 }
@@ -114,27 +143,49 @@ impl Actor for Stream {
         match msg {
             StreamMsg::Pause => {
                 log::debug!("pausing audio output stream");
-                if let Err(err) = self.stream.stop() {
-                    log::error!("failed to stop stream: {}", err);
+                if let Some(stream) = &self.stream {
+                    if let Err(err) = stream.stop() {
+                        log::error!("failed to stop stream: {}", err);
+                    }
                 }
                 Ok(Act::Continue)
             }
             StreamMsg::Resume => {
                 log::debug!("resuming audio output stream");
-                if let Err(err) = self.stream.start() {
-                    log::error!("failed to start stream: {}", err);
+                if self.stream.is_none() {
+                    if let Err(err) = self.reopen() {
+                        log::error!("failed to reopen stream: {}", err);
+                        return Ok(Act::Continue);
+                    }
+                }
+                if let Some(stream) = &self.stream {
+                    if let Err(err) = stream.start() {
+                        log::error!("failed to start stream: {}", err);
+                    }
+                }
+                Ok(Act::Continue)
+            }
+            StreamMsg::Release => {
+                log::debug!("releasing audio output stream");
+                if let Some(stream) = self.stream.take() {
+                    let _ = stream.stop();
                 }
                 Ok(Act::Continue)
             }
             StreamMsg::Close => {
                 log::debug!("closing audio output stream");
-                let _ = self.stream.stop();
+                if let Some(stream) = &self.stream {
+                    let _ = stream.stop();
+                }
                 Ok(Act::Shutdown)
             }
             StreamMsg::SetVolume(volume) => {
                 log::debug!("setting volume");
-                if let Err(err) = self.stream.set_volume(volume) {
-                    log::error!("failed to set volume: {}", err);
+                self.volume = volume;
+                if let Some(stream) = &self.stream {
+                    if let Err(err) = stream.set_volume(volume) {
+                        log::error!("failed to set volume: {}", err);
+                    }
                 }
                 Ok(Act::Continue)
             }
@@ -181,6 +232,10 @@ impl AudioSink for CubebSink {
         self.pause();
     }
 
+    fn release(&self) {
+        self.stream_send.send(StreamMsg::Release).unwrap();
+    }
+
     fn close(&self) {
         self.stop();
     }
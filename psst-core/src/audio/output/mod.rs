@@ -27,5 +27,8 @@ pub trait AudioSink {
     fn pause(&self);
     fn resume(&self);
     fn stop(&self);
+    /// Close the output stream and give up the device until the next
+    /// `resume()`, which opens the stream again.
+    fn release(&self);
     fn close(&self);
 }
@@ -1,6 +1,10 @@
 use crate::error::Error;
 
-#[derive(Copy, Clone)]
+/// Converters of libsamplerate.  The sinc converters band-limit the signal,
+/// better ones use longer filters, costing more CPU time per sample and adding
+/// a few milliseconds of latency, which only matters at the start of an item.
+/// `ZeroOrderHold` and `Linear` are very cheap, but audibly alias.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum ResamplingQuality {
     SincBestQuality = libsamplerate::SRC_SINC_BEST_QUALITY as isize,
     SincMediumQuality = libsamplerate::SRC_SINC_MEDIUM_QUALITY as isize,
@@ -7,13 +7,22 @@ use std::{
 use crate::{
     audio::decrypt::AudioKey,
     error::Error,
-    item_id::{FileId, ItemId},
+    item_id::{FileId, ItemId, ItemIdType},
     protocol::metadata::{Episode, Track},
     util::{deserialize_protobuf, serialize_protobuf},
 };
 
 pub type CacheHandle = Arc<Cache>;
 
+/// Audio file of an item downloaded for offline playback.
+#[derive(Debug, Clone, Copy)]
+pub struct Download {
+    pub file_id: FileId,
+    /// Bitrate the file was chosen for, playback of the item keeps using it
+    /// regardless of the streaming quality.
+    pub bitrate: usize,
+}
+
 pub struct Cache {
     base: PathBuf,
 }
@@ -28,6 +37,7 @@ impl Cache {
         mkdir_if_not_exists(&base.join("episode"))?;
         mkdir_if_not_exists(&base.join("audio"))?;
         mkdir_if_not_exists(&base.join("key"))?;
+        mkdir_if_not_exists(&base.join("download"))?;
 
         let cache = Self { base };
         Ok(Arc::new(cache))
@@ -107,6 +117,57 @@ impl Cache {
         fs::copy(from_path, self.audio_file_path(file_id))?;
         Ok(())
     }
+
+    pub fn remove_audio_file(&self, file_id: FileId) -> Result<(), Error> {
+        log::debug!("removing audio file from cache: {:?}", file_id);
+        fs::remove_file(self.audio_file_path(file_id))?;
+        Ok(())
+    }
+}
+
+// Registry of items explicitly downloaded for offline playback.
+impl Cache {
+    pub fn downloaded_items(&self, id_type: ItemIdType) -> Vec<ItemId> {
+        fs::read_dir(self.base.join("download"))
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let name = entry.file_name();
+                        ItemId::from_base62(name.to_str()?, id_type)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn save_download(&self, item_id: ItemId, download: &Download) -> Result<(), Error> {
+        let content = format!("{} {}", download.file_id.to_base16(), download.bitrate);
+        fs::write(self.download_path(item_id), content)?;
+        Ok(())
+    }
+
+    pub fn get_download(&self, item_id: ItemId) -> Option<Download> {
+        let content = fs::read_to_string(self.download_path(item_id)).ok()?;
+        let mut parts = content.split_whitespace();
+        let file_id = FileId::from_base16(parts.next()?)?;
+        // Downloads saved before the bitrate was recorded have just the file
+        // ID, take them as the highest quality.
+        let bitrate = parts
+            .next()
+            .and_then(|bitrate| bitrate.parse().ok())
+            .unwrap_or(320);
+        Some(Download { file_id, bitrate })
+    }
+
+    pub fn remove_download(&self, item_id: ItemId) -> Result<(), Error> {
+        fs::remove_file(self.download_path(item_id))?;
+        Ok(())
+    }
+
+    fn download_path(&self, item_id: ItemId) -> PathBuf {
+        self.base.join("download").join(item_id.to_base62())
+    }
 }
 
 // Cache of user country code.
@@ -10,7 +10,7 @@ use crate::{
     error::Error,
     item_id::FileId,
     session::{access_token::TokenProvider, SessionService},
-    util::default_ureq_agent_builder,
+    util::{default_ureq_agent_builder, NetTimeouts},
 };
 
 pub type CdnHandle = Arc<Cdn>;
@@ -22,8 +22,12 @@ pub struct Cdn {
 }
 
 impl Cdn {
-    pub fn new(session: SessionService, proxy_url: Option<&str>) -> Result<CdnHandle, Error> {
-        let agent = default_ureq_agent_builder(proxy_url)?.build();
+    pub fn new(
+        session: SessionService,
+        proxy_url: Option<&str>,
+        timeouts: NetTimeouts,
+    ) -> Result<CdnHandle, Error> {
+        let agent = default_ureq_agent_builder(proxy_url, timeouts)?.build();
         Ok(Arc::new(Self {
             session,
             agent,
@@ -0,0 +1,655 @@
+use std::{
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crossbeam_channel::{select, unbounded, Receiver, RecvTimeoutError, Sender};
+
+use crate::{
+    error::Error,
+    item_id::{ItemId, ItemIdType, ItemKind},
+    protocol::spirc::{
+        Capability, CapabilityType, DeviceState, Frame, Goodbye, MessageType, PlayStatus, State,
+        TrackRef,
+    },
+    session::{SessionHandle, SessionService},
+    util::{deserialize_protobuf, serialize_protobuf},
+};
+
+const PROTOCOL_VERSION: &str = "2.0.0";
+const SOFTWARE_VERSION: &str = concat!("psst ", env!("CARGO_PKG_VERSION"));
+/// Number of the volume steps the other devices offer for this device.
+const VOLUME_STEPS: u32 = 64;
+/// How long to wait before subscribing again after the session died.
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// Identity of this device, as shown on the other Spotify Connect devices of
+/// the user.
+#[derive(Clone, Debug)]
+pub struct ConnectConfig {
+    pub name: String,
+    /// Unique ID of this device, should stay the same across restarts.
+    pub device_id: String,
+}
+
+impl ConnectConfig {
+    /// New device ID, to be generated once and kept.
+    pub fn random_device_id() -> String {
+        let bytes: [u8; 20] = rand::random();
+        bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+}
+
+/// Request from another device of the user, e.g. a phone controlling this
+/// device.
+#[derive(Clone, Debug)]
+pub enum ConnectCommand {
+    /// Replace the queue with `items` and start from `position`, `progress`
+    /// into it.
+    Load {
+        items: Vec<ItemId>,
+        position: usize,
+        progress: Duration,
+        play: bool,
+    },
+    Play,
+    Pause,
+    Next,
+    Previous,
+    Seek {
+        position: Duration,
+    },
+    SetVolume {
+        volume: f64,
+    },
+    SetShuffle {
+        shuffle: bool,
+    },
+    SetRepeat {
+        repeat: bool,
+    },
+    /// Another device took over the playback.
+    Deactivate,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ConnectStatus {
+    Stopped,
+    Loading,
+    Playing,
+    Paused,
+}
+
+impl Default for ConnectStatus {
+    fn default() -> Self {
+        Self::Stopped
+    }
+}
+
+/// State of the local playback, reported to the other devices.
+#[derive(Clone, Debug, Default)]
+pub struct ConnectState {
+    pub status: ConnectStatus,
+    pub items: Vec<ItemId>,
+    /// Index of the current item in `items`.
+    pub position: usize,
+    pub progress: Duration,
+    /// Volume from 0.0 to 1.0.
+    pub volume: f64,
+    pub shuffle: bool,
+    pub repeat: bool,
+}
+
+/// Presence of this client among the Spotify Connect devices of the user.
+/// Runs a thread that keeps a subscription to the messages of the other
+/// devices over the session, and passes the commands meant for this device to
+/// a callback.  The device says goodbye to the other devices once dropped.
+pub struct ConnectDevice {
+    sender: Sender<DeviceMsg>,
+    _thread: JoinHandle<()>,
+}
+
+enum DeviceMsg {
+    Update(ConnectState),
+    Shutdown,
+}
+
+impl ConnectDevice {
+    /// Start announcing the device.  `on_command` is called from the device
+    /// thread.
+    pub fn start(
+        session: SessionService,
+        config: ConnectConfig,
+        on_command: impl Fn(ConnectCommand) + Send + 'static,
+    ) -> Self {
+        let (sender, receiver) = unbounded();
+        let thread = thread::Builder::new()
+            .name("connect".to_string())
+            .spawn(move || {
+                let mut device = DeviceWorker::new(config);
+                device.run(&session, &receiver, &on_command);
+            })
+            .unwrap();
+        Self {
+            sender,
+            _thread: thread,
+        }
+    }
+
+    /// Report a change of the local playback to the other devices.
+    pub fn update(&self, state: ConnectState) {
+        let _ = self.sender.send(DeviceMsg::Update(state));
+    }
+}
+
+impl Drop for ConnectDevice {
+    fn drop(&mut self) {
+        // Don't wait for the thread, it might be in the middle of opening the
+        // session.
+        let _ = self.sender.send(DeviceMsg::Shutdown);
+    }
+}
+
+struct DeviceWorker {
+    config: ConnectConfig,
+    state: ConnectState,
+    is_active: bool,
+    /// Milliseconds since the Unix epoch.
+    became_active_at: i64,
+    /// When `state.progress` was measured, in milliseconds since the Unix
+    /// epoch.
+    progress_measured_at: i64,
+    seq_nr: u32,
+    /// Sender and sequence number of the last handled command.
+    last_command: Option<(String, u32)>,
+}
+
+impl DeviceWorker {
+    fn new(config: ConnectConfig) -> Self {
+        Self {
+            config,
+            state: ConnectState::default(),
+            is_active: false,
+            became_active_at: 0,
+            progress_measured_at: 0,
+            seq_nr: 0,
+            last_command: None,
+        }
+    }
+
+    fn run(
+        &mut self,
+        session: &SessionService,
+        receiver: &Receiver<DeviceMsg>,
+        on_command: &dyn Fn(ConnectCommand),
+    ) {
+        loop {
+            match self.serve(session, receiver, on_command) {
+                Ok(_) => return,
+                Err(err) => log::warn!("connect device disconnected: {}", err),
+            }
+            // Keep up with the local state until we try again.
+            let deadline = Instant::now() + RECONNECT_DELAY;
+            loop {
+                match receiver.recv_deadline(deadline) {
+                    Ok(DeviceMsg::Update(state)) => self.update_state(state),
+                    Ok(DeviceMsg::Shutdown) | Err(RecvTimeoutError::Disconnected) => return,
+                    Err(RecvTimeoutError::Timeout) => break,
+                }
+            }
+        }
+    }
+
+    /// Serve the device until a shutdown is requested, or until the session
+    /// dies.
+    fn serve(
+        &mut self,
+        session: &SessionService,
+        receiver: &Receiver<DeviceMsg>,
+        on_command: &dyn Fn(ConnectCommand),
+    ) -> Result<(), Error> {
+        let handle = session.connected()?;
+        let uri = format!("hm://remote/user/{}/", handle.username());
+        let frames = handle.subscribe_mercury(uri.clone())?;
+        self.send(&handle, &uri, MessageType::kMessageTypeHello)?;
+
+        loop {
+            select! {
+                recv(frames) -> response => {
+                    let response = response.map_err(|_| Error::SessionDisconnected)?;
+                    for part in response.payload {
+                        match deserialize_protobuf::<Frame>(&part) {
+                            Ok(frame) => self.handle_frame(&handle, &uri, frame, on_command)?,
+                            Err(err) => log::warn!("skipping malformed connect frame: {}", err),
+                        }
+                    }
+                }
+                recv(receiver) -> msg => match msg {
+                    Ok(DeviceMsg::Update(state)) => {
+                        self.update_state(state);
+                        self.send(&handle, &uri, MessageType::kMessageTypeNotify)?;
+                    }
+                    Ok(DeviceMsg::Shutdown) | Err(_) => {
+                        let goodbye = self.send(&handle, &uri, MessageType::kMessageTypeGoodbye);
+                        if let Err(err) = goodbye {
+                            log::warn!("failed to say goodbye: {}", err);
+                        }
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+
+    fn update_state(&mut self, state: ConnectState) {
+        // Starting the playback locally takes it over from the other devices.
+        if state.status == ConnectStatus::Playing && !self.is_active {
+            self.activate();
+        }
+        self.state = state;
+        self.progress_measured_at = unix_millis();
+    }
+
+    fn activate(&mut self) {
+        self.is_active = true;
+        self.became_active_at = unix_millis();
+    }
+
+    fn handle_frame(
+        &mut self,
+        handle: &SessionHandle,
+        uri: &str,
+        frame: Frame,
+        on_command: &dyn Fn(ConnectCommand),
+    ) -> Result<(), Error> {
+        if !self.is_meant_for_us(&frame) {
+            return Ok(());
+        }
+        if frame.typ == Some(MessageType::kMessageTypeHello) {
+            // Introduce ourselves to the newly connected device.
+            return self.send(handle, uri, MessageType::kMessageTypeNotify);
+        }
+        if let Some(command) = self.frame_command(frame) {
+            on_command(command);
+        }
+        Ok(())
+    }
+
+    fn is_meant_for_us(&self, frame: &Frame) -> bool {
+        frame.ident.as_deref() != Some(self.config.device_id.as_str())
+            && (frame.recipient.is_empty() || frame.recipient.contains(&self.config.device_id))
+    }
+
+    /// Command that `frame` asks this device to carry out, if any.
+    fn frame_command(&mut self, frame: Frame) -> Option<ConnectCommand> {
+        let ident = frame.ident.clone().unwrap_or_default();
+        let typ = frame.typ?;
+        log::debug!("connect frame {:?} from {}", typ, ident);
+
+        let command = match typ {
+            MessageType::kMessageTypeNotify => {
+                let other_is_active = frame
+                    .device_state
+                    .as_ref()
+                    .and_then(|device| device.is_active)
+                    .unwrap_or(false);
+                if self.is_active && other_is_active {
+                    self.is_active = false;
+                    Some(ConnectCommand::Deactivate)
+                } else {
+                    None
+                }
+            }
+            MessageType::kMessageTypeLoad | MessageType::kMessageTypeReplace => {
+                let state = frame.state.unwrap_or_default();
+                let (items, position) = load_items(
+                    &state.track,
+                    state.playing_track_index.unwrap_or_default() as usize,
+                );
+                if typ == MessageType::kMessageTypeLoad {
+                    self.activate();
+                }
+                Some(ConnectCommand::Load {
+                    items,
+                    position,
+                    progress: Duration::from_millis(state.position_ms.unwrap_or_default().into()),
+                    play: state.status == Some(PlayStatus::kPlayStatusPlay),
+                })
+            }
+            MessageType::kMessageTypePlay => Some(ConnectCommand::Play),
+            MessageType::kMessageTypePause => Some(ConnectCommand::Pause),
+            MessageType::kMessageTypePlayPause => {
+                if self.state.status == ConnectStatus::Playing {
+                    Some(ConnectCommand::Pause)
+                } else {
+                    Some(ConnectCommand::Play)
+                }
+            }
+            MessageType::kMessageTypeNext => Some(ConnectCommand::Next),
+            MessageType::kMessageTypePrev => Some(ConnectCommand::Previous),
+            MessageType::kMessageTypeSeek => frame.position.map(|ms| ConnectCommand::Seek {
+                position: Duration::from_millis(ms.into()),
+            }),
+            MessageType::kMessageTypeVolume => {
+                frame.volume.map(|volume| ConnectCommand::SetVolume {
+                    volume: f64::from(volume) / f64::from(u16::MAX),
+                })
+            }
+            MessageType::kMessageTypeVolumeUp | MessageType::kMessageTypeVolumeDown => {
+                let step = 1.0 / f64::from(VOLUME_STEPS);
+                let step = if typ == MessageType::kMessageTypeVolumeUp {
+                    step
+                } else {
+                    -step
+                };
+                Some(ConnectCommand::SetVolume {
+                    volume: (self.state.volume + step).clamp(0.0, 1.0),
+                })
+            }
+            MessageType::kMessageTypeShuffle => frame
+                .state
+                .and_then(|state| state.shuffle)
+                .map(|shuffle| ConnectCommand::SetShuffle { shuffle }),
+            MessageType::kMessageTypeRepeat => frame
+                .state
+                .and_then(|state| state.repeat)
+                .map(|repeat| ConnectCommand::SetRepeat { repeat }),
+            _ => None,
+        };
+        if command.is_some() {
+            self.last_command = Some((ident, frame.seq_nr.unwrap_or_default()));
+        }
+        command
+    }
+
+    fn send(&mut self, handle: &SessionHandle, uri: &str, typ: MessageType) -> Result<(), Error> {
+        self.seq_nr = self.seq_nr.wrapping_add(1);
+        let frame = Frame {
+            version: Some(1),
+            ident: Some(self.config.device_id.clone()),
+            protocol_version: Some(PROTOCOL_VERSION.to_string()),
+            seq_nr: Some(self.seq_nr),
+            typ: Some(typ),
+            device_state: Some(self.device_state()),
+            goodbye: (typ == MessageType::kMessageTypeGoodbye).then(|| Goodbye {
+                reason: Some("shutdown".to_string()),
+            }),
+            state: Some(self.playback_state()),
+            state_update_id: Some(unix_millis()),
+            ..Frame::default()
+        };
+        handle.send_mercury(uri.to_string(), serialize_protobuf(&frame)?)
+    }
+
+    fn device_state(&self) -> DeviceState {
+        DeviceState {
+            sw_version: Some(SOFTWARE_VERSION.to_string()),
+            is_active: Some(self.is_active),
+            can_play: Some(true),
+            volume: Some((self.state.volume.clamp(0.0, 1.0) * f64::from(u16::MAX)) as u32),
+            name: Some(self.config.name.clone()),
+            became_active_at: self.is_active.then(|| self.became_active_at),
+            capabilities: vec![
+                int_capability(CapabilityType::kCanBePlayer, 1),
+                // Computer.
+                int_capability(CapabilityType::kDeviceType, 1),
+                int_capability(CapabilityType::kGaiaEqConnectId, 1),
+                int_capability(CapabilityType::kSupportsLogout, 0),
+                int_capability(CapabilityType::kIsObservable, 1),
+                int_capability(CapabilityType::kVolumeSteps, VOLUME_STEPS.into()),
+                int_capability(CapabilityType::kCommandAcks, 1),
+                string_capability(
+                    CapabilityType::kSupportedContexts,
+                    &[
+                        "album", "playlist", "search", "inbox", "toplist", "starred", "track",
+                    ],
+                ),
+                string_capability(
+                    CapabilityType::kSupportedTypes,
+                    &["audio/track", "audio/episode"],
+                ),
+            ],
+            ..DeviceState::default()
+        }
+    }
+
+    fn playback_state(&self) -> State {
+        let (last_command_ident, last_command_msgid) = match &self.last_command {
+            Some((ident, msgid)) => (Some(ident.clone()), Some(*msgid)),
+            None => (None, None),
+        };
+        State {
+            index: Some(self.state.position as u32),
+            playing_track_index: Some(self.state.position as u32),
+            position_ms: Some(self.state.progress.as_millis() as u32),
+            position_measured_at: Some(self.progress_measured_at as u64),
+            status: Some(match self.state.status {
+                ConnectStatus::Stopped => PlayStatus::kPlayStatusStop,
+                ConnectStatus::Loading => PlayStatus::kPlayStatusLoading,
+                ConnectStatus::Playing => PlayStatus::kPlayStatusPlay,
+                ConnectStatus::Paused => PlayStatus::kPlayStatusPause,
+            }),
+            shuffle: Some(self.state.shuffle),
+            repeat: Some(self.state.repeat),
+            track: self.state.items.iter().map(item_track_ref).collect(),
+            last_command_ident,
+            last_command_msgid,
+            ..State::default()
+        }
+    }
+}
+
+fn int_capability(typ: CapabilityType, value: i64) -> Capability {
+    Capability {
+        typ: Some(typ),
+        intValue: vec![value],
+        ..Capability::default()
+    }
+}
+
+fn string_capability(typ: CapabilityType, values: &[&str]) -> Capability {
+    Capability {
+        typ: Some(typ),
+        stringValue: values.iter().map(|value| value.to_string()).collect(),
+        ..Capability::default()
+    }
+}
+
+/// Items of `tracks` we can play, and the index of the one at `index` among
+/// them.  If that one can't be played, the next playable one is picked.
+fn load_items(tracks: &[TrackRef], index: usize) -> (Vec<ItemId>, usize) {
+    let mut items = Vec::with_capacity(tracks.len());
+    let mut position = None;
+    for (i, track) in tracks.iter().enumerate() {
+        if let Some(item) = track_ref_item(track) {
+            if i >= index && position.is_none() {
+                position = Some(items.len());
+            }
+            items.push(item);
+        }
+    }
+    let position = position.unwrap_or_else(|| items.len().saturating_sub(1));
+    (items, position)
+}
+
+/// Tracks are referred to by their raw IDs, episodes by their URIs.
+fn track_ref_item(track: &TrackRef) -> Option<ItemId> {
+    let from_uri = || {
+        let uri = track.uri.as_deref()?;
+        ItemId::from_uri(uri, ItemKind::Track).or_else(|| ItemId::from_uri(uri, ItemKind::Episode))
+    };
+    match &track.gid {
+        Some(gid) => ItemId::from_raw(gid, ItemIdType::Track),
+        None => from_uri(),
+    }
+}
+
+fn item_track_ref(item: &ItemId) -> TrackRef {
+    match item.id_type {
+        ItemIdType::Podcast => TrackRef {
+            uri: Some(item.to_uri(ItemKind::Episode)),
+            ..TrackRef::default()
+        },
+        ItemIdType::Track | ItemIdType::Unknown => TrackRef {
+            gid: Some(item.to_raw().to_vec()),
+            ..TrackRef::default()
+        },
+    }
+}
+
+fn unix_millis() -> i64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_millis() as i64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DEVICE_ID: &str = "local";
+
+    fn worker() -> DeviceWorker {
+        DeviceWorker::new(ConnectConfig {
+            name: "Test".to_string(),
+            device_id: DEVICE_ID.to_string(),
+        })
+    }
+
+    fn frame(typ: MessageType) -> Frame {
+        Frame {
+            ident: Some("remote".to_string()),
+            seq_nr: Some(7),
+            typ: Some(typ),
+            ..Frame::default()
+        }
+    }
+
+    fn track(id: u128) -> TrackRef {
+        item_track_ref(&ItemId::new(id, ItemIdType::Track))
+    }
+
+    #[test]
+    fn ignores_own_and_foreign_frames() {
+        let worker = worker();
+        let own = Frame {
+            ident: Some(DEVICE_ID.to_string()),
+            ..frame(MessageType::kMessageTypePlay)
+        };
+        let foreign = Frame {
+            recipient: vec!["other".to_string()],
+            ..frame(MessageType::kMessageTypePlay)
+        };
+        let addressed = Frame {
+            recipient: vec![DEVICE_ID.to_string()],
+            ..frame(MessageType::kMessageTypePlay)
+        };
+        assert!(!worker.is_meant_for_us(&own));
+        assert!(!worker.is_meant_for_us(&foreign));
+        assert!(worker.is_meant_for_us(&addressed));
+        assert!(worker.is_meant_for_us(&frame(MessageType::kMessageTypePlay)));
+    }
+
+    #[test]
+    fn maps_frames_to_commands() {
+        let mut worker = worker();
+        assert!(matches!(
+            worker.frame_command(frame(MessageType::kMessageTypePlay)),
+            Some(ConnectCommand::Play)
+        ));
+        assert!(matches!(
+            worker.frame_command(frame(MessageType::kMessageTypePause)),
+            Some(ConnectCommand::Pause)
+        ));
+        assert!(matches!(
+            worker.frame_command(frame(MessageType::kMessageTypeNext)),
+            Some(ConnectCommand::Next)
+        ));
+        assert!(matches!(
+            worker.frame_command(Frame {
+                position: Some(1500),
+                ..frame(MessageType::kMessageTypeSeek)
+            }),
+            Some(ConnectCommand::Seek { position }) if position == Duration::from_millis(1500)
+        ));
+        assert!(matches!(
+            worker.frame_command(Frame {
+                volume: Some(u16::MAX.into()),
+                ..frame(MessageType::kMessageTypeVolume)
+            }),
+            Some(ConnectCommand::SetVolume { volume }) if volume == 1.0
+        ));
+        assert!(worker
+            .frame_command(frame(MessageType::kMessageTypeHello))
+            .is_none());
+        assert_eq!(worker.last_command, Some(("remote".to_string(), 7)));
+    }
+
+    #[test]
+    fn load_skips_unplayable_tracks_and_keeps_the_playing_one() {
+        let mut worker = worker();
+        let state = State {
+            track: vec![track(1), TrackRef::default(), track(2), track(3)],
+            playing_track_index: Some(2),
+            position_ms: Some(3000),
+            status: Some(PlayStatus::kPlayStatusPause),
+            ..State::default()
+        };
+        let command = worker.frame_command(Frame {
+            state: Some(state),
+            ..frame(MessageType::kMessageTypeLoad)
+        });
+        match command {
+            Some(ConnectCommand::Load {
+                items,
+                position,
+                progress,
+                play,
+            }) => {
+                assert_eq!(items.len(), 3);
+                assert_eq!(items[position], ItemId::new(2, ItemIdType::Track));
+                assert_eq!(progress, Duration::from_secs(3));
+                assert!(!play);
+            }
+            other => panic!("unexpected command {:?}", other),
+        }
+        assert!(worker.is_active);
+    }
+
+    #[test]
+    fn load_picks_the_next_playable_track() {
+        let tracks = [track(1), TrackRef::default(), track(3)];
+        let (items, position) = load_items(&tracks, 1);
+        assert_eq!(items[position], ItemId::new(3, ItemIdType::Track));
+        let (items, position) = load_items(&tracks[..2], 1);
+        assert_eq!(items[position], ItemId::new(1, ItemIdType::Track));
+    }
+
+    #[test]
+    fn reports_the_local_playback() {
+        let mut worker = worker();
+        let items = vec![
+            ItemId::new(1, ItemIdType::Track),
+            ItemId::new(2, ItemIdType::Podcast),
+        ];
+        worker.update_state(ConnectState {
+            status: ConnectStatus::Playing,
+            items: items.clone(),
+            position: 1,
+            progress: Duration::from_secs(42),
+            volume: 0.5,
+            shuffle: true,
+            repeat: false,
+        });
+        let measured_at = worker.progress_measured_at;
+        let state = worker.playback_state();
+        assert_eq!(state.playing_track_index, Some(1));
+        assert_eq!(state.position_ms, Some(42_000));
+        assert_eq!(state.position_measured_at, Some(measured_at as u64));
+        assert_eq!(state.status, Some(PlayStatus::kPlayStatusPlay));
+        assert_eq!(state.shuffle, Some(true));
+        let reported: Vec<_> = state.track.iter().filter_map(track_ref_item).collect();
+        assert_eq!(reported, items);
+        assert!(worker.is_active);
+    }
+}
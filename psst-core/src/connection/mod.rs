@@ -22,10 +22,7 @@ use crate::{
     },
     error::Error,
     protocol::authentication::AuthenticationType,
-    util::{
-        default_ureq_agent_builder, deserialize_protobuf, serialize_protobuf, NET_CONNECT_TIMEOUT,
-        NET_IO_TIMEOUT,
-    },
+    util::{default_ureq_agent_builder, deserialize_protobuf, serialize_protobuf, NetTimeouts},
 };
 
 // Device ID used for authentication message.
@@ -37,6 +34,9 @@ const AP_RESOLVE_ENDPOINT: &str = "http://apresolve.spotify.com";
 // Access-point used in case the resolving fails.
 const AP_FALLBACK: &str = "ap.spotify.com:443";
 
+// Upper limit of the response header of an HTTP proxy to the CONNECT request.
+const MAX_PROXY_HEADER_SIZE: usize = 8 * 1024;
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(from = "SerializedCredentials")]
 #[serde(into = "SerializedCredentials")]
@@ -91,8 +91,8 @@ pub struct Transport {
 }
 
 impl Transport {
-    pub fn resolve_ap_with_fallback(proxy_url: Option<&str>) -> String {
-        match Self::resolve_ap(proxy_url) {
+    pub fn resolve_ap_with_fallback(proxy_url: Option<&str>, timeouts: NetTimeouts) -> String {
+        match Self::resolve_ap(proxy_url, timeouts) {
             Ok(ap) => ap,
             Err(err) => {
                 log::error!("using AP fallback, error while resolving: {:?}", err);
@@ -101,13 +101,13 @@ impl Transport {
         }
     }
 
-    pub fn resolve_ap(proxy_url: Option<&str>) -> Result<String, Error> {
+    pub fn resolve_ap(proxy_url: Option<&str>, timeouts: NetTimeouts) -> Result<String, Error> {
         #[derive(Clone, Debug, Deserialize)]
         struct APResolveData {
             ap_list: Vec<String>,
         }
 
-        let agent = default_ureq_agent_builder(proxy_url)?.build();
+        let agent = default_ureq_agent_builder(proxy_url, timeouts)?.build();
         let data: APResolveData = agent.get(AP_RESOLVE_ENDPOINT).call()?.into_json()?;
         data.ap_list
             .into_iter()
@@ -115,24 +115,28 @@ impl Transport {
             .ok_or(Error::UnexpectedResponse)
     }
 
-    pub fn connect(ap: &str, proxy_url: Option<&str>) -> Result<Self, Error> {
+    pub fn connect(
+        ap: &str,
+        proxy_url: Option<&str>,
+        timeouts: NetTimeouts,
+    ) -> Result<Self, Error> {
         log::trace!("connecting to: {:?} with proxy: {:?}", ap, proxy_url);
         let stream = if let Some(url) = proxy_url {
-            Self::stream_through_proxy(ap, url)?
+            Self::stream_through_proxy(ap, url, timeouts)?
         } else {
-            Self::stream_without_proxy(ap)?
+            Self::stream_without_proxy(ap, timeouts)?
         };
-        if let Err(err) = stream.set_write_timeout(Some(NET_IO_TIMEOUT)) {
+        if let Err(err) = stream.set_write_timeout(Some(timeouts.io)) {
             log::warn!("failed to set TCP write timeout: {:?}", err);
         }
         log::trace!("connected");
         Self::exchange_keys(stream)
     }
 
-    fn stream_without_proxy(ap: &str) -> Result<TcpStream, io::Error> {
+    fn stream_without_proxy(ap: &str, timeouts: NetTimeouts) -> Result<TcpStream, io::Error> {
         let mut last_err = None;
         for addr in ap.to_socket_addrs()? {
-            match TcpStream::connect_timeout(&addr, NET_CONNECT_TIMEOUT) {
+            match TcpStream::connect_timeout(&addr, timeouts.connect) {
                 Ok(stream) => {
                     return Ok(stream);
                 }
@@ -149,12 +153,18 @@ impl Transport {
         }))
     }
 
-    fn stream_through_proxy(ap: &str, url: &str) -> Result<TcpStream, Error> {
+    fn stream_through_proxy(
+        ap: &str,
+        url: &str,
+        timeouts: NetTimeouts,
+    ) -> Result<TcpStream, Error> {
         match Url::parse(url) {
             Ok(url) if url.scheme() == "socks" || url.scheme() == "socks5" => {
-                // Currently we only support SOCKS5 proxies.
                 Self::stream_through_socks5_proxy(ap, &url)
             }
+            Ok(url) if url.scheme() == "http" => {
+                Self::stream_through_http_proxy(ap, &url, timeouts)
+            }
             _ => {
                 // Proxy URL failed to parse or has unsupported scheme.
                 Err(Error::ProxyUrlInvalid)
@@ -175,6 +185,37 @@ impl Transport {
         Ok(proxy.into_inner())
     }
 
+    fn stream_through_http_proxy(
+        ap: &str,
+        url: &Url,
+        timeouts: NetTimeouts,
+    ) -> Result<TcpStream, Error> {
+        let addrs = url.socket_addrs(|| Some(80))?;
+        let proxy_addr = addrs.first().ok_or(Error::ProxyUrlInvalid)?.to_owned();
+        let mut stream = TcpStream::connect_timeout(&proxy_addr, timeouts.connect)?;
+        stream.set_read_timeout(Some(timeouts.io))?;
+
+        // Ask the proxy to open a tunnel to the AP.
+        write!(stream, "CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\n", ap, ap)?;
+
+        // Read the response header byte by byte, so we don't consume anything past it.
+        let mut header = Vec::new();
+        while !header.ends_with(b"\r\n\r\n") {
+            if header.len() >= MAX_PROXY_HEADER_SIZE {
+                return Err(Error::MessageTooLarge(header.len()));
+            }
+            header.push(stream.read_u8()?);
+        }
+        let status_line = header.split(|&b| b == b'\n').next().unwrap_or_default();
+        let status = String::from_utf8_lossy(status_line);
+        if status.split_whitespace().nth(1) != Some("200") {
+            log::error!("proxy refused to connect: {:?}", status.trim());
+            return Err(Error::UnexpectedResponse);
+        }
+        stream.set_read_timeout(None)?;
+        Ok(stream)
+    }
+
     pub fn exchange_keys(mut stream: TcpStream) -> Result<Self, Error> {
         use crate::protocol::keyexchange::APResponseMessage;
 
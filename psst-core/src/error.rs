@@ -14,6 +14,8 @@ pub enum Error {
     ResamplingError(i32),
     IoError(io::Error),
     SendError,
+    Cancelled,
+    MessageTooLarge(usize),
 }
 
 impl error::Error for Error {}
@@ -48,6 +50,10 @@ impl fmt::Display for Error {
             | Self::AudioOutputError(err) => err.fmt(f),
             Self::IoError(err) => err.fmt(f),
             Self::SendError => write!(f, "Failed to send into a channel"),
+            Self::Cancelled => write!(f, "Operation was cancelled"),
+            Self::MessageTooLarge(size) => {
+                write!(f, "Message of {} bytes exceeds the size limit", size)
+            }
         }
     }
 }
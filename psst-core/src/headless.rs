@@ -0,0 +1,142 @@
+use std::{
+    path::PathBuf,
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crossbeam_channel::{select, unbounded, Sender};
+
+use crate::{
+    audio::{
+        normalize::NormalizationLevel,
+        output::{AudioOutput, AudioSink, DefaultAudioOutput},
+    },
+    cache::Cache,
+    cdn::Cdn,
+    error::Error,
+    item_id::ItemId,
+    player::{item::PlaybackItem, PlaybackConfig, Player, PlayerCommand, PlayerEvent},
+    session::{SessionConfig, SessionService},
+};
+
+/// Player usable without any UI, e.g. from scripts, CLI tools or integration
+/// tests.  Owns the session, the audio output and a thread running the player.
+pub struct HeadlessPlayer {
+    session: SessionService,
+    sender: Sender<PlayerEvent>,
+    /// Ends the player thread.
+    shutdown: Sender<()>,
+    output: DefaultAudioOutput,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HeadlessPlayer {
+    /// Log in, open the default audio output and start the player.  Every
+    /// player event is passed to `on_event`, called from the player thread.
+    pub fn start(
+        session_config: SessionConfig,
+        cache_dir: PathBuf,
+        config: PlaybackConfig,
+        mut on_event: impl FnMut(&PlayerEvent) + Send + 'static,
+    ) -> Result<Self, Error> {
+        let proxy_url = session_config.proxy_url.clone();
+        let timeouts = session_config.timeouts;
+        let session = SessionService::with_config(session_config);
+        // Connect right away, so we fail early on bad credentials.
+        session.connected()?;
+
+        let output = DefaultAudioOutput::open()?;
+        let cdn = Cdn::new(session.clone(), proxy_url.as_deref(), timeouts)?;
+        let cache = Cache::new(cache_dir)?;
+        let mut player = Player::new(session.clone(), cdn, cache, config, &output);
+        let sender = player.sender();
+        let (shutdown, shutdown_receiver) = unbounded();
+        let thread = thread::spawn(move || {
+            let events = player.receiver();
+            loop {
+                select! {
+                    recv(events) -> event => match event {
+                        Ok(event) => {
+                            on_event(&event);
+                            player.handle(event);
+                        }
+                        Err(_) => break,
+                    },
+                    recv(shutdown_receiver) -> _ => break,
+                }
+            }
+        });
+
+        Ok(Self {
+            session,
+            sender,
+            shutdown,
+            output,
+            thread: Some(thread),
+        })
+    }
+
+    pub fn session(&self) -> &SessionService {
+        &self.session
+    }
+
+    /// Play a single track or episode.
+    pub fn play(&self, item_id: ItemId) {
+        self.play_queue(vec![item_id], 0);
+    }
+
+    /// Queue all of `item_ids` and start playing from the one at `position`.
+    pub fn play_queue(&self, item_ids: Vec<ItemId>, position: usize) {
+        let items = item_ids
+            .into_iter()
+            .map(|item_id| PlaybackItem {
+                item_id,
+                norm_level: NormalizationLevel::Track,
+            })
+            .collect();
+        self.send(PlayerCommand::LoadQueue {
+            items,
+            position,
+            shuffle_weights: None,
+        });
+    }
+
+    pub fn pause(&self) {
+        self.send(PlayerCommand::Pause);
+    }
+
+    pub fn resume(&self) {
+        self.send(PlayerCommand::Resume);
+    }
+
+    pub fn seek(&self, position: Duration) {
+        self.send(PlayerCommand::Seek { position });
+    }
+
+    pub fn stop(&self) {
+        self.send(PlayerCommand::Stop);
+    }
+
+    pub fn configure(&self, config: PlaybackConfig) {
+        self.send(PlayerCommand::Configure { config });
+    }
+
+    fn send(&self, command: PlayerCommand) {
+        // The player thread lives as long as we do, so this cannot fail.
+        self.sender.send(PlayerEvent::Command(command)).unwrap();
+    }
+}
+
+impl Drop for HeadlessPlayer {
+    fn drop(&mut self) {
+        self.stop();
+        let _ = self.shutdown.send(());
+        if let Some(thread) = self.thread.take() {
+            if thread.join().is_err() {
+                log::error!("player thread panicked");
+            }
+        }
+        self.output.sink().close();
+        self.session.shutdown();
+    }
+}
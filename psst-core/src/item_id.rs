@@ -1,4 +1,4 @@
-use std::{convert::TryInto, fmt, ops::Deref};
+use std::{convert::TryInto, error, fmt, ops::Deref};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ItemIdType {
@@ -7,12 +7,74 @@ pub enum ItemIdType {
     Unknown,
 }
 
+/// Kind of an item, as used in Spotify URIs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ItemKind {
+    Track,
+    Album,
+    Artist,
+    Playlist,
+    Episode,
+    Show,
+}
+
+impl ItemKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Track => "track",
+            Self::Album => "album",
+            Self::Artist => "artist",
+            Self::Playlist => "playlist",
+            Self::Episode => "episode",
+            Self::Show => "show",
+        }
+    }
+
+    pub fn id_type(&self) -> ItemIdType {
+        match self {
+            Self::Track => ItemIdType::Track,
+            Self::Episode => ItemIdType::Podcast,
+            Self::Album | Self::Artist | Self::Playlist | Self::Show => ItemIdType::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for ItemKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Reason a base62 ID failed to parse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidId {
+    Length(usize),
+    Character(char),
+    Overflow,
+}
+
+impl error::Error for InvalidId {}
+
+impl fmt::Display for InvalidId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Length(len) => {
+                write!(f, "ID has {} characters instead of {}", len, BASE62_LENGTH)
+            }
+            Self::Character(c) => write!(f, "ID contains invalid character {:?}", c),
+            Self::Overflow => write!(f, "ID is out of range"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct ItemId {
     pub id: u128,
     pub id_type: ItemIdType,
 }
 
+const URI_PREFIX: &str = "spotify";
+const BASE62_LENGTH: usize = 22;
 const BASE62_DIGITS: &[u8] = b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
 const BASE16_DIGITS: &[u8] = b"0123456789abcdef";
 
@@ -34,13 +96,28 @@ impl ItemId {
     }
 
     pub fn from_base62(id: &str, id_type: ItemIdType) -> Option<Self> {
+        Self::parse_base62(id, id_type).ok()
+    }
+
+    /// Parses a 22 character base62 ID, reporting why it's invalid.
+    pub fn parse_base62(id: &str, id_type: ItemIdType) -> Result<Self, InvalidId> {
+        let len = id.chars().count();
+        if len != BASE62_LENGTH {
+            return Err(InvalidId::Length(len));
+        }
         let mut n = 0_u128;
-        for c in id.as_bytes() {
-            let d = BASE62_DIGITS.iter().position(|e| e == c)? as u128;
-            n *= 62;
-            n += d;
+        for c in id.chars() {
+            let d = BASE62_DIGITS
+                .iter()
+                .position(|&e| e as char == c)
+                .ok_or(InvalidId::Character(c))? as u128;
+            // 22 base62 digits can exceed `u128::MAX`.
+            n = n
+                .checked_mul(62)
+                .and_then(|n| n.checked_add(d))
+                .ok_or(InvalidId::Overflow)?;
         }
-        Some(Self::new(n, id_type))
+        Ok(Self::new(n, id_type))
     }
 
     pub fn from_raw(data: &[u8], id_type: ItemIdType) -> Option<Self> {
@@ -48,25 +125,23 @@ impl ItemId {
         Some(Self::new(n, id_type))
     }
 
-    pub fn from_uri(uri: &str) -> Option<Self> {
-        let gid = uri.split(':').last()?;
-        if uri.contains(":episode:") {
-            Self::from_base62(gid, ItemIdType::Podcast)
-        } else if uri.contains(":track:") {
-            Self::from_base62(gid, ItemIdType::Track)
-        } else {
-            Self::from_base62(gid, ItemIdType::Unknown)
+    /// Parses an URI in the `spotify:{kind}:{base62}` form.  Returns `None` if
+    /// the URI is malformed or of a different kind.
+    pub fn from_uri(uri: &str, kind: ItemKind) -> Option<Self> {
+        let mut parts = uri.split(':');
+        if parts.next()? != URI_PREFIX || parts.next()? != kind.as_str() {
+            return None;
+        }
+        let base62 = parts.next()?;
+        if parts.next().is_some() {
+            return None;
         }
+        Self::from_base62(base62, kind.id_type())
     }
 
     /// Converts an ID to an URI as described in: https://developer.spotify.com/documentation/web-api/#spotify-uris-and-ids
-    pub fn to_uri(&self) -> Option<String> {
-        let b64 = self.to_base62();
-        match self.id_type {
-            ItemIdType::Track => Some(format!("spotify:track:{}", b64)),
-            ItemIdType::Podcast => Some(format!("spotify:podcast:{}", b64)),
-            ItemIdType::Unknown => None,
-        }
+    pub fn to_uri(&self, kind: ItemKind) -> String {
+        format!("{}:{}:{}", URI_PREFIX, kind, self.to_base62())
     }
 
     pub fn to_base16(&self) -> String {
@@ -108,6 +183,17 @@ impl FileId {
         Some(FileId(data.try_into().ok()?))
     }
 
+    pub fn from_base16(id: &str) -> Option<Self> {
+        if id.len() != 40 {
+            return None;
+        }
+        let mut data = [0_u8; 20];
+        for (i, byte) in data.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(id.get(i * 2..i * 2 + 2)?, 16).ok()?;
+        }
+        Some(FileId(data))
+    }
+
     pub fn to_base16(&self) -> String {
         self.0
             .iter()
@@ -136,3 +222,71 @@ impl fmt::Display for FileId {
         f.write_str(&self.to_base16())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BASE62: &str = "4uLU6hMCjMI75M1A2tKUQC";
+
+    #[test]
+    fn parses_valid_base62() {
+        let id = ItemId::parse_base62(BASE62, ItemIdType::Track).unwrap();
+        assert_eq!(id.id_type, ItemIdType::Track);
+        assert_eq!(id.to_base62(), BASE62);
+        let zero = ItemId::parse_base62(&"0".repeat(22), ItemIdType::Track).unwrap();
+        assert_eq!(zero.id, 0);
+    }
+
+    #[test]
+    fn rejects_invalid_base62() {
+        assert_eq!(
+            ItemId::parse_base62(&BASE62[1..], ItemIdType::Track),
+            Err(InvalidId::Length(21))
+        );
+        assert_eq!(
+            ItemId::parse_base62("", ItemIdType::Track),
+            Err(InvalidId::Length(0))
+        );
+        assert_eq!(
+            ItemId::parse_base62("4uLU6hMCjMI75M1A2tKU-C", ItemIdType::Track),
+            Err(InvalidId::Character('-'))
+        );
+        assert_eq!(
+            ItemId::parse_base62(&"Z".repeat(22), ItemIdType::Track),
+            Err(InvalidId::Overflow)
+        );
+        assert_eq!(
+            ItemId::from_base62("4uLU6hMCjMI75M1A2tKU-C", ItemIdType::Track),
+            None
+        );
+    }
+
+    #[test]
+    fn uri_round_trips() {
+        for kind in [
+            ItemKind::Track,
+            ItemKind::Album,
+            ItemKind::Artist,
+            ItemKind::Playlist,
+            ItemKind::Episode,
+        ] {
+            let uri = format!("spotify:{}:{}", kind, BASE62);
+            let id = ItemId::from_uri(&uri, kind).unwrap();
+            assert_eq!(id.id_type, kind.id_type());
+            assert_eq!(id.to_base62(), BASE62);
+            assert_eq!(id.to_uri(kind), uri);
+        }
+    }
+
+    #[test]
+    fn uri_of_another_kind_is_rejected() {
+        let uri = format!("spotify:album:{}", BASE62);
+        assert_eq!(ItemId::from_uri(&uri, ItemKind::Track), None);
+        assert_eq!(
+            ItemId::from_uri(&format!("{}:extra", uri), ItemKind::Album),
+            None
+        );
+        assert_eq!(ItemId::from_uri(BASE62, ItemKind::Album), None);
+    }
+}
@@ -4,8 +4,10 @@ pub mod actor;
 pub mod audio;
 pub mod cache;
 pub mod cdn;
+pub mod connect;
 pub mod connection;
 pub mod error;
+pub mod headless;
 pub mod item_id;
 pub mod metadata;
 pub mod player;
@@ -84,13 +84,22 @@ impl ToMediaPath for Episode {
 }
 
 fn select_preferred_file(files: &[AudioFile], preferred_bitrate: usize) -> Option<&AudioFile> {
-    MediaFile::supported_audio_formats_for_bitrate(preferred_bitrate)
+    let file = MediaFile::supported_audio_formats_for_bitrate(preferred_bitrate)
         .iter()
         .find_map(|&preferred_format| {
             files
                 .iter()
                 .find(|file| file.format == Some(preferred_format))
-        })
+        })?;
+    let bitrate = MediaFile::bitrate_of_format(file.format?);
+    if bitrate != preferred_bitrate {
+        log::info!(
+            "{}kbit audio file not available, falling back to {}kbit",
+            preferred_bitrate,
+            bitrate
+        );
+    }
+    Some(file)
 }
 
 fn is_restricted_in_region(restriction: &Restriction, country: &str) -> bool {
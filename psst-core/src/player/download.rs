@@ -0,0 +1,170 @@
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    thread,
+};
+
+use crossbeam_channel::Sender;
+
+use crate::{cache::CacheHandle, cdn::CdnHandle, error::Error, session::SessionService};
+
+use super::{item::PlaybackItem, PlaybackConfig, PlayerEvent};
+
+/// Counts of the items queued for download since the queue was last empty.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub struct DownloadQueueProgress {
+    pub done: usize,
+    pub failed: usize,
+    /// All the items, including the finished ones.
+    pub total: usize,
+}
+
+impl DownloadQueueProgress {
+    pub fn is_finished(&self) -> bool {
+        self.done + self.failed == self.total
+    }
+}
+
+/// Items waiting to be downloaded for offline playback.  Items are downloaded
+/// one at a time, in the order they were queued in.  Changes of the queue are
+/// reported as `PlayerEvent`s.
+pub struct DownloadQueue {
+    pending: VecDeque<PlaybackItem>,
+    active: Option<(PlaybackItem, Arc<AtomicBool>)>,
+    progress: DownloadQueueProgress,
+    sender: Sender<PlayerEvent>,
+}
+
+impl DownloadQueue {
+    pub fn new(sender: Sender<PlayerEvent>) -> Self {
+        Self {
+            pending: VecDeque::new(),
+            active: None,
+            progress: DownloadQueueProgress::default(),
+            sender,
+        }
+    }
+
+    pub fn push(&mut self, item: PlaybackItem) {
+        if self.contains(item) {
+            return;
+        }
+        if self.progress.is_finished() {
+            self.progress = DownloadQueueProgress::default();
+        }
+        self.pending.push_back(item);
+        self.progress.total += 1;
+        self.send(PlayerEvent::DownloadQueued { item });
+        self.report();
+    }
+
+    /// Remove `item` from the queue, or stop its download if it's in
+    /// progress.  Returns false if the item is not in the queue.
+    pub fn cancel(&mut self, item: PlaybackItem) -> bool {
+        if let Some(index) = self
+            .pending
+            .iter()
+            .position(|pending| pending.item_id == item.item_id)
+        {
+            self.pending.remove(index);
+            self.progress.total -= 1;
+            self.report();
+            true
+        } else if let Some((active, cancelled)) = &self.active {
+            // The download removes its partial file and finishes with
+            // `Error::Cancelled`.
+            let is_active = active.item_id == item.item_id;
+            if is_active {
+                cancelled.store(true, Ordering::Relaxed);
+            }
+            is_active
+        } else {
+            false
+        }
+    }
+
+    /// Start downloading the next item, unless there is a download running.
+    pub fn start_next(
+        &mut self,
+        session: &SessionService,
+        cdn: &CdnHandle,
+        cache: &CacheHandle,
+        config: &PlaybackConfig,
+    ) {
+        if self.active.is_some() {
+            return;
+        }
+        let item = match self.pending.pop_front() {
+            Some(item) => item,
+            None => return,
+        };
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.active = Some((item, cancelled.clone()));
+
+        thread::spawn({
+            let sender = self.sender.clone();
+            let session = session.clone();
+            let cdn = cdn.clone();
+            let cache = cache.clone();
+            let config = config.clone();
+            move || {
+                let result = item.download(
+                    &session,
+                    cdn,
+                    cache,
+                    &config,
+                    &cancelled,
+                    |downloaded, total| {
+                        let _ = sender.send(PlayerEvent::Downloading {
+                            item,
+                            downloaded,
+                            total,
+                        });
+                    },
+                );
+                if let Err(err) = &result {
+                    log::error!("failed to download item: {}", err);
+                }
+                sender
+                    .send(PlayerEvent::Downloaded { item, result })
+                    .unwrap();
+            }
+        });
+    }
+
+    /// Account for the finished download of `item`.  Failed items can be
+    /// queued again.
+    pub fn finish(&mut self, item: PlaybackItem, result: &Result<(), Error>) {
+        if matches!(&self.active, Some((active, _)) if active.item_id == item.item_id) {
+            self.active = None;
+        }
+        match result {
+            Ok(_) => self.progress.done += 1,
+            Err(Error::Cancelled) => self.progress.total -= 1,
+            Err(_) => self.progress.failed += 1,
+        }
+        self.report();
+    }
+
+    fn contains(&self, item: PlaybackItem) -> bool {
+        let is_active = matches!(&self.active, Some((active, _)) if active.item_id == item.item_id);
+        is_active
+            || self
+                .pending
+                .iter()
+                .any(|pending| pending.item_id == item.item_id)
+    }
+
+    fn report(&self) {
+        self.send(PlayerEvent::DownloadQueueChanged {
+            progress: self.progress,
+        });
+    }
+
+    fn send(&self, event: PlayerEvent) {
+        let _ = self.sender.send(event);
+    }
+}
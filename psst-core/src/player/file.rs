@@ -32,6 +32,17 @@ pub struct MediaPath {
     pub duration: Duration,
 }
 
+impl MediaPath {
+    pub fn bitrate(&self) -> usize {
+        MediaFile::bitrate_of_format(self.file_format)
+    }
+
+    /// Approximate number of bytes covering `duration` of the audio.
+    pub fn byte_length(&self, duration: Duration) -> u64 {
+        (self.bitrate() as f64 * 1000.0 / 8.0 * duration.as_secs_f64()) as u64
+    }
+}
+
 pub enum MediaFile {
     Streamed {
         streamed_file: Arc<StreamedFile>,
@@ -79,13 +90,29 @@ impl MediaFile {
         }
     }
 
-    pub fn open(path: MediaPath, cdn: CdnHandle, cache: CacheHandle) -> Result<Self, Error> {
+    pub fn bitrate_of_format(format: Format) -> usize {
+        match format {
+            Format::OGG_VORBIS_96 | Format::MP3_96 => 96,
+            Format::OGG_VORBIS_160 | Format::MP3_160 | Format::MP3_160_ENC => 160,
+            Format::MP3_256 => 256,
+            Format::OGG_VORBIS_320 | Format::MP3_320 => 320,
+            _ => 0,
+        }
+    }
+
+    pub fn open(
+        path: MediaPath,
+        cdn: CdnHandle,
+        cache: CacheHandle,
+        buffer: Duration,
+    ) -> Result<Self, Error> {
         let cached_path = cache.audio_file_path(path.file_id);
         if cached_path.exists() {
             let cached_file = CachedFile::open(path, cached_path)?;
             Ok(Self::Cached { cached_file })
         } else {
-            let streamed_file = Arc::new(StreamedFile::open(path, cdn, cache)?);
+            let prefetch_length = path.byte_length(buffer);
+            let streamed_file = Arc::new(StreamedFile::open(path, cdn, cache, prefetch_length)?);
             let servicing_handle = thread::spawn({
                 let streamed_file = Arc::clone(&streamed_file);
                 move || {
@@ -115,13 +142,38 @@ impl MediaFile {
         }
     }
 
+    /// Block until the initial buffer is downloaded, or until it takes too
+    /// long, in which case we start playing anyway.
+    pub fn wait_for_buffer(&self) {
+        const BUFFER_TIMEOUT: Duration = Duration::from_secs(10);
+
+        if !self.storage().wait_for_prefetch(BUFFER_TIMEOUT) {
+            log::warn!("timed out while buffering, starting playback anyway");
+        }
+    }
+
     pub fn audio_source(&self, key: AudioKey) -> Result<(AudioDecoder, NormalizationData), Error> {
+        let (encoded, normalization) = self.encoded_stream(key)?;
+        let decoded = AudioDecoder::new(encoded, self.codec_format())?;
+        Ok((decoded, normalization))
+    }
+
+    /// Decrypted contents of the file, without the Spotify-specific header.
+    pub fn encoded_stream(
+        &self,
+        key: AudioKey,
+    ) -> Result<
+        (
+            impl io::Read + io::Seek + Send + Sync + 'static,
+            NormalizationData,
+        ),
+        Error,
+    > {
         let reader = self.storage().reader()?;
         let mut decrypted = AudioDecrypt::new(key, reader);
         let normalization = NormalizationData::parse(&mut decrypted)?;
         let encoded = OffsetFile::new(decrypted, self.header_length())?;
-        let decoded = AudioDecoder::new(encoded, self.codec_format())?;
-        Ok((decoded, normalization))
+        Ok((encoded, normalization))
     }
 
     fn header_length(&self) -> u64 {
@@ -155,7 +207,12 @@ pub struct StreamedFile {
 }
 
 impl StreamedFile {
-    fn open(path: MediaPath, cdn: CdnHandle, cache: CacheHandle) -> Result<StreamedFile, Error> {
+    fn open(
+        path: MediaPath,
+        cdn: CdnHandle,
+        cache: CacheHandle,
+        prefetch_length: u64,
+    ) -> Result<StreamedFile, Error> {
         // First, we need to resolve URL of the file contents.
         let url = cdn.resolve_audio_file_url(path.file_id)?;
         log::debug!("resolved file URL: {:?}", url.url);
@@ -168,7 +225,7 @@ impl StreamedFile {
         // data storage.
         let (total_length, mut initial_data) =
             cdn.fetch_file_range(&url.url, 0, INITIAL_REQUEST_LENGTH)?;
-        let storage = StreamStorage::new(total_length)?;
+        let storage = StreamStorage::new(total_length, prefetch_length)?;
 
         // Pipe the initial data from the request body into storage.
         io::copy(&mut initial_data, &mut storage.writer()?)?;
@@ -1,6 +1,15 @@
+use std::{
+    fs::{self, File},
+    io,
+    io::{Read, Write},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use symphonia::core::audio::SampleBuffer;
+
 use crate::{
     audio::{decode::AudioDecoder, decrypt::AudioKey, normalize::NormalizationLevel},
-    cache::CacheHandle,
+    cache::{CacheHandle, Download},
     cdn::CdnHandle,
     error::Error,
     item_id::{ItemId, ItemIdType},
@@ -14,6 +23,15 @@ use super::{
     PlaybackConfig,
 };
 
+/// Form of the audio written by `PlaybackItem::export`.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ExportFormat {
+    /// Decrypted audio file, Ogg Vorbis or MP3, as stored by Spotify.
+    Encoded,
+    /// Decoded interleaved samples, as little-endian `f32`s.
+    Pcm,
+}
+
 pub struct LoadedPlaybackItem {
     pub file: MediaFile,
     pub source: AudioDecoder,
@@ -34,10 +52,16 @@ impl PlaybackItem {
         cache: CacheHandle,
         config: &PlaybackConfig,
     ) -> Result<LoadedPlaybackItem, Error> {
-        let path = load_media_path(self.item_id, session, &cache, config)?;
+        // Play downloaded items from the file on disk, whatever the current
+        // streaming quality is.
+        let bitrate = cache
+            .get_download(self.item_id)
+            .map_or(config.bitrate, |download| download.bitrate);
+        let path = load_media_path(self.item_id, session, &cache, bitrate)?;
         let key = load_audio_key(&path, session, &cache)?;
-        let file = MediaFile::open(path, cdn, cache)?;
+        let file = MediaFile::open(path, cdn, cache, config.buffer_duration)?;
         let (source, norm_data) = file.audio_source(key)?;
+        file.wait_for_buffer();
         let norm_factor = norm_data.factor_for_level(self.norm_level, config.pregain);
         Ok(LoadedPlaybackItem {
             file,
@@ -45,19 +69,115 @@ impl PlaybackItem {
             norm_factor,
         })
     }
+
+    /// Stream the audio of this item into `output`, either as the decrypted
+    /// file or decoded into samples.  `shutdown` is checked between chunks, the
+    /// export stops with `Error::Cancelled` once it's set.
+    #[allow(clippy::too_many_arguments)]
+    pub fn export(
+        &self,
+        session: &SessionService,
+        cdn: CdnHandle,
+        cache: CacheHandle,
+        config: &PlaybackConfig,
+        format: ExportFormat,
+        output: &mut impl Write,
+        shutdown: &AtomicBool,
+    ) -> Result<(), Error> {
+        const CHUNK_LENGTH: usize = 64 * 1024;
+        const DEFAULT_MAX_FRAMES: u64 = 8 * 1024;
+
+        let is_cancelled = || shutdown.load(Ordering::Relaxed);
+
+        let path = load_media_path(self.item_id, session, &cache, config.bitrate)?;
+        let key = load_audio_key(&path, session, &cache)?;
+        let file = MediaFile::open(path, cdn, cache, config.buffer_duration)?;
+        match format {
+            ExportFormat::Encoded => {
+                let (mut encoded, _) = file.encoded_stream(key)?;
+                let mut chunk = vec![0; CHUNK_LENGTH];
+                loop {
+                    if is_cancelled() {
+                        return Err(Error::Cancelled);
+                    }
+                    let read = encoded.read(&mut chunk)?;
+                    if read == 0 {
+                        break;
+                    }
+                    output.write_all(&chunk[..read])?;
+                }
+            }
+            ExportFormat::Pcm => {
+                let (mut decoder, _) = file.audio_source(key)?;
+                let max_frames = decoder
+                    .codec_params()
+                    .max_frames_per_packet
+                    .unwrap_or(DEFAULT_MAX_FRAMES);
+                let mut samples = SampleBuffer::<f32>::new(max_frames, decoder.signal_spec());
+                // Unlike the playback, which ends the track early, an export
+                // cut short by a decoding error fails.
+                while decoder.try_read_packet(&mut samples)?.is_some() {
+                    if is_cancelled() {
+                        return Err(Error::Cancelled);
+                    }
+                    for sample in samples.samples() {
+                        output.write_all(&sample.to_le_bytes())?;
+                    }
+                }
+            }
+        }
+        output.flush()?;
+        Ok(())
+    }
+
+    /// Fetch the complete audio file of this item into the cache, so it can be
+    /// played from disk.  `progress` is called with the count of downloaded and
+    /// total bytes.  `cancelled` is checked between chunks, like in `export`.
+    pub fn download(
+        &self,
+        session: &SessionService,
+        cdn: CdnHandle,
+        cache: CacheHandle,
+        config: &PlaybackConfig,
+        cancelled: &AtomicBool,
+        mut progress: impl FnMut(u64, u64),
+    ) -> Result<(), Error> {
+        let bitrate = config.download_bitrate;
+        let path = load_media_path(self.item_id, session, &cache, bitrate)?;
+        // Make sure the audio key is in the cache as well.
+        load_audio_key(&path, session, &cache)?;
+        if !cache.audio_file_path(path.file_id).exists() {
+            download_audio_file(&path, &cdn, &cache, cancelled, &mut progress)?;
+        }
+        let download = Download {
+            file_id: path.file_id,
+            bitrate,
+        };
+        cache.save_download(self.item_id, &download)?;
+        Ok(())
+    }
+
+    pub fn remove_download(&self, cache: CacheHandle) -> Result<(), Error> {
+        if let Some(download) = cache.get_download(self.item_id) {
+            if let Err(err) = cache.remove_audio_file(download.file_id) {
+                log::warn!("failed to remove downloaded audio file: {:?}", err);
+            }
+        }
+        cache.remove_download(self.item_id)
+    }
 }
 
 fn load_media_path(
     item_id: ItemId,
     session: &SessionService,
     cache: &CacheHandle,
-    config: &PlaybackConfig,
+    bitrate: usize,
 ) -> Result<MediaPath, Error> {
     match item_id.id_type {
         ItemIdType::Track => {
-            load_media_path_from_track_or_alternative(item_id, session, cache, config)
+            load_media_path_from_track_or_alternative(item_id, session, cache, bitrate)
         }
-        ItemIdType::Podcast => load_media_path_from_episode(item_id, session, cache, config),
+        ItemIdType::Podcast => load_media_path_from_episode(item_id, session, cache, bitrate),
         ItemIdType::Unknown => unimplemented!(),
     }
 }
@@ -66,7 +186,7 @@ fn load_media_path_from_track_or_alternative(
     item_id: ItemId,
     session: &SessionService,
     cache: &CacheHandle,
-    config: &PlaybackConfig,
+    bitrate: usize,
 ) -> Result<MediaPath, Error> {
     let track = load_track(item_id, session, cache)?;
     let country = get_country_code(session, cache);
@@ -79,7 +199,7 @@ fn load_media_path_from_track_or_alternative(
                 .ok_or(Error::MediaFileNotFound)?;
             let alt_track = load_track(alt_id, session, cache)?;
             let alt_path = alt_track
-                .to_media_path(config.bitrate)
+                .to_media_path(bitrate)
                 .ok_or(Error::MediaFileNotFound)?;
             // We've found an alternative track with a fitting audio file.  Let's cheat a
             // little and pretend we've obtained it from the requested track.
@@ -93,7 +213,7 @@ fn load_media_path_from_track_or_alternative(
             // Either we do not have a country code loaded or the track is available, return
             // it.
             track
-                .to_media_path(config.bitrate)
+                .to_media_path(bitrate)
                 .ok_or(Error::MediaFileNotFound)?
         }
     };
@@ -104,7 +224,7 @@ fn load_media_path_from_episode(
     item_id: ItemId,
     session: &SessionService,
     cache: &CacheHandle,
-    config: &PlaybackConfig,
+    bitrate: usize,
 ) -> Result<MediaPath, Error> {
     let episode = load_episode(item_id, session, cache)?;
     let country = get_country_code(session, cache);
@@ -114,7 +234,7 @@ fn load_media_path_from_episode(
             return Err(Error::MediaFileNotFound);
         }
         _ => episode
-            .to_media_path(config.bitrate)
+            .to_media_path(bitrate)
             .ok_or(Error::MediaFileNotFound)?,
     };
     Ok(path)
@@ -181,3 +301,59 @@ fn load_audio_key(
         Ok(key)
     }
 }
+
+fn download_audio_file(
+    path: &MediaPath,
+    cdn: &CdnHandle,
+    cache: &CacheHandle,
+    cancelled: &AtomicBool,
+    progress: &mut impl FnMut(u64, u64),
+) -> Result<(), Error> {
+    // Download into a partial file first, and only move it into the cache after
+    // the whole content is written.
+    let cached_path = cache.audio_file_path(path.file_id);
+    let part_path = cached_path.with_extension("part");
+    let mut file = File::create(&part_path)?;
+    if let Err(err) = write_audio_file(path, cdn, &mut file, cancelled, progress) {
+        // Don't leave the incomplete content around, e.g. after cancelling.
+        drop(file);
+        if let Err(err) = fs::remove_file(&part_path) {
+            log::warn!("failed to remove partial audio file: {:?}", err);
+        }
+        return Err(err);
+    }
+    drop(file);
+    fs::rename(part_path, cached_path)?;
+    Ok(())
+}
+
+fn write_audio_file(
+    path: &MediaPath,
+    cdn: &CdnHandle,
+    file: &mut File,
+    cancelled: &AtomicBool,
+    progress: &mut impl FnMut(u64, u64),
+) -> Result<(), Error> {
+    const CHUNK_LENGTH: u64 = 512 * 1024;
+
+    let mut url = cdn.resolve_audio_file_url(path.file_id)?;
+    let mut offset = 0;
+    let mut total_length = u64::MAX;
+    while offset < total_length {
+        if cancelled.load(Ordering::Relaxed) {
+            return Err(Error::Cancelled);
+        }
+        if url.is_expired() {
+            url = cdn.resolve_audio_file_url(path.file_id)?;
+        }
+        let (total, mut reader) = cdn.fetch_file_range(&url.url, offset, CHUNK_LENGTH)?;
+        let written = io::copy(&mut reader, file)?;
+        if written == 0 {
+            return Err(Error::UnexpectedResponse);
+        }
+        total_length = total;
+        offset += written;
+        progress(offset, total_length);
+    }
+    Ok(())
+}
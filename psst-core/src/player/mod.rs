@@ -1,3 +1,4 @@
+pub mod download;
 pub mod file;
 pub mod item;
 pub mod queue;
@@ -9,7 +10,10 @@ use std::{mem, thread, thread::JoinHandle, time::Duration};
 use crossbeam_channel::{unbounded, Receiver, Sender};
 
 use crate::{
-    audio::output::{AudioOutput, AudioSink, DefaultAudioOutput, DefaultAudioSink},
+    audio::{
+        output::{AudioOutput, AudioSink, DefaultAudioOutput, DefaultAudioSink},
+        resample::ResamplingQuality,
+    },
     cache::CacheHandle,
     cdn::CdnHandle,
     error::Error,
@@ -17,6 +21,7 @@ use crate::{
 };
 
 use self::{
+    download::{DownloadQueue, DownloadQueueProgress},
     file::MediaPath,
     item::{LoadedPlaybackItem, PlaybackItem},
     queue::{Queue, QueueBehavior},
@@ -29,14 +34,29 @@ const STOP_AFTER_CONSECUTIVE_LOADING_FAILURES: usize = 3;
 #[derive(Clone)]
 pub struct PlaybackConfig {
     pub bitrate: usize,
+    /// Bitrate of the items downloaded for offline playback.
+    pub download_bitrate: usize,
     pub pregain: f32,
+    /// Skip tracks that can't be played (e.g. region-locked) instead of
+    /// stopping the playback.
+    pub skip_unavailable: bool,
+    /// Amount of audio to keep downloaded ahead of the play-head.  Streamed
+    /// items start playing only after this much is buffered.
+    pub buffer_duration: Duration,
+    /// Converter used when the output device runs at a different sample rate
+    /// than the item, applies from the next item on.
+    pub resampling_quality: ResamplingQuality,
 }
 
 impl Default for PlaybackConfig {
     fn default() -> Self {
         Self {
             bitrate: 320,
+            download_bitrate: 320,
             pregain: 3.0,
+            skip_unavailable: true,
+            buffer_duration: Duration::from_secs(2),
+            resampling_quality: ResamplingQuality::SincMediumQuality,
         }
     }
 }
@@ -53,7 +73,10 @@ pub struct Player {
     receiver: Receiver<PlayerEvent>,
     audio_output_sink: DefaultAudioSink,
     playback_mgr: PlaybackManager,
+    downloads: DownloadQueue,
     consecutive_loading_failures: usize,
+    /// Paused while loading, the loaded item starts out paused.
+    start_paused: bool,
 }
 
 impl Player {
@@ -67,6 +90,7 @@ impl Player {
         let (sender, receiver) = unbounded();
         Self {
             playback_mgr: PlaybackManager::new(audio_output.sink(), sender.clone()),
+            downloads: DownloadQueue::new(sender.clone()),
             session,
             cdn,
             cache,
@@ -78,6 +102,7 @@ impl Player {
             preload: PreloadState::None,
             queue: Queue::new(),
             consecutive_loading_failures: 0,
+            start_paused: false,
         }
     }
 
@@ -96,18 +121,35 @@ impl Player {
             PlayerEvent::Preloaded { item, result } => self.handle_preloaded(item, result),
             PlayerEvent::Position { position, path } => self.handle_position(position, path),
             PlayerEvent::EndOfTrack { .. } => self.handle_end_of_track(),
+            PlayerEvent::Downloaded { item, result } => self.handle_downloaded(item, result),
             PlayerEvent::Loading { .. }
+            | PlayerEvent::DownloadQueued { .. }
+            | PlayerEvent::Downloading { .. }
+            | PlayerEvent::DownloadQueueChanged { .. }
             | PlayerEvent::Playing { .. }
             | PlayerEvent::Pausing { .. }
             | PlayerEvent::Resuming { .. }
             | PlayerEvent::Stopped { .. }
+            | PlayerEvent::QueueReordered { .. }
+            | PlayerEvent::Unavailable { .. }
             | PlayerEvent::Blocked { .. } => {}
         };
     }
 
     fn handle_command(&mut self, cmd: PlayerCommand) {
         match cmd {
-            PlayerCommand::LoadQueue { items, position } => self.load_queue(items, position),
+            PlayerCommand::LoadQueue {
+                items,
+                position,
+                shuffle_weights,
+            } => self.load_queue(items, position, shuffle_weights),
+            PlayerCommand::InsertIntoQueue { index, item } => self.queue.insert(index, item),
+            PlayerCommand::InsertNextInQueue { items } => self.queue.insert_next(items),
+            PlayerCommand::RemoveFromQueue { index } => {
+                self.queue.remove(index);
+            }
+            PlayerCommand::MoveInQueue { from, to } => self.queue.move_item(from, to),
+            PlayerCommand::ShuffleRemaining => self.shuffle_remaining(),
             PlayerCommand::LoadAndPlay { item } => self.load_and_play(item),
             PlayerCommand::Preload { item } => self.preload(item),
             PlayerCommand::Pause => self.pause(),
@@ -120,6 +162,8 @@ impl Player {
             PlayerCommand::Configure { config } => self.configure(config),
             PlayerCommand::SetQueueBehavior { behavior } => self.queue.set_behaviour(behavior),
             PlayerCommand::SetVolume { volume } => self.set_volume(volume),
+            PlayerCommand::Download { item } => self.download(item),
+            PlayerCommand::RemoveDownload { item } => self.remove_download(item),
         }
     }
 
@@ -133,6 +177,24 @@ impl Player {
                     self.consecutive_loading_failures = 0;
                     self.play_loaded(loaded_item);
                 }
+                Err(Error::MediaFileNotFound) => {
+                    // The track is not available for playback at all.  Skip it if configured to,
+                    // but stop once we went through the whole queue without finding anything
+                    // playable.
+                    self.consecutive_loading_failures += 1;
+                    let skipped = self.config.skip_unavailable
+                        && self.consecutive_loading_failures < self.queue.len();
+                    self.sender
+                        .send(PlayerEvent::Unavailable { item, skipped })
+                        .unwrap();
+                    if skipped {
+                        log::warn!("skipping, track is unavailable");
+                        self.next();
+                    } else {
+                        log::warn!("stopping, track is unavailable");
+                        self.stop();
+                    }
+                }
                 Err(err) => {
                     self.consecutive_loading_failures += 1;
                     if self.consecutive_loading_failures < STOP_AFTER_CONSECUTIVE_LOADING_FAILURES {
@@ -203,8 +265,13 @@ impl Player {
         }
     }
 
-    fn load_queue(&mut self, items: Vec<PlaybackItem>, position: usize) {
-        self.queue.fill(items, position);
+    fn load_queue(
+        &mut self,
+        items: Vec<PlaybackItem>,
+        position: usize,
+        shuffle_weights: Option<Vec<f64>>,
+    ) {
+        self.queue.fill(items, position, shuffle_weights);
         if let Some(&item) = self.queue.get_current() {
             self.load_and_play(item);
         } else {
@@ -283,19 +350,60 @@ impl Player {
         };
     }
 
+    fn download(&mut self, item: PlaybackItem) {
+        self.downloads.push(item);
+        self.start_next_download();
+    }
+
+    fn handle_downloaded(&mut self, item: PlaybackItem, result: Result<(), Error>) {
+        self.downloads.finish(item, &result);
+        self.start_next_download();
+    }
+
+    fn start_next_download(&mut self) {
+        self.downloads
+            .start_next(&self.session, &self.cdn, &self.cache, &self.config);
+    }
+
+    fn remove_download(&mut self, item: PlaybackItem) {
+        if self.downloads.cancel(item) {
+            return;
+        }
+        if let Err(err) = item.remove_download(self.cache.clone()) {
+            log::error!("failed to remove downloaded item: {}", err);
+        }
+    }
+
+    fn shuffle_remaining(&mut self) {
+        if let Some(order) = self.queue.shuffle_remaining() {
+            self.sender
+                .send(PlayerEvent::QueueReordered { order })
+                .unwrap();
+        }
+    }
+
     fn set_volume(&mut self, volume: f64) {
         self.audio_output_sink.set_volume(volume as f32);
     }
 
     fn play_loaded(&mut self, loaded_item: LoadedPlaybackItem) {
-        log::info!("starting playback");
+        let paused = mem::take(&mut self.start_paused);
+        log::info!("starting playback, paused: {}", paused);
         let path = loaded_item.file.path();
         let position = Duration::default();
-        self.playback_mgr.play(loaded_item);
-        self.state = PlayerState::Playing { path, position };
+        self.playback_mgr
+            .play(loaded_item, self.config.resampling_quality, paused);
         self.sender
             .send(PlayerEvent::Playing { path, position })
             .unwrap();
+        if paused {
+            self.sender
+                .send(PlayerEvent::Pausing { path, position })
+                .unwrap();
+            self.state = PlayerState::Paused { path, position };
+        } else {
+            self.state = PlayerState::Playing { path, position };
+        }
     }
 
     fn pause(&mut self) {
@@ -308,6 +416,11 @@ impl Player {
                     .unwrap();
                 self.state = PlayerState::Paused { path, position };
             }
+            loading @ PlayerState::Loading { .. } => {
+                log::info!("pausing once loaded");
+                self.start_paused = true;
+                self.state = loading;
+            }
             _ => {
                 log::warn!("invalid state transition");
             }
@@ -324,6 +437,10 @@ impl Player {
                     .unwrap();
                 self.state = PlayerState::Playing { path, position };
             }
+            loading @ PlayerState::Loading { .. } => {
+                self.start_paused = false;
+                self.state = loading;
+            }
             _ => {
                 log::warn!("invalid state transition");
             }
@@ -368,6 +485,7 @@ impl Player {
         self.state = PlayerState::Stopped;
         self.queue.clear();
         self.consecutive_loading_failures = 0;
+        self.start_paused = false;
     }
 
     fn seek(&mut self, position: Duration) {
@@ -375,6 +493,11 @@ impl Player {
     }
 
     fn configure(&mut self, config: PlaybackConfig) {
+        if self.config.bitrate != config.bitrate {
+            // Any preloaded item was opened with the previous bitrate, drop it, so the
+            // following track is loaded with the new configuration.
+            self.preload = PreloadState::None;
+        }
         self.config = config;
     }
 
@@ -400,7 +523,31 @@ pub enum PlayerCommand {
     LoadQueue {
         items: Vec<PlaybackItem>,
         position: usize,
+        /// Chances of `items` to get played early in random order, relative
+        /// to each other.  `None` shuffles uniformly.
+        shuffle_weights: Option<Vec<f64>>,
+    },
+    /// Queue edits keep the currently playing item playing, indices are into
+    /// the queue order, as given in `LoadQueue`.
+    InsertIntoQueue {
+        index: usize,
+        item: PlaybackItem,
     },
+    /// Insert items to play right after the current one, also in random
+    /// order.
+    InsertNextInQueue {
+        items: Vec<PlaybackItem>,
+    },
+    RemoveFromQueue {
+        index: usize,
+    },
+    MoveInQueue {
+        from: usize,
+        to: usize,
+    },
+    /// Shuffle the items after the current one, leaving the already played
+    /// items alone.
+    ShuffleRemaining,
     LoadAndPlay {
         item: PlaybackItem,
     },
@@ -426,6 +573,16 @@ pub enum PlayerCommand {
     SetVolume {
         volume: f64,
     },
+    /// Queue the complete audio file of an item for download into the
+    /// cache.
+    Download {
+        item: PlaybackItem,
+    },
+    /// Remove a previously downloaded item from the cache, or cancel its
+    /// download if it's not finished yet.
+    RemoveDownload {
+        item: PlaybackItem,
+    },
 }
 
 pub enum PlayerEvent {
@@ -446,6 +603,26 @@ pub enum PlayerEvent {
         item: PlaybackItem,
         result: Result<LoadedPlaybackItem, Error>,
     },
+    /// Item is waiting for its download.  `Downloading` follows.
+    DownloadQueued {
+        item: PlaybackItem,
+    },
+    /// Download of an item is in progress.  `Downloaded` follows.
+    Downloading {
+        item: PlaybackItem,
+        downloaded: u64,
+        total: u64,
+    },
+    /// Item download either succeeded, failed, or was cancelled with
+    /// `Error::Cancelled`.
+    Downloaded {
+        item: PlaybackItem,
+        result: Result<(), Error>,
+    },
+    /// Item got queued for download, or its download finished.
+    DownloadQueueChanged {
+        progress: DownloadQueueProgress,
+    },
     /// Player has started playing new track.  `Position` events will follow.
     Playing {
         path: MediaPath,
@@ -466,11 +643,23 @@ pub enum PlayerEvent {
         path: MediaPath,
         position: Duration,
     },
+    /// Track can't be played, e.g. because of regional restrictions.  If
+    /// `skipped` is true, `Loading` of the next track in queue follows,
+    /// otherwise `Stopped` does.
+    Unavailable {
+        item: PlaybackItem,
+        skipped: bool,
+    },
     /// Player would like to continue playing, but is blocked, waiting for I/O.
     Blocked {
         path: MediaPath,
         position: Duration,
     },
+    /// Items of the queue got reordered, `order[i]` is the previous index of
+    /// the item now at `i`.
+    QueueReordered {
+        order: Vec<usize>,
+    },
     /// Player has finished playing a track.  `Loading` or `Playing` might
     /// follow if the queue is not empty, `Stopped` will follow if it is.
     EndOfTrack,
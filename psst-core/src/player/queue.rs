@@ -1,4 +1,6 @@
-use rand::prelude::SliceRandom;
+use std::cmp::Ordering;
+
+use rand::{prelude::SliceRandom, Rng};
 
 use super::PlaybackItem;
 
@@ -21,6 +23,12 @@ pub struct Queue {
     position: usize,
     positions: Vec<usize>,
     behavior: QueueBehavior,
+    /// The current item has been removed from the queue, and `position`
+    /// already points to the one that would follow it.
+    removed_current: bool,
+    /// Shuffle weights of `items`, see `weighted_shuffle`.  `None` shuffles
+    /// uniformly.
+    weights: Option<Vec<f64>>,
 }
 
 impl Queue {
@@ -30,6 +38,8 @@ impl Queue {
             position: 0,
             positions: Vec::new(),
             behavior: QueueBehavior::default(),
+            removed_current: false,
+            weights: None,
         }
     }
 
@@ -37,14 +47,170 @@ impl Queue {
         self.items.clear();
         self.positions.clear();
         self.position = 0;
+        self.removed_current = false;
+        self.weights = None;
     }
 
-    pub fn fill(&mut self, items: Vec<PlaybackItem>, position: usize) {
+    /// Replace the queue with `items`, starting at `position`.  In random
+    /// order, items with larger `weights` tend to play sooner, `None` gives
+    /// all of them the same chance.
+    pub fn fill(&mut self, items: Vec<PlaybackItem>, position: usize, weights: Option<Vec<f64>>) {
+        self.position = position.min(items.len().saturating_sub(1));
+        self.weights = weights.filter(|weights| weights.len() == items.len());
         self.items = items;
-        self.position = position;
+        self.removed_current = false;
         self.compute_positions();
     }
 
+    /// Insert an item at `index` of the queue order.  In random order, it gets
+    /// played at a random point after the current item.
+    pub fn insert(&mut self, index: usize, item: PlaybackItem) {
+        let index = index.min(self.items.len());
+        let slot = match self.behavior {
+            QueueBehavior::Random => {
+                let start = (self.position + 1).min(self.positions.len());
+                rand::thread_rng().gen_range(start..=self.positions.len())
+            }
+            _ => index,
+        };
+        self.insert_at(index, slot, item);
+    }
+
+    /// Insert `items` right after the current item, both in the queue order
+    /// and in the play order, so they play next even in random order.
+    pub fn insert_next(&mut self, items: Vec<PlaybackItem>) {
+        let current = self.positions.get(self.position).copied();
+        let (mut index, mut slot) = match current {
+            // `position` already points to the item following the removed one.
+            Some(following) if self.removed_current => (following, self.position),
+            Some(current) => (current + 1, self.position + 1),
+            None => (self.items.len(), self.positions.len()),
+        };
+        for item in items {
+            self.insert_at(index, slot, item);
+            index += 1;
+            slot += 1;
+        }
+    }
+
+    /// Insert an item at `index` of the queue order and `slot` of the play
+    /// order.
+    fn insert_at(&mut self, index: usize, slot: usize, item: PlaybackItem) {
+        self.items.insert(index, item);
+        if let Some(weights) = &mut self.weights {
+            weights.insert(index, 1.0);
+        }
+        for p in &mut self.positions {
+            if *p >= index {
+                *p += 1;
+            }
+        }
+        self.positions.insert(slot, index);
+        if slot < self.position || (slot == self.position && !self.removed_current) {
+            self.position += 1;
+        }
+    }
+
+    /// Remove the item at `index` of the queue order, keeping the current item
+    /// in place.  Removing the current item makes the following one play next.
+    pub fn remove(&mut self, index: usize) -> Option<PlaybackItem> {
+        if index >= self.items.len() {
+            return None;
+        }
+        let item = self.items.remove(index);
+        if let Some(weights) = &mut self.weights {
+            weights.remove(index);
+        }
+        if let Some(slot) = self.positions.iter().position(|&p| p == index) {
+            self.positions.remove(slot);
+            if slot < self.position {
+                self.position -= 1;
+            } else if slot == self.position {
+                self.removed_current = true;
+            }
+        }
+        for p in &mut self.positions {
+            if *p > index {
+                *p -= 1;
+            }
+        }
+        Some(item)
+    }
+
+    /// Move the item at `from` to `to` in the queue order, keeping the current
+    /// item in place.
+    pub fn move_item(&mut self, from: usize, to: usize) {
+        if from >= self.items.len() || to >= self.items.len() || from == to {
+            return;
+        }
+        let item = self.items.remove(from);
+        self.items.insert(to, item);
+        if let Some(weights) = &mut self.weights {
+            let weight = weights.remove(from);
+            weights.insert(to, weight);
+        }
+        let remap = |p: usize| {
+            if p == from {
+                to
+            } else if from < to && (from + 1..=to).contains(&p) {
+                p - 1
+            } else if to < from && (to..from).contains(&p) {
+                p + 1
+            } else {
+                p
+            }
+        };
+        match self.behavior {
+            QueueBehavior::Random => {
+                for p in &mut self.positions {
+                    *p = remap(*p);
+                }
+            }
+            _ => {
+                // Play order follows the queue order, find the current item in it again.
+                if let Some(&current) = self.positions.get(self.position) {
+                    self.position = remap(current);
+                }
+            }
+        }
+    }
+
+    /// Shuffle the items that haven't played yet, keeping the played ones and
+    /// the current item in place.  Unlike random behavior, the shuffled order
+    /// stays once the queue gets back to sequential playback.  Returns the new
+    /// queue order as indices into the previous one if the items themselves
+    /// moved, which they don't in random order.
+    pub fn shuffle_remaining(&mut self) -> Option<Vec<usize>> {
+        // A removed current item leaves `position` at the next unplayed item.
+        let start = if self.removed_current {
+            self.position
+        } else {
+            self.position + 1
+        };
+        let mut rng = rand::thread_rng();
+        match self.behavior {
+            QueueBehavior::Random => {
+                if let Some(rest) = self.positions.get_mut(start..) {
+                    rest.shuffle(&mut rng);
+                }
+                None
+            }
+            _ => {
+                // Play order follows the queue order, so shuffle the items
+                // themselves, together with their weights.
+                let mut order: Vec<usize> = (0..self.items.len()).collect();
+                if let Some(rest) = order.get_mut(start..) {
+                    rest.shuffle(&mut rng);
+                }
+                self.items = order.iter().map(|&i| self.items[i]).collect();
+                if let Some(weights) = &mut self.weights {
+                    *weights = order.iter().map(|&i| weights[i]).collect();
+                }
+                Some(order)
+            }
+        }
+    }
+
     pub fn set_behaviour(&mut self, behavior: QueueBehavior) {
         self.behavior = behavior;
         self.compute_positions();
@@ -60,7 +226,11 @@ impl Queue {
             // items and set the position to 0.
             if self.positions.len() > 1 {
                 self.positions.swap(0, self.position);
-                self.positions[1..].shuffle(&mut rand::thread_rng());
+                let rest = &mut self.positions[1..];
+                match &self.weights {
+                    Some(weights) => weighted_shuffle(rest, weights, &mut rand::thread_rng()),
+                    None => rest.shuffle(&mut rand::thread_rng()),
+                }
             }
             self.position = 0;
         }
@@ -68,14 +238,25 @@ impl Queue {
 
     pub fn skip_to_previous(&mut self) {
         self.position = self.previous_position();
+        self.removed_current = false;
     }
 
     pub fn skip_to_next(&mut self) {
         self.position = self.next_position();
+        self.removed_current = false;
     }
 
     pub fn skip_to_following(&mut self) {
         self.position = self.following_position();
+        self.removed_current = false;
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
     }
 
     pub fn get_current(&self) -> Option<&PlaybackItem> {
@@ -98,6 +279,9 @@ impl Queue {
     }
 
     fn next_position(&self) -> usize {
+        if self.removed_current {
+            return self.wrapped_current_position();
+        }
         match self.behavior {
             QueueBehavior::Sequential | QueueBehavior::Random | QueueBehavior::LoopTrack => {
                 self.position + 1
@@ -107,10 +291,260 @@ impl Queue {
     }
 
     fn following_position(&self) -> usize {
+        if self.removed_current {
+            return self.wrapped_current_position();
+        }
         match self.behavior {
             QueueBehavior::Sequential | QueueBehavior::Random => self.position + 1,
             QueueBehavior::LoopTrack => self.position,
             QueueBehavior::LoopAll => (self.position + 1) % self.items.len(),
         }
     }
+
+    /// Position of the item following a removed current item.
+    fn wrapped_current_position(&self) -> usize {
+        match self.behavior {
+            QueueBehavior::LoopAll if self.position >= self.items.len() => 0,
+            _ => self.position,
+        }
+    }
+}
+
+/// Shuffle `positions` so that items with larger `weights` tend to come
+/// sooner.  Every position gets a random key `u^(1/weight)` and the positions
+/// are sorted by it (Efraimidis-Spirakis sampling), so the result is still a
+/// permutation and every item gets played eventually, the weights only change
+/// how early.
+fn weighted_shuffle(positions: &mut [usize], weights: &[f64], rng: &mut impl Rng) {
+    const MIN_WEIGHT: f64 = 1e-3;
+
+    let mut keyed: Vec<(f64, usize)> = positions
+        .iter()
+        .map(|&p| {
+            let weight = weights.get(p).copied().unwrap_or(1.0).max(MIN_WEIGHT);
+            let key = rng.gen::<f64>().powf(1.0 / weight);
+            (key, p)
+        })
+        .collect();
+    keyed.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal));
+    for (slot, (_, p)) in positions.iter_mut().zip(keyed) {
+        *slot = p;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use crate::{
+        audio::normalize::NormalizationLevel,
+        item_id::{ItemId, ItemIdType},
+    };
+
+    use super::*;
+
+    fn item(id: u128) -> PlaybackItem {
+        PlaybackItem {
+            item_id: ItemId::new(id, ItemIdType::Track),
+            norm_level: NormalizationLevel::Track,
+        }
+    }
+
+    fn queue(len: u128, position: usize) -> Queue {
+        let mut queue = Queue::new();
+        queue.fill((0..len).map(item).collect(), position, None);
+        queue
+    }
+
+    #[test]
+    fn shuffle_remaining_keeps_the_played_items() {
+        let mut queue = queue(10, 3);
+        let before = queue.items.clone();
+        let order = queue.shuffle_remaining().unwrap();
+        assert_eq!(queue.items[..4], before[..4]);
+        assert_eq!(order[..4], [0, 1, 2, 3]);
+        let mut rest = order[4..].to_vec();
+        rest.sort_unstable();
+        assert_eq!(rest, (4..10).collect::<Vec<_>>());
+        for (i, &previous) in order.iter().enumerate() {
+            assert_eq!(queue.items[i], before[previous]);
+        }
+        assert_eq!(queue.get_current(), Some(&item(3)));
+    }
+
+    #[test]
+    fn shuffle_remaining_in_random_order_keeps_the_items() {
+        let mut queue = queue(10, 3);
+        queue.set_behaviour(QueueBehavior::Random);
+        let before = queue.items.clone();
+        let current = queue.get_current().copied();
+        assert!(queue.shuffle_remaining().is_none());
+        assert_eq!(queue.items, before);
+        assert_eq!(queue.get_current().copied(), current);
+    }
+
+    #[test]
+    fn fill_clamps_the_position() {
+        let mut queue = queue(3, 7);
+        assert_eq!(queue.get_current(), Some(&item(2)));
+        queue.fill(Vec::new(), 2, None);
+        assert_eq!(queue.get_current(), None);
+    }
+
+    #[test]
+    fn weighted_shuffle_is_a_permutation() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let weights = [1.0, 5.0, 0.0, 2.0, 1.0];
+        let mut positions = [0, 1, 2, 3, 4];
+        weighted_shuffle(&mut positions, &weights, &mut rng);
+        let mut sorted = positions;
+        sorted.sort_unstable();
+        assert_eq!(sorted, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn weighted_shuffle_plays_heavier_items_sooner() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let weights = [1.0, 1.0, 1.0, 20.0];
+        let mut firsts = [0; 4];
+        for _ in 0..1000 {
+            let mut positions = [0, 1, 2, 3];
+            weighted_shuffle(&mut positions, &weights, &mut rng);
+            firsts[positions[0]] += 1;
+        }
+        // The heavy item has a 20/23 chance to come first.
+        assert!(firsts[3] > 800, "{:?}", firsts);
+        assert!(firsts[..3].iter().all(|&n| n > 0), "{:?}", firsts);
+    }
+
+    #[test]
+    fn weighted_shuffle_is_deterministic_for_a_seed() {
+        let weights = [3.0, 1.0, 2.0, 1.0, 4.0, 1.0];
+        let shuffle = |seed| {
+            let mut positions = [0, 1, 2, 3, 4, 5];
+            weighted_shuffle(&mut positions, &weights, &mut StdRng::seed_from_u64(seed));
+            positions
+        };
+        assert_eq!(shuffle(3), shuffle(3));
+    }
+
+    fn play_order(queue: &mut Queue) -> Vec<PlaybackItem> {
+        let mut order = Vec::new();
+        while let Some(&item) = queue.get_current() {
+            order.push(item);
+            queue.skip_to_next();
+        }
+        order
+    }
+
+    #[test]
+    fn insert_keeps_the_current_item() {
+        let mut queue = queue(4, 2);
+        queue.insert(0, item(10));
+        assert_eq!(queue.get_current(), Some(&item(2)));
+        assert_eq!(queue.get_following(), Some(&item(3)));
+        queue.insert(4, item(11));
+        assert_eq!(queue.get_current(), Some(&item(2)));
+        assert_eq!(queue.get_following(), Some(&item(11)));
+    }
+
+    #[test]
+    fn insert_keeps_the_current_item_in_random_order() {
+        let mut queue = queue(6, 2);
+        queue.set_behaviour(QueueBehavior::Random);
+        queue.skip_to_next();
+        let current = *queue.get_current().unwrap();
+        queue.insert(0, item(10));
+        assert_eq!(queue.get_current(), Some(&current));
+        let order = play_order(&mut queue);
+        assert!(order.contains(&item(10)));
+    }
+
+    #[test]
+    fn remove_keeps_the_current_item() {
+        let mut queue = queue(5, 2);
+        assert_eq!(queue.remove(0), Some(item(0)));
+        assert_eq!(queue.get_current(), Some(&item(2)));
+        assert_eq!(queue.remove(3), Some(item(4)));
+        assert_eq!(queue.get_current(), Some(&item(2)));
+        assert_eq!(queue.get_following(), Some(&item(3)));
+        assert_eq!(queue.remove(10), None);
+    }
+
+    #[test]
+    fn removing_the_current_item_plays_the_following_one() {
+        let mut queue = queue(4, 1);
+        queue.remove(1);
+        assert_eq!(queue.get_following(), Some(&item(2)));
+        queue.skip_to_next();
+        assert_eq!(play_order(&mut queue), [item(2), item(3)]);
+    }
+
+    #[test]
+    fn move_item_keeps_the_current_item() {
+        let mut queue = queue(5, 2);
+        queue.move_item(4, 0);
+        assert_eq!(queue.items, [item(4), item(0), item(1), item(2), item(3)]);
+        assert_eq!(queue.get_current(), Some(&item(2)));
+        queue.move_item(3, 4);
+        assert_eq!(queue.get_current(), Some(&item(2)));
+        assert_eq!(play_order(&mut queue), [item(2)]);
+    }
+
+    #[test]
+    fn move_item_keeps_the_current_item_in_random_order() {
+        let mut queue = queue(6, 2);
+        queue.set_behaviour(QueueBehavior::Random);
+        let current = *queue.get_current().unwrap();
+        queue.move_item(5, 0);
+        queue.move_item(1, 4);
+        assert_eq!(queue.get_current(), Some(&current));
+        let mut order = play_order(&mut queue);
+        order.sort_unstable_by_key(|played| played.item_id.id);
+        assert_eq!(order, (0..6).map(item).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn insert_at_the_end_plays_last() {
+        let mut queue = queue(3, 1);
+        queue.insert(3, item(10));
+        assert_eq!(play_order(&mut queue), [item(1), item(2), item(10)]);
+    }
+
+    #[test]
+    fn insert_next_plays_right_after_the_current_item() {
+        let mut queue = queue(4, 1);
+        queue.insert_next(vec![item(10), item(11)]);
+        assert_eq!(
+            queue.items,
+            [item(0), item(1), item(10), item(11), item(2), item(3)]
+        );
+        assert_eq!(
+            play_order(&mut queue),
+            [item(1), item(10), item(11), item(2), item(3)]
+        );
+    }
+
+    #[test]
+    fn insert_next_plays_right_after_the_current_item_in_random_order() {
+        let mut queue = queue(10, 4);
+        queue.set_behaviour(QueueBehavior::Random);
+        queue.skip_to_next();
+        let current = *queue.get_current().unwrap();
+        queue.insert_next(vec![item(10), item(11)]);
+        assert_eq!(queue.get_current(), Some(&current));
+        let order = play_order(&mut queue);
+        assert_eq!(order[..3], [current, item(10), item(11)]);
+        assert_eq!(order.len(), 11);
+    }
+
+    #[test]
+    fn insert_next_after_removing_the_current_item() {
+        let mut queue = queue(4, 1);
+        queue.remove(1);
+        queue.insert_next(vec![item(10)]);
+        // The player moves on from the removed item.
+        queue.skip_to_next();
+        assert_eq!(play_order(&mut queue), [item(10), item(2), item(3)]);
+    }
 }
@@ -5,6 +5,7 @@ use std::{
     ops::Range,
     path::{Path, PathBuf},
     sync::Arc,
+    time::{Duration, Instant},
 };
 
 use crossbeam_channel::{unbounded, Receiver, Sender};
@@ -22,12 +23,15 @@ pub struct StreamStorage {
     data_map: Arc<StreamDataMap>,
     req_receiver: Receiver<StreamRequest>,
     req_sender: Sender<StreamRequest>,
+    /// Number of bytes to keep requested in front of the reading head.
+    prefetch_length: u64,
 }
 
 pub struct StreamReader {
     reader: File,
     data_map: Arc<StreamDataMap>,
     req_sender: Sender<StreamRequest>,
+    prefetch_length: u64,
 }
 
 pub struct StreamWriter {
@@ -36,7 +40,7 @@ pub struct StreamWriter {
 }
 
 impl StreamStorage {
-    pub fn new(total_size: u64) -> io::Result<StreamStorage> {
+    pub fn new(total_size: u64, prefetch_length: u64) -> io::Result<StreamStorage> {
         // Use a temporary file for the backing storage, stretched to the full size, so
         // we can seek freely.
         let tmp_file = NamedTempFile::new()?;
@@ -49,6 +53,7 @@ impl StreamStorage {
             file: StreamFile::Temporary(tmp_file),
             req_receiver: data_req_receiver,
             req_sender: data_req_sender,
+            prefetch_length: prefetch_length.max(PREFETCH_READ_LENGTH),
             data_map: Arc::new(StreamDataMap {
                 total_size,
                 downloaded: Mutex::new(RangeSet::new()),
@@ -77,6 +82,7 @@ impl StreamStorage {
             file: StreamFile::Persisted(path),
             req_receiver: data_req_receiver,
             req_sender: data_req_sender,
+            prefetch_length: PREFETCH_READ_LENGTH,
             data_map: Arc::new(StreamDataMap {
                 total_size,
                 downloaded: Mutex::new(downloaded_set),
@@ -91,9 +97,19 @@ impl StreamStorage {
             reader: self.file.reopen()?, // Re-opened files have a starting seek position.
             data_map: self.data_map.clone(),
             req_sender: self.req_sender.clone(),
+            prefetch_length: self.prefetch_length,
         })
     }
 
+    /// Request the prefetched range at the beginning of the file and block until
+    /// it's downloaded.  Returns `false` if `timeout` elapsed first.
+    pub fn wait_for_prefetch(&self, timeout: Duration) -> bool {
+        let length = self.prefetch_length.min(self.data_map.total_size);
+        request_range(&self.data_map, &self.req_sender, 0, length);
+        self.data_map
+            .wait_for_range(0, length, Instant::now() + timeout)
+    }
+
     pub fn writer(&self) -> io::Result<StreamWriter> {
         Ok(StreamWriter {
             writer: self.file.reopen()?, // Re-opened files have a starting seek position.
@@ -172,20 +188,10 @@ impl Read for StreamReader {
         }
         let needed_len = remaining_len.min(buf.len() as u64);
 
-        // Make sure that at least `PREFETCH_READ_LENGTH` bytes in front of the reading
-        // head is requested.
-        let prefetch_len = needed_len.max(PREFETCH_READ_LENGTH).min(remaining_len);
-        for (pos, len) in self.data_map.not_yet_requested(position, prefetch_len) {
-            let req_pos = round_down_to_multiple(pos, 4);
-            let req_len = round_up_to_multiple(len, 4).max(MINIMUM_READ_LENGTH);
-            self.data_map.mark_as_requested(req_pos, req_len);
-            self.req_sender
-                .send(StreamRequest::Preload {
-                    offset: req_pos,
-                    length: req_len,
-                })
-                .expect("Data request channel was closed");
-        }
+        // Make sure that at least `prefetch_length` bytes in front of the reading head
+        // is requested.
+        let prefetch_len = needed_len.max(self.prefetch_length).min(remaining_len);
+        request_range(&self.data_map, &self.req_sender, position, prefetch_len);
 
         // Block and wait until at least a part of the range is available, and read it.
         let ready_to_read_len = self.data_map.wait_for(position, |offset| {
@@ -207,6 +213,26 @@ impl Seek for StreamReader {
     }
 }
 
+/// Request the parts of `offset..offset+length` that are not requested yet.
+fn request_range(
+    data_map: &StreamDataMap,
+    req_sender: &Sender<StreamRequest>,
+    offset: u64,
+    length: u64,
+) {
+    for (pos, len) in data_map.not_yet_requested(offset, length) {
+        let req_pos = round_down_to_multiple(pos, 4);
+        let req_len = round_up_to_multiple(len, 4).max(MINIMUM_READ_LENGTH);
+        data_map.mark_as_requested(req_pos, req_len);
+        req_sender
+            .send(StreamRequest::Preload {
+                offset: req_pos,
+                length: req_len,
+            })
+            .expect("Data request channel was closed");
+    }
+}
+
 fn round_down_to_multiple(n: u64, m: u64) -> u64 {
     n - n % m
 }
@@ -286,6 +312,23 @@ impl StreamDataMap {
         }
     }
 
+    /// Block until the whole `offset..offset+length` range is downloaded, or
+    /// until `deadline`.  Returns `false` on timeout.
+    fn wait_for_range(&self, offset: u64, length: u64, deadline: Instant) -> bool {
+        let range = offset..offset + length;
+        let mut downloaded = self.downloaded.lock();
+        while downloaded.gaps(&range).next().is_some() {
+            if self
+                .condvar
+                .wait_until(&mut downloaded, deadline)
+                .timed_out()
+            {
+                return false;
+            }
+        }
+        true
+    }
+
     // Returns true if data is completely downloaded.
     fn is_complete(&self) -> bool {
         self.downloaded
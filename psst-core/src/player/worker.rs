@@ -45,7 +45,9 @@ impl PlaybackManager {
         }
     }
 
-    pub fn play(&mut self, loaded: LoadedPlaybackItem) {
+    /// Start playing `loaded`, or only queue it into the sink if `paused`, so
+    /// not a single sample gets out before resuming.
+    pub fn play(&mut self, loaded: LoadedPlaybackItem, quality: ResamplingQuality, paused: bool) {
         let path = loaded.file.path();
         let source = DecoderSource::new(
             loaded.file,
@@ -62,17 +64,15 @@ impl PlaybackManager {
         } else {
             // Some output streams have different sample rate than the source, so we need to
             // resample before pushing to the sink.
-            let source = ResampledSource::new(
-                source,
-                self.sink.sample_rate(),
-                ResamplingQuality::SincMediumQuality,
-            );
+            let source = ResampledSource::new(source, self.sink.sample_rate(), quality);
             // Source output streams also have a different channel count. Map the stereo
             // channels and silence the others.
             let source = StereoMappedSource::new(source, self.sink.channel_count());
             self.sink.play(source);
         }
-        self.sink.resume();
+        if !paused {
+            self.sink.resume();
+        }
     }
 
     pub fn seek(&self, position: Duration) {
@@ -16,6 +16,10 @@ use crate::{
 pub struct MercuryDispatcher {
     sequence: Sequence<u64>,
     pending: HashMap<u64, Pending>,
+    /// Parts of the pushed messages that didn't arrive whole yet.
+    pending_pubs: HashMap<u64, Vec<Msg>>,
+    /// URI prefixes and the channels their pushed messages go to.
+    subscriptions: Vec<(String, Sender<MercuryResponse>)>,
 }
 
 impl MercuryDispatcher {
@@ -23,6 +27,8 @@ impl MercuryDispatcher {
         Self {
             sequence: Sequence::new(0),
             pending: HashMap::new(),
+            pending_pubs: HashMap::new(),
+            subscriptions: Vec::new(),
         }
     }
 
@@ -42,6 +48,20 @@ impl MercuryDispatcher {
         ShannonMsg::new(ShannonMsg::MERCURY_REQ, req.encode_to_mercury_message(seq))
     }
 
+    /// Like `enqueue_request`, but also route all messages later pushed to
+    /// URIs starting with the URI of `req` to `events`.  The response to the
+    /// subscription itself goes to `callback`.
+    pub fn enqueue_subscription(
+        &mut self,
+        req: MercuryRequest,
+        callback: Sender<MercuryResponse>,
+        events: Sender<MercuryResponse>,
+    ) -> ShannonMsg {
+        self.subscriptions.push((req.uri.clone(), events));
+        let msg = self.enqueue_request(req, callback);
+        ShannonMsg::new(ShannonMsg::MERCURY_SUB, msg.payload)
+    }
+
     pub fn handle_mercury_req(&mut self, shannon_msg: ShannonMsg) {
         let msg = Msg::decode(shannon_msg.payload);
         let msg_flags = msg.flags;
@@ -62,6 +82,24 @@ impl MercuryDispatcher {
             log::warn!("received unexpected mercury msg, seq: {}", msg_seq);
         }
     }
+
+    pub fn handle_mercury_pub(&mut self, shannon_msg: ShannonMsg) {
+        let msg = Msg::decode(shannon_msg.payload);
+        let msg_flags = msg.flags;
+        let msg_seq = msg.seq;
+        let mut messages = self.pending_pubs.remove(&msg_seq).unwrap_or_default();
+        messages.push(msg);
+        if msg_flags != Msg::FINAL {
+            self.pending_pubs.insert(msg_seq, messages);
+            return;
+        }
+        let parts = Msg::aggregate(messages);
+        let response = MercuryResponse::decode_from_parts(parts);
+        // Forget the subscriptions whose receiving side got dropped.
+        self.subscriptions.retain(|(uri, events)| {
+            !response.uri.starts_with(uri.as_str()) || events.send(response.clone()).is_ok()
+        });
+    }
 }
 
 #[derive(Debug)]
@@ -88,6 +126,14 @@ impl MercuryRequest {
         }
     }
 
+    pub fn subscribe(uri: String) -> Self {
+        Self {
+            uri,
+            method: "SUB".to_string(),
+            payload: Vec::new(),
+        }
+    }
+
     fn encode_to_mercury_message(self, seq: u64) -> Vec<u8> {
         let parts = self.encode_to_parts();
         let msg = Msg::new(seq, Msg::FINAL, parts);
@@ -121,7 +167,8 @@ impl MercuryResponse {
             deserialize_protobuf(&header_part).expect("Failed to deserialize message header");
         Self {
             uri: header.uri.unwrap(),
-            status_code: header.status_code.unwrap(),
+            // Pushed messages come without a status code.
+            status_code: header.status_code.unwrap_or_default(),
             payload: parts,
         }
     }
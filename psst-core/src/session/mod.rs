@@ -25,7 +25,7 @@ use crate::{
     },
     error::Error,
     item_id::{FileId, ItemId},
-    util::deserialize_protobuf,
+    util::{deserialize_protobuf, NetTimeouts},
 };
 
 use self::{
@@ -38,6 +38,9 @@ use self::{
 pub struct SessionConfig {
     pub login_creds: Credentials,
     pub proxy_url: Option<String>,
+    /// Timeouts of the HTTP requests made while connecting, i.e. resolving
+    /// the access point.
+    pub timeouts: NetTimeouts,
 }
 
 /// Cheap to clone, shareable service handle that holds the active session
@@ -100,7 +103,7 @@ impl SessionService {
                     .ok_or(Error::SessionDisconnected)?
                     .clone(),
             )?;
-            let worker = SessionWorker::run(connection.transport);
+            let worker = SessionWorker::run(connection.transport, connection.credentials.username);
             connected.replace(worker);
         }
         connected
@@ -133,8 +136,8 @@ impl SessionConnection {
     pub fn open(config: SessionConfig) -> Result<Self, Error> {
         // Connect to the server and exchange keys.
         let proxy_url = config.proxy_url.as_deref();
-        let ap_url = Transport::resolve_ap_with_fallback(proxy_url);
-        let mut transport = Transport::connect(&ap_url, proxy_url)?;
+        let ap_url = Transport::resolve_ap_with_fallback(proxy_url, config.timeouts);
+        let mut transport = Transport::connect(&ap_url, proxy_url, config.timeouts)?;
         // Authenticate with provided credentials (either username/password, or saved,
         // reusable credential blob from an earlier run).
         let credentials = transport.authenticate(config.login_creds)?;
@@ -147,6 +150,8 @@ impl SessionConnection {
 
 pub struct SessionWorker {
     sender: Sender<DispatchCmd>,
+    /// Canonical name of the authenticated user.
+    username: Arc<str>,
     decoding_thread: JoinHandle<()>,
     encoding_thread: JoinHandle<()>,
     dispatching_thread: JoinHandle<()>,
@@ -154,7 +159,7 @@ pub struct SessionWorker {
 }
 
 impl SessionWorker {
-    pub fn run(transport: Transport) -> Self {
+    pub fn run(transport: Transport, username: String) -> Self {
         let (disp_send, disp_recv) = unbounded();
         let (msg_send, msg_recv) = unbounded();
         let terminated = Arc::new(AtomicBool::new(false));
@@ -178,6 +183,7 @@ impl SessionWorker {
                 })
             },
             sender: disp_send,
+            username: username.into(),
             terminated,
         }
     }
@@ -185,6 +191,7 @@ impl SessionWorker {
     pub fn handle(&self) -> SessionHandle {
         SessionHandle {
             sender: self.sender.clone(),
+            username: self.username.clone(),
         }
     }
 
@@ -208,9 +215,14 @@ impl SessionWorker {
 #[derive(Clone)]
 pub struct SessionHandle {
     sender: Sender<DispatchCmd>,
+    username: Arc<str>,
 }
 
 impl SessionHandle {
+    pub fn username(&self) -> &str {
+        &self.username
+    }
+
     pub fn get_mercury_protobuf<T>(&self, uri: String) -> Result<T, Error>
     where
         T: MessageRead<'static>,
@@ -245,6 +257,45 @@ impl SessionHandle {
         Ok(first_part)
     }
 
+    /// Send `data` to `uri`, e.g. a message to the other clients of the user.
+    pub fn send_mercury(&self, uri: String, data: Vec<u8>) -> Result<(), Error> {
+        let (callback, receiver) = unbounded();
+        let request = MercuryRequest::send(uri, data);
+        self.sender
+            .send(DispatchCmd::MercuryReq { callback, request })
+            .ok()
+            .ok_or(Error::SessionDisconnected)?;
+        let response = receiver.recv().ok().ok_or(Error::SessionDisconnected)?;
+        if response.status_code == 200 {
+            Ok(())
+        } else {
+            Err(Error::UnexpectedResponse)
+        }
+    }
+
+    /// Subscribe to the messages pushed to URIs starting with `uri`.  The
+    /// subscription lasts until the session terminates, or until the returned
+    /// receiver is dropped.
+    pub fn subscribe_mercury(&self, uri: String) -> Result<Receiver<MercuryResponse>, Error> {
+        let (callback, receiver) = unbounded();
+        let (events, events_receiver) = unbounded();
+        let request = MercuryRequest::subscribe(uri);
+        self.sender
+            .send(DispatchCmd::MercurySub {
+                callback,
+                events,
+                request,
+            })
+            .ok()
+            .ok_or(Error::SessionDisconnected)?;
+        let response = receiver.recv().ok().ok_or(Error::SessionDisconnected)?;
+        if response.status_code == 200 {
+            Ok(events_receiver)
+        } else {
+            Err(Error::UnexpectedResponse)
+        }
+    }
+
     pub fn get_audio_key(&self, track: ItemId, file: FileId) -> Result<AudioKey, Error> {
         let (callback, receiver) = unbounded();
         self.sender
@@ -320,6 +371,11 @@ enum DispatchCmd {
         request: MercuryRequest,
         callback: Sender<MercuryResponse>,
     },
+    MercurySub {
+        request: MercuryRequest,
+        callback: Sender<MercuryResponse>,
+        events: Sender<MercuryResponse>,
+    },
     AudioKeyReq {
         track: ItemId,
         file: FileId,
@@ -349,6 +405,14 @@ fn dispatch_messages(
                 let msg = mercury.enqueue_request(request, callback);
                 let _ = messages.send(msg);
             }
+            DispatchCmd::MercurySub {
+                request,
+                callback,
+                events,
+            } => {
+                let msg = mercury.enqueue_subscription(request, callback, events);
+                let _ = messages.send(msg);
+            }
             DispatchCmd::AudioKeyReq {
                 track,
                 file,
@@ -372,9 +436,14 @@ fn dispatch_messages(
             DispatchCmd::DecodedMsg(msg) if msg.cmd == ShannonMsg::AES_KEY_ERROR => {
                 audio_key.handle_aes_key_error(msg)
             }
-            DispatchCmd::DecodedMsg(msg) if msg.cmd == ShannonMsg::MERCURY_REQ => {
+            DispatchCmd::DecodedMsg(msg)
+                if msg.cmd == ShannonMsg::MERCURY_REQ || msg.cmd == ShannonMsg::MERCURY_SUB =>
+            {
                 mercury.handle_mercury_req(msg)
             }
+            DispatchCmd::DecodedMsg(msg) if msg.cmd == ShannonMsg::MERCURY_PUB => {
+                mercury.handle_mercury_pub(msg)
+            }
             DispatchCmd::DecodedMsg(msg) => {
                 log::debug!("ignored message: {:?}", msg.cmd);
             }
@@ -1,4 +1,9 @@
-use std::{io, io::SeekFrom, mem, time::Duration};
+use std::{
+    io,
+    io::{Read, SeekFrom},
+    mem,
+    time::Duration,
+};
 
 use num_traits::{One, WrappingAdd};
 use quick_protobuf::{BytesReader, MessageRead, MessageWrite, Writer};
@@ -9,11 +14,31 @@ pub const NET_CONNECT_TIMEOUT: Duration = Duration::from_millis(8 * 1000);
 
 pub const NET_IO_TIMEOUT: Duration = Duration::from_millis(8 * 1000);
 
-pub fn default_ureq_agent_builder(proxy_url: Option<&str>) -> Result<ureq::AgentBuilder, Error> {
+/// Timeouts of the HTTP connections, `connect` bounds the connection setup,
+/// `io` every single read or write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetTimeouts {
+    pub connect: Duration,
+    pub io: Duration,
+}
+
+impl Default for NetTimeouts {
+    fn default() -> Self {
+        Self {
+            connect: NET_CONNECT_TIMEOUT,
+            io: NET_IO_TIMEOUT,
+        }
+    }
+}
+
+pub fn default_ureq_agent_builder(
+    proxy_url: Option<&str>,
+    timeouts: NetTimeouts,
+) -> Result<ureq::AgentBuilder, Error> {
     let builder = ureq::AgentBuilder::new()
-        .timeout_connect(NET_CONNECT_TIMEOUT)
-        .timeout_read(NET_IO_TIMEOUT)
-        .timeout_write(NET_IO_TIMEOUT);
+        .timeout_connect(timeouts.connect)
+        .timeout_read(timeouts.io)
+        .timeout_write(timeouts.io);
     if let Some(url) = proxy_url {
         let proxy = ureq::Proxy::new(url)?;
         Ok(builder.proxy(proxy))
@@ -34,29 +59,76 @@ impl<T: One + WrappingAdd> Sequence<T> {
         let next = self.0.wrapping_add(&T::one());
         mem::replace(&mut self.0, next)
     }
+
+    /// Start over from `value`, e.g. after re-connecting.
+    pub fn reset(&mut self, value: T) {
+        self.0 = value;
+    }
+}
+
+impl<T: Copy> Sequence<T> {
+    /// Value the next `advance()` is going to return.
+    pub fn current(&self) -> T {
+        self.0
+    }
 }
 
 pub struct OffsetFile<T> {
     stream: T,
     offset: u64,
+    /// Length of the visible window, `None` reaches up to the end of `stream`.
+    length: Option<u64>,
+    /// Current position, relative to `offset`.
+    position: u64,
 }
 
 impl<T: io::Seek> OffsetFile<T> {
-    pub fn new(mut stream: T, offset: u64) -> io::Result<OffsetFile<T>> {
+    pub fn new(stream: T, offset: u64) -> io::Result<OffsetFile<T>> {
+        Self::open(stream, offset, None)
+    }
+
+    /// View of `length` bytes of `stream`, starting at `offset`.  Reads past
+    /// the end of the window return EOF, seeks are clamped into it.
+    pub fn with_length(stream: T, offset: u64, length: u64) -> io::Result<OffsetFile<T>> {
+        Self::open(stream, offset, Some(length))
+    }
+
+    fn open(mut stream: T, offset: u64, length: Option<u64>) -> io::Result<OffsetFile<T>> {
         stream.seek(SeekFrom::Start(offset))?;
-        Ok(OffsetFile { stream, offset })
+        Ok(OffsetFile {
+            stream,
+            offset,
+            length,
+            position: 0,
+        })
+    }
+}
+
+impl<T> OffsetFile<T> {
+    /// Caps `len` to the bytes left in the window.
+    fn available(&self, len: usize) -> usize {
+        match self.length {
+            Some(length) => length.saturating_sub(self.position).min(len as u64) as usize,
+            None => len,
+        }
     }
 }
 
 impl<T: io::Read> io::Read for OffsetFile<T> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.stream.read(buf)
+        let len = self.available(buf.len());
+        let n = self.stream.read(&mut buf[..len])?;
+        self.position += n as u64;
+        Ok(n)
     }
 }
 
 impl<T: io::Write> io::Write for OffsetFile<T> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.stream.write(buf)
+        let len = self.available(buf.len());
+        let n = self.stream.write(&buf[..len])?;
+        self.position += n as u64;
+        Ok(n)
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -66,13 +138,24 @@ impl<T: io::Write> io::Write for OffsetFile<T> {
 
 impl<T: io::Seek> io::Seek for OffsetFile<T> {
     fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
-        let offset_pos = match pos {
-            SeekFrom::Start(offset) => SeekFrom::Start(offset + self.offset),
-            from_end_or_current => from_end_or_current,
+        let offset_pos = match (pos, self.length) {
+            (pos, Some(length)) => {
+                let clamp = |base: u64, delta: i64| {
+                    (base as i128 + delta as i128).clamp(0, length as i128) as u64
+                };
+                let window_pos = match pos {
+                    SeekFrom::Start(pos) => pos.min(length),
+                    SeekFrom::End(delta) => clamp(length, delta),
+                    SeekFrom::Current(delta) => clamp(self.position, delta),
+                };
+                SeekFrom::Start(window_pos + self.offset)
+            }
+            (SeekFrom::Start(offset), None) => SeekFrom::Start(offset + self.offset),
+            (from_end_or_current, None) => from_end_or_current,
         };
         let new_pos = self.stream.seek(offset_pos)?;
-        let offset_new_pos = new_pos.saturating_sub(self.offset);
-        Ok(offset_new_pos)
+        self.position = new_pos.saturating_sub(self.offset);
+        Ok(self.position)
     }
 }
 
@@ -131,10 +214,27 @@ where
     Ok(buf)
 }
 
+/// Largest protobuf message `deserialize_protobuf` accepts.  Well above any
+/// message the servers send, but keeps a malformed frame from making us
+/// allocate arbitrary amounts of memory.
+pub const MAX_PROTOBUF_SIZE: usize = 16 * 1024 * 1024;
+
 pub fn deserialize_protobuf<T>(buf: &[u8]) -> Result<T, Error>
 where
     T: MessageRead<'static>,
 {
+    deserialize_protobuf_with_limit(buf, MAX_PROTOBUF_SIZE)
+}
+
+/// Like `deserialize_protobuf`, but rejects buffers longer than `max_size`
+/// bytes with `Error::MessageTooLarge`, before any parsing takes place.
+pub fn deserialize_protobuf_with_limit<T>(buf: &[u8], max_size: usize) -> Result<T, Error>
+where
+    T: MessageRead<'static>,
+{
+    if buf.len() > max_size {
+        return Err(Error::MessageTooLarge(buf.len()));
+    }
     let mut reader = BytesReader::from_bytes(buf);
     let msg = {
         let static_buf: &'static [u8] = unsafe {
@@ -148,3 +248,131 @@ where
     };
     Ok(msg)
 }
+
+/// Read a protobuf message from `reader`.  `quick-protobuf` cannot parse
+/// partial input, so the message is still buffered whole, but into a buffer
+/// owned by this function, which makes the `'static` trick of
+/// `deserialize_protobuf` unnecessary.  Messages longer than
+/// `MAX_PROTOBUF_SIZE` are rejected without reading them to the end.
+pub fn deserialize_protobuf_from<T, R>(reader: R) -> Result<T, Error>
+where
+    T: for<'a> MessageRead<'a>,
+    R: Read,
+{
+    let mut buf = Vec::new();
+    reader
+        .take(MAX_PROTOBUF_SIZE as u64 + 1)
+        .read_to_end(&mut buf)?;
+    if buf.len() > MAX_PROTOBUF_SIZE {
+        return Err(Error::MessageTooLarge(buf.len()));
+    }
+    let mut bytes_reader = BytesReader::from_bytes(&buf);
+    let msg = T::from_reader(&mut bytes_reader, &buf)?;
+    Ok(msg)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Seek};
+
+    use crate::protocol::spirc::Frame;
+
+    use super::*;
+
+    fn window() -> OffsetFile<Cursor<Vec<u8>>> {
+        OffsetFile::with_length(Cursor::new((0..20).collect()), 5, 10).unwrap()
+    }
+
+    #[test]
+    fn offset_file_reads_within_the_window() {
+        let mut file = window();
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, (5..15).collect::<Vec<u8>>());
+        assert_eq!(file.read(&mut [0; 4]).unwrap(), 0);
+    }
+
+    #[test]
+    fn offset_file_clamps_seeks_into_the_window() {
+        let mut file = window();
+        assert_eq!(file.seek(SeekFrom::Start(20)).unwrap(), 10);
+        assert_eq!(file.read(&mut [0; 4]).unwrap(), 0);
+        assert_eq!(file.seek(SeekFrom::End(-3)).unwrap(), 7);
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, [12, 13, 14]);
+        assert_eq!(file.seek(SeekFrom::Current(-100)).unwrap(), 0);
+        assert_eq!(file.seek(SeekFrom::End(5)).unwrap(), 10);
+    }
+
+    #[test]
+    fn offset_file_without_length_reaches_the_end() {
+        let mut file = OffsetFile::new(Cursor::new((0..20).collect::<Vec<u8>>()), 15).unwrap();
+        let mut buf = Vec::new();
+        file.read_to_end(&mut buf).unwrap();
+        assert_eq!(buf, [15, 16, 17, 18, 19]);
+        assert_eq!(file.seek(SeekFrom::Start(2)).unwrap(), 2);
+    }
+
+    fn frame() -> Frame {
+        Frame {
+            version: Some(1),
+            ident: Some("device".to_string()),
+            seq_nr: Some(42),
+            recipient: vec!["other".to_string()],
+            ..Frame::default()
+        }
+    }
+
+    #[test]
+    fn protobuf_round_trips() {
+        let buf = serialize_protobuf(&frame()).unwrap();
+        let parsed: Frame = deserialize_protobuf(&buf).unwrap();
+        assert_eq!(parsed, frame());
+    }
+
+    #[test]
+    fn oversized_protobuf_is_rejected() {
+        let buf = serialize_protobuf(&frame()).unwrap();
+        let result = deserialize_protobuf_with_limit::<Frame>(&buf, buf.len() - 1);
+        assert!(matches!(result, Err(Error::MessageTooLarge(len)) if len == buf.len()));
+        assert!(deserialize_protobuf_with_limit::<Frame>(&buf, buf.len()).is_ok());
+    }
+
+    #[test]
+    fn protobuf_round_trips_through_a_reader() {
+        let buf = serialize_protobuf(&frame()).unwrap();
+        let parsed: Frame = deserialize_protobuf_from(Cursor::new(buf)).unwrap();
+        assert_eq!(parsed, frame());
+    }
+
+    #[test]
+    fn oversized_protobuf_from_a_reader_is_rejected() {
+        let reader = io::repeat(0).take(MAX_PROTOBUF_SIZE as u64 * 2);
+        let result = deserialize_protobuf_from::<Frame, _>(reader);
+        assert!(matches!(
+            result,
+            Err(Error::MessageTooLarge(len)) if len == MAX_PROTOBUF_SIZE + 1
+        ));
+    }
+
+    #[test]
+    fn sequence_wraps_around() {
+        let mut seq = Sequence::new(254_u8);
+        assert_eq!(seq.advance(), 254);
+        assert_eq!(seq.advance(), 255);
+        assert_eq!(seq.current(), 0);
+        assert_eq!(seq.advance(), 0);
+        assert_eq!(seq.current(), 1);
+    }
+
+    #[test]
+    fn sequence_resets() {
+        let mut seq = Sequence::new(0_u32);
+        seq.advance();
+        seq.advance();
+        seq.reset(10);
+        assert_eq!(seq.current(), 10);
+        assert_eq!(seq.advance(), 10);
+    }
+}
@@ -0,0 +1,73 @@
+//! Drives a real session, so it needs Spotify Premium credentials in
+//! `SPOTIFY_USERNAME` and `SPOTIFY_PASSWORD`, and an audio output device.  Run
+//! with `cargo test -p psst-core --test headless -- --ignored`.
+
+use std::{env, time::Duration};
+
+use crossbeam_channel::unbounded;
+use psst_core::{
+    connection::Credentials,
+    headless::HeadlessPlayer,
+    item_id::{ItemId, ItemIdType},
+    player::{PlaybackConfig, PlayerEvent},
+    session::SessionConfig,
+    util::NetTimeouts,
+};
+
+/// "Never Gonna Give You Up", available in most regions.
+const TRACK_ID: &str = "4uLU6hMCjMI75M1A2tKUQC";
+const EVENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Player events we wait for, `PlayerEvent` itself can't be cloned.
+#[derive(Debug, PartialEq, Eq)]
+enum Observed {
+    Playing,
+    Position,
+    Pausing,
+}
+
+#[test]
+#[ignore = "needs Spotify credentials and an audio device"]
+fn plays_pauses_and_shuts_down() {
+    let session_config = SessionConfig {
+        login_creds: Credentials::from_username_and_password(
+            env::var("SPOTIFY_USERNAME").unwrap(),
+            env::var("SPOTIFY_PASSWORD").unwrap(),
+        ),
+        proxy_url: None,
+        timeouts: NetTimeouts::default(),
+    };
+    let (events, received) = unbounded();
+    let player = HeadlessPlayer::start(
+        session_config,
+        env::temp_dir().join("psst-headless-test"),
+        PlaybackConfig::default(),
+        move |event| {
+            let observed = match event {
+                PlayerEvent::Playing { .. } => Observed::Playing,
+                PlayerEvent::Position { .. } => Observed::Position,
+                PlayerEvent::Pausing { .. } => Observed::Pausing,
+                _ => return,
+            };
+            let _ = events.send(observed);
+        },
+    )
+    .unwrap();
+    assert!(player.session().is_connected());
+
+    let wait_for = |expected: Observed| {
+        while received.recv_timeout(EVENT_TIMEOUT).unwrap() != expected {}
+    };
+
+    player.play(ItemId::from_base62(TRACK_ID, ItemIdType::Track).unwrap());
+    wait_for(Observed::Playing);
+    wait_for(Observed::Position);
+
+    player.pause();
+    wait_for(Observed::Pausing);
+
+    let session = player.session().clone();
+    // Joins the player thread and closes the session.
+    drop(player);
+    assert!(!session.is_connected());
+}
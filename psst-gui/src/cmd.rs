@@ -1,10 +1,10 @@
 use std::time::Duration;
 
 use druid::{Selector, WidgetId};
-use psst_core::item_id::ItemId;
+use psst_core::{item_id::ItemId, player::download::DownloadQueueProgress};
 
 use crate::{
-    data::{Nav, PlaybackPayload, QueueBehavior},
+    data::{EpisodeId, Nav, PlaybackPayload, QueueBehavior, TrackId},
     ui::find::Find,
 };
 
@@ -17,40 +17,83 @@ pub const WIDGET_SEARCH_INPUT: WidgetId = WidgetId::reserved(1);
 pub const SHOW_MAIN: Selector = Selector::new("app.show-main");
 pub const SET_FOCUS: Selector = Selector::new("app.set-focus");
 pub const COPY: Selector<String> = Selector::new("app.copy-to-clipboard");
+pub const COPY_DEBUG_INFO: Selector = Selector::new("app.copy-debug-info");
+/// Copy the playing item as text, the flag adds a link to it.
+pub const COPY_NOW_PLAYING: Selector<bool> = Selector::new("app.copy-now-playing");
+pub const RETRY: Selector = Selector::new("app.retry");
 
 // Find
 
 pub const TOGGLE_FINDER: Selector = Selector::new("app.show-finder");
-pub const FIND_IN_PLAYLIST: Selector<Find> = Selector::new("find-in-playlist");
-pub const FIND_IN_SAVED_TRACKS: Selector<Find> = Selector::new("find-in-saved-tracks");
+pub const FIND_IN_TRACKS: Selector<Find> = Selector::new("find-in-tracks");
+pub const JUMP_TO_PLAYING: Selector = Selector::new("app.jump-to-playing");
+
+// Command palette
+
+pub const TOGGLE_PALETTE: Selector = Selector::new("app.toggle-palette");
 
 // Session
 
 pub const SESSION_CONNECT: Selector = Selector::new("app.session-connect");
+pub const SESSION_UNAUTHORIZED: Selector = Selector::new("app.session-unauthorized");
+pub const TOGGLE_OFFLINE: Selector = Selector::new("app.toggle-offline");
+pub const SYSTEM_WAKE: Selector = Selector::new("app.system-wake");
 
 // Navigation
 
 pub const NAVIGATE: Selector<Nav> = Selector::new("app.navigates");
 pub const NAVIGATE_BACK: Selector<usize> = Selector::new("app.navigate-back");
+pub const GO_TO_PLAYING_ALBUM: Selector = Selector::new("app.go-to-playing-album");
+pub const GO_TO_PLAYING_ARTIST: Selector = Selector::new("app.go-to-playing-artist");
+/// Focus the search input, showing the results of the query in it.
+pub const FOCUS_SEARCH: Selector = Selector::new("app.focus-search");
 
 // Playback state
 
 pub const PLAYBACK_LOADING: Selector<ItemId> = Selector::new("app.playback-loading");
-pub const PLAYBACK_PLAYING: Selector<(ItemId, Duration)> = Selector::new("app.playback-playing");
+pub const PLAYBACK_PLAYING: Selector<(ItemId, Duration, usize)> =
+    Selector::new("app.playback-playing");
 pub const PLAYBACK_PROGRESS: Selector<Duration> = Selector::new("app.playback-progress");
 pub const PLAYBACK_PAUSING: Selector = Selector::new("app.playback-pausing");
 pub const PLAYBACK_RESUMING: Selector = Selector::new("app.playback-resuming");
+pub const PLAYBACK_UNAVAILABLE: Selector<(ItemId, bool)> =
+    Selector::new("app.playback-unavailable");
 pub const PLAYBACK_BLOCKED: Selector = Selector::new("app.playback-blocked");
 pub const PLAYBACK_STOPPED: Selector = Selector::new("app.playback-stopped");
+pub const PLAYBACK_QUEUE_REORDERED: Selector<Vec<usize>> =
+    Selector::new("app.playback-queue-reordered");
+
+// Downloads
+
+pub const DOWNLOAD_EPISODE: Selector<EpisodeId> = Selector::new("app.download-episode");
+pub const REMOVE_EPISODE_DOWNLOAD: Selector<EpisodeId> =
+    Selector::new("app.remove-episode-download");
+pub const DOWNLOADS_LOADED: Selector<Vec<ItemId>> = Selector::new("app.downloads-loaded");
+pub const DOWNLOAD_QUEUED: Selector<ItemId> = Selector::new("app.download-queued");
+pub const DOWNLOAD_PROGRESS: Selector<(ItemId, f64)> = Selector::new("app.download-progress");
+pub const DOWNLOAD_FINISHED: Selector<(ItemId, Result<(), String>)> =
+    Selector::new("app.download-finished");
+pub const DOWNLOAD_CANCELLED: Selector<ItemId> = Selector::new("app.download-cancelled");
+pub const DOWNLOAD_QUEUE_CHANGED: Selector<DownloadQueueProgress> =
+    Selector::new("app.download-queue-changed");
 
 // Playback control
 
 pub const PLAY: Selector<usize> = Selector::new("app.play-index");
 pub const PLAY_TRACKS: Selector<PlaybackPayload> = Selector::new("app.play-tracks");
+pub const PLAY_SHUFFLED: Selector<PlaybackPayload> = Selector::new("app.play-shuffled");
+pub const PLAY_ALBUMS_SHUFFLED: Selector<PlaybackPayload> =
+    Selector::new("app.play-albums-shuffled");
+pub const QUEUE_TRACKS: Selector<PlaybackPayload> = Selector::new("app.queue-tracks");
+pub const QUEUE_TRACKS_NEXT: Selector<PlaybackPayload> = Selector::new("app.queue-tracks-next");
 pub const PLAY_PREVIOUS: Selector = Selector::new("app.play-previous");
 pub const PLAY_PAUSE: Selector = Selector::new("app.play-pause");
+pub const PLAY_PAUSE_OR_RESUME: Selector = Selector::new("app.play-pause-or-resume");
 pub const PLAY_RESUME: Selector = Selector::new("app.play-resume");
 pub const PLAY_NEXT: Selector = Selector::new("app.play-next");
 pub const PLAY_STOP: Selector = Selector::new("app.play-stop");
 pub const PLAY_QUEUE_BEHAVIOR: Selector<QueueBehavior> = Selector::new("app.play-queue-behavior");
+pub const PLAY_CYCLE_QUEUE_BEHAVIOR: Selector = Selector::new("app.play-cycle-queue-behavior");
+pub const PLAY_SHUFFLE_REMAINING: Selector = Selector::new("app.play-shuffle-remaining");
 pub const PLAY_SEEK: Selector<f64> = Selector::new("app.play-seek");
+pub const TOGGLE_BLOCKED_TRACK: Selector<TrackId> = Selector::new("app.toggle-blocked-track");
@@ -22,10 +22,18 @@ impl<T: Data> ExClick<T> {
 
 impl<T: Data, W: Widget<T>> Controller<T, W> for ExClick<T> {
     fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        // Let the child go first, so clicks on nested clickable widgets don't
+        // trigger this action as well.
+        child.event(ctx, event, data, env);
+        if ctx.is_handled() {
+            return;
+        }
+
         match event {
             Event::MouseDown(mouse_event) => {
                 if mouse_event.button == self.button.unwrap_or(mouse_event.button) {
                     ctx.set_active(true);
+                    ctx.set_handled();
                     ctx.request_paint();
                 }
             }
@@ -34,6 +42,7 @@ impl<T: Data, W: Widget<T>> Controller<T, W> for ExClick<T> {
                     && ctx.is_active()
                 {
                     ctx.set_active(false);
+                    ctx.set_handled();
                     if ctx.is_hot() {
                         (self.action)(ctx, mouse_event, data, env);
                     }
@@ -42,8 +51,6 @@ impl<T: Data, W: Widget<T>> Controller<T, W> for ExClick<T> {
             }
             _ => {}
         }
-
-        child.event(ctx, event, data, env);
     }
 
     fn lifecycle(
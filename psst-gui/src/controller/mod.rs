@@ -5,9 +5,12 @@ mod nav;
 mod on_command;
 mod on_command_async;
 mod on_debounce;
+mod on_hover;
+mod on_notification;
 mod on_update;
 mod playback;
 mod session;
+mod shortcuts;
 
 pub use after_delay::AfterDelay;
 pub use ex_click::ExClick;
@@ -16,6 +19,9 @@ pub use nav::NavController;
 pub use on_command::OnCommand;
 pub use on_command_async::OnCommandAsync;
 pub use on_debounce::OnDebounce;
+pub use on_hover::OnHover;
+pub use on_notification::OnNotification;
 pub use on_update::OnUpdate;
 pub use playback::PlaybackController;
 pub use session::SessionController;
+pub use shortcuts::ShortcutController;
@@ -13,12 +13,12 @@ impl NavController {
         match &data.nav {
             Nav::Home => {}
             Nav::SavedTracks => {
-                if !data.library.saved_tracks.is_resolved() {
+                if data.library.needs_saved_tracks() {
                     ctx.submit_command(library::LOAD_TRACKS);
                 }
             }
             Nav::SavedAlbums => {
-                if !data.library.saved_albums.is_resolved() {
+                if data.library.needs_saved_albums() {
                     ctx.submit_command(library::LOAD_ALBUMS);
                 }
             }
@@ -59,6 +59,13 @@ impl NavController {
                     ctx.submit_command(recommend::LOAD_RESULTS.with(request.clone()));
                 }
             }
+            Nav::MostPlayed => {
+                // The counts change with every play, always reload.
+                ctx.submit_command(library::LOAD_MOST_PLAYED);
+            }
+            Nav::RecentlyPlayed => {
+                ctx.submit_command(library::LOAD_RECENTLY_PLAYED);
+            }
         }
     }
 }
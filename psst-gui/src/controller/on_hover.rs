@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use druid::{
+    widget::Controller, Data, Env, Event, EventCtx, LifeCycle, LifeCycleCtx, TimerToken, Widget,
+};
+
+/// Calls the handler once the pointer rests over the widget for `duration`.
+/// Leaving the widget earlier cancels the call.
+pub struct OnHover<T> {
+    duration: Duration,
+    timer: TimerToken,
+    handler: Box<dyn Fn(&mut EventCtx, &mut T, &Env)>,
+}
+
+impl<T> OnHover<T> {
+    pub fn new(
+        duration: Duration,
+        handler: impl Fn(&mut EventCtx, &mut T, &Env) + 'static,
+    ) -> Self {
+        Self {
+            duration,
+            timer: TimerToken::INVALID,
+            handler: Box::new(handler),
+        }
+    }
+}
+
+impl<T, W> Controller<T, W> for OnHover<T>
+where
+    T: Data,
+    W: Widget<T>,
+{
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        match event {
+            Event::Timer(token) if token == &self.timer => {
+                (self.handler)(ctx, data, env);
+                self.timer = TimerToken::INVALID;
+                ctx.set_handled();
+            }
+            _ => child.event(ctx, event, data, env),
+        }
+    }
+
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &T,
+        env: &Env,
+    ) {
+        if let LifeCycle::HotChanged(is_hot) = event {
+            self.timer = if *is_hot {
+                ctx.request_timer(self.duration)
+            } else {
+                TimerToken::INVALID
+            };
+        }
+        child.lifecycle(ctx, event, data, env)
+    }
+}
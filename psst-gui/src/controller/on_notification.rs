@@ -0,0 +1,35 @@
+use druid::{widget::Controller, Data, Env, Event, EventCtx, Selector, Widget};
+
+pub struct OnNotification<U, F> {
+    selector: Selector<U>,
+    handler: F,
+}
+
+impl<U, F> OnNotification<U, F> {
+    pub fn new<T>(selector: Selector<U>, handler: F) -> Self
+    where
+        F: Fn(&mut EventCtx, &U, &mut T),
+    {
+        Self { selector, handler }
+    }
+}
+
+impl<T, U, F, W> Controller<T, W> for OnNotification<U, F>
+where
+    T: Data,
+    U: 'static,
+    F: Fn(&mut EventCtx, &U, &mut T),
+    W: Widget<T>,
+{
+    fn event(&mut self, child: &mut W, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        match event {
+            Event::Notification(note) if note.is(self.selector) => {
+                (self.handler)(ctx, note.get(self.selector).unwrap(), data);
+                ctx.set_handled();
+            }
+            _ => {
+                child.event(ctx, event, data, env);
+            }
+        }
+    }
+}
@@ -1,20 +1,27 @@
 use std::{
     thread::{self, JoinHandle},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use crossbeam_channel::Sender;
 use druid::{
     im::Vector,
     widget::{prelude::*, Controller},
-    Code, ExtEventSink, InternalLifeCycle, KbKey, WindowHandle,
+    ExtEventSink, InternalLifeCycle, Selector, TimerToken, WindowHandle,
 };
 use psst_core::{
-    audio::{normalize::NormalizationLevel, output::DefaultAudioOutput},
+    audio::{
+        normalize::NormalizationLevel,
+        output::{AudioOutput, AudioSink, DefaultAudioOutput},
+    },
     cache::Cache,
     cdn::Cdn,
+    connect::{ConnectCommand, ConnectDevice, ConnectState, ConnectStatus},
+    error::Error,
+    item_id::{ItemId, ItemIdType},
     player::{item::PlaybackItem, PlaybackConfig, Player, PlayerCommand, PlayerEvent},
     session::SessionService,
+    util::NetTimeouts,
 };
 use souvlaki::{
     MediaControlEvent, MediaControls, MediaMetadata, MediaPlayback, MediaPosition, PlatformConfig,
@@ -22,14 +29,70 @@ use souvlaki::{
 
 use crate::{
     cmd,
-    data::{AppState, Config, Playback, PlaybackOrigin, PlaybackState, QueueBehavior, QueueEntry},
+    data::{
+        AppState, Config, EpisodeDownload, EpisodeId, Playable, Playback, PlaybackOrigin,
+        PlaybackPayload, PlaybackState, QueueBehavior, QueueEntry, ResumePoint, TrackId,
+    },
+    power::{self, PowerEvent},
+    ui::recommend,
+    webapi::WebApi,
 };
 
+/// Resume point of an episode, freshly loaded from the server.
+const RESUME_POINT_LOADED: Selector<(EpisodeId, ResumePoint)> =
+    Selector::new("app.playback.resume-point-loaded");
+
+/// Start of the first audible part of a track opening with a long silence.
+const AUDIBLE_START_LOADED: Selector<(TrackId, Duration)> =
+    Selector::new("app.playback.audible-start-loaded");
+
+/// Command from another Spotify Connect device.
+const CONNECT_COMMAND: Selector<ConnectCommand> = Selector::new("app.playback.connect-command");
+
+/// Queue loaded by another Spotify Connect device, and whether it should start
+/// playing right away.
+const CONNECT_LOADED: Selector<(PlaybackPayload, bool)> =
+    Selector::new("app.playback.connect-loaded");
+
+/// Session got reconnected, or failed to, after the playback of an item
+/// stalled.
+const STALL_RECONNECTED: Selector<ItemId> = Selector::new("app.playback.stall-reconnected");
+
+/// The playlist radio gets refilled once fewer items than this are queued
+/// after the current one.
+const RADIO_REFILL_THRESHOLD: usize = 5;
+/// Already played items of the playlist radio kept in the queue, older ones
+/// get dropped so the queue doesn't grow forever.
+const RADIO_MAX_PLAYED: usize = 50;
+
+/// Playing without any progress for this long means the stream most likely
+/// dropped.
+const STALL_TIMEOUT: Duration = Duration::from_secs(5);
+/// How often to check for a stalled playback.
+const STALL_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+/// How far an episode can play before a resume point loaded late stops
+/// moving it.
+const RESUME_POINT_TOLERANCE: Duration = Duration::from_secs(10);
+
 pub struct PlaybackController {
     sender: Option<Sender<PlayerEvent>>,
     thread: Option<JoinHandle<()>>,
     output: Option<DefaultAudioOutput>,
     media_controls: Option<MediaControls>,
+    /// Item that should start playing from the given position, instead of
+    /// from the beginning.
+    pending_start: Option<(ItemId, Duration)>,
+    /// Number of blocked tracks skipped in a row, so we can stop once the
+    /// whole queue turns out to be blocked.
+    blocked_skips: usize,
+    connect: Option<ConnectDevice>,
+    /// Fires when the playback has been idle long enough to release the
+    /// audio device, see `Config::release_device_when_idle`.
+    idle_timer: TimerToken,
+    /// Checks the playback keeps progressing, see `recover_stalled_playback`.
+    stall_timer: TimerToken,
+    last_progress: Instant,
 }
 
 impl PlaybackController {
@@ -39,6 +102,12 @@ impl PlaybackController {
             thread: None,
             output: None,
             media_controls: None,
+            pending_start: None,
+            blocked_skips: 0,
+            connect: None,
+            idle_timer: TimerToken::INVALID,
+            stall_timer: TimerToken::INVALID,
+            last_progress: Instant::now(),
         }
     }
 
@@ -46,17 +115,26 @@ impl PlaybackController {
         &mut self,
         session: SessionService,
         config: PlaybackConfig,
+        proxy_url: Option<String>,
+        timeouts: NetTimeouts,
         event_sink: ExtEventSink,
         widget_id: WidgetId,
         #[allow(unused_variables)] window: &WindowHandle,
     ) {
         let output = DefaultAudioOutput::open().unwrap();
         let cache_dir = Config::cache_dir().unwrap();
-        let proxy_url = Config::proxy();
+        let cache = Cache::new(cache_dir).unwrap();
+        event_sink
+            .submit_command(
+                cmd::DOWNLOADS_LOADED,
+                cache.downloaded_items(ItemIdType::Podcast),
+                widget_id,
+            )
+            .unwrap();
         let player = Player::new(
             session.clone(),
-            Cdn::new(session, proxy_url.as_deref()).unwrap(),
-            Cache::new(cache_dir).unwrap(),
+            Cdn::new(session, proxy_url.as_deref(), timeouts).unwrap(),
+            cache,
             config,
             &output,
         );
@@ -84,7 +162,11 @@ impl PlaybackController {
                 PlayerEvent::Playing { path, position } => {
                     let progress = position.to_owned();
                     event_sink
-                        .submit_command(cmd::PLAYBACK_PLAYING, (path.item_id, progress), widget_id)
+                        .submit_command(
+                            cmd::PLAYBACK_PLAYING,
+                            (path.item_id, progress, path.bitrate()),
+                            widget_id,
+                        )
                         .unwrap();
                 }
                 PlayerEvent::Pausing { .. } => {
@@ -103,6 +185,15 @@ impl PlaybackController {
                         .submit_command(cmd::PLAYBACK_PROGRESS, progress, widget_id)
                         .unwrap();
                 }
+                PlayerEvent::Unavailable { item, skipped } => {
+                    event_sink
+                        .submit_command(
+                            cmd::PLAYBACK_UNAVAILABLE,
+                            (item.item_id, *skipped),
+                            widget_id,
+                        )
+                        .unwrap();
+                }
                 PlayerEvent::Blocked { .. } => {
                     event_sink
                         .submit_command(cmd::PLAYBACK_BLOCKED, (), widget_id)
@@ -113,6 +204,45 @@ impl PlaybackController {
                         .submit_command(cmd::PLAYBACK_STOPPED, (), widget_id)
                         .unwrap();
                 }
+                PlayerEvent::QueueReordered { order } => {
+                    event_sink
+                        .submit_command(cmd::PLAYBACK_QUEUE_REORDERED, order.clone(), widget_id)
+                        .unwrap();
+                }
+                PlayerEvent::DownloadQueued { item } => {
+                    event_sink
+                        .submit_command(cmd::DOWNLOAD_QUEUED, item.item_id, widget_id)
+                        .unwrap();
+                }
+                PlayerEvent::Downloading {
+                    item,
+                    downloaded,
+                    total,
+                } => {
+                    let progress = *downloaded as f64 / *total as f64;
+                    event_sink
+                        .submit_command(cmd::DOWNLOAD_PROGRESS, (item.item_id, progress), widget_id)
+                        .unwrap();
+                }
+                PlayerEvent::Downloaded {
+                    item,
+                    result: Err(Error::Cancelled),
+                } => {
+                    event_sink
+                        .submit_command(cmd::DOWNLOAD_CANCELLED, item.item_id, widget_id)
+                        .unwrap();
+                }
+                PlayerEvent::Downloaded { item, result } => {
+                    let result = result.as_ref().map(|_| ()).map_err(|err| err.to_string());
+                    event_sink
+                        .submit_command(cmd::DOWNLOAD_FINISHED, (item.item_id, result), widget_id)
+                        .unwrap();
+                }
+                PlayerEvent::DownloadQueueChanged { progress } => {
+                    event_sink
+                        .submit_command(cmd::DOWNLOAD_QUEUE_CHANGED, *progress, widget_id)
+                        .unwrap();
+                }
                 _ => {}
             }
 
@@ -152,6 +282,198 @@ impl PlaybackController {
         Ok(media_controls)
     }
 
+    fn start_connect_device(
+        &mut self,
+        data: &AppState,
+        event_sink: ExtEventSink,
+        widget_id: WidgetId,
+    ) {
+        let device = ConnectDevice::start(
+            data.session.clone(),
+            data.config.connect(),
+            move |command| {
+                event_sink
+                    .submit_command(CONNECT_COMMAND, command, widget_id)
+                    .unwrap();
+            },
+        );
+        device.update(Self::connect_state(data));
+        self.connect = Some(device);
+    }
+
+    fn connect_state(data: &AppState) -> ConnectState {
+        let playback = &data.playback;
+        let now_playing = playback.now_playing.as_ref();
+        ConnectState {
+            status: match playback.state {
+                PlaybackState::Loading => ConnectStatus::Loading,
+                PlaybackState::Playing => ConnectStatus::Playing,
+                PlaybackState::Paused => ConnectStatus::Paused,
+                PlaybackState::Stopped => ConnectStatus::Stopped,
+            },
+            items: playback.queue.iter().map(|entry| entry.item.id()).collect(),
+            position: now_playing
+                .and_then(|now_playing| {
+                    playback
+                        .queue
+                        .iter()
+                        .position(|entry| entry.item.id() == now_playing.item.id())
+                })
+                .unwrap_or(0),
+            progress: now_playing.map_or(Duration::ZERO, |now_playing| now_playing.progress),
+            volume: playback.volume,
+            shuffle: playback.queue_behavior == QueueBehavior::Random,
+            repeat: matches!(
+                playback.queue_behavior,
+                QueueBehavior::LoopAll | QueueBehavior::LoopTrack
+            ),
+        }
+    }
+
+    fn handle_connect_command(
+        &mut self,
+        ctx: &mut EventCtx,
+        data: &mut AppState,
+        command: &ConnectCommand,
+    ) {
+        match command {
+            ConnectCommand::Load {
+                items,
+                position,
+                progress,
+                play,
+            } => {
+                Self::load_connect_queue(
+                    ctx.get_external_handle(),
+                    ctx.widget_id(),
+                    items.to_owned(),
+                    *position,
+                    *progress,
+                    *play,
+                );
+            }
+            ConnectCommand::Play => self.resume(),
+            ConnectCommand::Pause | ConnectCommand::Deactivate => self.pause(),
+            ConnectCommand::Next => self.next(),
+            ConnectCommand::Previous => self.previous(),
+            ConnectCommand::Seek { position } => self.seek(*position),
+            ConnectCommand::SetVolume { volume } => {
+                data.playback.volume = volume.clamp(0.0, 1.0);
+            }
+            ConnectCommand::SetShuffle { shuffle } => {
+                let behavior = if *shuffle {
+                    QueueBehavior::Random
+                } else {
+                    QueueBehavior::Sequential
+                };
+                data.set_queue_behavior(behavior);
+                self.set_queue_behavior(behavior);
+            }
+            ConnectCommand::SetRepeat { repeat } => {
+                let behavior = if *repeat {
+                    QueueBehavior::LoopAll
+                } else {
+                    QueueBehavior::Sequential
+                };
+                data.set_queue_behavior(behavior);
+                self.set_queue_behavior(behavior);
+            }
+        }
+    }
+
+    /// Look up the items another device asked us to play.
+    fn load_connect_queue(
+        event_sink: ExtEventSink,
+        widget_id: WidgetId,
+        items: Vec<ItemId>,
+        position: usize,
+        progress: Duration,
+        play: bool,
+    ) {
+        thread::spawn(move || match WebApi::global().get_playables(&items) {
+            Ok(playables) => {
+                // Unknown items are left out, keep pointing at the same item.
+                let position = items
+                    .get(position)
+                    .and_then(|id| playables.iter().position(|item| item.id() == *id))
+                    .unwrap_or(0);
+                let payload = PlaybackPayload {
+                    origin: PlaybackOrigin::Remote,
+                    items: playables,
+                    position,
+                    start_at: Some(progress).filter(|progress| !progress.is_zero()),
+                    then: Vector::new(),
+                };
+                event_sink
+                    .submit_command(CONNECT_LOADED, (payload, play), widget_id)
+                    .unwrap();
+            }
+            Err(err) => {
+                log::error!("failed to load the remote queue: {:?}", err);
+            }
+        });
+    }
+
+    fn watch_power_events(event_sink: ExtEventSink, widget_id: WidgetId) {
+        power::platform().watch(Box::new(move |event| {
+            let selector = match event {
+                PowerEvent::Wake => cmd::SYSTEM_WAKE,
+            };
+            event_sink.submit_command(selector, (), widget_id).unwrap();
+        }));
+    }
+
+    /// The resume point of an episode in a loaded list can be stale, e.g.
+    /// after listening to it on another device, so ask the server again.
+    fn load_resume_point(event_sink: ExtEventSink, widget_id: WidgetId, id: EpisodeId) {
+        thread::spawn(move || match WebApi::global().get_episodes([id]) {
+            Ok(episodes) => {
+                let resume = episodes
+                    .front()
+                    .and_then(|episode| episode.resume_point.clone());
+                if let Some(resume) = resume {
+                    event_sink
+                        .submit_command(RESUME_POINT_LOADED, (id, resume), widget_id)
+                        .unwrap();
+                }
+            }
+            Err(err) => {
+                log::warn!("failed to load episode resume point: {:?}", err);
+            }
+        });
+    }
+
+    fn load_audible_start(event_sink: ExtEventSink, widget_id: WidgetId, id: TrackId) {
+        thread::spawn(
+            move || match WebApi::global().get_audio_analysis(&id.0.to_base62()) {
+                Ok(analysis) => {
+                    if let Some(start) = analysis.audible_start() {
+                        event_sink
+                            .submit_command(AUDIBLE_START_LOADED, (id, start), widget_id)
+                            .unwrap();
+                    }
+                }
+                Err(err) => {
+                    log::warn!("failed to load audio analysis: {:?}", err);
+                }
+            },
+        );
+    }
+
+    fn skip_episode_ad(&mut self, data: &AppState, progress: Duration) {
+        if !data.config.skip_episode_ads {
+            return;
+        }
+        if let Some(now_playing) = &data.playback.now_playing {
+            if let Playable::Episode(episode) = &now_playing.item {
+                if let Some(chapter) = episode.ad_chapter_at(progress) {
+                    log::info!("skipping episode chapter {:?}", chapter.title);
+                    self.seek(chapter.end);
+                }
+            }
+        }
+    }
+
     fn handle_media_control_event(event: MediaControlEvent, sender: &Sender<PlayerEvent>) {
         let cmd = match event {
             MediaControlEvent::Play => PlayerEvent::Command(PlayerCommand::Resume),
@@ -216,23 +538,121 @@ impl PlaybackController {
         self.sender.as_mut().unwrap().send(event).unwrap();
     }
 
-    fn play(&mut self, items: &Vector<QueueEntry>, position: usize) {
-        let items = items
-            .iter()
-            .map(|queued| PlaybackItem {
-                item_id: queued.item.id(),
-                norm_level: match queued.origin {
-                    PlaybackOrigin::Album(_) => NormalizationLevel::Album,
-                    _ => NormalizationLevel::Track,
-                },
-            })
-            .collect();
+    fn play_payload(&mut self, data: &mut AppState, payload: &PlaybackPayload) {
+        data.playback.queue = payload.entries().collect();
+        self.pending_start = payload.start_at.and_then(|start_at| {
+            let item = payload.items.get(payload.position)?;
+            Some((item.id(), start_at))
+        });
+        self.play(
+            &data.playback.queue,
+            payload.position,
+            data.shuffle_weights(),
+        );
+    }
+
+    fn play(
+        &mut self,
+        items: &Vector<QueueEntry>,
+        position: usize,
+        shuffle_weights: Option<Vec<f64>>,
+    ) {
+        let items = items.iter().map(Self::playback_item).collect();
         self.send(PlayerEvent::Command(PlayerCommand::LoadQueue {
             items,
             position,
+            shuffle_weights,
         }));
     }
 
+    /// Add the payload items to the queue without interrupting the current
+    /// item, either right after it or at the end of the queue.
+    fn enqueue_payload(&mut self, data: &mut AppState, payload: &PlaybackPayload, next: bool) {
+        let current = data.playback.now_playing.as_ref().and_then(|now_playing| {
+            let id = now_playing.item.id();
+            data.playback
+                .queue
+                .iter()
+                .position(|queued| queued.item.id() == id)
+        });
+        let current = match current {
+            Some(current) => current,
+            None => {
+                // Nothing to keep playing, just play the payload.
+                self.play_payload(data, payload);
+                return;
+            }
+        };
+        if next {
+            // Let the player pick the place in its play order, we only know the queue
+            // order, and that one doesn't say what comes next in random order.
+            let entries: Vec<_> = payload.entries().collect();
+            self.send(PlayerEvent::Command(PlayerCommand::InsertNextInQueue {
+                items: entries.iter().map(Self::playback_item).collect(),
+            }));
+            for (index, entry) in (current + 1..).zip(entries) {
+                data.playback.queue.insert(index, entry);
+            }
+        } else {
+            let mut index = data.playback.queue.len();
+            for entry in payload.entries() {
+                self.send(PlayerEvent::Command(PlayerCommand::InsertIntoQueue {
+                    index,
+                    item: Self::playback_item(&entry),
+                }));
+                data.playback.queue.insert(index, entry);
+                index += 1;
+            }
+        }
+    }
+
+    /// Refill the queue of the playlist radio when it runs low, and trim its
+    /// played items.  The radio stops once something else plays.
+    fn keep_radio_going(&mut self, ctx: &mut EventCtx, data: &mut AppState) {
+        let (radio, now_playing) = match (&data.playlist_radio, &data.playback.now_playing) {
+            (Some(radio), Some(now_playing)) => (radio, now_playing),
+            _ => return,
+        };
+        if !radio.owns(&now_playing.origin) {
+            data.playlist_radio = None;
+            return;
+        }
+        let id = now_playing.item.id();
+        let current = match data
+            .playback
+            .queue
+            .iter()
+            .position(|queued| queued.item.id() == id)
+        {
+            Some(current) => current,
+            None => return,
+        };
+        if data.playback.queue.len() - current - 1 < RADIO_REFILL_THRESHOLD {
+            ctx.submit_command(recommend::REFILL_RADIO);
+        }
+        if data.playback.queue_behavior == QueueBehavior::Random {
+            // Items before the current one in the queue are not necessarily played
+            // when shuffling, keep them all.
+            return;
+        }
+        for _ in RADIO_MAX_PLAYED..current {
+            self.send(PlayerEvent::Command(PlayerCommand::RemoveFromQueue {
+                index: 0,
+            }));
+            data.playback.queue.pop_front();
+        }
+    }
+
+    fn playback_item(queued: &QueueEntry) -> PlaybackItem {
+        PlaybackItem {
+            item_id: queued.item.id(),
+            norm_level: match queued.origin {
+                PlaybackOrigin::Album(_) => NormalizationLevel::Album,
+                _ => NormalizationLevel::Track,
+            },
+        }
+    }
+
     fn pause(&mut self) {
         self.send(PlayerEvent::Command(PlayerCommand::Pause));
     }
@@ -261,10 +681,122 @@ impl PlaybackController {
         self.send(PlayerEvent::Command(PlayerCommand::Seek { position }));
     }
 
+    /// Volume change of a volume key, holding Alt makes finer steps.
     fn set_volume(&mut self, volume: f64) {
         self.send(PlayerEvent::Command(PlayerCommand::SetVolume { volume }));
     }
 
+    fn download(&mut self, item_id: ItemId) {
+        self.send(PlayerEvent::Command(PlayerCommand::Download {
+            item: PlaybackItem {
+                item_id,
+                norm_level: NormalizationLevel::Track,
+            },
+        }));
+    }
+
+    fn remove_download(&mut self, item_id: ItemId) {
+        self.send(PlayerEvent::Command(PlayerCommand::RemoveDownload {
+            item: PlaybackItem {
+                item_id,
+                norm_level: NormalizationLevel::Track,
+            },
+        }));
+    }
+
+    fn skip_blocked(&mut self, data: &mut AppState, item_id: ItemId) {
+        self.blocked_skips += 1;
+        let skipped = self.blocked_skips < data.playback.queue.len();
+        data.playback_blocked_track(item_id, skipped);
+        if skipped {
+            self.next();
+        } else {
+            self.blocked_skips = 0;
+            self.stop();
+        }
+    }
+
+    fn start_idle_timer(&mut self, ctx: &mut EventCtx, config: &Config) {
+        self.idle_timer = match config.idle_release_timeout() {
+            Some(timeout) => ctx.request_timer(timeout),
+            None => TimerToken::INVALID,
+        };
+    }
+
+    /// Note the playback moved on, and keep watching it for stalls.
+    fn watch_progress(&mut self, ctx: &mut EventCtx) {
+        self.last_progress = Instant::now();
+        if self.stall_timer == TimerToken::INVALID {
+            self.stall_timer = ctx.request_timer(STALL_CHECK_INTERVAL);
+        }
+    }
+
+    /// Playing without any progress for `STALL_TIMEOUT` until `now`.
+    fn is_stalled(&self, state: PlaybackState, now: Instant) -> bool {
+        state == PlaybackState::Playing
+            && now.saturating_duration_since(self.last_progress) >= STALL_TIMEOUT
+    }
+
+    /// The playback stopped progressing, most likely because the connection
+    /// dropped.  Reconnect the session and, once connected, play the item
+    /// again from where it stopped, showing it as loading in the meantime.
+    fn recover_stalled_playback(&mut self, ctx: &mut EventCtx, data: &mut AppState) {
+        let now_playing = match &data.playback.now_playing {
+            Some(now_playing) => now_playing,
+            None => return,
+        };
+        log::warn!(
+            "playback stalled at {:?}, reconnecting",
+            now_playing.progress
+        );
+        let id = now_playing.item.id();
+        self.pending_start = Some((id, now_playing.progress));
+        data.playback.state = PlaybackState::Loading;
+        let session = data.session.clone();
+        let event_sink = ctx.get_external_handle();
+        let widget_id = ctx.widget_id();
+        thread::spawn(move || {
+            // The stalled session most likely died with the connection, open a fresh one.
+            session.shutdown();
+            if let Err(err) = session.connected() {
+                log::error!("failed to reconnect after a stall: {}", err);
+            }
+            event_sink
+                .submit_command(STALL_RECONNECTED, id, widget_id)
+                .unwrap();
+        });
+    }
+
+    /// Position `id` should start from, within its `duration`, if it's the
+    /// pending start.
+    fn take_pending_start(&mut self, id: ItemId, duration: Duration) -> Option<Duration> {
+        match self.pending_start.take() {
+            Some((pending_id, start_at)) if pending_id == id => Some(start_at.min(duration)),
+            pending => {
+                self.pending_start = pending;
+                None
+            }
+        }
+    }
+
+    /// Whether `id` is about to start from a given position, unless something
+    /// else got played in the meantime.
+    fn is_pending_start(&self, id: ItemId) -> bool {
+        matches!(self.pending_start, Some((pending_id, _)) if pending_id == id)
+    }
+
+    /// Close the audio output stream, the next resume opens it again.
+    fn release_audio_device(&mut self) {
+        if let Some(output) = &self.output {
+            log::info!("releasing idle audio device");
+            output.sink().release();
+        }
+    }
+
+    fn configure(&mut self, config: PlaybackConfig) {
+        self.send(PlayerEvent::Command(PlayerCommand::Configure { config }));
+    }
+
     fn set_queue_behavior(&mut self, behavior: QueueBehavior) {
         self.send(PlayerEvent::Command(PlayerCommand::SetQueueBehavior {
             behavior: match behavior {
@@ -296,21 +828,70 @@ where
             // Player events.
             Event::Command(cmd) if cmd.is(cmd::PLAYBACK_LOADING) => {
                 let item = cmd.get_unchecked(cmd::PLAYBACK_LOADING);
+                self.idle_timer = TimerToken::INVALID;
 
-                if let Some(queued) = data.queued_entry(*item) {
+                if data.is_blocked(*item) {
+                    self.skip_blocked(data, *item);
+                } else if let Some(queued) = data.queued_entry(*item) {
                     data.loading_playback(queued.item, queued.origin);
+                    if let Some((pending_id, start_at)) = self.pending_start {
+                        if pending_id == *item {
+                            data.progress_playback(start_at);
+                        }
+                    }
                     self.update_media_control_playback(&data.playback);
                     self.update_media_control_metadata(&data.playback);
+                    self.keep_radio_going(ctx, data);
                 } else {
                     log::warn!("loaded item not found in playback queue");
                 }
                 ctx.set_handled();
             }
             Event::Command(cmd) if cmd.is(cmd::PLAYBACK_PLAYING) => {
-                let (item, progress) = cmd.get_unchecked(cmd::PLAYBACK_PLAYING);
+                let (item, progress, bitrate) = cmd.get_unchecked(cmd::PLAYBACK_PLAYING);
+                self.blocked_skips = 0;
 
                 if let Some(queued) = data.queued_entry(*item) {
-                    data.start_playback(queued.item, queued.origin, progress.to_owned());
+                    // Continue listening to episodes from the last known position.
+                    if let Playable::Episode(episode) = &queued.item {
+                        match &episode.resume_point {
+                            Some(resume) if !resume.fully_played && progress.is_zero() => {
+                                self.seek(resume.resume_position);
+                            }
+                            _ => {}
+                        }
+                        if progress.is_zero() {
+                            Self::load_resume_point(
+                                ctx.get_external_handle(),
+                                ctx.widget_id(),
+                                episode.id,
+                            );
+                        }
+                    }
+                    let has_pending_start = self.is_pending_start(*item);
+                    if let Playable::Track(track) = &queued.item {
+                        if data.config.fetch_analysis
+                            && data.config.trim_silence
+                            && progress.is_zero()
+                            && !has_pending_start
+                        {
+                            Self::load_audible_start(
+                                ctx.get_external_handle(),
+                                ctx.widget_id(),
+                                track.id,
+                            );
+                        }
+                    }
+                    if let Some(start_at) = self.take_pending_start(*item, queued.item.duration()) {
+                        self.seek(start_at);
+                    }
+                    self.watch_progress(ctx);
+                    data.start_playback(
+                        queued.item,
+                        queued.origin,
+                        progress.to_owned(),
+                        bitrate.to_owned(),
+                    );
                     self.update_media_control_playback(&data.playback);
                     self.update_media_control_metadata(&data.playback);
                 } else {
@@ -321,18 +902,67 @@ where
             Event::Command(cmd) if cmd.is(cmd::PLAYBACK_PROGRESS) => {
                 let progress = cmd.get_unchecked(cmd::PLAYBACK_PROGRESS);
                 data.progress_playback(progress.to_owned());
+                self.watch_progress(ctx);
+                if data.count_play() {
+                    data.common_ctx.play_counts.save();
+                    data.play_history.save();
+                }
+                self.skip_episode_ad(data, *progress);
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(RESUME_POINT_LOADED) => {
+                let (id, resume) = cmd.get_unchecked(RESUME_POINT_LOADED);
+                if let Some(now_playing) = &data.playback.now_playing {
+                    if let Playable::Episode(episode) = &now_playing.item {
+                        let start = episode
+                            .resume_point
+                            .as_ref()
+                            .filter(|resume| !resume.fully_played)
+                            .map(|resume| resume.resume_position)
+                            .unwrap_or_default();
+                        // Leave the position alone if it was changed in the
+                        // meantime.
+                        let untouched = now_playing.progress >= start
+                            && now_playing.progress - start < RESUME_POINT_TOLERANCE;
+                        if episode.id == *id
+                            && !resume.fully_played
+                            && resume.resume_position != start
+                            && untouched
+                        {
+                            self.seek(resume.resume_position);
+                        }
+                    }
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(AUDIBLE_START_LOADED) => {
+                let (id, start) = cmd.get_unchecked(AUDIBLE_START_LOADED);
+                if let Some(now_playing) = &data.playback.now_playing {
+                    // Don't jump back if the silence is already over.
+                    if now_playing.item.id() == id.0 && now_playing.progress < *start {
+                        self.seek(*start);
+                    }
+                }
                 ctx.set_handled();
             }
             Event::Command(cmd) if cmd.is(cmd::PLAYBACK_PAUSING) => {
                 data.pause_playback();
                 self.update_media_control_playback(&data.playback);
+                self.start_idle_timer(ctx, &data.config);
                 ctx.set_handled();
             }
             Event::Command(cmd) if cmd.is(cmd::PLAYBACK_RESUMING) => {
+                self.idle_timer = TimerToken::INVALID;
+                self.watch_progress(ctx);
                 data.resume_playback();
                 self.update_media_control_playback(&data.playback);
                 ctx.set_handled();
             }
+            Event::Command(cmd) if cmd.is(cmd::PLAYBACK_UNAVAILABLE) => {
+                let (item_id, skipped) = cmd.get_unchecked(cmd::PLAYBACK_UNAVAILABLE);
+                data.playback_unavailable(*item_id, *skipped);
+                ctx.set_handled();
+            }
             Event::Command(cmd) if cmd.is(cmd::PLAYBACK_BLOCKED) => {
                 data.block_playback();
                 ctx.set_handled();
@@ -340,20 +970,159 @@ where
             Event::Command(cmd) if cmd.is(cmd::PLAYBACK_STOPPED) => {
                 data.stop_playback();
                 self.update_media_control_playback(&data.playback);
+                self.start_idle_timer(ctx, &data.config);
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::PLAYBACK_QUEUE_REORDERED) => {
+                let order = cmd.get_unchecked(cmd::PLAYBACK_QUEUE_REORDERED);
+                // Mirror the player queue, so the displayed queue and the indices of further
+                // queue edits keep matching it.
+                if order.len() == data.playback.queue.len() {
+                    let queue = &data.playback.queue;
+                    data.playback.queue = order.iter().map(|&i| queue[i].clone()).collect();
+                }
+                ctx.set_handled();
+            }
+            Event::Timer(token) if *token == self.stall_timer => {
+                self.stall_timer = TimerToken::INVALID;
+                // Paused or loading playback doesn't progress, the next progress
+                // report starts the checks again.
+                if self.is_stalled(data.playback.state, Instant::now()) {
+                    self.recover_stalled_playback(ctx, data);
+                } else if data.playback.state == PlaybackState::Playing {
+                    self.stall_timer = ctx.request_timer(STALL_CHECK_INTERVAL);
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(STALL_RECONNECTED) => {
+                let id = *cmd.get_unchecked(STALL_RECONNECTED);
+                if self.is_pending_start(id) {
+                    if let Some(queued) = data.queued_entry(id) {
+                        self.send(PlayerEvent::Command(PlayerCommand::LoadAndPlay {
+                            item: Self::playback_item(&queued),
+                        }));
+                    }
+                }
+                ctx.set_handled();
+            }
+            Event::Timer(token) if *token == self.idle_timer => {
+                self.idle_timer = TimerToken::INVALID;
+                if matches!(
+                    data.playback.state,
+                    PlaybackState::Paused | PlaybackState::Stopped
+                ) {
+                    self.release_audio_device();
+                }
+                ctx.set_handled();
+            }
+            // Downloads.
+            Event::Command(cmd) if cmd.is(cmd::DOWNLOADS_LOADED) => {
+                let items = cmd.get_unchecked(cmd::DOWNLOADS_LOADED);
+                let downloads = &mut data.common_ctx_mut().downloads;
+                for &item_id in items {
+                    downloads.insert(EpisodeId(item_id), EpisodeDownload::Downloaded);
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::DOWNLOAD_EPISODE) => {
+                let id = cmd.get_unchecked(cmd::DOWNLOAD_EPISODE);
+                self.download(id.0);
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::DOWNLOAD_QUEUED) => {
+                let item_id = cmd.get_unchecked(cmd::DOWNLOAD_QUEUED);
+                data.common_ctx_mut()
+                    .downloads
+                    .insert(EpisodeId(*item_id), EpisodeDownload::Queued);
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::REMOVE_EPISODE_DOWNLOAD) => {
+                let id = cmd.get_unchecked(cmd::REMOVE_EPISODE_DOWNLOAD);
+                self.remove_download(id.0);
+                data.common_ctx_mut().downloads.remove(id);
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::DOWNLOAD_PROGRESS) => {
+                let (item_id, progress) = cmd.get_unchecked(cmd::DOWNLOAD_PROGRESS);
+                data.common_ctx_mut().downloads.insert(
+                    EpisodeId(*item_id),
+                    EpisodeDownload::Downloading {
+                        progress: *progress,
+                    },
+                );
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::DOWNLOAD_FINISHED) => {
+                let (item_id, result) = cmd.get_unchecked(cmd::DOWNLOAD_FINISHED);
+                match result {
+                    Ok(_) => {
+                        data.common_ctx_mut()
+                            .downloads
+                            .insert(EpisodeId(*item_id), EpisodeDownload::Downloaded);
+                    }
+                    Err(err) => {
+                        data.common_ctx_mut()
+                            .downloads
+                            .insert(EpisodeId(*item_id), EpisodeDownload::Failed);
+                        data.action_failed("Downloading episode", err);
+                    }
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::DOWNLOAD_CANCELLED) => {
+                let item_id = cmd.get_unchecked(cmd::DOWNLOAD_CANCELLED);
+                data.common_ctx_mut().downloads.remove(&EpisodeId(*item_id));
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::DOWNLOAD_QUEUE_CHANGED) => {
+                let progress = cmd.get_unchecked(cmd::DOWNLOAD_QUEUE_CHANGED);
+                if progress.is_finished() && progress.total > 1 {
+                    data.info_alert(format!(
+                        "Downloaded {} of {} episodes.",
+                        progress.done, progress.total
+                    ));
+                }
                 ctx.set_handled();
             }
             // Playback actions.
             Event::Command(cmd) if cmd.is(cmd::PLAY_TRACKS) => {
                 let payload = cmd.get_unchecked(cmd::PLAY_TRACKS);
-                data.playback.queue = payload
-                    .items
-                    .iter()
-                    .map(|item| QueueEntry {
-                        origin: payload.origin.to_owned(),
-                        item: item.to_owned(),
-                    })
-                    .collect();
-                self.play(&data.playback.queue, payload.position);
+                if let Some(behavior) = data.restore_queue_behavior(&payload.origin) {
+                    self.set_queue_behavior(behavior);
+                }
+                if matches!(payload.origin, PlaybackOrigin::Search(_))
+                    && !data.config.search_play_continues
+                {
+                    self.play_payload(data, &payload.to_owned().only_position());
+                } else {
+                    self.play_payload(data, payload);
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::PLAY_SHUFFLED) => {
+                let payload = cmd.get_unchecked(cmd::PLAY_SHUFFLED);
+                if !payload.items.is_empty() {
+                    data.shuffle_queue_behavior();
+                    self.set_queue_behavior(QueueBehavior::Random);
+                    self.play_payload(data, &payload.to_owned().at_random_position());
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::PLAY_ALBUMS_SHUFFLED) => {
+                let payload = cmd.get_unchecked(cmd::PLAY_ALBUMS_SHUFFLED);
+                data.album_shuffle_queue_behavior();
+                self.set_queue_behavior(QueueBehavior::Sequential);
+                self.play_payload(data, payload);
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::QUEUE_TRACKS) => {
+                let payload = cmd.get_unchecked(cmd::QUEUE_TRACKS);
+                self.enqueue_payload(data, payload, false);
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::QUEUE_TRACKS_NEXT) => {
+                let payload = cmd.get_unchecked(cmd::QUEUE_TRACKS_NEXT);
+                self.enqueue_payload(data, payload, true);
                 ctx.set_handled();
             }
             Event::Command(cmd) if cmd.is(cmd::PLAY_PAUSE) => {
@@ -364,6 +1133,10 @@ where
                 self.resume();
                 ctx.set_handled();
             }
+            Event::Command(cmd) if cmd.is(cmd::PLAY_PAUSE_OR_RESUME) => {
+                self.pause_or_resume();
+                ctx.set_handled();
+            }
             Event::Command(cmd) if cmd.is(cmd::PLAY_PREVIOUS) => {
                 self.previous();
                 ctx.set_handled();
@@ -382,6 +1155,27 @@ where
                 self.set_queue_behavior(behavior.to_owned());
                 ctx.set_handled();
             }
+            Event::Command(cmd) if cmd.is(cmd::PLAY_CYCLE_QUEUE_BEHAVIOR) => {
+                let behavior = data.cycle_queue_behavior();
+                self.set_queue_behavior(behavior);
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::PLAY_SHUFFLE_REMAINING) => {
+                self.send(PlayerEvent::Command(PlayerCommand::ShuffleRemaining));
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::TOGGLE_BLOCKED_TRACK) => {
+                let track_id = cmd.get_unchecked(cmd::TOGGLE_BLOCKED_TRACK);
+                let is_blocked = data.toggle_blocked_track(*track_id);
+                let is_playing = matches!(
+                    &data.playback.now_playing,
+                    Some(now_playing) if now_playing.item.id() == track_id.0
+                );
+                if is_blocked && is_playing {
+                    self.next();
+                }
+                ctx.set_handled();
+            }
             Event::Command(cmd) if cmd.is(cmd::PLAY_SEEK) => {
                 if let Some(now_playing) = &data.playback.now_playing {
                     let fraction = cmd.get_unchecked(cmd::PLAY_SEEK);
@@ -392,25 +1186,37 @@ where
                 }
                 ctx.set_handled();
             }
-            // Keyboard shortcuts.
-            Event::KeyDown(key) if key.code == Code::Space => {
-                self.pause_or_resume();
-                ctx.set_handled();
-            }
-            Event::KeyDown(key) if key.code == Code::ArrowRight => {
-                self.next();
-                ctx.set_handled();
-            }
-            Event::KeyDown(key) if key.code == Code::ArrowLeft => {
-                self.previous();
+            // Spotify Connect.
+            Event::Command(cmd) if cmd.is(CONNECT_COMMAND) => {
+                let command = cmd.get_unchecked(CONNECT_COMMAND);
+                self.handle_connect_command(ctx, data, command);
                 ctx.set_handled();
             }
-            Event::KeyDown(key) if key.key == KbKey::Character("+".to_string()) => {
-                data.playback.volume = (data.playback.volume + 0.1).min(1.0);
+            Event::Command(cmd) if cmd.is(CONNECT_LOADED) => {
+                let (payload, play) = cmd.get_unchecked(CONNECT_LOADED);
+                if payload.items.is_empty() {
+                    log::warn!("none of the remote queue items is available");
+                } else {
+                    if let Some(behavior) = data.restore_queue_behavior(&payload.origin) {
+                        self.set_queue_behavior(behavior);
+                    }
+                    self.play_payload(data, payload);
+                    if !play {
+                        // Keeps the loaded item from starting, see `Player::pause`.
+                        self.pause();
+                    }
+                }
                 ctx.set_handled();
             }
-            Event::KeyDown(key) if key.key == KbKey::Character("-".to_string()) => {
-                data.playback.volume = (data.playback.volume - 0.1).max(0.0);
+            // Power events.
+            Event::Command(cmd) if cmd.is(cmd::SYSTEM_WAKE) => {
+                // We can't see the machine going to sleep, so pause only now, before the
+                // playback carries on after the gap.
+                if data.config.pause_on_sleep && data.playback.state == PlaybackState::Playing {
+                    self.pause();
+                }
+                // The session connection most likely died during the sleep.
+                ctx.submit_command(cmd::SESSION_CONNECT);
                 ctx.set_handled();
             }
             //
@@ -431,6 +1237,8 @@ where
                 self.open_audio_output_and_start_threads(
                     data.session.clone(),
                     data.config.playback(),
+                    data.config.proxy(),
+                    data.config.net_timeouts(),
                     ctx.get_external_handle(),
                     ctx.widget_id(),
                     ctx.window(),
@@ -440,7 +1248,14 @@ where
                 self.set_volume(data.playback.volume);
                 self.set_queue_behavior(data.playback.queue_behavior);
 
-                // Request focus so we can receive keyboard events.
+                Self::watch_power_events(ctx.get_external_handle(), ctx.widget_id());
+
+                if data.config.connect_device {
+                    self.start_connect_device(data, ctx.get_external_handle(), ctx.widget_id());
+                }
+
+                // Druid only routes keyboard events along the focus path, take the focus
+                // so the global shortcuts work even if nothing else is focused.
                 ctx.submit_command(cmd::SET_FOCUS.to(ctx.widget_id()));
             }
             LifeCycle::Internal(InternalLifeCycle::RouteFocusChanged { new: None, .. }) => {
@@ -464,6 +1279,84 @@ where
         if !old_data.playback.volume.same(&data.playback.volume) {
             self.set_volume(data.playback.volume);
         }
+        if !old_data
+            .config
+            .audio_quality
+            .same(&data.config.audio_quality)
+            || !old_data
+                .config
+                .download_quality
+                .same(&data.config.download_quality)
+            || old_data.config.skip_unavailable != data.config.skip_unavailable
+            || old_data.config.resampler_quality != data.config.resampler_quality
+        {
+            // New quality takes effect from the next loaded track.
+            self.configure(data.config.playback());
+        }
+        if old_data.config.connect_device != data.config.connect_device {
+            if data.config.connect_device {
+                self.start_connect_device(data, ctx.get_external_handle(), ctx.widget_id());
+            } else {
+                // Dropping the device takes it off the list of the devices.
+                self.connect = None;
+            }
+        } else if let Some(connect) = &self.connect {
+            let now_playing_id =
+                |data: &AppState| data.playback.now_playing.as_ref().map(|np| np.item.id());
+            if old_data.playback.state != data.playback.state
+                || now_playing_id(old_data) != now_playing_id(data)
+                || !old_data.playback.queue.same(&data.playback.queue)
+                || !old_data.playback.volume.same(&data.playback.volume)
+                || old_data.playback.queue_behavior != data.playback.queue_behavior
+            {
+                connect.update(Self::connect_state(data));
+            }
+        }
         child.update(ctx, old_data, data, env);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn playback_without_progress_stalls() {
+        let controller = PlaybackController::new();
+        let since = controller.last_progress;
+        let later = since + STALL_TIMEOUT;
+        assert!(!controller.is_stalled(PlaybackState::Playing, since + STALL_CHECK_INTERVAL));
+        assert!(controller.is_stalled(PlaybackState::Playing, later));
+        assert!(!controller.is_stalled(PlaybackState::Paused, later));
+        assert!(!controller.is_stalled(PlaybackState::Loading, later));
+    }
+
+    #[test]
+    fn stalled_item_resumes_from_its_progress() {
+        let mut controller = PlaybackController::new();
+        let stalled = ItemId::new(1, ItemIdType::Track);
+        let other = ItemId::new(2, ItemIdType::Track);
+        let duration = Duration::from_secs(180);
+        // As left by `recover_stalled_playback`.
+        controller.pending_start = Some((stalled, Duration::from_secs(42)));
+        assert!(controller.is_pending_start(stalled));
+        assert!(!controller.is_pending_start(other));
+        assert_eq!(controller.take_pending_start(other, duration), None);
+        assert_eq!(
+            controller.take_pending_start(stalled, duration),
+            Some(Duration::from_secs(42))
+        );
+        assert_eq!(controller.take_pending_start(stalled, duration), None);
+    }
+
+    #[test]
+    fn resume_position_stays_within_the_item() {
+        let mut controller = PlaybackController::new();
+        let id = ItemId::new(1, ItemIdType::Track);
+        controller.pending_start = Some((id, Duration::from_secs(200)));
+        assert_eq!(
+            controller.take_pending_start(id, Duration::from_secs(180)),
+            Some(Duration::from_secs(180))
+        );
+    }
+}
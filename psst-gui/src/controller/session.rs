@@ -1,21 +1,42 @@
-use druid::widget::{prelude::*, Controller};
+use druid::{
+    commands,
+    widget::{prelude::*, Controller},
+    Selector, WindowState,
+};
 
 use crate::{
     cmd,
-    data::AppState,
+    data::{AppState, PreferencesTab},
+    network::{self, NetworkEvent},
     ui::{home, playlist, user},
+    webapi::WebApi,
 };
 
-pub struct SessionController;
+const WATCH_NETWORK: Selector = Selector::new("app.session.watch-network");
+const NETWORK_CHANGED: Selector<NetworkEvent> = Selector::new("app.session.network-changed");
+
+pub struct SessionController {
+    /// The network watcher keeps running once started, its events are
+    /// ignored while `Config::auto_offline` is off.
+    watching_network: bool,
+}
 
 impl SessionController {
+    pub fn new() -> Self {
+        Self {
+            watching_network: false,
+        }
+    }
+
     fn connect(&self, ctx: &mut EventCtx, data: &mut AppState) {
         // Update the session configuration, any active session will get shut down.
         data.session.update_config(data.config.session());
+        data.unauthorized = false;
 
         // Reload the global, usually visible data.
         ctx.submit_command(playlist::LOAD_LIST);
         ctx.submit_command(home::LOAD_MADE_FOR_YOU);
+        ctx.submit_command(home::LOAD_CONTINUE_LISTENING.with(data.play_history.clone()));
         ctx.submit_command(user::LOAD_PROFILE);
     }
 }
@@ -39,12 +60,85 @@ where
                 }
                 ctx.set_handled();
             }
+            Event::Command(cmd) if cmd.is(WATCH_NETWORK) => {
+                if !self.watching_network {
+                    self.watching_network = true;
+                    let event_sink = ctx.get_external_handle();
+                    let widget_id = ctx.widget_id();
+                    network::watch(
+                        data.config.proxy(),
+                        Box::new(move |event| {
+                            event_sink
+                                .submit_command(NETWORK_CHANGED, event, widget_id)
+                                .unwrap();
+                        }),
+                    );
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(NETWORK_CHANGED) => {
+                let event = cmd.get_unchecked(NETWORK_CHANGED);
+                data.network_lost = data.config.auto_offline && *event == NetworkEvent::Lost;
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::TOGGLE_OFFLINE) => {
+                data.config.offline = !data.config.offline;
+                data.config.save();
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(cmd::SESSION_UNAUTHORIZED) => {
+                if !data.unauthorized {
+                    // Hide the content and ask for fresh credentials in the preferences.
+                    data.unauthorized = true;
+                    data.preferences.active = PreferencesTab::Account;
+                    ctx.submit_command(commands::SHOW_PREFERENCES);
+                }
+                ctx.set_handled();
+            }
+            Event::WindowCloseRequested if data.config.close_to_tray => {
+                // Keep the window, and so the session and the playback, alive.  Quitting
+                // closes the windows without asking, so it still shuts the session down.
+                if cfg!(target_os = "macos") {
+                    ctx.submit_command(commands::HIDE_APPLICATION);
+                } else {
+                    ctx.window().set_window_state(WindowState::Minimized);
+                }
+                ctx.set_handled();
+            }
             _ => {
                 child.event(ctx, event, data, env);
             }
         }
     }
 
+    fn update(
+        &mut self,
+        child: &mut W,
+        ctx: &mut UpdateCtx,
+        old_data: &AppState,
+        data: &AppState,
+        env: &Env,
+    ) {
+        if old_data.config.auto_offline != data.config.auto_offline {
+            if data.config.auto_offline {
+                ctx.submit_command(WATCH_NETWORK);
+            } else if data.network_lost {
+                ctx.submit_command(NETWORK_CHANGED.with(NetworkEvent::Restored));
+            }
+        }
+        if old_data.is_offline() != data.is_offline() {
+            WebApi::global().set_offline(data.is_offline());
+            if !data.is_offline() {
+                // Back online, reload the data we might have missed.
+                ctx.submit_command(cmd::SESSION_CONNECT);
+            }
+        }
+        if old_data.config.response_cache != data.config.response_cache {
+            WebApi::global().set_response_cache(data.config.response_cache);
+        }
+        child.update(ctx, old_data, data, env)
+    }
+
     fn lifecycle(
         &mut self,
         child: &mut W,
@@ -55,6 +149,9 @@ where
     ) {
         if let LifeCycle::WidgetAdded = event {
             ctx.submit_command(cmd::SESSION_CONNECT);
+            if data.config.auto_offline {
+                ctx.submit_command(WATCH_NETWORK);
+            }
         }
         child.lifecycle(ctx, event, data, env)
     }
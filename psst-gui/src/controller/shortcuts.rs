@@ -0,0 +1,76 @@
+use druid::{
+    widget::{prelude::*, Controller},
+    Code, KbKey, KeyEvent,
+};
+
+use crate::{cmd, data::AppState};
+
+const VOLUME_STEP: f64 = 0.05;
+const FINE_VOLUME_STEP: f64 = 0.01;
+
+/// Global keyboard shortcuts. Wraps the root widget and only acts on keys
+/// that the focused widget left unhandled, so a focused list or text box
+/// doesn't hide the shortcuts, and can still use the keys it needs.
+pub struct ShortcutController;
+
+impl ShortcutController {
+    fn volume_step(key: &KeyEvent) -> f64 {
+        if key.mods.alt() {
+            FINE_VOLUME_STEP
+        } else {
+            VOLUME_STEP
+        }
+    }
+
+    fn handle_key(&self, ctx: &mut EventCtx, key: &KeyEvent, data: &mut AppState) -> bool {
+        match key {
+            key if key.code == Code::Space => {
+                ctx.submit_command(cmd::PLAY_PAUSE_OR_RESUME);
+            }
+            key if key.code == Code::ArrowRight => {
+                ctx.submit_command(cmd::PLAY_NEXT);
+            }
+            key if key.code == Code::ArrowLeft => {
+                ctx.submit_command(cmd::PLAY_PREVIOUS);
+            }
+            key if key.code == Code::ArrowUp || key.key == KbKey::Character("+".to_string()) => {
+                data.change_volume(Self::volume_step(key));
+            }
+            key if key.code == Code::ArrowDown || key.key == KbKey::Character("-".to_string()) => {
+                data.change_volume(-Self::volume_step(key));
+            }
+            key if key.code == Code::KeyM && key.mods.is_empty() => {
+                data.toggle_mute();
+            }
+            key if key.code == Code::KeyP && key.mods.shift() && key.mods.ctrl() => {
+                ctx.submit_command(cmd::TOGGLE_PALETTE);
+            }
+            key if key.key == KbKey::Character("/".to_string()) => {
+                ctx.submit_command(cmd::FOCUS_SEARCH);
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+impl<W> Controller<AppState, W> for ShortcutController
+where
+    W: Widget<AppState>,
+{
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        child.event(ctx, event, data, env);
+        if let Event::KeyDown(key) = event {
+            if !ctx.is_handled() && self.handle_key(ctx, key, data) {
+                ctx.set_handled();
+            }
+        }
+    }
+}
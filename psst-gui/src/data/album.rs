@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{cmp::Ordering, sync::Arc, time::Duration};
 
 use druid::{im::Vector, Data, Lens};
 use serde::{Deserialize, Serialize};
@@ -36,12 +36,25 @@ pub struct Album {
 }
 
 impl Album {
+    /// Duration of the loaded tracks, only the first page of them is part of
+    /// the album response.
+    pub fn duration(&self) -> Duration {
+        Track::total_duration(&self.tracks)
+    }
+
     pub fn release(&self) -> String {
-        self.release_with_format(match self.release_date_precision {
-            Some(DatePrecision::Year) | None => format_description!("[year]"),
-            Some(DatePrecision::Month) => format_description!("[month repr:long] [year]"),
-            Some(DatePrecision::Day) => format_description!("[month repr:long] [day], [year]"),
-        })
+        format_release_date(
+            self.release_date.as_ref(),
+            self.release_date_precision.as_ref(),
+        )
+    }
+
+    /// Compare the release dates.  A date known only to the year (or the
+    /// month) goes before the precise dates within it, albums without any
+    /// date go first.
+    pub fn release_cmp(&self, other: &Self) -> Ordering {
+        let key = |album: &Self| (album.release_date, album.release_date_precision.clone());
+        key(self).cmp(&key(other))
     }
 
     pub fn release_year(&self) -> String {
@@ -101,7 +114,7 @@ impl Default for AlbumType {
     }
 }
 
-#[derive(Clone, Debug, Eq, PartialEq, Data, Deserialize)]
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Data, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum DatePrecision {
     Year,
@@ -109,6 +122,30 @@ pub enum DatePrecision {
     Day,
 }
 
+impl DatePrecision {
+    /// Format `date` showing only the parts that are known with this
+    /// precision, i.e. "2019", "Jan 2019" or "Jan 1, 2019".
+    pub fn format(&self, date: &Date) -> String {
+        let format = match self {
+            DatePrecision::Year => format_description!("[year]"),
+            DatePrecision::Month => format_description!("[month repr:short] [year]"),
+            DatePrecision::Day => {
+                format_description!("[month repr:short] [day padding:none], [year]")
+            }
+        };
+        date.format(format).expect("invalid format")
+    }
+}
+
+/// Format a release date according to its precision.  Shows just the year if
+/// the precision is unknown, and "-" if there's no date at all.
+pub fn format_release_date(date: Option<&Date>, precision: Option<&DatePrecision>) -> String {
+    match date {
+        Some(date) => precision.unwrap_or(&DatePrecision::Year).format(date),
+        None => '-'.to_string(),
+    }
+}
+
 #[derive(Clone, Debug, Data, Lens, Deserialize)]
 pub struct Copyright {
     pub text: Arc<str>,
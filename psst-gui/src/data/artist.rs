@@ -1,9 +1,9 @@
-use std::sync::Arc;
+use std::{cmp::Ordering, sync::Arc};
 
 use druid::{im::Vector, Data, Lens};
 use serde::{Deserialize, Serialize};
 
-use crate::data::{Album, Cached, Image, Promise, Track};
+use crate::data::{Album, AlbumSort, Cached, Image, Promise, Track};
 
 #[derive(Clone, Data, Lens)]
 pub struct ArtistDetail {
@@ -39,6 +39,54 @@ pub struct ArtistAlbums {
     pub singles: Vector<Arc<Album>>,
     pub compilations: Vector<Arc<Album>>,
     pub appears_on: Vector<Arc<Album>>,
+    /// Order the lists above are sorted in.
+    pub sort: AlbumSort,
+}
+
+impl ArtistAlbums {
+    pub fn sort_by(&mut self, sort: AlbumSort) {
+        for albums in [
+            &mut self.albums,
+            &mut self.singles,
+            &mut self.compilations,
+            &mut self.appears_on,
+        ] {
+            albums.sort_by(|a, b| Self::compare(sort, a, b));
+        }
+        self.sort = sort;
+    }
+
+    /// Albums, singles and compilations in one chronological list, newest
+    /// first unless sorted by the oldest.
+    pub fn merged(&self) -> Vector<Arc<Album>> {
+        let sort = match self.sort {
+            AlbumSort::Oldest => AlbumSort::Oldest,
+            AlbumSort::Newest | AlbumSort::Name => AlbumSort::Newest,
+        };
+        let mut merged = self.albums.clone();
+        merged.append(self.singles.clone());
+        merged.append(self.compilations.clone());
+        merged.sort_by(|a, b| Self::compare(sort, a, b));
+        merged
+    }
+
+    /// The most recently released album or single.
+    pub fn latest_release(&self) -> Option<Arc<Album>> {
+        self.albums
+            .iter()
+            .chain(self.singles.iter())
+            .max_by(|a, b| a.release_cmp(b))
+            .cloned()
+    }
+
+    fn compare(sort: AlbumSort, a: &Album, b: &Album) -> Ordering {
+        let order = match sort {
+            AlbumSort::Newest => b.release_cmp(a),
+            AlbumSort::Oldest => a.release_cmp(b),
+            AlbumSort::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        };
+        order.then_with(|| a.name.cmp(&b.name))
+    }
 }
 
 #[derive(Clone, Data, Lens)]
@@ -64,6 +112,12 @@ pub struct ArtistLink {
 }
 
 impl ArtistLink {
+    /// Artists of local tracks have no Spotify ID, and therefore no detail page
+    /// to navigate to.
+    pub fn is_local(&self) -> bool {
+        self.id.as_ref() == "null"
+    }
+
     pub fn url(&self) -> String {
         format!("https://open.spotify.com/artist/{id}", id = self.id)
     }
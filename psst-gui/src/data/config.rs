@@ -1,20 +1,28 @@
-use std::{env, env::VarError, fs::File, path::PathBuf};
+use std::{env, env::VarError, fs::File, path::PathBuf, sync::Arc, time::Duration};
 
 use std::fs::OpenOptions;
 #[cfg(target_family = "unix")]
 use std::os::unix::fs::OpenOptionsExt;
 
-use druid::{Data, Lens};
+use druid::{
+    im::{HashMap, HashSet, Vector},
+    Data, Lens,
+};
+use log::LevelFilter;
 use platform_dirs::AppDirs;
 use psst_core::{
+    audio::resample::ResamplingQuality,
     cache::mkdir_if_not_exists,
+    connect::ConnectConfig,
     connection::Credentials,
     player::PlaybackConfig,
     session::{SessionConfig, SessionConnection},
+    util::NetTimeouts,
 };
 use serde::{Deserialize, Serialize};
+use url::Url;
 
-use super::{Nav, Promise, QueueBehavior};
+use super::{Nav, Promise, QueueBehavior, SavedSearch, TrackId};
 
 #[derive(Clone, Debug, Data, Lens)]
 pub struct Preferences {
@@ -49,13 +57,18 @@ pub struct Authentication {
 }
 
 impl Authentication {
-    pub fn session_config(&self) -> SessionConfig {
+    pub fn session_config(
+        &self,
+        proxy_url: Option<String>,
+        timeouts: NetTimeouts,
+    ) -> SessionConfig {
         SessionConfig {
             login_creds: Credentials::from_username_and_password(
                 self.username.to_owned(),
                 self.password.to_owned(),
             ),
-            proxy_url: Config::proxy(),
+            proxy_url,
+            timeouts,
         }
     }
 
@@ -67,7 +80,11 @@ impl Authentication {
 
 const APP_NAME: &str = "Psst";
 const CONFIG_FILENAME: &str = "config.json";
+const CREDENTIALS_KEY: &str = "credentials";
 const PROXY_ENV_VAR: &str = "SOCKS_PROXY";
+const MIN_BUFFER_SECONDS: f64 = 0.5;
+const MAX_BUFFER_SECONDS: f64 = 30.0;
+const DEFAULT_TIMEOUT_SECONDS: f64 = 8.0;
 
 #[derive(Clone, Debug, Data, Lens, Serialize, Deserialize)]
 #[serde(default)]
@@ -75,11 +92,90 @@ pub struct Config {
     #[data(ignore)]
     credentials: Option<Credentials>,
     pub audio_quality: AudioQuality,
+    /// Quality of the episodes downloaded for offline playback.
+    pub download_quality: AudioQuality,
     pub theme: Theme,
+    /// Spacing of track rows, and whether they show the album.
+    pub list_density: ListDensity,
+    /// Order of the albums on artist pages.
+    pub artist_albums_sort: AlbumSort,
+    /// Show the albums, singles and compilations of an artist in one list.
+    pub artist_albums_merged: bool,
+    /// Show a compact overview of artists, or everything about them.
+    pub artist_view: ArtistView,
+    pub saved_tracks_sort: SavedTracksSort,
+    /// Order of the tracks in playlists without an override.
+    pub playlist_sort: PlaylistSort,
+    /// Per-playlist overrides of `playlist_sort`, keyed by the playlist ID.
+    pub playlist_sorts: HashMap<Arc<str>, PlaylistSort>,
     pub volume: f64,
     pub last_route: Option<Nav>,
     pub queue_behavior: QueueBehavior,
+    /// How `QueueBehavior::Random` orders the queue, applies to the queues
+    /// played afterwards.
+    pub shuffle_mode: ShuffleMode,
     pub show_track_cover: bool,
+    /// Show the locally counted plays in track lists.
+    pub show_play_counts: bool,
+    pub proxy_url: Option<String>,
+    pub skip_unavailable: bool,
+    /// Playing a track from the search results queues the other results
+    /// after it, instead of playing just that track.
+    pub search_play_continues: bool,
+    /// Tolerance of the "Play Similar Mood" recommendations, see
+    /// `RecommendationsRequest::similar_to`.
+    pub similar_tolerance: f64,
+    /// Serve only cached Web API responses, without touching the network.
+    pub offline: bool,
+    /// Go offline on its own while the network can't be reached, see
+    /// `AppState::network_lost`.
+    pub auto_offline: bool,
+    /// Reuse the responses of identical Web API requests sent within a few
+    /// seconds, see `WebApi::set_response_cache`.
+    pub response_cache: bool,
+    /// Most detailed messages psst logs.  Changes apply right away, at
+    /// startup the `PSST_LOG` env variable takes precedence.
+    pub log_level: LogLevel,
+    /// Tracks that never get played, they are skipped in any queue.
+    pub blocked_tracks: HashSet<TrackId>,
+    /// Seconds of audio to buffer ahead, larger values make the playback
+    /// start later but survive a flaky connection better.
+    pub buffer_seconds: f64,
+    /// Quality of the sample rate conversion to the output device rate.
+    pub resampler_quality: ResamplerQuality,
+    /// Seconds to wait for an HTTP connection to open.
+    pub connect_timeout_seconds: f64,
+    /// Seconds to wait for a single HTTP read or write.
+    pub io_timeout_seconds: f64,
+    /// Number of search results loaded for each topic at once, at most 50.
+    pub search_limit: usize,
+    /// Queries pinned to the sidebar.
+    pub saved_searches: Vector<SavedSearch>,
+    /// Open the main window minimized, e.g. when launched at login.
+    pub start_minimized: bool,
+    /// Keep playing in the background when the main window gets closed,
+    /// instead of quitting.
+    pub close_to_tray: bool,
+    /// Pause the playback when the machine wakes up from sleep.
+    pub pause_on_sleep: bool,
+    /// Jump over episode chapters marked as ads or sponsor segments.
+    pub skip_episode_ads: bool,
+    /// Skip the silence some tracks open with, according to their audio
+    /// analysis.
+    pub trim_silence: bool,
+    /// Load the audio analysis of tracks, which costs a Web API request per
+    /// track.  Features relying on it are off without it.
+    pub fetch_analysis: bool,
+    /// Show up among the Spotify Connect devices, so other Spotify apps can
+    /// control the playback.
+    pub connect_device: bool,
+    /// Spotify Connect device ID, generated once per installation.
+    pub connect_device_id: String,
+    /// Close the audio output once the playback stays paused or stopped for
+    /// `idle_release_seconds`, so the device can power down.  Resuming opens
+    /// it again, with a bit of latency.
+    pub release_device_when_idle: bool,
+    pub idle_release_seconds: f64,
 }
 
 impl Default for Config {
@@ -87,11 +183,46 @@ impl Default for Config {
         Self {
             credentials: Default::default(),
             audio_quality: Default::default(),
+            download_quality: Default::default(),
             theme: Default::default(),
+            list_density: Default::default(),
+            artist_albums_sort: Default::default(),
+            artist_albums_merged: false,
+            artist_view: Default::default(),
+            saved_tracks_sort: Default::default(),
+            playlist_sort: Default::default(),
+            playlist_sorts: HashMap::new(),
             volume: 1.0,
             last_route: Default::default(),
             queue_behavior: Default::default(),
+            shuffle_mode: Default::default(),
             show_track_cover: Default::default(),
+            show_play_counts: false,
+            proxy_url: Default::default(),
+            skip_unavailable: true,
+            search_play_continues: true,
+            similar_tolerance: 0.1,
+            offline: false,
+            auto_offline: false,
+            response_cache: true,
+            log_level: Default::default(),
+            blocked_tracks: HashSet::new(),
+            buffer_seconds: 2.0,
+            resampler_quality: Default::default(),
+            connect_timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            io_timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+            search_limit: 25,
+            saved_searches: Vector::new(),
+            start_minimized: false,
+            close_to_tray: false,
+            pause_on_sleep: false,
+            skip_episode_ads: false,
+            trim_silence: false,
+            fetch_analysis: true,
+            connect_device: false,
+            connect_device_id: ConnectConfig::random_device_id(),
+            release_device_when_idle: false,
+            idle_release_seconds: 60.0,
         }
     }
 }
@@ -126,7 +257,9 @@ impl Config {
         let path = Self::config_path().expect("Failed to get config path");
         if let Ok(file) = File::open(&path) {
             log::info!("loading config: {:?}", &path);
-            Some(serde_json::from_reader(file).expect("Failed to read config"))
+            let mut config: Config = serde_json::from_reader(file).expect("Failed to read config");
+            config.drop_invalid_proxy();
+            Some(config)
         } else {
             None
         }
@@ -148,6 +281,67 @@ impl Config {
         log::info!("saved config: {:?}", &path);
     }
 
+    /// Serialize the settings into pretty JSON.  Credentials are never
+    /// included.
+    pub fn export(&self) -> Result<String, serde_json::Error> {
+        let mut value = serde_json::to_value(self)?;
+        if let Some(object) = value.as_object_mut() {
+            object.remove(CREDENTIALS_KEY);
+        }
+        serde_json::to_string_pretty(&value)
+    }
+
+    /// Merge settings exported by `export` into a copy of this config.  Keys
+    /// we don't know are ignored, so newer exports still import, but all the
+    /// known ones need to be valid.  Credentials are kept from `self`.
+    pub fn import(&self, json: &str) -> Result<Config, serde_json::Error> {
+        let imported: serde_json::Map<String, serde_json::Value> = serde_json::from_str(json)?;
+        let mut merged = match serde_json::to_value(self)? {
+            serde_json::Value::Object(object) => object,
+            _ => unreachable!("config serializes into an object"),
+        };
+        for (key, value) in imported {
+            if key == CREDENTIALS_KEY {
+                continue;
+            }
+            if merged.contains_key(&key) {
+                merged.insert(key, value);
+            } else {
+                log::warn!("ignoring unknown settings key: {:?}", key);
+            }
+        }
+        let mut config: Config = serde_json::from_value(merged.into())?;
+        config.drop_invalid_proxy();
+        Ok(config)
+    }
+
+    fn drop_invalid_proxy(&mut self) {
+        if let Some(url) = &self.proxy_url {
+            if !Self::is_valid_proxy(url) {
+                log::error!("ignoring invalid proxy URL in config: {:?}", url);
+                self.proxy_url = None;
+            }
+        }
+    }
+
+    /// Order of the tracks in playlist `id`, its override or the default.
+    pub fn playlist_sort(&self, id: &str) -> PlaylistSort {
+        self.playlist_sorts
+            .get(id)
+            .copied()
+            .unwrap_or(self.playlist_sort)
+    }
+
+    /// Remember `sort` for playlist `id`.  Sorts matching the default don't
+    /// need an override.
+    pub fn set_playlist_sort(&mut self, id: Arc<str>, sort: PlaylistSort) {
+        if sort == self.playlist_sort {
+            self.playlist_sorts.remove(&id);
+        } else {
+            self.playlist_sorts.insert(id, sort);
+        }
+    }
+
     pub fn has_credentials(&self) -> bool {
         self.credentials.is_some()
     }
@@ -163,19 +357,65 @@ impl Config {
     pub fn session(&self) -> SessionConfig {
         SessionConfig {
             login_creds: self.credentials.clone().expect("Missing credentials"),
-            proxy_url: Config::proxy(),
+            proxy_url: self.proxy(),
+            timeouts: self.net_timeouts(),
         }
     }
 
     pub fn playback(&self) -> PlaybackConfig {
         PlaybackConfig {
             bitrate: self.audio_quality.as_bitrate(),
+            download_bitrate: self.download_quality.as_bitrate(),
+            skip_unavailable: self.skip_unavailable,
+            buffer_duration: Duration::from_secs_f64(
+                self.buffer_seconds
+                    .clamp(MIN_BUFFER_SECONDS, MAX_BUFFER_SECONDS),
+            ),
+            resampling_quality: self.resampler_quality.resampling_quality(),
             ..PlaybackConfig::default()
         }
     }
 
-    pub fn proxy() -> Option<String> {
-        env::var(PROXY_ENV_VAR).map_or_else(
+    pub fn connect(&self) -> ConnectConfig {
+        ConnectConfig {
+            name: APP_NAME.to_string(),
+            device_id: self.connect_device_id.clone(),
+        }
+    }
+
+    /// Timeouts of the Web API, image and CDN connections.  Values that are
+    /// not positive fall back to the defaults.
+    pub fn net_timeouts(&self) -> NetTimeouts {
+        let defaults = NetTimeouts::default();
+        NetTimeouts {
+            connect: Self::timeout(self.connect_timeout_seconds).unwrap_or(defaults.connect),
+            io: Self::timeout(self.io_timeout_seconds).unwrap_or(defaults.io),
+        }
+    }
+
+    /// How long the playback can stay idle before releasing the audio
+    /// device, `None` if it should be kept open.
+    pub fn idle_release_timeout(&self) -> Option<Duration> {
+        if self.release_device_when_idle {
+            Self::timeout(self.idle_release_seconds)
+        } else {
+            None
+        }
+    }
+
+    fn timeout(seconds: f64) -> Option<Duration> {
+        if seconds.is_finite() && seconds > 0.0 {
+            Some(Duration::from_secs_f64(seconds))
+        } else {
+            log::error!("ignoring invalid timeout: {:?}", seconds);
+            None
+        }
+    }
+
+    /// Proxy URL to use for all connections.  `SOCKS_PROXY` env variable takes
+    /// precedence over the configured URL.
+    pub fn proxy(&self) -> Option<String> {
+        let env_proxy = env::var(PROXY_ENV_VAR).map_or_else(
             |err| match err {
                 VarError::NotPresent => None,
                 VarError::NotUnicode(_) => {
@@ -184,7 +424,25 @@ impl Config {
                 }
             },
             Some,
-        )
+        );
+        env_proxy
+            .filter(|url| {
+                let is_valid = Self::is_valid_proxy(url);
+                if !is_valid {
+                    log::error!("ignoring invalid proxy URL: {:?}", url);
+                }
+                is_valid
+            })
+            .or_else(|| self.proxy_url.clone())
+    }
+
+    fn is_valid_proxy(url: &str) -> bool {
+        match Url::parse(url) {
+            Ok(url) => {
+                matches!(url.scheme(), "http" | "socks" | "socks5") && url.host_str().is_some()
+            }
+            Err(_) => false,
+        }
     }
 }
 
@@ -211,6 +469,53 @@ impl Default for AudioQuality {
     }
 }
 
+/// Only used when the output device doesn't run at the 44.1 kHz of the
+/// streams.  All of them are band-limited sinc converters, see
+/// `ResamplingQuality`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data, Serialize, Deserialize)]
+pub enum ResamplerQuality {
+    /// Shortest filter, for low-power machines.  Passes about 80% of the
+    /// bandwidth cleanly.
+    Fast,
+    /// Passes about 90% of the bandwidth, at a fraction of the CPU time of
+    /// `Best`.
+    Balanced,
+    /// Longest filter with about 97% of the bandwidth, costs several times
+    /// the CPU time of `Balanced` and adds a few milliseconds of latency.
+    Best,
+}
+
+impl ResamplerQuality {
+    fn resampling_quality(self) -> ResamplingQuality {
+        match self {
+            ResamplerQuality::Fast => ResamplingQuality::SincFastest,
+            ResamplerQuality::Balanced => ResamplingQuality::SincMediumQuality,
+            ResamplerQuality::Best => ResamplingQuality::SincBestQuality,
+        }
+    }
+}
+
+impl Default for ResamplerQuality {
+    fn default() -> Self {
+        Self::Balanced
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data, Serialize, Deserialize)]
+pub enum ShuffleMode {
+    /// Every order is equally likely.
+    Uniform,
+    /// Tracks played recently or often tend to come later, see
+    /// `PlayCounts::shuffle_weight`.
+    Weighted,
+}
+
+impl Default for ShuffleMode {
+    fn default() -> Self {
+        Self::Uniform
+    }
+}
+
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Data, Serialize, Deserialize)]
 pub enum Theme {
     Light,
@@ -222,3 +527,97 @@ impl Default for Theme {
         Self::Light
     }
 }
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data, Serialize, Deserialize)]
+pub enum ListDensity {
+    Compact,
+    Comfortable,
+}
+
+impl Default for ListDensity {
+    fn default() -> Self {
+        Self::Comfortable
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data, Serialize, Deserialize)]
+pub enum ArtistView {
+    Overview,
+    Full,
+}
+
+impl Default for ArtistView {
+    fn default() -> Self {
+        Self::Overview
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data, Serialize, Deserialize)]
+pub enum AlbumSort {
+    Newest,
+    Oldest,
+    Name,
+}
+
+impl Default for AlbumSort {
+    fn default() -> Self {
+        Self::Newest
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data, Serialize, Deserialize)]
+pub enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn filter(self) -> LevelFilter {
+        match self {
+            LogLevel::Error => LevelFilter::Error,
+            LogLevel::Warn => LevelFilter::Warn,
+            LogLevel::Info => LevelFilter::Info,
+            LogLevel::Debug => LevelFilter::Debug,
+            LogLevel::Trace => LevelFilter::Trace,
+        }
+    }
+}
+
+impl Default for LogLevel {
+    fn default() -> Self {
+        Self::Info
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data, Serialize, Deserialize)]
+pub enum SavedTracksSort {
+    RecentlyAdded,
+    Title,
+    Artist,
+    Album,
+}
+
+impl Default for SavedTracksSort {
+    fn default() -> Self {
+        Self::RecentlyAdded
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data, Serialize, Deserialize)]
+pub enum PlaylistSort {
+    /// The order the playlist is saved in.
+    Manual,
+    RecentlyAdded,
+    Title,
+    Artist,
+    Album,
+}
+
+impl Default for PlaylistSort {
+    fn default() -> Self {
+        Self::Manual
+    }
+}
@@ -38,7 +38,7 @@ impl Finder {
     }
 
     pub fn focus_next(&mut self) {
-        self.focused_result = if self.focused_result < self.results - 1 {
+        self.focused_result = if self.focused_result + 1 < self.results {
             self.focused_result + 1
         } else {
             0
@@ -5,9 +5,12 @@ mod ctx;
 mod find;
 mod id;
 mod nav;
+mod palette;
+mod play_count;
 mod playback;
 mod playlist;
 mod promise;
+mod recent;
 mod recommend;
 mod search;
 mod show;
@@ -26,34 +29,52 @@ use std::{
 };
 
 use druid::{
-    im::{HashSet, Vector},
+    im::{HashMap, HashSet, Vector},
     Data, Lens,
 };
 use psst_core::{item_id::ItemId, session::SessionService};
+use serde::Deserialize;
+use time::{macros::format_description, OffsetDateTime};
+
+use crate::log_file::LogFile;
 
 pub use crate::data::{
     album::{Album, AlbumDetail, AlbumLink, AlbumType, Copyright, CopyrightType},
     artist::{Artist, ArtistAlbums, ArtistDetail, ArtistLink, ArtistTracks},
-    config::{AudioQuality, Authentication, Config, Preferences, PreferencesTab, Theme},
+    config::{
+        AlbumSort, ArtistView, AudioQuality, Authentication, Config, ListDensity, LogLevel,
+        PlaylistSort, Preferences, PreferencesTab, ResamplerQuality, SavedTracksSort, ShuffleMode,
+        Theme,
+    },
     ctx::Ctx,
     find::{FindQuery, Finder, MatchFindQuery},
     nav::{Nav, Route, SpotifyUrl},
+    palette::{Palette, PaletteAction},
+    play_count::{MostPlayed, PlayCounts},
     playback::{
         NowPlaying, Playable, PlayableMatcher, Playback, PlaybackOrigin, PlaybackPayload,
         PlaybackState, QueueBehavior, QueueEntry,
     },
     playlist::{
-        Playlist, PlaylistAddTrack, PlaylistDetail, PlaylistLink, PlaylistRemoveTrack,
-        PlaylistTracks,
+        Playlist, PlaylistAddTrack, PlaylistDetail, PlaylistEntry, PlaylistExport,
+        PlaylistExportFormat, PlaylistImport, PlaylistLink, PlaylistRemoveDuplicates,
+        PlaylistRemoveTrack, PlaylistTracks,
     },
     promise::{Promise, PromiseState},
+    recent::{ContinueListening, PlayHistory, RecentPlay, RecentlyPlayed, RecordedPlay},
     recommend::{
-        Range, Recommend, Recommendations, RecommendationsKnobs, RecommendationsParams,
-        RecommendationsRequest, Toggled,
+        PlaylistRadio, Range, Recommend, Recommendations, RecommendationsKnobs,
+        RecommendationsParams, RecommendationsRequest, Toggled,
+    },
+    search::{SavedSearch, Search, SearchPage, SearchResults, SearchTopic},
+    show::{
+        Episode, EpisodeDownload, EpisodeId, EpisodeLink, ResumePoint, Show, ShowDetail,
+        ShowEpisodes, ShowLink,
+    },
+    track::{
+        AudioAnalysis, AudioFeatures, AudioSegment, IsrcImport, IsrcMatches, TimeInterval, Track,
+        TrackId,
     },
-    search::{Search, SearchResults, SearchTopic},
-    show::{Episode, EpisodeId, EpisodeLink, Show, ShowDetail, ShowEpisodes, ShowLink},
-    track::{AudioAnalysis, AudioSegment, TimeInterval, Track, TrackId},
     user::UserProfile,
     utils::{Cached, Float64, Image, Page},
 };
@@ -70,15 +91,32 @@ pub struct AppState {
     pub playback: Playback,
     pub search: Search,
     pub recommend: Recommend,
+    /// Running playlist radio, dropped once something else plays.
+    pub playlist_radio: Option<PlaylistRadio>,
     pub album_detail: AlbumDetail,
     pub artist_detail: ArtistDetail,
     pub playlist_detail: PlaylistDetail,
     pub show_detail: ShowDetail,
+    pub audio_features: Promise<Cached<AudioFeatures>, TrackId>,
+    /// Track shown in the track info panel, its features load into
+    /// `audio_features`.
+    pub track_info: Option<Arc<Track>>,
     pub library: Arc<Library>,
     pub common_ctx: Arc<CommonCtx>,
     pub personalized: Personalized,
+    pub most_played: Promise<MostPlayed>,
+    pub play_history: PlayHistory,
+    pub recently_played: Promise<RecentlyPlayed>,
     pub alerts: Vector<Alert>,
+    pub errors: Vector<ErrorEntry>,
     pub finder: Finder,
+    pub palette: Palette,
+    /// Stored credentials were rejected, content requiring authentication is
+    /// hidden until the user logs in again.
+    pub unauthorized: bool,
+    /// The network can't be reached and `Config::auto_offline` is on, so we
+    /// are offline until it's back, without touching `Config::offline`.
+    pub network_lost: bool,
 }
 
 impl AppState {
@@ -94,13 +132,20 @@ impl AppState {
             now_playing: None,
             library: Arc::clone(&library),
             show_track_cover: config.show_track_cover,
+            show_play_counts: config.show_play_counts,
+            play_counts: PlayCounts::default(),
+            blocked_tracks: config.blocked_tracks.clone(),
+            downloads: HashMap::new(),
+            selected_row: None,
         });
         let playback = Playback {
             state: PlaybackState::Stopped,
             now_playing: None,
             queue_behavior: config.queue_behavior,
+            behavior_before_shuffle: None,
             queue: Vector::new(),
             volume: config.volume,
+            volume_before_mute: None,
         };
         Self {
             session: SessionService::empty(),
@@ -120,11 +165,14 @@ impl AppState {
             search: Search {
                 input: "".into(),
                 results: Promise::Empty,
+                library_matches: SearchResults::empty("".into()),
+                renaming: None,
             },
             recommend: Recommend {
                 knobs: Default::default(),
                 results: Promise::Empty,
             },
+            playlist_radio: None,
             album_detail: AlbumDetail {
                 album: Promise::Empty,
             },
@@ -142,17 +190,34 @@ impl AppState {
                 show: Promise::Empty,
                 episodes: Promise::Empty,
             },
+            audio_features: Promise::Empty,
+            track_info: None,
             library,
             common_ctx,
+            most_played: Promise::Empty,
+            play_history: PlayHistory::default(),
+            recently_played: Promise::Empty,
             personalized: Personalized {
                 made_for_you: Promise::Empty,
+                continue_listening: Promise::Empty,
             },
             alerts: Vector::new(),
+            errors: Vector::new(),
             finder: Finder::new(),
+            palette: Palette::new(),
+            unauthorized: false,
+            network_lost: false,
         }
     }
 }
 
+impl AppState {
+    /// Offline by choice, or because the network was lost.
+    pub fn is_offline(&self) -> bool {
+        self.config.offline || self.network_lost
+    }
+}
+
 impl AppState {
     pub fn navigate(&mut self, nav: &Nav) {
         if &self.nav != nav {
@@ -172,6 +237,17 @@ impl AppState {
     }
 }
 
+impl AppState {
+    /// Change the search query, matching it against the library right away.
+    pub fn set_search_input(&mut self, input: String) {
+        if self.search.input != input {
+            self.search.library_matches =
+                SearchResults::from_library(input.trim().into(), &self.library);
+            self.search.input = input;
+        }
+    }
+}
+
 impl AppState {
     pub fn queued_entry(&self, item_id: ItemId) -> Option<QueueEntry> {
         self.playback
@@ -188,17 +264,27 @@ impl AppState {
             item,
             origin,
             progress: Duration::default(),
+            bitrate: None,
+            counted: false,
             library: Arc::clone(&self.library),
         });
     }
 
-    pub fn start_playback(&mut self, item: Playable, origin: PlaybackOrigin, progress: Duration) {
+    pub fn start_playback(
+        &mut self,
+        item: Playable,
+        origin: PlaybackOrigin,
+        progress: Duration,
+        bitrate: usize,
+    ) {
         self.common_ctx_mut().now_playing.replace(item.clone());
         self.playback.state = PlaybackState::Playing;
         self.playback.now_playing.replace(NowPlaying {
             item,
             origin,
             progress,
+            bitrate: Some(bitrate),
+            counted: false,
             library: Arc::clone(&self.library),
         });
     }
@@ -209,6 +295,48 @@ impl AppState {
         }
     }
 
+    /// Add the playing track to the play counts and the play history, once
+    /// enough of it got played.  Returns true if they changed.
+    pub fn count_play(&mut self) -> bool {
+        let now_playing = match &mut self.playback.now_playing {
+            Some(now_playing) if !now_playing.counted => now_playing,
+            _ => return false,
+        };
+        let track_id = match &now_playing.item {
+            Playable::Track(track)
+                if !track.is_local
+                    && PlayCounts::reached_threshold(now_playing.progress, track.duration) =>
+            {
+                track.id
+            }
+            _ => return false,
+        };
+        now_playing.counted = true;
+        self.play_history.record(track_id, &now_playing.origin);
+        self.common_ctx_mut().play_counts.increment(track_id);
+        true
+    }
+
+    /// Shuffle weights of the queued items for the player, if the weighted
+    /// shuffle is enabled.
+    pub fn shuffle_weights(&self) -> Option<Vec<f64>> {
+        match self.config.shuffle_mode {
+            ShuffleMode::Uniform => None,
+            ShuffleMode::Weighted => Some(
+                self.playback
+                    .queue
+                    .iter()
+                    .map(|queued| match &queued.item {
+                        Playable::Track(track) => {
+                            self.common_ctx.play_counts.shuffle_weight(&track.id)
+                        }
+                        Playable::Episode(_) => 1.0,
+                    })
+                    .collect(),
+            ),
+        }
+    }
+
     pub fn pause_playback(&mut self) {
         self.playback.state = PlaybackState::Paused;
     }
@@ -217,6 +345,38 @@ impl AppState {
         self.playback.state = PlaybackState::Playing;
     }
 
+    pub fn playback_unavailable(&mut self, item_id: ItemId, skipped: bool) {
+        let name = self
+            .queued_entry(item_id)
+            .map(|entry| entry.item.name().to_string())
+            .unwrap_or_else(|| "Track".to_string());
+        if skipped {
+            self.record_error(
+                "Skipping unavailable track",
+                format!("{} is unavailable", name),
+            );
+            self.info_alert(format!("Skipped {}, it is unavailable.", name));
+        } else {
+            self.action_failed(
+                "Playback",
+                format!("{} is unavailable, no playable tracks left", name),
+            );
+        }
+    }
+
+    pub fn playback_blocked_track(&mut self, item_id: ItemId, skipped: bool) {
+        let name = self
+            .queued_entry(item_id)
+            .map(|entry| entry.item.name().to_string())
+            .unwrap_or_else(|| "Track".to_string());
+        if skipped {
+            self.record_error("Skipping blocked track", format!("{} is blocked", name));
+        } else {
+            self.record_error("Playback", format!("{} is blocked", name));
+            self.info_alert("All tracks in the queue are blocked.");
+        }
+    }
+
     pub fn block_playback(&mut self) {
         // TODO: Figure out how to signal blocked playback properly.
     }
@@ -229,9 +389,130 @@ impl AppState {
 
     pub fn set_queue_behavior(&mut self, queue_behavior: QueueBehavior) {
         self.playback.queue_behavior = queue_behavior;
+        self.playback.behavior_before_shuffle = None;
         self.config.queue_behavior = queue_behavior;
         self.config.save();
     }
+
+    /// Block the track if it isn't blocked yet, or unblock it.  Returns `true`
+    /// if the track is now blocked.
+    pub fn toggle_blocked_track(&mut self, track_id: TrackId) -> bool {
+        let is_blocked = if self.config.blocked_tracks.remove(&track_id).is_some() {
+            false
+        } else {
+            self.config.blocked_tracks.insert(track_id);
+            true
+        };
+        self.config.save();
+        self.common_ctx_mut().blocked_tracks = self.config.blocked_tracks.clone();
+        is_blocked
+    }
+
+    pub fn is_blocked(&self, item_id: ItemId) -> bool {
+        self.config.blocked_tracks.contains(&TrackId(item_id))
+    }
+
+    /// Change the volume by `delta`, starting from the unmuted volume if muted.
+    pub fn change_volume(&mut self, delta: f64) {
+        let volume = if self.is_muted() {
+            self.playback.volume_before_mute.take().unwrap_or_default()
+        } else {
+            self.playback.volume
+        };
+        self.playback.volume_before_mute = None;
+        self.playback.volume = (volume + delta).clamp(0.0, 1.0);
+    }
+
+    /// Mute, or go back to the volume from before muting.
+    pub fn toggle_mute(&mut self) {
+        if self.is_muted() {
+            self.playback.volume = self.playback.volume_before_mute.take().unwrap_or(1.0);
+        } else {
+            self.playback.volume_before_mute = Some(self.playback.volume);
+            self.playback.volume = 0.0;
+        }
+    }
+
+    /// Moving the volume slider away from zero counts as unmuting.
+    fn is_muted(&self) -> bool {
+        self.playback.volume_before_mute.is_some() && self.playback.volume == 0.0
+    }
+
+    pub fn cycle_queue_behavior(&mut self) -> QueueBehavior {
+        let queue_behavior = self.playback.queue_behavior.next();
+        self.set_queue_behavior(queue_behavior);
+        queue_behavior
+    }
+
+    /// Pin `query` to the sidebar, named after the query itself.  Returns
+    /// `false` if the query is saved already.
+    pub fn add_saved_search(&mut self, query: Arc<str>) -> bool {
+        let searches = &mut self.config.saved_searches;
+        if searches.iter().any(|saved| saved.query == query) {
+            return false;
+        }
+        searches.push_back(SavedSearch {
+            name: query.clone(),
+            query,
+        });
+        self.config.save();
+        true
+    }
+
+    pub fn rename_saved_search(&mut self, query: &str, name: Arc<str>) {
+        if name.trim().is_empty() {
+            return;
+        }
+        for saved in self.config.saved_searches.iter_mut() {
+            if saved.query.as_ref() == query {
+                saved.name = name.clone();
+            }
+        }
+        self.config.save();
+    }
+
+    pub fn remove_saved_search(&mut self, query: &str) {
+        self.config
+            .saved_searches
+            .retain(|saved| saved.query.as_ref() != query);
+        self.config.save();
+    }
+
+    /// Switch to random queue behavior for a shuffled play, without saving it
+    /// into the config.  The behavior selected until now is kept around for
+    /// `restore_queue_behavior`.
+    pub fn shuffle_queue_behavior(&mut self) {
+        self.override_queue_behavior(QueueBehavior::Random);
+    }
+
+    /// Like `shuffle_queue_behavior`, but for album shuffle, which shuffles
+    /// the albums up front and needs the queue played in order.
+    pub fn album_shuffle_queue_behavior(&mut self) {
+        self.override_queue_behavior(QueueBehavior::Sequential);
+    }
+
+    fn override_queue_behavior(&mut self, queue_behavior: QueueBehavior) {
+        if self.playback.behavior_before_shuffle.is_none() {
+            self.playback.behavior_before_shuffle = Some(self.playback.queue_behavior);
+        }
+        self.playback.queue_behavior = queue_behavior;
+    }
+
+    /// Return to the queue behavior selected before a shuffled play, in case
+    /// `origin` is a different collection than the one currently queued.
+    /// Returns the restored behavior.
+    pub fn restore_queue_behavior(&mut self, origin: &PlaybackOrigin) -> Option<QueueBehavior> {
+        let is_same_origin = matches!(
+            self.playback.queue.front(),
+            Some(entry) if entry.origin.same(origin)
+        );
+        if is_same_origin {
+            return None;
+        }
+        let queue_behavior = self.playback.behavior_before_shuffle.take()?;
+        self.playback.queue_behavior = queue_behavior;
+        Some(queue_behavior)
+    }
 }
 
 impl AppState {
@@ -252,6 +533,50 @@ impl AppState {
     }
 }
 
+impl AppState {
+    /// Gather non-sensitive diagnostics for bug reports.  Never include the
+    /// credentials or access tokens here.
+    pub fn debug_info(&self) -> String {
+        // Walking the cache directory takes a while, report the size only if
+        // the preferences have already measured it.
+        let cache_size = self
+            .preferences
+            .cache_size
+            .resolved()
+            .map(|size| format!("{:.2} MB", *size as f64 / 1e6_f64))
+            .unwrap_or_else(|| "Unknown".to_string());
+        let log_file = LogFile::path()
+            .map(|path| path.to_string_lossy().to_string())
+            .unwrap_or_else(|| "None".to_string());
+        let recent_errors = self
+            .errors
+            .iter()
+            .take(10)
+            .map(|entry| format!("  - {}", entry))
+            .collect::<Vec<_>>()
+            .join("\n");
+        format!(
+            "Psst {version}\n\
+             OS: {os} ({arch})\n\
+             Route: {nav:?}\n\
+             Playback: {playback:?}\n\
+             Connected: {connected}\n\
+             Cache size: {cache_size}\n\
+             Log file: {log_file}\n\
+             Recent errors:\n{recent_errors}",
+            version = env!("CARGO_PKG_VERSION"),
+            os = std::env::consts::OS,
+            arch = std::env::consts::ARCH,
+            nav = self.nav,
+            playback = self.playback.state,
+            connected = self.session.is_connected(),
+            cache_size = cache_size,
+            log_file = log_file,
+            recent_errors = recent_errors,
+        )
+    }
+}
+
 impl AppState {
     pub fn info_alert(&mut self, message: impl Display) {
         self.alerts.push_back(Alert {
@@ -272,6 +597,24 @@ impl AppState {
     pub fn dismiss_alert(&mut self, id: usize) {
         self.alerts.retain(|a| a.id != id);
     }
+
+    /// Show an error alert and keep the error in the list of recent failures.
+    pub fn action_failed(&mut self, action: &str, err: impl Display) {
+        self.record_error(action, &err);
+        self.error_alert(err);
+    }
+
+    /// Keep the error in the list of recent failures, without alerting.
+    pub fn record_error(&mut self, action: &str, err: impl Display) {
+        const MAX_ERRORS: usize = 50;
+
+        self.errors.push_front(ErrorEntry {
+            action: action.into(),
+            message: err.to_string().into(),
+            time: OffsetDateTime::now_utc(),
+        });
+        self.errors.truncate(MAX_ERRORS);
+    }
 }
 
 #[derive(Clone, Data, Lens)]
@@ -285,10 +628,7 @@ pub struct Library {
 
 impl Library {
     pub fn add_track(&mut self, track: Arc<Track>) {
-        if let Some(saved) = self.saved_tracks.resolved_mut() {
-            saved.set.insert(track.id);
-            saved.tracks.push_front(track);
-        }
+        self.add_tracks([track]);
     }
 
     pub fn remove_track(&mut self, track_id: &TrackId) {
@@ -298,6 +638,46 @@ impl Library {
         }
     }
 
+    pub fn set_saved_tracks(&mut self, saved: SavedTracks) {
+        self.saved_tracks.resolve((), saved);
+    }
+
+    /// Saved tracks are either not loaded yet, or only loaded from cache.
+    pub fn needs_saved_tracks(&self) -> bool {
+        self.saved_tracks
+            .resolved()
+            .map_or(true, |saved| saved.is_cached)
+    }
+
+    pub fn add_tracks(&mut self, tracks: impl IntoIterator<Item = Arc<Track>>) {
+        if let Some(saved) = self.saved_tracks.resolved_mut() {
+            let now = OffsetDateTime::now_utc();
+            for track in tracks {
+                if saved.set.insert(track.id).is_none() {
+                    saved.added_at.insert(track.id, now);
+                    saved.tracks.push_front(track);
+                }
+            }
+            saved.sort_by(saved.sort);
+        }
+    }
+
+    pub fn sort_saved_tracks(&mut self, sort: SavedTracksSort) {
+        if let Some(saved) = self.saved_tracks.resolved_mut() {
+            saved.sort_by(sort);
+        }
+    }
+
+    pub fn remove_tracks(&mut self, track_ids: impl IntoIterator<Item = TrackId>) {
+        if let Some(saved) = self.saved_tracks.resolved_mut() {
+            for track_id in track_ids {
+                saved.set.remove(&track_id);
+            }
+            let set = &saved.set;
+            saved.tracks.retain(|t| set.contains(&t.id));
+        }
+    }
+
     pub fn contains_track(&self, track: &Track) -> bool {
         if let Some(saved) = self.saved_tracks.resolved() {
             saved.set.contains(&track.id)
@@ -306,6 +686,17 @@ impl Library {
         }
     }
 
+    pub fn set_saved_albums(&mut self, saved: SavedAlbums) {
+        self.saved_albums.resolve((), saved);
+    }
+
+    /// Saved albums are either not loaded yet, or only loaded from cache.
+    pub fn needs_saved_albums(&self) -> bool {
+        self.saved_albums
+            .resolved()
+            .map_or(true, |saved| saved.is_cached)
+    }
+
     pub fn add_album(&mut self, album: Arc<Album>) {
         if let Some(saved) = self.saved_albums.resolved_mut() {
             saved.set.insert(album.id.clone());
@@ -321,8 +712,12 @@ impl Library {
     }
 
     pub fn contains_album(&self, album: &Album) -> bool {
+        self.contains_album_id(&album.id)
+    }
+
+    pub fn contains_album_id(&self, album_id: &Arc<str>) -> bool {
         if let Some(saved) = self.saved_albums.resolved() {
-            saved.set.contains(&album.id)
+            saved.set.contains(album_id)
         } else {
             false
         }
@@ -367,6 +762,12 @@ impl Library {
         }
     }
 
+    pub fn add_playlist(&mut self, playlist: Playlist) {
+        if let Some(saved) = self.playlists.resolved_mut() {
+            saved.push_front(playlist);
+        }
+    }
+
     pub fn increment_playlist_track_count(&mut self, link: &PlaylistLink) {
         if let Some(saved) = self.playlists.resolved_mut() {
             for playlist in saved.iter_mut() {
@@ -378,26 +779,78 @@ impl Library {
     }
 
     pub fn decrement_playlist_track_count(&mut self, link: &PlaylistLink) {
+        self.subtract_playlist_track_count(link, 1);
+    }
+
+    pub fn subtract_playlist_track_count(&mut self, link: &PlaylistLink, count: usize) {
         if let Some(saved) = self.playlists.resolved_mut() {
             for playlist in saved.iter_mut() {
                 if playlist.id == link.id {
-                    playlist.track_count -= 1;
+                    playlist.track_count = playlist.track_count.saturating_sub(count);
                 }
             }
         }
     }
 }
 
+#[derive(Clone, Deserialize)]
+pub struct SavedTrack {
+    pub track: Arc<Track>,
+    #[serde(default, deserialize_with = "utils::deserialize_datetime_option")]
+    pub added_at: Option<OffsetDateTime>,
+}
+
 #[derive(Clone, Default, Data, Lens)]
 pub struct SavedTracks {
     pub tracks: Vector<Arc<Track>>,
     pub set: HashSet<TrackId>,
+    /// Only used for sorting, which changes `tracks` anyway.
+    #[data(ignore)]
+    pub added_at: HashMap<TrackId, OffsetDateTime>,
+    /// Order `tracks` are sorted in, the API returns them recently added
+    /// first.
+    pub sort: SavedTracksSort,
+    /// Loaded from the local cache, a refresh is due.
+    pub is_cached: bool,
 }
 
 impl SavedTracks {
-    pub fn new(tracks: Vector<Arc<Track>>) -> Self {
-        let set = tracks.iter().map(|t| t.id).collect();
-        Self { tracks, set }
+    pub fn new(saved: Vector<SavedTrack>) -> Self {
+        let set = saved.iter().map(|s| s.track.id).collect();
+        let added_at = saved
+            .iter()
+            .filter_map(|s| Some((s.track.id, s.added_at?)))
+            .collect();
+        Self {
+            tracks: saved.into_iter().map(|s| s.track).collect(),
+            set,
+            added_at,
+            sort: SavedTracksSort::RecentlyAdded,
+            is_cached: false,
+        }
+    }
+
+    pub fn cached(saved: Vector<SavedTrack>) -> Self {
+        Self {
+            is_cached: true,
+            ..Self::new(saved)
+        }
+    }
+
+    pub fn sort_by(&mut self, sort: SavedTracksSort) {
+        let added_at = &self.added_at;
+        let album_key = |t: &Track| (t.album_name().to_lowercase(), t.disc_number, t.track_number);
+        self.tracks.sort_by(|a, b| match sort {
+            SavedTracksSort::RecentlyAdded => added_at.get(&b.id).cmp(&added_at.get(&a.id)),
+            SavedTracksSort::Title => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            SavedTracksSort::Artist => a
+                .artist_name()
+                .to_lowercase()
+                .cmp(&b.artist_name().to_lowercase())
+                .then_with(|| album_key(a).cmp(&album_key(b))),
+            SavedTracksSort::Album => album_key(a).cmp(&album_key(b)),
+        });
+        self.sort = sort;
     }
 }
 
@@ -405,12 +858,25 @@ impl SavedTracks {
 pub struct SavedAlbums {
     pub albums: Vector<Arc<Album>>,
     pub set: HashSet<Arc<str>>,
+    /// Loaded from the local cache, a refresh is due.
+    pub is_cached: bool,
 }
 
 impl SavedAlbums {
     pub fn new(albums: Vector<Arc<Album>>) -> Self {
         let set = albums.iter().map(|a| a.id.clone()).collect();
-        Self { albums, set }
+        Self {
+            albums,
+            set,
+            is_cached: false,
+        }
+    }
+
+    pub fn cached(albums: Vector<Arc<Album>>) -> Self {
+        Self {
+            is_cached: true,
+            ..Self::new(albums)
+        }
     }
 }
 
@@ -432,12 +898,36 @@ pub struct CommonCtx {
     pub now_playing: Option<Playable>,
     pub library: Arc<Library>,
     pub show_track_cover: bool,
+    pub show_play_counts: bool,
+    pub play_counts: PlayCounts,
+    pub blocked_tracks: HashSet<TrackId>,
+    pub downloads: HashMap<EpisodeId, EpisodeDownload>,
+    pub selected_row: Option<SelectedRow>,
+}
+
+/// Row of a track list selected with the keyboard, identified by the origin
+/// of the list and the position in it.
+#[derive(Clone, Debug, Data)]
+pub struct SelectedRow {
+    pub origin: PlaybackOrigin,
+    pub position: usize,
 }
 
 impl CommonCtx {
     pub fn is_playing(&self, item: &Playable) -> bool {
         matches!(&self.now_playing, Some(i) if i.same(item))
     }
+
+    pub fn is_track_blocked(&self, track: &Track) -> bool {
+        self.blocked_tracks.contains(&track.id)
+    }
+
+    pub fn selected_position(&self, origin: &PlaybackOrigin) -> Option<usize> {
+        self.selected_row
+            .as_ref()
+            .filter(|row| row.origin.same(origin))
+            .map(|row| row.position)
+    }
 }
 
 pub type WithCtx<T> = Ctx<Arc<CommonCtx>, T>;
@@ -445,6 +935,7 @@ pub type WithCtx<T> = Ctx<Arc<CommonCtx>, T>;
 #[derive(Clone, Data, Lens)]
 pub struct Personalized {
     pub made_for_you: Promise<Vector<Playlist>>,
+    pub continue_listening: Promise<ContinueListening>,
 }
 
 static ALERT_ID: AtomicUsize = AtomicUsize::new(0);
@@ -467,3 +958,69 @@ pub enum AlertStyle {
     Error,
     Info,
 }
+
+#[derive(Clone, Data, Lens)]
+pub struct ErrorEntry {
+    pub action: Arc<str>,
+    pub message: Arc<str>,
+    #[data(same_fn = "PartialEq::eq")]
+    pub time: OffsetDateTime,
+}
+
+impl Display for ErrorEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let format = format_description!("[hour]:[minute]:[second]");
+        let time = self.time.format(format).map_err(|_| std::fmt::Error)?;
+        write!(f, "[{} UTC] {}: {}", time, self.action, self.message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn playlist(id: &str) -> PlaybackOrigin {
+        PlaybackOrigin::Playlist(PlaylistLink {
+            id: id.into(),
+            name: id.into(),
+        })
+    }
+
+    fn queue_from(data: &mut AppState, origin: PlaybackOrigin) {
+        data.playback.queue = Vector::unit(QueueEntry {
+            item: Playable::Track(Track::for_test(1)),
+            origin,
+        });
+    }
+
+    #[test]
+    fn shuffled_play_keeps_the_selected_behavior() {
+        let mut data = AppState::default_with_config(Config::default());
+        data.playback.queue_behavior = QueueBehavior::LoopAll;
+        data.shuffle_queue_behavior();
+        data.shuffle_queue_behavior();
+        assert_eq!(data.playback.queue_behavior, QueueBehavior::Random);
+        assert_eq!(
+            data.playback.behavior_before_shuffle,
+            Some(QueueBehavior::LoopAll)
+        );
+    }
+
+    #[test]
+    fn playing_another_collection_restores_the_behavior() {
+        let mut data = AppState::default_with_config(Config::default());
+        data.playback.queue_behavior = QueueBehavior::Sequential;
+        data.shuffle_queue_behavior();
+        queue_from(&mut data, playlist("shuffled"));
+
+        assert_eq!(data.restore_queue_behavior(&playlist("shuffled")), None);
+        assert_eq!(data.playback.queue_behavior, QueueBehavior::Random);
+
+        assert_eq!(
+            data.restore_queue_behavior(&playlist("other")),
+            Some(QueueBehavior::Sequential)
+        );
+        assert_eq!(data.playback.queue_behavior, QueueBehavior::Sequential);
+        assert_eq!(data.playback.behavior_before_shuffle, None);
+    }
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, time::Duration};
 
 use druid::Data;
 use serde::{Deserialize, Serialize};
@@ -20,6 +20,8 @@ pub enum Route {
     ShowDetail,
     PlaylistDetail,
     Recommendations,
+    MostPlayed,
+    RecentlyPlayed,
 }
 
 #[derive(Clone, Debug, Data, PartialEq, Eq, Deserialize, Serialize)]
@@ -34,6 +36,8 @@ pub enum Nav {
     ShowDetail(ShowLink),
     PlaylistDetail(PlaylistLink),
     Recommendations(Arc<RecommendationsRequest>),
+    MostPlayed,
+    RecentlyPlayed,
 }
 
 impl Nav {
@@ -49,6 +53,8 @@ impl Nav {
             Nav::PlaylistDetail(_) => Route::PlaylistDetail,
             Nav::ShowDetail(_) => Route::ShowDetail,
             Nav::Recommendations(_) => Route::Recommendations,
+            Nav::MostPlayed => Route::MostPlayed,
+            Nav::RecentlyPlayed => Route::RecentlyPlayed,
         }
     }
 
@@ -64,6 +70,8 @@ impl Nav {
             Nav::PlaylistDetail(link) => link.name.to_string(),
             Nav::ShowDetail(link) => link.name.to_string(),
             Nav::Recommendations(_) => "Recommended".to_string(),
+            Nav::MostPlayed => "Most Played".to_string(),
+            Nav::RecentlyPlayed => "Recently Played".to_string(),
         }
     }
 
@@ -79,6 +87,8 @@ impl Nav {
             Nav::PlaylistDetail(link) => format!("Playlist “{}”", link.name),
             Nav::ShowDetail(link) => format!("Show “{}”", link.name),
             Nav::Recommendations(_) => "Recommended".to_string(),
+            Nav::MostPlayed => "Most Played".to_string(),
+            Nav::RecentlyPlayed => "Recently Played".to_string(),
         }
     }
 }
@@ -88,7 +98,8 @@ pub enum SpotifyUrl {
     Playlist(Arc<str>),
     Artist(Arc<str>),
     Album(Arc<str>),
-    Track(Arc<str>),
+    /// Track, optionally with a position to start the playback at.
+    Track(Arc<str>, Option<Duration>),
     Show(Arc<str>),
 }
 
@@ -103,7 +114,13 @@ impl SpotifyUrl {
             "playlist" => Some(Self::Playlist(id.into())),
             "artist" => Some(Self::Artist(id.into())),
             "album" => Some(Self::Album(id.into())),
-            "track" => Some(Self::Track(id.into())),
+            "track" => {
+                let start_at = url
+                    .query_pairs()
+                    .find(|(key, _)| key == "t")
+                    .and_then(|(_, value)| parse_timestamp(&value));
+                Some(Self::Track(id.into(), start_at))
+            }
             "show" => Some(Self::Show(id.into())),
             _ => None,
         }
@@ -114,8 +131,18 @@ impl SpotifyUrl {
             SpotifyUrl::Playlist(id) => id.clone(),
             SpotifyUrl::Artist(id) => id.clone(),
             SpotifyUrl::Album(id) => id.clone(),
-            SpotifyUrl::Track(id) => id.clone(),
+            SpotifyUrl::Track(id, _) => id.clone(),
             SpotifyUrl::Show(id) => id.clone(),
         }
     }
 }
+
+/// Parse a link timestamp, either in seconds (`90`) or as minutes and seconds
+/// (`1:30`).
+fn parse_timestamp(t: &str) -> Option<Duration> {
+    let secs = match t.split_once(':') {
+        Some((mins, secs)) => mins.parse::<u64>().ok()? * 60 + secs.parse::<u64>().ok()?,
+        None => t.parse().ok()?,
+    };
+    Some(Duration::from_secs(secs))
+}
@@ -0,0 +1,109 @@
+use druid::{im::Vector, Data, Lens};
+
+use crate::data::utils::fuzzy_score;
+
+/// Quick action of the command palette.
+#[derive(Clone, Copy, Debug, Data, Eq, PartialEq)]
+pub enum PaletteAction {
+    PlayPause,
+    Next,
+    Previous,
+    ToggleShuffle,
+    GoToNowPlaying,
+    GoHome,
+    GoToSavedTracks,
+    GoToSavedAlbums,
+    GoToSavedShows,
+    GoToMostPlayed,
+    GoToRecentlyPlayed,
+    Search,
+    OpenPreferences,
+}
+
+impl PaletteAction {
+    pub const ALL: [Self; 13] = [
+        Self::PlayPause,
+        Self::Next,
+        Self::Previous,
+        Self::ToggleShuffle,
+        Self::GoToNowPlaying,
+        Self::GoHome,
+        Self::GoToSavedTracks,
+        Self::GoToSavedAlbums,
+        Self::GoToSavedShows,
+        Self::GoToMostPlayed,
+        Self::GoToRecentlyPlayed,
+        Self::Search,
+        Self::OpenPreferences,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::PlayPause => "Play / Pause",
+            Self::Next => "Next Track",
+            Self::Previous => "Previous Track",
+            Self::ToggleShuffle => "Toggle Shuffle",
+            Self::GoToNowPlaying => "Go to Now Playing",
+            Self::GoHome => "Go to Home",
+            Self::GoToSavedTracks => "Go to Saved Tracks",
+            Self::GoToSavedAlbums => "Go to Saved Albums",
+            Self::GoToSavedShows => "Go to Saved Shows",
+            Self::GoToMostPlayed => "Go to Most Played",
+            Self::GoToRecentlyPlayed => "Go to Recently Played",
+            Self::Search => "Search",
+            Self::OpenPreferences => "Open Preferences",
+        }
+    }
+}
+
+#[derive(Clone, Default, Debug, Data, Lens)]
+pub struct Palette {
+    pub show: bool,
+    pub query: String,
+    /// Index into `matches()` of the action run by Enter.
+    pub selected: usize,
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.query = String::new();
+        self.selected = 0;
+    }
+
+    /// Actions matching the query, the best matches first.
+    pub fn matches(&self) -> Vector<PaletteAction> {
+        let mut scored: Vec<(i64, PaletteAction)> = PaletteAction::ALL
+            .iter()
+            .filter_map(|&action| Some((fuzzy_score(&self.query, action.name())?, action)))
+            .collect();
+        // The sort is stable, equally good matches keep their order.
+        scored.sort_by(|a, b| b.0.cmp(&a.0));
+        scored.into_iter().map(|(_, action)| action).collect()
+    }
+
+    pub fn selected_action(&self) -> Option<PaletteAction> {
+        self.matches().get(self.selected).copied()
+    }
+
+    pub fn select_previous(&mut self) {
+        let count = self.matches().len();
+        self.selected = if self.selected > 0 {
+            self.selected - 1
+        } else {
+            count.saturating_sub(1)
+        };
+    }
+
+    pub fn select_next(&mut self) {
+        let count = self.matches().len();
+        self.selected = if self.selected + 1 < count {
+            self.selected + 1
+        } else {
+            0
+        };
+    }
+}
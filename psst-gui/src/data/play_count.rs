@@ -0,0 +1,139 @@
+use std::{
+    fs::File,
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use druid::{
+    im::{HashMap, Vector},
+    Data, Lens,
+};
+use psst_core::cache::mkdir_if_not_exists;
+use serde::{Deserialize, Serialize};
+
+use crate::data::{Config, Track, TrackId};
+
+const PLAY_COUNTS_FILENAME: &str = "play-counts.json";
+/// Tracks shorter than this never get counted.
+const MIN_COUNTED_DURATION: Duration = Duration::from_secs(30);
+/// A track counts as played after half of it, or after this much of it,
+/// whichever comes first.
+const MAX_PLAYED_THRESHOLD: Duration = Duration::from_secs(4 * 60);
+/// Tracks played longer ago than this are not considered recently played by
+/// the weighted shuffle.
+const RECENCY_WINDOW: Duration = Duration::from_secs(3 * 24 * 60 * 60);
+/// Shuffle weight of a track that has just been played, relative to a track
+/// that hasn't been played within `RECENCY_WINDOW`.
+const MIN_RECENCY_WEIGHT: f64 = 0.1;
+
+/// Number of times each track was played on this machine, Spotify doesn't
+/// expose the personal play counts.
+#[derive(Clone, Debug, Default, Data, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PlayCounts {
+    counts: HashMap<TrackId, TrackPlays>,
+}
+
+#[derive(Clone, Copy, Debug, Data, Serialize, Deserialize)]
+struct TrackPlays {
+    count: u32,
+    /// Seconds since the Unix epoch.
+    last_played: u64,
+}
+
+impl PlayCounts {
+    /// Whether playing a track `duration` long up to `progress` counts as a
+    /// play.
+    pub fn reached_threshold(progress: Duration, duration: Duration) -> bool {
+        duration >= MIN_COUNTED_DURATION && progress >= (duration / 2).min(MAX_PLAYED_THRESHOLD)
+    }
+
+    pub fn get(&self, id: &TrackId) -> u32 {
+        self.counts.get(id).map_or(0, |plays| plays.count)
+    }
+
+    pub fn increment(&mut self, id: TrackId) {
+        let plays = self.counts.entry(id).or_insert(TrackPlays {
+            count: 0,
+            last_played: 0,
+        });
+        plays.count += 1;
+        plays.last_played = unix_now();
+    }
+
+    /// Relative chance of track `id` to get picked early by the weighted
+    /// shuffle, in 0.0..=1.0.  Tracks played recently or often weigh less.
+    pub fn shuffle_weight(&self, id: &TrackId) -> f64 {
+        let plays = match self.counts.get(id) {
+            Some(plays) => plays,
+            None => return 1.0,
+        };
+        // Frequency decays slowly, so a track played 100 times is not 100x
+        // less likely than a track played once.
+        let frequency = 1.0 / (1.0 + f64::from(plays.count).ln_1p());
+        let age = unix_now().saturating_sub(plays.last_played) as f64;
+        let recency = (age / RECENCY_WINDOW.as_secs_f64()).min(1.0);
+        frequency * (MIN_RECENCY_WEIGHT + (1.0 - MIN_RECENCY_WEIGHT) * recency)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.counts.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.counts.clear();
+    }
+
+    /// Up to `limit` tracks with the highest counts, the most played first.
+    pub fn most_played(&self, limit: usize) -> Vec<TrackId> {
+        let mut counts: Vec<(TrackId, u32)> = self
+            .counts
+            .iter()
+            .map(|(id, plays)| (*id, plays.count))
+            .collect();
+        counts.sort_by(|a, b| b.1.cmp(&a.1));
+        counts.into_iter().take(limit).map(|(id, _)| id).collect()
+    }
+
+    pub fn load() -> Self {
+        let file = match Self::path().and_then(|path| File::open(path).ok()) {
+            Some(file) => file,
+            None => return Self::default(),
+        };
+        serde_json::from_reader(file).unwrap_or_else(|err| {
+            log::error!("failed to read play counts: {:?}", err);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let (dir, path) = match (Config::cache_dir(), Self::path()) {
+            (Some(dir), Some(path)) => (dir, path),
+            _ => return,
+        };
+        let result = mkdir_if_not_exists(&dir)
+            .and_then(|_| File::create(&path))
+            .map_err(serde_json::Error::io)
+            .and_then(|file| serde_json::to_writer(file, self));
+        if let Err(err) = result {
+            log::error!("failed to save play counts: {:?}", err);
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        Config::cache_dir().map(|dir| dir.join(PLAY_COUNTS_FILENAME))
+    }
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Tracks with the highest `PlayCounts`.
+#[derive(Clone, Debug, Data, Lens)]
+pub struct MostPlayed {
+    pub tracks: Vector<Arc<Track>>,
+}
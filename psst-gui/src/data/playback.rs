@@ -3,11 +3,12 @@ use std::{fmt, sync::Arc, time::Duration};
 use druid::{im::Vector, Data, Lens};
 use druid_enums::Matcher;
 use psst_core::item_id::ItemId;
+use rand::{prelude::SliceRandom, Rng};
 use serde::{Deserialize, Serialize};
 
 use super::{
-    AlbumLink, ArtistLink, Episode, Library, Nav, PlaylistLink, RecommendationsRequest, ShowLink,
-    Track,
+    Album, AlbumLink, ArtistLink, Episode, Library, Nav, PlaylistLink, RecommendationsRequest,
+    ShowLink, Track,
 };
 
 #[derive(Clone, Data, Lens)]
@@ -15,8 +16,13 @@ pub struct Playback {
     pub state: PlaybackState,
     pub now_playing: Option<NowPlaying>,
     pub queue_behavior: QueueBehavior,
+    /// Queue behavior selected before a shuffled play switched away from it,
+    /// restored when another collection gets played.
+    pub behavior_before_shuffle: Option<QueueBehavior>,
     pub queue: Vector<QueueEntry>,
     pub volume: f64,
+    /// Volume to go back to when unmuting.
+    pub volume_before_mute: Option<f64>,
 }
 
 #[derive(Clone, Debug, Data, Lens)]
@@ -68,6 +74,33 @@ impl Playable {
             Playable::Episode(episode) => episode.duration,
         }
     }
+
+    pub fn url(&self) -> String {
+        match self {
+            Playable::Track(track) => track.url(),
+            Playable::Episode(episode) => episode.url(),
+        }
+    }
+
+    /// Text for sharing, "Artist – Title" for tracks and "Show – Title" for
+    /// episodes, optionally followed by the link.
+    pub fn share_text(&self, with_url: bool) -> String {
+        let author = match self {
+            Playable::Track(track) => track
+                .artists
+                .iter()
+                .map(|artist| artist.name.as_ref())
+                .collect::<Vec<_>>()
+                .join(", "),
+            Playable::Episode(episode) => episode.show.name.to_string(),
+        };
+        let text = format!("{} – {}", author, self.name());
+        if with_url {
+            format!("{} {}", text, self.url())
+        } else {
+            text
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Data, Eq, PartialEq, Serialize, Deserialize)]
@@ -78,6 +111,19 @@ pub enum QueueBehavior {
     LoopAll,
 }
 
+impl QueueBehavior {
+    /// Behavior following this one when cycling through all of them with a
+    /// single button.
+    pub fn next(self) -> Self {
+        match self {
+            QueueBehavior::Sequential => QueueBehavior::Random,
+            QueueBehavior::Random => QueueBehavior::LoopAll,
+            QueueBehavior::LoopAll => QueueBehavior::LoopTrack,
+            QueueBehavior::LoopTrack => QueueBehavior::Sequential,
+        }
+    }
+}
+
 impl Default for QueueBehavior {
     fn default() -> Self {
         QueueBehavior::Sequential
@@ -97,6 +143,9 @@ pub struct NowPlaying {
     pub item: Playable,
     pub origin: PlaybackOrigin,
     pub progress: Duration,
+    pub bitrate: Option<usize>,
+    /// Already added to the play counts.
+    pub counted: bool,
 
     // Although keeping a ref to the `Library` here is a bit of a hack, it dramatically
     // simplifies displaying the track context menu in the playback bar.
@@ -116,6 +165,29 @@ impl NowPlaying {
             Playable::Episode(episode) => Some(&episode.image(width, height)?.url),
         }
     }
+
+    /// Page of the album of the playing track, or of the show of the playing
+    /// episode.
+    pub fn album_nav(&self) -> Option<Nav> {
+        match &self.item {
+            Playable::Track(track) => {
+                let album = track.album.as_ref().or(match &self.origin {
+                    PlaybackOrigin::Album(album) => Some(album),
+                    _ => None,
+                })?;
+                Some(Nav::AlbumDetail(album.clone()))
+            }
+            Playable::Episode(episode) => Some(Nav::ShowDetail(episode.show.clone())),
+        }
+    }
+
+    /// Page of the first artist of the playing track.
+    pub fn artist_nav(&self) -> Option<Nav> {
+        match &self.item {
+            Playable::Track(track) => track.artists.front().cloned().map(Nav::ArtistDetail),
+            Playable::Episode(_) => None,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Data)]
@@ -127,6 +199,10 @@ pub enum PlaybackOrigin {
     Show(ShowLink),
     Search(Arc<str>),
     Recommendations(Arc<RecommendationsRequest>),
+    MostPlayed,
+    RecentlyPlayed,
+    /// Loaded by another device through Spotify Connect.
+    Remote,
 }
 
 impl PlaybackOrigin {
@@ -139,6 +215,9 @@ impl PlaybackOrigin {
             PlaybackOrigin::Show(link) => Nav::ShowDetail(link.clone()),
             PlaybackOrigin::Search(query) => Nav::SearchResults(query.clone()),
             PlaybackOrigin::Recommendations(request) => Nav::Recommendations(request.clone()),
+            PlaybackOrigin::MostPlayed => Nav::MostPlayed,
+            PlaybackOrigin::RecentlyPlayed => Nav::RecentlyPlayed,
+            PlaybackOrigin::Remote => Nav::Home,
         }
     }
 }
@@ -153,6 +232,9 @@ impl fmt::Display for PlaybackOrigin {
             PlaybackOrigin::Show(link) => link.name.fmt(f),
             PlaybackOrigin::Search(query) => query.fmt(f),
             PlaybackOrigin::Recommendations(_) => f.write_str("Recommended"),
+            PlaybackOrigin::MostPlayed => f.write_str("Most Played"),
+            PlaybackOrigin::RecentlyPlayed => f.write_str("Recently Played"),
+            PlaybackOrigin::Remote => f.write_str("Spotify Connect"),
         }
     }
 }
@@ -161,5 +243,130 @@ impl fmt::Display for PlaybackOrigin {
 pub struct PlaybackPayload {
     pub origin: PlaybackOrigin,
     pub items: Vector<Playable>,
+    /// Index into `items` to start playing from, the whole list gets queued.
     pub position: usize,
+    /// Position to seek to once the item at `position` starts playing.
+    pub start_at: Option<Duration>,
+    /// Items queued after `items`, each keeping its own origin.
+    pub then: Vector<QueueEntry>,
+}
+
+impl PlaybackPayload {
+    /// Queue entries of `items` followed by `then`.
+    pub fn entries(&self) -> impl Iterator<Item = QueueEntry> + '_ {
+        self.items
+            .iter()
+            .map(move |item| QueueEntry {
+                origin: self.origin.to_owned(),
+                item: item.to_owned(),
+            })
+            .chain(self.then.iter().cloned())
+    }
+
+    /// Whole albums one after another, in random album order but each album
+    /// in its track order.  `None` if there are no tracks to play.
+    pub fn shuffled_albums(albums: &Vector<Arc<Album>>) -> Option<Self> {
+        let mut albums: Vec<&Arc<Album>> = albums
+            .iter()
+            .filter(|album| !album.tracks.is_empty())
+            .collect();
+        albums.shuffle(&mut rand::thread_rng());
+        let (first, rest) = albums.split_first()?;
+        let then = rest
+            .iter()
+            .flat_map(|album| {
+                album.tracks.iter().map(move |track| QueueEntry {
+                    item: Playable::Track(track.to_owned()),
+                    origin: PlaybackOrigin::Album(album.link()),
+                })
+            })
+            .collect();
+        Some(Self {
+            origin: PlaybackOrigin::Album(first.link()),
+            items: first
+                .tracks
+                .iter()
+                .map(|track| Playable::Track(track.to_owned()))
+                .collect(),
+            position: 0,
+            start_at: None,
+            then,
+        })
+    }
+
+    /// Only the item at `position`, without the rest of the list.
+    pub fn only_position(mut self) -> Self {
+        if let Some(item) = self.items.get(self.position).cloned() {
+            self.items = Vector::unit(item);
+            self.position = 0;
+        }
+        self.then.clear();
+        self
+    }
+
+    /// Start the playback from a random item instead of `position`.
+    pub fn at_random_position(mut self) -> Self {
+        if !self.items.is_empty() {
+            self.position = rand::thread_rng().gen_range(0..self.items.len());
+        }
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search_payload() -> PlaybackPayload {
+        PlaybackPayload {
+            origin: PlaybackOrigin::Search("query".into()),
+            items: (0..5)
+                .map(|id| Playable::Track(Track::for_test(id)))
+                .collect(),
+            position: 2,
+            start_at: None,
+            then: Vector::unit(QueueEntry {
+                item: Playable::Track(Track::for_test(10)),
+                origin: PlaybackOrigin::Library,
+            }),
+        }
+    }
+
+    fn ids(payload: &PlaybackPayload) -> Vec<ItemId> {
+        payload.entries().map(|entry| entry.item.id()).collect()
+    }
+
+    #[test]
+    fn full_payload_queues_the_whole_list() {
+        let payload = search_payload();
+        let track_ids: Vec<ItemId> = [0, 1, 2, 3, 4, 10]
+            .iter()
+            .map(|&id| Track::for_test(id).id.0)
+            .collect();
+        assert_eq!(ids(&payload), track_ids);
+        assert_eq!(payload.items[payload.position].id(), track_ids[2]);
+    }
+
+    #[test]
+    fn random_position_stays_within_the_items() {
+        for _ in 0..100 {
+            let payload = search_payload().at_random_position();
+            assert!(payload.position < payload.items.len());
+        }
+        let empty = PlaybackPayload {
+            items: Vector::new(),
+            position: 0,
+            ..search_payload()
+        }
+        .at_random_position();
+        assert_eq!(empty.position, 0);
+    }
+
+    #[test]
+    fn only_position_queues_the_chosen_item() {
+        let payload = search_payload().only_position();
+        assert_eq!(payload.position, 0);
+        assert_eq!(ids(&payload), [Track::for_test(2).id.0]);
+        assert!(matches!(payload.origin, PlaybackOrigin::Search(_)));
+    }
 }
@@ -1,9 +1,12 @@
-use std::sync::Arc;
+use std::{
+    borrow::Cow, cmp::Ordering, collections::HashSet, convert::TryFrom, sync::Arc, time::Duration,
+};
 
 use druid::{im::Vector, Data, Lens};
 use serde::{Deserialize, Deserializer, Serialize};
+use time::OffsetDateTime;
 
-use crate::data::{user::PublicUser, Image, Promise, Track, TrackId};
+use crate::data::{user::PublicUser, Image, PlaylistSort, Promise, SpotifyUrl, Track, TrackId};
 
 #[derive(Clone, Debug, Data, Lens)]
 pub struct PlaylistDetail {
@@ -17,12 +20,58 @@ pub struct PlaylistAddTrack {
     pub track_id: TrackId,
 }
 
+/// Repeated occurrences of tracks, to be removed from a playlist.
+#[derive(Clone, Debug, Data)]
+pub struct PlaylistRemoveDuplicates {
+    pub link: PlaylistLink,
+    /// Track and its position in the saved playlist order.
+    pub duplicates: Vector<(TrackId, usize)>,
+}
+
 #[derive(Clone, Debug, Data, Lens, Deserialize)]
 pub struct PlaylistRemoveTrack {
     pub link: PlaylistLink,
     pub track_id: TrackId,
 }
 
+/// Tracks to put into a new playlist, parsed from a list of `spotify:track:`
+/// URIs or track links, one per line.
+#[derive(Clone, Debug, Data)]
+pub struct PlaylistImport {
+    pub name: Arc<str>,
+    pub track_ids: Vector<TrackId>,
+    /// Non-empty lines that are neither track URIs nor track links.
+    pub invalid_lines: Vector<Arc<str>>,
+}
+
+impl PlaylistImport {
+    pub fn parse(name: &str, text: &str) -> Self {
+        let mut track_ids = Vector::new();
+        let mut invalid_lines = Vector::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match Self::parse_track_id(line) {
+                Some(id) => track_ids.push_back(id),
+                None => invalid_lines.push_back(line.into()),
+            }
+        }
+        Self {
+            name: name.into(),
+            track_ids,
+            invalid_lines,
+        }
+    }
+
+    fn parse_track_id(line: &str) -> Option<TrackId> {
+        if let Some(id) = TrackId::from_uri(line) {
+            return Some(id);
+        }
+        match SpotifyUrl::parse(line)? {
+            SpotifyUrl::Track(id, _) => TrackId::try_from(id.to_string()).ok(),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Data, Lens, Deserialize)]
 pub struct Playlist {
     pub id: Arc<str>,
@@ -53,20 +102,178 @@ impl Playlist {
     }
 }
 
+/// Track of a playlist, in the order the playlist is saved in.
+#[derive(Clone, Debug)]
+pub struct PlaylistEntry {
+    pub track: Arc<Track>,
+    pub added_at: Option<OffsetDateTime>,
+}
+
 #[derive(Clone, Debug, Data, Lens)]
 pub struct PlaylistTracks {
     pub id: Arc<str>,
     pub name: Arc<str>,
+    /// Tracks in the order of `sort`.
     pub tracks: Vector<Arc<Track>>,
+    /// Only used for sorting, which changes `tracks` anyway.
+    #[data(ignore)]
+    pub entries: Vector<PlaylistEntry>,
+    pub sort: PlaylistSort,
 }
 
 impl PlaylistTracks {
+    pub fn new(link: &PlaylistLink, entries: Vector<PlaylistEntry>, sort: PlaylistSort) -> Self {
+        let mut tracks = Self {
+            id: link.id.clone(),
+            name: link.name.clone(),
+            tracks: Vector::new(),
+            entries,
+            sort,
+        };
+        tracks.sort_by(sort);
+        tracks
+    }
+
+    /// Re-order `tracks` by `sort`.  The sort is stable, so tracks that
+    /// compare equal keep the playlist order.
+    pub fn sort_by(&mut self, sort: PlaylistSort) {
+        let album_key = |t: &Track| (t.album_name().to_lowercase(), t.disc_number, t.track_number);
+        let mut entries: Vec<&PlaylistEntry> = self.entries.iter().collect();
+        entries.sort_by(|a, b| {
+            let (a_track, b_track) = (&a.track, &b.track);
+            match sort {
+                PlaylistSort::Manual => Ordering::Equal,
+                PlaylistSort::RecentlyAdded => b.added_at.cmp(&a.added_at),
+                PlaylistSort::Title => a_track
+                    .name
+                    .to_lowercase()
+                    .cmp(&b_track.name.to_lowercase()),
+                PlaylistSort::Artist => a_track
+                    .artist_name()
+                    .to_lowercase()
+                    .cmp(&b_track.artist_name().to_lowercase())
+                    .then_with(|| album_key(a_track).cmp(&album_key(b_track))),
+                PlaylistSort::Album => album_key(a_track).cmp(&album_key(b_track)),
+            }
+        });
+        self.tracks = entries
+            .into_iter()
+            .map(|entry| entry.track.clone())
+            .collect();
+        self.sort = sort;
+    }
+
+    pub fn duration(&self) -> Duration {
+        Track::total_duration(&self.tracks)
+    }
+
+    /// Every occurrence of a track after its first one, with its position in
+    /// the saved playlist order.  Local tracks have no ID to compare by and
+    /// never count as duplicates.
+    pub fn duplicates(&self) -> Vector<(TrackId, usize)> {
+        let mut seen = HashSet::new();
+        self.entries
+            .iter()
+            .enumerate()
+            .filter(|(_, entry)| !entry.track.is_local && !seen.insert(entry.track.id))
+            .map(|(position, entry)| (entry.track.id, position))
+            .collect()
+    }
+
+    /// Drop the entries at `positions` of the saved playlist order, keeping
+    /// the current sort.
+    pub fn remove_positions(&mut self, positions: &HashSet<usize>) {
+        self.entries = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(position, _)| !positions.contains(position))
+            .map(|(_, entry)| entry.clone())
+            .collect();
+        self.sort_by(self.sort);
+    }
+
     pub fn link(&self) -> PlaylistLink {
         PlaylistLink {
             id: self.id.clone(),
             name: self.name.clone(),
         }
     }
+
+    pub fn export(&self, format: PlaylistExportFormat) -> String {
+        match format {
+            PlaylistExportFormat::Uris => self.to_uri_list(),
+            PlaylistExportFormat::Csv => self.to_csv(),
+        }
+    }
+
+    /// One `spotify:track:` URI per line, in the format `PlaylistImport`
+    /// reads.  Local tracks have no URI and are left out.
+    fn to_uri_list(&self) -> String {
+        self.tracks
+            .iter()
+            .filter(|track| !track.is_local)
+            .map(|track| track.id.to_uri() + "\n")
+            .collect()
+    }
+
+    fn to_csv(&self) -> String {
+        let mut csv = String::from("uri,title,artist,album\n");
+        for track in &self.tracks {
+            let uri = if track.is_local {
+                String::new()
+            } else {
+                track.id.to_uri()
+            };
+            let artists = track
+                .artists
+                .iter()
+                .map(|artist| artist.name.as_ref())
+                .collect::<Vec<_>>()
+                .join(", ");
+            let album = track.album_name();
+            let fields = [
+                csv_field(&uri),
+                csv_field(&track.name),
+                csv_field(&artists),
+                csv_field(&album),
+            ];
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+}
+
+/// Quote a CSV field if it contains a separator, a quote or a line break,
+/// doubling any quotes inside.
+fn csv_field(field: &str) -> Cow<str> {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Data)]
+pub enum PlaylistExportFormat {
+    Uris,
+    Csv,
+}
+
+impl PlaylistExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Uris => "txt",
+            Self::Csv => "csv",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Data)]
+pub struct PlaylistExport {
+    pub link: PlaylistLink,
+    pub format: PlaylistExportFormat,
 }
 
 #[derive(Clone, Debug, Data, Lens, Eq, PartialEq, Hash, Deserialize, Serialize)]
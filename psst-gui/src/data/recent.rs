@@ -0,0 +1,151 @@
+use std::{collections::HashSet, fs::File, path::PathBuf, sync::Arc};
+
+use druid::{im::Vector, Data, Lens};
+use psst_core::cache::mkdir_if_not_exists;
+use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+
+use crate::data::{Album, AlbumLink, Config, Episode, Nav, PlaybackOrigin, Track, TrackId};
+
+const PLAY_HISTORY_FILENAME: &str = "play-history.json";
+/// Most plays kept in the local history.
+const MAX_HISTORY_LEN: usize = 200;
+
+/// Tracks played on this computer, the latest first.  Spotify records plays
+/// with a delay and only remembers the last 50 of them.
+#[derive(Clone, Debug, Default, Data, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PlayHistory {
+    plays: Vector<RecordedPlay>,
+}
+
+#[derive(Clone, Debug, Data, Serialize, Deserialize)]
+pub struct RecordedPlay {
+    pub track_id: TrackId,
+    /// Seconds since the Unix epoch.
+    pub played_at: i64,
+    /// Album, playlist or artist the track was played from.
+    pub context: Option<Nav>,
+}
+
+impl PlayHistory {
+    pub fn record(&mut self, track_id: TrackId, origin: &PlaybackOrigin) {
+        let context = match origin {
+            PlaybackOrigin::Album(_) | PlaybackOrigin::Artist(_) | PlaybackOrigin::Playlist(_) => {
+                Some(origin.to_nav())
+            }
+            _ => None,
+        };
+        self.plays.push_front(RecordedPlay {
+            track_id,
+            played_at: OffsetDateTime::now_utc().unix_timestamp(),
+            context,
+        });
+        self.plays.truncate(MAX_HISTORY_LEN);
+    }
+
+    pub fn plays(&self) -> &Vector<RecordedPlay> {
+        &self.plays
+    }
+
+    /// Albums the latest plays came from, each once, the latest first.
+    pub fn recent_albums(&self, limit: usize) -> Vector<AlbumLink> {
+        let mut albums = Vector::new();
+        for play in &self.plays {
+            if let Some(Nav::AlbumDetail(link)) = &play.context {
+                if !albums.contains(link) {
+                    albums.push_back(link.to_owned());
+                }
+            }
+            if albums.len() == limit {
+                break;
+            }
+        }
+        albums
+    }
+
+    pub fn load() -> Self {
+        let file = match Self::path().and_then(|path| File::open(path).ok()) {
+            Some(file) => file,
+            None => return Self::default(),
+        };
+        serde_json::from_reader(file).unwrap_or_else(|err| {
+            log::error!("failed to read play history: {:?}", err);
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) {
+        let (dir, path) = match (Config::cache_dir(), Self::path()) {
+            (Some(dir), Some(path)) => (dir, path),
+            _ => return,
+        };
+        let result = mkdir_if_not_exists(&dir)
+            .and_then(|_| File::create(&path))
+            .map_err(serde_json::Error::io)
+            .and_then(|file| serde_json::to_writer(file, self));
+        if let Err(err) = result {
+            log::error!("failed to save play history: {:?}", err);
+        }
+    }
+
+    fn path() -> Option<PathBuf> {
+        Config::cache_dir().map(|dir| dir.join(PLAY_HISTORY_FILENAME))
+    }
+}
+
+/// Single play of a track, either recorded locally or by Spotify.
+pub struct RecentPlay {
+    pub track: Arc<Track>,
+    /// Seconds since the Unix epoch.
+    pub played_at: i64,
+    pub context: Option<Nav>,
+}
+
+#[derive(Clone, Debug, Data, Lens)]
+pub struct RecentlyPlayed {
+    /// Each track once, the latest played first.
+    pub tracks: Vector<Arc<Track>>,
+    /// Albums, playlists and artists the tracks were played from, the latest
+    /// first.
+    pub contexts: Vector<Nav>,
+}
+
+impl RecentlyPlayed {
+    /// Merge the local and the server plays, keeping only the latest play of
+    /// every track and context.
+    pub fn merge(mut plays: Vec<RecentPlay>) -> Self {
+        plays.sort_by(|a, b| b.played_at.cmp(&a.played_at));
+        let mut seen_tracks = HashSet::new();
+        let mut tracks = Vector::new();
+        let mut contexts = Vector::new();
+        for play in plays {
+            if seen_tracks.insert(play.track.id) {
+                tracks.push_back(play.track);
+            }
+            if let Some(context) = play.context {
+                if !contexts.contains(&context) {
+                    contexts.push_back(context);
+                }
+            }
+        }
+        Self { tracks, contexts }
+    }
+}
+
+/// Where to pick up listening: episodes started but not finished, and the
+/// albums played lately.
+#[derive(Clone, Data, Lens)]
+pub struct ContinueListening {
+    /// The latest released first.  Spotify doesn't tell when an episode was
+    /// last listened to.
+    pub episodes: Vector<Arc<Episode>>,
+    /// The latest played first.
+    pub albums: Vector<Arc<Album>>,
+}
+
+impl ContinueListening {
+    pub fn is_empty(&self) -> bool {
+        self.episodes.is_empty() && self.albums.is_empty()
+    }
+}
@@ -1,12 +1,21 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use druid::{
     im::{vector, Vector},
     Data, Lens,
 };
+use itertools::Itertools;
+use rand::prelude::SliceRandom;
 use serde::{Deserialize, Serialize};
 
-use super::{ArtistLink, Float64, Promise, Track, TrackId};
+use super::{
+    ArtistLink, AudioFeatures, Float64, PlaybackOrigin, PlaylistLink, Promise, Track, TrackId,
+};
+
+/// The recommendations API accepts at most 5 seeds in total.
+const MAX_SEEDS: usize = 5;
+/// Refill requests a playlist radio remembers, enough to cover its queue.
+const MAX_RADIO_REQUESTS: usize = 10;
 
 #[derive(Clone, Data, Lens)]
 pub struct Recommend {
@@ -30,6 +39,102 @@ impl RecommendationsRequest {
         }
     }
 
+    /// Request tracks with a mood close to the track with given `features`.
+    /// Tempo, energy and valence are constrained to a range around the track's
+    /// values, `tolerance` is a fraction of the tempo, and an absolute
+    /// difference for the 0.0..=1.0 features.
+    pub fn similar_to(id: TrackId, features: &AudioFeatures, tolerance: f64) -> Self {
+        Self::for_track(id).with_params(RecommendationsParams::similar_to(features, tolerance))
+    }
+
+    /// Request recommendations seeded by a random sample of `tracks`.  Tracks
+    /// by different artists are preferred, the first few become track seeds,
+    /// the rest seed by their artist.
+    pub fn sampled_from(tracks: &Vector<Arc<Track>>) -> Self {
+        const MAX_SEED_TRACKS: usize = 3;
+
+        let mut sample: Vec<_> = tracks.iter().filter(|track| !track.is_local).collect();
+        sample.shuffle(&mut rand::thread_rng());
+
+        let mut seen_artists = HashSet::new();
+        let (fresh, repeated): (Vec<_>, Vec<_>) = sample.into_iter().partition(|track| {
+            track
+                .artists
+                .front()
+                .map_or(false, |artist| seen_artists.insert(artist.id.clone()))
+        });
+
+        let mut request = Self::default();
+        let fresh_count = fresh.len();
+        for (i, track) in fresh
+            .into_iter()
+            .chain(repeated)
+            .take(MAX_SEEDS)
+            .enumerate()
+        {
+            match track.artists.front() {
+                Some(artist) if i >= MAX_SEED_TRACKS && i < fresh_count => {
+                    request.seed_artists.push_back(artist.to_owned());
+                }
+                _ => {
+                    request.seed_tracks.push_back(track.id);
+                }
+            }
+        }
+        request
+    }
+
+    /// Request recommendations blending the tastes behind two track lists,
+    /// e.g. the top tracks of two listeners.  Tracks found in both lists seed
+    /// first, then tracks by artists found in both, the remaining seeds
+    /// alternate between the lists.
+    pub fn blended(mine: &Vector<Arc<Track>>, theirs: &Vector<Arc<Track>>) -> Self {
+        let artist_ids = |tracks: &Vector<Arc<Track>>| -> HashSet<Arc<str>> {
+            tracks
+                .iter()
+                .flat_map(|track| track.artists.iter().map(|artist| artist.id.clone()))
+                .collect()
+        };
+        let shared_artists: HashSet<_> = artist_ids(mine)
+            .intersection(&artist_ids(theirs))
+            .cloned()
+            .collect();
+        let their_tracks: HashSet<_> = theirs.iter().map(|track| track.id).collect();
+        let my_tracks: HashSet<_> = mine.iter().map(|track| track.id).collect();
+
+        let rank = |track: &&Arc<Track>| {
+            if my_tracks.contains(&track.id) && their_tracks.contains(&track.id) {
+                0
+            } else if track
+                .artists
+                .iter()
+                .any(|artist| shared_artists.contains(&artist.id))
+            {
+                1
+            } else {
+                2
+            }
+        };
+        let [my_sample, their_sample] = [mine, theirs].map(|tracks| {
+            let mut sample: Vec<_> = tracks.iter().filter(|track| !track.is_local).collect();
+            sample.shuffle(&mut rand::thread_rng());
+            sample.sort_by_key(rank);
+            sample
+        });
+
+        let mut seeded = HashSet::new();
+        Self {
+            seed_tracks: my_sample
+                .into_iter()
+                .interleave(their_sample)
+                .filter(|track| seeded.insert(track.id))
+                .take(MAX_SEEDS)
+                .map(|track| track.id)
+                .collect(),
+            ..Self::default()
+        }
+    }
+
     pub fn with_params(mut self, params: RecommendationsParams) -> Self {
         self.params = params;
         self
@@ -121,6 +226,30 @@ pub struct RecommendationsParams {
     pub valence: Range<Float64>,
 }
 
+impl RecommendationsParams {
+    pub fn similar_to(features: &AudioFeatures, tolerance: f64) -> Self {
+        let tempo_delta = features.tempo * tolerance;
+        let tempo = Range::new(
+            Some((features.tempo - tempo_delta).max(0.0).round() as u64),
+            Some((features.tempo + tempo_delta).round() as u64),
+            Some(features.tempo.round() as u64),
+        );
+        let around = |value: f64| {
+            Range::new(
+                Some((value - tolerance).max(0.0).into()),
+                Some((value + tolerance).min(1.0).into()),
+                Some(value.into()),
+            )
+        };
+        Self {
+            tempo,
+            energy: around(features.energy),
+            valence: around(features.valence),
+            ..Self::default()
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Data, Lens)]
 pub struct Range<T> {
     pub min: Option<T>,
@@ -163,3 +292,50 @@ pub enum RecommendationsSeedType {
     Track,
     Genre,
 }
+
+/// Recommendations seeded by a playlist that keep getting queued as the queue
+/// runs low, each refill from a new sample of the playlist tracks.
+#[derive(Clone, Data)]
+pub struct PlaylistRadio {
+    pub link: PlaylistLink,
+    /// Tracks of the playlist, the seeds get sampled from them.
+    pub tracks: Vector<Arc<Track>>,
+    /// Latest requests of the radio, queue entries with one of these as
+    /// origin belong to the radio.
+    requests: Vector<Arc<RecommendationsRequest>>,
+    /// A refill is loading.
+    pub refilling: bool,
+}
+
+impl PlaylistRadio {
+    pub fn new(
+        link: PlaylistLink,
+        tracks: Vector<Arc<Track>>,
+        request: Arc<RecommendationsRequest>,
+    ) -> Self {
+        Self {
+            link,
+            tracks,
+            requests: vector![request],
+            refilling: false,
+        }
+    }
+
+    pub fn next_request(&self) -> Arc<RecommendationsRequest> {
+        Arc::new(RecommendationsRequest::sampled_from(&self.tracks))
+    }
+
+    pub fn add_request(&mut self, request: Arc<RecommendationsRequest>) {
+        self.requests.push_back(request);
+        while self.requests.len() > MAX_RADIO_REQUESTS {
+            self.requests.pop_front();
+        }
+    }
+
+    pub fn owns(&self, origin: &PlaybackOrigin) -> bool {
+        match origin {
+            PlaybackOrigin::Recommendations(request) => self.requests.contains(request),
+            _ => false,
+        }
+    }
+}
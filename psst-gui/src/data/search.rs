@@ -1,16 +1,37 @@
 use std::sync::Arc;
 
-use druid::{im::Vector, Data, Lens};
+use druid::{
+    im::{HashSet, Vector},
+    Data, Lens,
+};
+use serde::{Deserialize, Serialize};
 
-use crate::data::{Album, Artist, Playlist, Promise, Show, Track};
+use crate::data::{utils::fuzzy_score, Album, Artist, Library, Playlist, Promise, Show, Track};
+
+/// Shortest query matched against the library, shorter ones match too much.
+const MIN_LIBRARY_QUERY_LEN: usize = 2;
+/// Most library matches per topic.
+const MAX_LIBRARY_MATCHES: usize = 5;
 
 #[derive(Clone, Data, Lens)]
 pub struct Search {
     pub input: String,
     pub results: Promise<SearchResults, Arc<str>>,
+    /// Library items matching `input`, found without asking the server.
+    pub library_matches: SearchResults,
+    /// Saved search currently being renamed in the sidebar, with the edited
+    /// name.
+    pub renaming: Option<SavedSearch>,
+}
+
+/// Query pinned to the sidebar under a custom name.
+#[derive(Clone, Debug, Data, Lens, Eq, PartialEq, Deserialize, Serialize)]
+pub struct SavedSearch {
+    pub name: Arc<str>,
+    pub query: Arc<str>,
 }
 
-#[derive(Copy, Clone, Data, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Data, Eq, PartialEq, Hash)]
 pub enum SearchTopic {
     Artist,
     Album,
@@ -49,4 +70,151 @@ pub struct SearchResults {
     pub tracks: Vector<Arc<Track>>,
     pub playlists: Vector<Playlist>,
     pub shows: Vector<Arc<Show>>,
+    /// Topics with more results than we've loaded so far.
+    pub topics_with_more: HashSet<SearchTopic>,
+}
+
+impl SearchResults {
+    pub fn empty(query: Arc<str>) -> Self {
+        Self {
+            query,
+            artists: Vector::new(),
+            albums: Vector::new(),
+            tracks: Vector::new(),
+            playlists: Vector::new(),
+            shows: Vector::new(),
+            topics_with_more: HashSet::new(),
+        }
+    }
+
+    /// Saved items and playlists of `library` with names fuzzy-matching
+    /// `query`, the best matches first.  Artists come from the saved albums
+    /// and tracks.
+    pub fn from_library(query: Arc<str>, library: &Library) -> Self {
+        let mut results = Self::empty(query.clone());
+        if query.chars().count() < MIN_LIBRARY_QUERY_LEN {
+            return results;
+        }
+        let saved_albums = library.saved_albums.resolved();
+        let saved_tracks = library.saved_tracks.resolved();
+
+        let mut artist_ids = HashSet::new();
+        let artists = saved_albums
+            .iter()
+            .flat_map(|saved| saved.albums.iter().flat_map(|album| album.artists.iter()))
+            .chain(
+                saved_tracks
+                    .iter()
+                    .flat_map(|saved| saved.tracks.iter().flat_map(|track| track.artists.iter())),
+            )
+            .filter(|link| artist_ids.insert(link.id.clone()).is_none())
+            .map(|link| Artist {
+                id: link.id.clone(),
+                name: link.name.clone(),
+                images: Vector::new(),
+            });
+        results.artists = best_matches(&query, artists, |artist| artist.name.as_ref());
+        if let Some(saved) = saved_albums {
+            results.albums = best_matches(&query, saved.albums.iter().cloned(), |album| {
+                album.name.as_ref()
+            });
+        }
+        if let Some(saved) = saved_tracks {
+            results.tracks = best_matches(&query, saved.tracks.iter().cloned(), |track| {
+                track.name.as_ref()
+            });
+        }
+        if let Some(playlists) = library.playlists.resolved() {
+            results.playlists = best_matches(&query, playlists.iter().cloned(), |playlist| {
+                playlist.name.as_ref()
+            });
+        }
+        if let Some(saved) = library.saved_shows.resolved() {
+            results.shows = best_matches(&query, saved.shows.iter().cloned(), |show| {
+                show.name.as_ref()
+            });
+        }
+        results
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.artists.is_empty()
+            && self.albums.is_empty()
+            && self.tracks.is_empty()
+            && self.playlists.is_empty()
+            && self.shows.is_empty()
+    }
+
+    /// Drop the items that `known` contains already.
+    pub fn remove_known(&mut self, known: &SearchResults) {
+        self.artists
+            .retain(|artist| !known.artists.iter().any(|a| a.id == artist.id));
+        self.albums
+            .retain(|album| !known.albums.iter().any(|a| a.id == album.id));
+        self.tracks
+            .retain(|track| !known.tracks.iter().any(|t| t.id == track.id));
+        self.playlists
+            .retain(|playlist| !known.playlists.iter().any(|p| p.id == playlist.id));
+        self.shows
+            .retain(|show| !known.shows.iter().any(|s| s.id == show.id));
+    }
+
+    /// Number of loaded results of `topic`, i.e. the offset of the next page.
+    pub fn count(&self, topic: SearchTopic) -> usize {
+        match topic {
+            SearchTopic::Artist => self.artists.len(),
+            SearchTopic::Album => self.albums.len(),
+            SearchTopic::Track => self.tracks.len(),
+            SearchTopic::Playlist => self.playlists.len(),
+            SearchTopic::Show => self.shows.len(),
+        }
+    }
+
+    /// Append the next page of `topic` results from `more`.
+    pub fn append(&mut self, topic: SearchTopic, more: SearchResults) {
+        match topic {
+            SearchTopic::Artist => self.artists.append(more.artists),
+            SearchTopic::Album => self.albums.append(more.albums),
+            SearchTopic::Track => self.tracks.append(more.tracks),
+            SearchTopic::Playlist => self.playlists.append(more.playlists),
+            SearchTopic::Show => self.shows.append(more.shows),
+        }
+        if more.topics_with_more.contains(&topic) {
+            self.topics_with_more.insert(topic);
+        } else {
+            self.topics_with_more.remove(&topic);
+        }
+    }
+}
+
+/// Up to `MAX_LIBRARY_MATCHES` of `items` with names matching `query`, the
+/// best matches first.
+fn best_matches<T: Clone>(
+    query: &str,
+    items: impl IntoIterator<Item = T>,
+    name: impl Fn(&T) -> &str,
+) -> Vector<T> {
+    let mut scored: Vec<(i64, T)> = items
+        .into_iter()
+        .filter_map(|item| {
+            // Scattered matches score below zero.
+            let score = fuzzy_score(query, name(&item)).filter(|&score| score > 0)?;
+            Some((score, item))
+        })
+        .collect();
+    scored.sort_by(|a, b| b.0.cmp(&a.0));
+    scored
+        .into_iter()
+        .take(MAX_LIBRARY_MATCHES)
+        .map(|(_, item)| item)
+        .collect()
+}
+
+/// Next page of search results of a single topic.
+#[derive(Clone, Data)]
+pub struct SearchPage {
+    pub query: Arc<str>,
+    pub topic: SearchTopic,
+    pub offset: usize,
+    pub limit: usize,
 }
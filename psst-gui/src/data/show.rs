@@ -1,13 +1,13 @@
 use std::{convert::TryFrom, sync::Arc, time::Duration};
 
 use druid::{im::Vector, Data, Lens};
-use psst_core::item_id::{ItemId, ItemIdType};
+use psst_core::item_id::{InvalidId, ItemId, ItemIdType, ItemKind};
 use serde::{Deserialize, Serialize};
-use time::{macros::format_description, Date};
+use time::Date;
 
 use crate::data::{Image, Promise};
 
-use super::album::DatePrecision;
+use super::album::{format_release_date, DatePrecision};
 
 #[derive(Clone, Data, Lens)]
 pub struct ShowDetail {
@@ -22,6 +22,8 @@ pub struct Show {
     pub images: Vector<Image>,
     pub publisher: Arc<str>,
     pub description: Arc<str>,
+    #[serde(default)]
+    pub total_episodes: usize,
 }
 
 impl Show {
@@ -72,6 +74,9 @@ pub struct Episode {
     #[data(same_fn = "PartialEq::eq")]
     pub release_date_precision: Option<DatePrecision>,
     pub resume_point: Option<ResumePoint>,
+    /// Chapter markers, if the feed of the show provides any.
+    #[serde(default)]
+    pub chapters: Vector<Chapter>,
 }
 
 impl Episode {
@@ -86,12 +91,70 @@ impl Episode {
         )
     }
 
-    pub fn release(&self) -> String {
-        let format = format_description!("[month repr:short] [day], [year]");
-        self.release_date
+    /// Started but not finished listening to.
+    pub fn is_in_progress(&self) -> bool {
+        matches!(
+            &self.resume_point,
+            Some(resume) if !resume.fully_played && !resume.resume_position.is_zero()
+        )
+    }
+
+    /// Time left to listen to, from the resume point.
+    pub fn remaining(&self) -> Duration {
+        let position = self
+            .resume_point
             .as_ref()
-            .map(|date| date.format(format).expect("Invalid format"))
-            .unwrap_or_else(|| '-'.to_string())
+            .map_or(Duration::ZERO, |resume| resume.resume_position);
+        self.duration.saturating_sub(position)
+    }
+
+    pub fn release(&self) -> String {
+        format_release_date(
+            self.release_date.as_ref(),
+            self.release_date_precision.as_ref(),
+        )
+    }
+
+    /// Ad or sponsor chapter playing at `position`.
+    pub fn ad_chapter_at(&self, position: Duration) -> Option<&Chapter> {
+        self.chapters.iter().find(|chapter| {
+            chapter.kind.is_ad() && chapter.start <= position && position < chapter.end
+        })
+    }
+}
+
+#[derive(Clone, Debug, Data, Lens, Deserialize)]
+pub struct Chapter {
+    #[serde(rename = "start_ms")]
+    #[serde(deserialize_with = "super::utils::deserialize_millis")]
+    pub start: Duration,
+    #[serde(rename = "end_ms")]
+    #[serde(deserialize_with = "super::utils::deserialize_millis")]
+    pub end: Duration,
+    #[serde(default = "super::utils::default_str")]
+    pub title: Arc<str>,
+    #[serde(rename = "type", default)]
+    pub kind: ChapterKind,
+}
+
+#[derive(Clone, Copy, Debug, Data, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChapterKind {
+    #[serde(other)]
+    Content,
+    Ad,
+    Sponsor,
+}
+
+impl ChapterKind {
+    pub fn is_ad(self) -> bool {
+        matches!(self, ChapterKind::Ad | ChapterKind::Sponsor)
+    }
+}
+
+impl Default for ChapterKind {
+    fn default() -> Self {
+        Self::Content
     }
 }
 
@@ -101,6 +164,17 @@ pub struct EpisodeLink {
     pub name: Arc<str>,
 }
 
+#[derive(Clone, Copy, Debug, Data, PartialEq)]
+pub enum EpisodeDownload {
+    Queued,
+    Downloading {
+        progress: f64,
+    },
+    Downloaded,
+    /// Download can be retried.
+    Failed,
+}
+
 #[derive(Clone, Debug, Data, Lens, Deserialize)]
 pub struct ResumePoint {
     pub fully_played: bool,
@@ -114,6 +188,16 @@ pub struct ResumePoint {
 #[serde(into = "String")]
 pub struct EpisodeId(pub ItemId);
 
+impl EpisodeId {
+    pub fn from_uri(uri: &str) -> Option<Self> {
+        ItemId::from_uri(uri, ItemKind::Episode).map(Self)
+    }
+
+    pub fn to_uri(&self) -> String {
+        self.0.to_uri(ItemKind::Episode)
+    }
+}
+
 impl Data for EpisodeId {
     fn same(&self, other: &Self) -> bool {
         self.0 == other.0
@@ -121,12 +205,10 @@ impl Data for EpisodeId {
 }
 
 impl TryFrom<String> for EpisodeId {
-    type Error = &'static str;
+    type Error = InvalidId;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        ItemId::from_base62(&value, ItemIdType::Podcast)
-            .ok_or("Invalid ID")
-            .map(Self)
+        ItemId::parse_base62(&value, ItemIdType::Podcast).map(Self)
     }
 }
 
@@ -1,14 +1,20 @@
 use std::{convert::TryFrom, sync::Arc, time::Duration};
 
 use druid::{im::Vector, lens::Map, Data, Lens};
-use psst_core::item_id::{ItemId, ItemIdType};
-use serde::{Deserialize, Serialize};
+use psst_core::item_id::{InvalidId, ItemId, ItemIdType, ItemKind};
+use serde::{Deserialize, Deserializer, Serialize};
 
 use crate::data::{AlbumLink, ArtistLink};
 
+/// Segments peaking below this loudness, in dB, count as silent.
+const SILENCE_LOUDNESS: f64 = -50.0;
+/// Shorter intro silences are left alone, they are more likely an intentional
+/// part of the track than a gap to skip.
+const MIN_TRIMMED_SILENCE: Duration = Duration::from_millis(1500);
+
 #[derive(Clone, Debug, Data, Lens, Deserialize)]
 pub struct Track {
-    #[serde(default)]
+    #[serde(default, deserialize_with = "deserialize_track_id")]
     pub id: TrackId,
     pub name: Arc<str>,
     pub album: Option<AlbumLink>,
@@ -24,18 +30,16 @@ pub struct Track {
     pub local_path: Option<Arc<str>>,
     pub is_playable: Option<bool>,
     pub popularity: Option<u32>,
+    /// International Standard Recording Code, if Spotify knows it.
+    #[serde(
+        default,
+        rename = "external_ids",
+        deserialize_with = "deserialize_isrc"
+    )]
+    pub isrc: Option<Arc<str>>,
 }
 
 impl Track {
-    pub fn lens_artist_name() -> impl Lens<Self, Arc<str>> {
-        Map::new(
-            |track: &Self| track.artist_name(),
-            |_, _| {
-                // Immutable.
-            },
-        )
-    }
-
     pub fn lens_album_name() -> impl Lens<Self, Arc<str>> {
         Map::new(
             |track: &Self| track.album_name(),
@@ -45,6 +49,16 @@ impl Track {
         )
     }
 
+    /// Sum of the durations of all `tracks`.
+    pub fn total_duration<'a>(tracks: impl IntoIterator<Item = &'a Arc<Track>>) -> Duration {
+        tracks.into_iter().map(|track| track.duration).sum()
+    }
+
+    /// Track can't be played, e.g. because of regional restrictions.
+    pub fn is_unavailable(&self) -> bool {
+        self.is_playable == Some(false)
+    }
+
     pub fn artist_name(&self) -> Arc<str> {
         self.artists
             .front()
@@ -62,6 +76,26 @@ impl Track {
     pub fn url(&self) -> String {
         format!("https://open.spotify.com/track/{}", self.id.0.to_base62())
     }
+
+    /// Minimal track, only telling tracks apart by `id`.
+    #[cfg(test)]
+    pub fn for_test(id: u128) -> Arc<Self> {
+        Arc::new(Self {
+            id: TrackId(ItemId::new(id, ItemIdType::Track)),
+            name: format!("Track {}", id).into(),
+            album: None,
+            artists: Vector::new(),
+            duration: Duration::from_secs(180),
+            disc_number: 1,
+            track_number: 1,
+            explicit: false,
+            is_local: false,
+            local_path: None,
+            is_playable: None,
+            popularity: None,
+            isrc: None,
+        })
+    }
 }
 
 #[derive(Clone, Copy, Default, PartialEq, Eq, Debug, Hash, Deserialize, Serialize)]
@@ -69,6 +103,16 @@ impl Track {
 #[serde(into = "String")]
 pub struct TrackId(pub ItemId);
 
+impl TrackId {
+    pub fn from_uri(uri: &str) -> Option<Self> {
+        ItemId::from_uri(uri, ItemKind::Track).map(Self)
+    }
+
+    pub fn to_uri(&self) -> String {
+        self.0.to_uri(ItemKind::Track)
+    }
+}
+
 impl Data for TrackId {
     fn same(&self, other: &Self) -> bool {
         self.0 == other.0
@@ -76,13 +120,41 @@ impl Data for TrackId {
 }
 
 impl TryFrom<String> for TrackId {
-    type Error = &'static str;
+    type Error = InvalidId;
 
     fn try_from(value: String) -> Result<Self, Self::Error> {
-        ItemId::from_base62(&value, ItemIdType::Track)
-            .ok_or("Invalid ID")
-            .map(Self)
+        ItemId::parse_base62(&value, ItemIdType::Track).map(Self)
+    }
+}
+
+/// Picks the ISRC out of the `external_ids` object of a track.
+fn deserialize_isrc<'de, D>(deserializer: D) -> Result<Option<Arc<str>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    struct ExternalIds {
+        isrc: Option<Arc<str>>,
     }
+
+    let ids = Option::<ExternalIds>::deserialize(deserializer)?;
+    Ok(ids.and_then(|ids| ids.isrc))
+}
+
+/// A malformed track ID from the API shouldn't fail the whole response, the
+/// track gets the same default ID as local tracks instead.
+fn deserialize_track_id<'de, D>(deserializer: D) -> Result<TrackId, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let id = match Option::<String>::deserialize(deserializer)? {
+        Some(id) => id,
+        None => return Ok(TrackId::default()),
+    };
+    Ok(TrackId::try_from(id.clone()).unwrap_or_else(|err| {
+        log::warn!("invalid track id {:?}: {}", id, err);
+        TrackId::default()
+    }))
 }
 
 impl From<TrackId> for String {
@@ -91,11 +163,110 @@ impl From<TrackId> for String {
     }
 }
 
+/// International Standard Recording Codes of tracks to look up, parsed from
+/// a list with one code per line.  Used to bring over tracks liked in other
+/// services.
+#[derive(Clone, Debug, Data)]
+pub struct IsrcImport {
+    pub isrcs: Vector<Arc<str>>,
+    /// Non-empty lines that are not ISRCs.
+    pub invalid_lines: Vector<Arc<str>>,
+}
+
+impl IsrcImport {
+    pub fn parse(text: &str) -> Self {
+        let mut isrcs = Vector::new();
+        let mut invalid_lines = Vector::new();
+        for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+            match Self::parse_isrc(line) {
+                Some(isrc) => isrcs.push_back(isrc.into()),
+                None => invalid_lines.push_back(line.into()),
+            }
+        }
+        Self {
+            isrcs,
+            invalid_lines,
+        }
+    }
+
+    /// Normalize an ISRC like `US-RC1-76-07839` into `USRC17607839`.  The code
+    /// is a country code, an alphanumeric registrant code, two digits of the
+    /// year and five digits of the designation.
+    fn parse_isrc(line: &str) -> Option<String> {
+        let isrc: String = line
+            .chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_ascii_uppercase())
+            .collect();
+        let bytes = isrc.as_bytes();
+        let is_valid = bytes.len() == 12
+            && bytes[..2].iter().all(u8::is_ascii_alphabetic)
+            && bytes[2..5].iter().all(u8::is_ascii_alphanumeric)
+            && bytes[5..].iter().all(u8::is_ascii_digit);
+        is_valid.then(|| isrc)
+    }
+}
+
+/// Results of looking up an `IsrcImport`.
+#[derive(Clone, Debug, Data)]
+pub struct IsrcMatches {
+    /// Best matching track of each found ISRC, in the order of the import.
+    pub tracks: Vector<Arc<Track>>,
+    pub unmatched: Vector<Arc<str>>,
+}
+
+#[derive(Clone, Data, Debug, Deserialize)]
+pub struct AudioFeatures {
+    pub acousticness: f64,
+    pub danceability: f64,
+    pub energy: f64,
+    pub instrumentalness: f64,
+    pub key: i64,
+    pub liveness: f64,
+    pub loudness: f64,
+    pub mode: u64,
+    pub speechiness: f64,
+    pub tempo: f64,
+    pub time_signature: u64,
+    pub valence: f64,
+}
+
+impl AudioFeatures {
+    /// Name of the estimated key in pitch class notation, including the
+    /// modality, i.e. "F♯ minor".
+    pub fn key_name(&self) -> String {
+        const PITCH_CLASSES: [&str; 12] = [
+            "C", "C♯", "D", "D♯", "E", "F", "F♯", "G", "G♯", "A", "A♯", "B",
+        ];
+
+        match PITCH_CLASSES.get(self.key as usize) {
+            Some(pitch) if self.key >= 0 => {
+                let mode = if self.mode == 1 { "major" } else { "minor" };
+                format!("{} {}", pitch, mode)
+            }
+            _ => "Unknown".to_string(),
+        }
+    }
+}
+
 #[derive(Clone, Data, Debug, Deserialize)]
 pub struct AudioAnalysis {
     pub segments: Vector<AudioSegment>,
 }
 
+impl AudioAnalysis {
+    /// Start of the first audible segment, if the track opens with a silence
+    /// long enough to be worth skipping.
+    pub fn audible_start(&self) -> Option<Duration> {
+        let first_audible = self
+            .segments
+            .iter()
+            .find(|segment| segment.loudness_max > SILENCE_LOUDNESS)?;
+        let start = first_audible.interval.start;
+        (start > MIN_TRIMMED_SILENCE).then(|| start)
+    }
+}
+
 #[derive(Clone, Data, Debug, Deserialize)]
 pub struct AudioSegment {
     #[serde(flatten)]
@@ -7,7 +7,7 @@ use std::{
 
 use druid::{im::Vector, Data, Lens};
 use serde::{Deserialize, Deserializer, Serialize};
-use time::{Date, Month};
+use time::{format_description::well_known::Rfc3339, Date, Month, OffsetDateTime};
 
 #[derive(Clone, Data, Lens)]
 pub struct Cached<T: Data> {
@@ -158,6 +158,19 @@ where
     Ok(Option::deserialize(deserializer)?.map(|Wrapper(val)| val))
 }
 
+pub fn deserialize_datetime_option<'de, D>(
+    deserializer: D,
+) -> Result<Option<OffsetDateTime>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let datetime: Option<String> = Option::deserialize(deserializer)?;
+    datetime
+        .map(|datetime| OffsetDateTime::parse(&datetime, &Rfc3339))
+        .transpose()
+        .map_err(|_err| serde::de::Error::custom("Invalid date and time"))
+}
+
 pub fn deserialize_first_page<'de, D, T>(deserializer: D) -> Result<Vector<T>, D::Error>
 where
     T: Clone,
@@ -175,3 +188,34 @@ where
     let opt = Option::deserialize(deserializer)?;
     Ok(opt.unwrap_or_else(default_str))
 }
+
+/// Score of `name` containing the characters of `query` in order, ignoring
+/// case and whitespace, or `None` if it doesn't.  Runs of consecutive
+/// characters and characters starting a word score higher.
+pub fn fuzzy_score(query: &str, name: &str) -> Option<i64> {
+    const MATCH: i64 = 1;
+    const CONSECUTIVE_BONUS: i64 = 4;
+    const WORD_START_BONUS: i64 = 3;
+    const SKIPPED_PENALTY: i64 = 1;
+
+    let name: Vec<char> = name.chars().flat_map(char::to_lowercase).collect();
+    let mut score = 0;
+    // Index of the first character following the previous match.
+    let mut next = 0;
+    for q in query
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .flat_map(char::to_lowercase)
+    {
+        let index = next + name[next..].iter().position(|&c| c == q)?;
+        score += MATCH - SKIPPED_PENALTY * (index - next) as i64;
+        if next > 0 && index == next {
+            score += CONSECUTIVE_BONUS;
+        }
+        if index == 0 || !name[index - 1].is_alphanumeric() {
+            score += WORD_START_BONUS;
+        }
+        next = index + 1;
+    }
+    Some(score)
+}
@@ -79,6 +79,31 @@ impl AppDelegate<AppState> for Delegate {
         } else if let Some(text) = cmd.get(cmd::COPY) {
             Application::global().clipboard().put_string(&text);
             Handled::Yes
+        } else if cmd.is(cmd::COPY_DEBUG_INFO) {
+            Application::global()
+                .clipboard()
+                .put_string(&data.debug_info());
+            data.info_alert("Debug info copied to clipboard.");
+            Handled::Yes
+        } else if let Some(with_url) = cmd.get(cmd::COPY_NOW_PLAYING) {
+            if let Some(now_playing) = &data.playback.now_playing {
+                Application::global()
+                    .clipboard()
+                    .put_string(&now_playing.item.share_text(*with_url));
+            }
+            Handled::Yes
+        } else if cmd.is(cmd::GO_TO_PLAYING_ALBUM) || cmd.is(cmd::GO_TO_PLAYING_ARTIST) {
+            let nav = data.playback.now_playing.as_ref().and_then(|now_playing| {
+                if cmd.is(cmd::GO_TO_PLAYING_ALBUM) {
+                    now_playing.album_nav()
+                } else {
+                    now_playing.artist_nav()
+                }
+            });
+            if let Some(nav) = nav {
+                ctx.submit_command(cmd::NAVIGATE.with(nav));
+            }
+            Handled::Yes
         } else if let Handled::Yes = self.command_image(ctx, target, cmd, data) {
             Handled::Yes
         } else {
@@ -99,6 +124,10 @@ impl AppDelegate<AppState> for Delegate {
         }
         if self.main_window == Some(id) {
             self.main_window.take();
+            // Close the session connection properly, so the server doesn't keep
+            // us around as a lingering device.  The session re-connects lazily,
+            // in case the main window gets opened again.
+            data.session.shutdown();
         }
     }
 }
@@ -122,7 +151,13 @@ impl Delegate {
                     .unwrap();
             } else {
                 self.image_pool.execute(move || {
-                    let image_buf = WebApi::global().get_image(location.clone()).unwrap();
+                    let image_buf = match WebApi::global().get_image(location.clone()) {
+                        Ok(image_buf) => image_buf,
+                        Err(err) => {
+                            log::warn!("failed to fetch image: {}", err);
+                            return;
+                        }
+                    };
                     let payload = remote_image::ImagePayload {
                         location,
                         image_buf,
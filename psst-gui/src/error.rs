@@ -5,6 +5,10 @@ use druid::Data;
 #[derive(Clone, Debug, Data)]
 pub enum Error {
     WebApiError(String),
+    /// Stored credentials were rejected, the user needs to log in again.
+    Unauthorized,
+    /// Offline mode is enabled and the response is not cached.
+    Offline,
 }
 
 impl error::Error for Error {}
@@ -13,6 +17,8 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Self::WebApiError(err) => f.write_str(err),
+            Self::Unauthorized => f.write_str("Not authorized, please log in again"),
+            Self::Offline => f.write_str("Not available offline"),
         }
     }
 }
@@ -0,0 +1,105 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use crate::data::Config;
+
+const MAX_FILE_SIZE: u64 = 1_000_000;
+const MAX_FILES: usize = 5;
+
+/// Log file in the config directory, rotated once it grows over
+/// `MAX_FILE_SIZE`, keeping `MAX_FILES` of them: `psst.log` is the latest,
+/// followed by `psst.1.log`, `psst.2.log` and so on.
+pub struct LogFile {
+    dir: PathBuf,
+    file: File,
+    written: u64,
+}
+
+impl LogFile {
+    pub fn open() -> io::Result<Self> {
+        let dir = Config::config_dir()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no config directory"))?;
+        fs::create_dir_all(&dir)?;
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(Self::file_name(0)))?;
+        let written = file.metadata()?.len();
+        Ok(Self { dir, file, written })
+    }
+
+    /// Path of the latest log file.
+    pub fn path() -> Option<PathBuf> {
+        Config::config_dir().map(|dir| dir.join(Self::file_name(0)))
+    }
+
+    fn file_name(index: usize) -> String {
+        if index == 0 {
+            "psst.log".to_string()
+        } else {
+            format!("psst.{}.log", index)
+        }
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        for index in (1..MAX_FILES).rev() {
+            let from = self.dir.join(Self::file_name(index - 1));
+            if from.exists() {
+                fs::rename(from, self.dir.join(Self::file_name(index)))?;
+            }
+        }
+        self.file = File::create(self.dir.join(Self::file_name(0)))?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for LogFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.written >= MAX_FILE_SIZE {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// Writes the log to stderr, and to the log file while it keeps working.
+pub struct LogTarget {
+    file: Option<LogFile>,
+}
+
+impl LogTarget {
+    pub fn new(file: LogFile) -> Self {
+        Self { file: Some(file) }
+    }
+}
+
+impl Write for LogTarget {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(file) = &mut self.file {
+            if let Err(err) = file.write_all(buf) {
+                // Logging the error would end up here again.
+                eprintln!("failed to write the log file: {}", err);
+                self.file = None;
+            }
+        }
+        io::stderr().write_all(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(file) = &mut self.file {
+            file.flush()?;
+        }
+        io::stderr().flush()
+    }
+}
@@ -6,45 +6,89 @@ mod controller;
 mod data;
 mod delegate;
 mod error;
+mod log_file;
+mod network;
+mod power;
 mod ui;
 mod webapi;
 mod widget;
 
-use druid::AppLauncher;
-use env_logger::{Builder, Env};
+use druid::{AppLauncher, WindowState};
+use env_logger::{Builder, Env, Target};
 use webapi::WebApi;
 
 use crate::{
-    data::{AppState, Config},
+    data::{AppState, Config, PlayCounts, PlayHistory, SavedAlbums, SavedTracks},
     delegate::Delegate,
+    log_file::{LogFile, LogTarget},
 };
 
 const ENV_LOG: &str = "PSST_LOG";
 const ENV_LOG_STYLE: &str = "PSST_LOG_STYLE";
 
 fn main() {
-    // Setup logging from the env variables, with defaults.
-    Builder::from_env(
+    // Setup logging from the env variables, with defaults.  Without them,
+    // psst itself logs everything, capped at `Config::log_level` below, so
+    // the level can be raised at runtime.
+    let log_from_env = std::env::var_os(ENV_LOG).is_some();
+    let mut logger = Builder::from_env(
         Env::new()
-            .filter_or(ENV_LOG, "info")
+            .filter_or(ENV_LOG, "info,psst_gui=trace,psst_core=trace")
             .write_style(ENV_LOG_STYLE),
-    )
-    .init();
+    );
+    // Keep the log in a file as well, to attach it to bug reports.
+    let log_file_error = match LogFile::open() {
+        Ok(file) => {
+            logger.target(Target::Pipe(Box::new(LogTarget::new(file))));
+            None
+        }
+        Err(err) => Some(err),
+    };
+    logger.init();
+    if let Some(err) = log_file_error {
+        log::warn!("failed to open the log file: {}", err);
+    }
 
     let config = Config::load().unwrap_or_default();
-    let state = AppState::default_with_config(config);
+    if !log_from_env {
+        log::set_max_level(config.log_level.filter());
+    }
+    let mut state = AppState::default_with_config(config);
     WebApi::new(
         state.session.clone(),
-        Config::proxy().as_deref(),
+        state.config.proxy().as_deref(),
+        state.config.net_timeouts(),
         Config::cache_dir(),
     )
     .install_as_global();
+    WebApi::global().set_offline(state.config.offline);
+    WebApi::global().set_response_cache(state.config.response_cache);
+
+    state.common_ctx_mut().play_counts = PlayCounts::load();
+    state.play_history = PlayHistory::load();
+
+    // Show the library from the last run right away, it gets refreshed when visited.
+    let saved_tracks_sort = state.config.saved_tracks_sort;
+    state.with_library_mut(|library| {
+        if let Some(tracks) = WebApi::global().get_cached_saved_tracks() {
+            let mut saved = SavedTracks::cached(tracks);
+            saved.sort_by(saved_tracks_sort);
+            library.set_saved_tracks(saved);
+        }
+        if let Some(albums) = WebApi::global().get_cached_saved_albums() {
+            library.set_saved_albums(SavedAlbums::cached(albums));
+        }
+    });
 
     let delegate;
     let launcher;
     if state.config.has_credentials() {
         // Credentials are configured, open the main window.
-        let window = ui::main_window();
+        let mut window = ui::main_window();
+        if state.config.start_minimized {
+            // Playback keeps working, the window is restored from the taskbar or dock.
+            window = window.set_window_state(WindowState::Minimized);
+        }
         delegate = Delegate::with_main(window.id);
         launcher = AppLauncher::with_window(window).configure_env(ui::theme::setup);
 
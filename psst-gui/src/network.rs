@@ -0,0 +1,55 @@
+use std::{
+    net::{TcpStream, ToSocketAddrs},
+    thread,
+    time::Duration,
+};
+
+use url::Url;
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Change in reaching the network, see `watch`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum NetworkEvent {
+    Lost,
+    Restored,
+}
+
+/// Start checking periodically whether the Spotify servers, or the proxy if
+/// there's one, accept connections, calling `on_event` from a background
+/// thread whenever that changes.  Nothing is sent, a connection is enough.
+pub fn watch(proxy_url: Option<String>, on_event: Box<dyn Fn(NetworkEvent) + Send>) {
+    let target = proxy_url
+        .and_then(|url| {
+            let url = Url::parse(&url).ok()?;
+            Some((url.host_str()?.to_string(), url.port_or_known_default()?))
+        })
+        .unwrap_or_else(|| ("api.spotify.com".to_string(), 443));
+    thread::spawn(move || {
+        let mut reachable = true;
+        loop {
+            thread::sleep(CHECK_INTERVAL);
+            let now_reachable = is_reachable(&target);
+            if now_reachable != reachable {
+                reachable = now_reachable;
+                if reachable {
+                    log::info!("network is reachable again");
+                    on_event(NetworkEvent::Restored);
+                } else {
+                    log::warn!("network is not reachable");
+                    on_event(NetworkEvent::Lost);
+                }
+            }
+        }
+    });
+}
+
+fn is_reachable(target: &(String, u16)) -> bool {
+    match target.to_socket_addrs() {
+        Ok(mut addrs) => {
+            addrs.any(|addr| TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT).is_ok())
+        }
+        Err(_) => false,
+    }
+}
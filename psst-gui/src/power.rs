@@ -0,0 +1,59 @@
+use std::{
+    thread,
+    time::{Duration, SystemTime},
+};
+
+/// Power state change of the machine we are running on.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum PowerEvent {
+    Wake,
+}
+
+/// Source of power events, implemented per platform.
+pub trait PowerEvents {
+    /// Start watching for power events, calling `on_event` from a background
+    /// thread for each of them.
+    fn watch(self: Box<Self>, on_event: Box<dyn Fn(PowerEvent) + Send>);
+}
+
+/// Power event source for the current platform.
+pub fn platform() -> Box<dyn PowerEvents> {
+    Box::new(ClockJumps::default())
+}
+
+/// Portable fallback, noticing that the wall clock jumped further ahead than
+/// a periodic timer did, which happens when the machine wakes up.
+pub struct ClockJumps {
+    interval: Duration,
+    tolerance: Duration,
+}
+
+impl Default for ClockJumps {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(5),
+            tolerance: Duration::from_secs(15),
+        }
+    }
+}
+
+impl PowerEvents for ClockJumps {
+    fn watch(self: Box<Self>, on_event: Box<dyn Fn(PowerEvent) + Send>) {
+        thread::spawn(move || {
+            let mut last_tick = SystemTime::now();
+            loop {
+                thread::sleep(self.interval);
+                let now = SystemTime::now();
+                // The timer does not run while sleeping, but the wall clock does.  Clock
+                // adjustments going backwards are ignored.
+                if let Ok(elapsed) = now.duration_since(last_tick) {
+                    if elapsed > self.interval + self.tolerance {
+                        log::info!("woke up after {:?}", elapsed);
+                        on_event(PowerEvent::Wake);
+                    }
+                }
+                last_tick = now;
+            }
+        });
+    }
+}
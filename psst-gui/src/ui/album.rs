@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{sync::Arc, thread, time::Duration};
 
 use druid::{
     widget::{CrossAxisAlignment, Flex, Label, LineBreaking, List},
@@ -18,11 +18,15 @@ use super::{artist, library, playable, theme, track, utils};
 
 pub const LOAD_DETAIL: Selector<AlbumLink> = Selector::new("app.album.load-detail");
 
+/// How long the pointer needs to rest on an album before we start loading its
+/// detail in the background.
+const PREFETCH_DELAY: Duration = Duration::from_millis(300);
+
 pub fn detail_widget() -> impl Widget<AppState> {
     Async::new(
         utils::spinner_widget,
         loaded_detail_widget,
-        utils::error_widget,
+        utils::retry_error_widget,
     )
     .lens(
         Ctx::make(
@@ -37,6 +41,11 @@ pub fn detail_widget() -> impl Widget<AppState> {
         |_, data, d| data.album_detail.album.defer(d),
         |_, data, r| data.album_detail.album.update(r),
     )
+    .on_notification(cmd::RETRY, |ctx, _, data| {
+        if let Some(link) = data.album_detail.album.deferred() {
+            ctx.submit_command(LOAD_DETAIL.with(link.to_owned()));
+        }
+    })
 }
 
 fn loaded_detail_widget() -> impl Widget<WithCtx<Cached<Arc<Album>>>> {
@@ -49,6 +58,16 @@ fn loaded_detail_widget() -> impl Widget<WithCtx<Cached<Arc<Album>>>> {
     let album_date = Label::dynamic(|album: &Arc<Album>, _| album.release())
         .with_text_size(theme::TEXT_SIZE_SMALL);
 
+    let album_duration = Label::dynamic(|album: &Arc<Album>, _| {
+        format!(
+            "{} songs, {}",
+            album.tracks.len(),
+            utils::as_hours_and_minutes(album.duration())
+        )
+    })
+    .with_text_size(theme::TEXT_SIZE_SMALL)
+    .with_text_color(theme::PLACEHOLDER_COLOR);
+
     let album_label = Label::raw()
         .with_line_break_mode(LineBreaking::WordWrap)
         .with_text_size(theme::TEXT_SIZE_SMALL)
@@ -60,8 +79,19 @@ fn loaded_detail_widget() -> impl Widget<WithCtx<Cached<Arc<Album>>>> {
         .with_child(album_artists)
         .with_default_spacer()
         .with_child(album_date)
+        .with_spacer(2.0)
+        .with_child(album_duration)
         .with_default_spacer()
         .with_child(album_label)
+        .with_default_spacer()
+        .with_child(
+            Flex::row()
+                .with_child(playable::shuffle_widget())
+                .with_default_spacer()
+                .with_child(playable::play_next_widget())
+                .with_default_spacer()
+                .with_child(playable::add_to_queue_widget()),
+        )
         .padding(theme::grid(1.0));
 
     let album_top = Flex::row()
@@ -70,14 +100,17 @@ fn loaded_detail_widget() -> impl Widget<WithCtx<Cached<Arc<Album>>>> {
         .with_default_spacer()
         .with_child(album_info.lens(Ctx::data()));
 
-    let album_tracks = playable::list_widget(playable::Display {
-        track: track::Display {
-            number: true,
-            title: true,
-            artist: true,
-            ..track::Display::empty()
+    let album_tracks = playable::list_widget_with_find(
+        playable::Display {
+            track: track::Display {
+                number: true,
+                title: true,
+                artist: true,
+                ..track::Display::empty()
+            },
         },
-    });
+        cmd::FIND_IN_TRACKS,
+    );
 
     Flex::column()
         .cross_axis_alignment(CrossAxisAlignment::Start)
@@ -140,6 +173,10 @@ pub fn album_widget() -> impl Widget<WithCtx<Arc<Album>>> {
         .on_click(|ctx, album, _| {
             ctx.submit_command(cmd::NAVIGATE.with(Nav::AlbumDetail(album.data.link())));
         })
+        .on_hover(PREFETCH_DELAY, |_, album, _| {
+            let id = album.data.id.clone();
+            thread::spawn(move || WebApi::global().prefetch_album(&id));
+        })
         .context_menu(album_ctx_menu)
 }
 
@@ -171,6 +208,11 @@ fn album_menu(album: &Arc<Album>, library: &Arc<Library>) -> Menu<AppState> {
         .command(cmd::COPY.with(album.url())),
     );
 
+    // Editing the library needs the network.
+    if WebApi::global().is_offline() {
+        return menu;
+    }
+
     menu = menu.separator();
 
     if library.contains_album(album) {
@@ -1,43 +1,85 @@
+use std::sync::Arc;
+
 use druid::{
-    im::Vector,
+    im::{vector, Vector},
     kurbo::Circle,
-    widget::{CrossAxisAlignment, Flex, Label, LabelText, LineBreaking, List},
-    Data, Insets, LensExt, LocalizedString, Menu, MenuItem, Selector, Widget, WidgetExt,
+    lens::Map,
+    widget::{
+        Checkbox, CrossAxisAlignment, Either, Flex, Label, LineBreaking, List, RadioGroup, Scroll,
+        ViewSwitcher,
+    },
+    LensExt, LocalizedString, Menu, MenuItem, Selector, TextAlignment, Widget, WidgetExt,
 };
 
 use crate::{
     cmd,
     data::{
-        AppState, Artist, ArtistAlbums, ArtistDetail, ArtistLink, ArtistTracks, Cached, Ctx, Nav,
-        WithCtx,
+        AlbumSort, AppState, Artist, ArtistAlbums, ArtistDetail, ArtistLink, ArtistTracks,
+        ArtistView, Cached, Config, Ctx, Nav, Playable, PlaybackOrigin, PlaybackPayload,
+        QueueEntry, Recommendations, RecommendationsRequest, WithCtx,
     },
+    error::Error,
     webapi::WebApi,
-    widget::{Async, MyWidgetExt, RemoteImage},
+    widget::{Async, Maybe, MyWidgetExt, RemoteImage},
 };
 
-use super::{album, playable, theme, track, utils};
+use super::{
+    album,
+    playable::{self, PlayableIter},
+    theme, track, utils,
+};
 
 pub const LOAD_DETAIL: Selector<ArtistLink> = Selector::new("app.artist.load-detail");
 
+const SORT_ALBUMS: Selector = Selector::new("app.artist.sort-albums");
+const PLAY_WITH_RADIO: Selector<ArtistTracks> = Selector::new("app.artist.play-with-radio");
+
+/// Top tracks played before the artist radio takes over.
+const RADIO_TOP_TRACKS: usize = 5;
+/// Most radio tracks queued after the top tracks.
+const MAX_RADIO_TRACKS: usize = 50;
+
+const MAX_RELATED_ARTISTS: usize = 20;
+
+/// Top tracks shown in the artist overview.
+const OVERVIEW_TOP_TRACKS: usize = 5;
+/// Related artists shown in the artist overview.
+const OVERVIEW_RELATED_ARTISTS: usize = 6;
+
 pub fn detail_widget() -> impl Widget<AppState> {
     Flex::column()
+        .with_child(view_widget())
         .with_child(async_top_tracks_widget())
         .with_child(async_albums_widget().padding((theme::grid(1.0), 0.0)))
         .with_child(async_related_widget().padding((theme::grid(1.0), 0.0)))
 }
 
+fn is_overview(data: &AppState) -> bool {
+    data.config.artist_view == ArtistView::Overview
+}
+
+fn view_widget() -> impl Widget<AppState> {
+    RadioGroup::row(vec![
+        ("Overview", ArtistView::Overview),
+        ("Full", ArtistView::Full),
+    ])
+    .lens(AppState::config.then(Config::artist_view))
+    .padding((theme::grid(1.0), 0.0, theme::grid(1.0), theme::grid(1.0)))
+    .align_left()
+    .on_update(|_, old_data, data, _| {
+        if old_data.config.artist_view != data.config.artist_view {
+            data.config.save();
+        }
+    })
+}
+
 fn async_top_tracks_widget() -> impl Widget<AppState> {
-    Async::new(
-        utils::spinner_widget,
-        top_tracks_widget,
-        utils::error_widget,
-    )
-    .lens(
-        Ctx::make(
-            AppState::common_ctx,
-            AppState::artist_detail.then(ArtistDetail::top_tracks),
-        )
-        .then(Ctx::in_promise()),
+    // The sections switch views below their loaders, so the artist keeps
+    // loading whichever view is shown.
+    Either::new(
+        |data: &AppState, _| is_overview(data),
+        top_tracks_in_promise_widget(|| top_tracks_widget(OVERVIEW_TOP_TRACKS)),
+        top_tracks_in_promise_widget(|| top_tracks_widget(usize::MAX)),
     )
     .on_command_async(
         LOAD_DETAIL,
@@ -52,34 +94,113 @@ fn async_top_tracks_widget() -> impl Widget<AppState> {
             data.artist_detail.top_tracks.update((d, r))
         },
     )
+    .on_notification(cmd::RETRY, |ctx, _, data| {
+        if let Some(link) = data.artist_detail.top_tracks.deferred() {
+            ctx.submit_command(LOAD_DETAIL.with(link.to_owned()));
+        }
+    })
+    .on_command_async(
+        PLAY_WITH_RADIO,
+        |top| load_radio(top.link()),
+        |_, _, _| {},
+        |ctx, data, (top, r)| match r {
+            Ok(radio) => {
+                ctx.submit_command(cmd::PLAY_TRACKS.with(top_tracks_then_radio(&top, &radio)));
+            }
+            Err(err) => {
+                data.action_failed("Loading artist radio", err);
+            }
+        },
+    )
+}
+
+fn load_radio(link: ArtistLink) -> Result<Recommendations, Error> {
+    let request = RecommendationsRequest {
+        seed_artists: vector![link],
+        ..RecommendationsRequest::default()
+    };
+    WebApi::global().get_recommendations(Arc::new(request))
+}
+
+/// Queue the first few top tracks, continued by the radio tracks that are not
+/// among them.
+fn top_tracks_then_radio(top: &ArtistTracks, radio: &Recommendations) -> PlaybackPayload {
+    let mut payload = top.payload(0);
+    payload.items.truncate(RADIO_TOP_TRACKS);
+    let queued: Vec<_> = payload.items.iter().map(Playable::id).collect();
+    let origin = PlaybackOrigin::Recommendations(radio.request.clone());
+    payload.then = radio
+        .tracks
+        .iter()
+        .filter(|track| !queued.contains(&track.id.0))
+        .take(MAX_RADIO_TRACKS)
+        .map(|track| QueueEntry {
+            item: Playable::Track(track.clone()),
+            origin: origin.clone(),
+        })
+        .collect();
+    payload
 }
 
 fn async_albums_widget() -> impl Widget<AppState> {
-    Async::new(utils::spinner_widget, albums_widget, utils::error_widget)
-        .lens(
-            Ctx::make(
-                AppState::common_ctx,
-                AppState::artist_detail.then(ArtistDetail::albums),
-            )
-            .then(Ctx::in_promise()),
-        )
-        .on_command_async(
-            LOAD_DETAIL,
-            |d| WebApi::global().get_artist_albums(&d.id),
-            |_, data, d| data.artist_detail.albums.defer(d),
-            |_, data, r| data.artist_detail.albums.update(r),
-        )
+    let albums = Either::new(
+        |data: &AppState, _| data.config.artist_albums_merged,
+        albums_in_promise_widget(merged_albums_widget),
+        albums_in_promise_widget(albums_widget),
+    );
+    let discography = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(albums_order_widget())
+        .with_child(albums);
+
+    Either::new(
+        |data: &AppState, _| is_overview(data),
+        albums_in_promise_widget(latest_release_widget),
+        discography,
+    )
+    .on_command_async(
+        LOAD_DETAIL,
+        |d| WebApi::global().get_artist_albums(&d.id),
+        |_, data, d| data.artist_detail.albums.defer(d),
+        |_, data, (d, r)| {
+            let sort = data.config.artist_albums_sort;
+            let r = r.map(|mut albums| {
+                albums.sort_by(sort);
+                albums
+            });
+            data.artist_detail.albums.update((d, r))
+        },
+    )
+    .on_command(SORT_ALBUMS, |_, _, data| {
+        let sort = data.config.artist_albums_sort;
+        if let Some(albums) = data.artist_detail.albums.resolved_mut() {
+            albums.sort_by(sort);
+        }
+    })
+    .on_notification(cmd::RETRY, |ctx, _, data| {
+        if let Some(link) = data.artist_detail.albums.deferred() {
+            ctx.submit_command(LOAD_DETAIL.with(link.to_owned()));
+        }
+    })
 }
 
 fn async_related_widget() -> impl Widget<AppState> {
-    Async::new(utils::spinner_widget, related_widget, utils::error_widget)
-        .lens(AppState::artist_detail.then(ArtistDetail::related_artists))
-        .on_command_async(
-            LOAD_DETAIL,
-            |d| WebApi::global().get_related_artists(&d.id),
-            |_, data, d| data.artist_detail.related_artists.defer(d),
-            |_, data, r| data.artist_detail.related_artists.update(r),
-        )
+    Either::new(
+        |data: &AppState, _| is_overview(data),
+        related_in_promise_widget(|| related_widget(OVERVIEW_RELATED_ARTISTS)),
+        related_in_promise_widget(|| related_widget(MAX_RELATED_ARTISTS)),
+    )
+    .on_command_async(
+        LOAD_DETAIL,
+        |d| WebApi::global().get_related_artists(&d.id),
+        |_, data, d| data.artist_detail.related_artists.defer(d),
+        |_, data, r| data.artist_detail.related_artists.update(r),
+    )
+    .on_notification(cmd::RETRY, |ctx, _, data| {
+        if let Some(link) = data.artist_detail.related_artists.deferred() {
+            ctx.submit_command(LOAD_DETAIL.with(link.to_owned()));
+        }
+    })
 }
 
 pub fn artist_widget() -> impl Widget<Artist> {
@@ -112,6 +233,34 @@ pub fn link_widget() -> impl Widget<ArtistLink> {
         .context_menu(artist_menu)
 }
 
+/// Comma-separated artist names in small text, each navigating to its artist
+/// detail.  Artists of local tracks are rendered as plain text.
+pub fn small_links_widget() -> impl Widget<Vector<ArtistLink>> {
+    ViewSwitcher::new(
+        |artists: &Vector<ArtistLink>, _| artists.clone(),
+        |artists, _, _| {
+            let mut row = Flex::row();
+            for (i, artist) in artists.iter().enumerate() {
+                if i > 0 {
+                    row.add_child(Label::new(", ").with_text_size(theme::TEXT_SIZE_SMALL));
+                }
+                let name = Label::new(artist.name.to_string())
+                    .with_line_break_mode(LineBreaking::Clip)
+                    .with_text_size(theme::TEXT_SIZE_SMALL);
+                if artist.is_local() {
+                    row.add_child(name);
+                } else {
+                    let link = artist.to_owned();
+                    row.add_child(name.link().on_left_click(move |ctx, _, _, _| {
+                        ctx.submit_command(cmd::NAVIGATE.with(Nav::ArtistDetail(link.clone())));
+                    }));
+                }
+            }
+            row.boxed()
+        },
+    )
+}
+
 pub fn cover_widget(size: f64) -> impl Widget<Artist> {
     let radius = size / 2.0;
     RemoteImage::new(utils::placeholder_widget(), move |artist: &Artist, _| {
@@ -121,8 +270,36 @@ pub fn cover_widget(size: f64) -> impl Widget<Artist> {
     .clip(Circle::new((radius, radius), radius))
 }
 
-fn top_tracks_widget() -> impl Widget<WithCtx<ArtistTracks>> {
-    playable::list_widget(playable::Display {
+fn top_tracks_in_promise_widget<W>(loaded: impl Fn() -> W + 'static) -> impl Widget<AppState>
+where
+    W: Widget<WithCtx<ArtistTracks>> + 'static,
+{
+    Async::new(utils::spinner_widget, loaded, utils::retry_error_widget).lens(
+        Ctx::make(
+            AppState::common_ctx,
+            AppState::artist_detail.then(ArtistDetail::top_tracks),
+        )
+        .then(Ctx::in_promise()),
+    )
+}
+
+/// Top tracks of the artist, listing at most `count` of them.  The actions
+/// still play all of them.
+fn top_tracks_widget(count: usize) -> impl Widget<WithCtx<ArtistTracks>> {
+    let actions = Flex::row()
+        .with_child(playable::play_all_widget())
+        .with_default_spacer()
+        .with_child(playable::shuffle_widget())
+        .with_default_spacer()
+        .with_child(playable::button_widget("Top 5, Then Radio").on_click(
+            |ctx, top: &mut ArtistTracks, _| {
+                ctx.submit_command(PLAY_WITH_RADIO.with(top.to_owned()));
+            },
+        ))
+        .padding((theme::grid(1.0), 0.0))
+        .lens(Ctx::data());
+
+    let tracks = playable::list_widget(playable::Display {
         track: track::Display {
             title: true,
             album: true,
@@ -130,33 +307,151 @@ fn top_tracks_widget() -> impl Widget<WithCtx<ArtistTracks>> {
             ..track::Display::empty()
         },
     })
+    .lens(Ctx::map(Map::new(
+        move |top: &ArtistTracks| ArtistTracks {
+            tracks: top.tracks.iter().take(count).cloned().collect(),
+            ..top.to_owned()
+        },
+        |_, _| {
+            // Immutable.
+        },
+    )));
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(actions)
+        .with_child(tracks)
+}
+
+fn albums_in_promise_widget<W>(loaded: fn() -> W) -> impl Widget<AppState>
+where
+    W: Widget<WithCtx<ArtistAlbums>> + 'static,
+{
+    Async::new(utils::spinner_widget, loaded, utils::retry_error_widget).lens(
+        Ctx::make(
+            AppState::common_ctx,
+            AppState::artist_detail.then(ArtistDetail::albums),
+        )
+        .then(Ctx::in_promise()),
+    )
+}
+
+fn related_in_promise_widget<W>(loaded: impl Fn() -> W + 'static) -> impl Widget<AppState>
+where
+    W: Widget<Cached<Vector<Artist>>> + 'static,
+{
+    Async::new(utils::spinner_widget, loaded, utils::retry_error_widget)
+        .lens(AppState::artist_detail.then(ArtistDetail::related_artists))
+}
+
+fn albums_order_widget() -> impl Widget<AppState> {
+    Flex::row()
+        .with_child(
+            RadioGroup::row(vec![
+                ("Newest", AlbumSort::Newest),
+                ("Oldest", AlbumSort::Oldest),
+                ("Name", AlbumSort::Name),
+            ])
+            .lens(AppState::config.then(Config::artist_albums_sort)),
+        )
+        .with_default_spacer()
+        .with_child(
+            Checkbox::new("All in one list")
+                .lens(AppState::config.then(Config::artist_albums_merged)),
+        )
+        .padding((0.0, theme::grid(1.0)))
+        .on_update(|ctx, old_data, data, _| {
+            if !old_data.config.same(&data.config) {
+                data.config.save();
+            }
+            if old_data.config.artist_albums_sort != data.config.artist_albums_sort {
+                ctx.submit_command(SORT_ALBUMS);
+            }
+        })
+}
+
+fn merged_albums_widget() -> impl Widget<WithCtx<ArtistAlbums>> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(utils::header_widget("Discography"))
+        .with_child(List::new(album::album_widget).lens(Ctx::map(Map::new(
+            |albums: &ArtistAlbums| albums.merged(),
+            |_, _| {
+                // Immutable.
+            },
+        ))))
+}
+
+fn latest_release_widget() -> impl Widget<WithCtx<ArtistAlbums>> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(utils::header_widget("Latest Release"))
+        .with_child(Maybe::or_empty(album::album_widget).lens(Map::new(
+            |albums: &WithCtx<ArtistAlbums>| {
+                albums
+                    .data
+                    .latest_release()
+                    .map(|album| Ctx::new(albums.ctx.clone(), album))
+            },
+            |_, _| {
+                // Immutable.
+            },
+        )))
 }
 
 fn albums_widget() -> impl Widget<WithCtx<ArtistAlbums>> {
     Flex::column()
         .cross_axis_alignment(CrossAxisAlignment::Start)
-        .with_child(header_widget("Albums"))
+        .with_child(utils::header_widget("Albums"))
         .with_child(List::new(album::album_widget).lens(Ctx::map(ArtistAlbums::albums)))
-        .with_child(header_widget("Singles"))
+        .with_child(utils::header_widget("Singles"))
         .with_child(List::new(album::album_widget).lens(Ctx::map(ArtistAlbums::singles)))
-        .with_child(header_widget("Compilations"))
+        .with_child(utils::header_widget("Compilations"))
         .with_child(List::new(album::album_widget).lens(Ctx::map(ArtistAlbums::compilations)))
 }
 
-fn related_widget() -> impl Widget<Cached<Vector<Artist>>> {
+fn related_widget(count: usize) -> impl Widget<Cached<Vector<Artist>>> {
+    let carousel = Scroll::new(
+        List::new(related_artist_widget)
+            .horizontal()
+            .with_spacing(theme::grid(1.0)),
+    )
+    .horizontal()
+    .lens(Map::new(
+        move |artists: &Vector<Artist>| artists.iter().take(count).cloned().collect(),
+        |_, _| {
+            // Immutable.
+        },
+    ));
+
     Flex::column()
         .cross_axis_alignment(CrossAxisAlignment::Start)
-        .with_child(header_widget("Related Artists"))
-        .with_child(List::new(artist_widget))
+        .with_child(utils::header_widget("Related Artists"))
+        .with_child(carousel)
         .lens(Cached::data)
 }
 
-fn header_widget<T: Data>(text: impl Into<LabelText<T>>) -> impl Widget<T> {
-    Label::new(text)
+fn related_artist_widget() -> impl Widget<Artist> {
+    let size = theme::grid(12.0);
+    let artist_image = cover_widget(size);
+    let artist_label = Label::raw()
         .with_font(theme::UI_FONT_MEDIUM)
-        .with_text_color(theme::PLACEHOLDER_COLOR)
         .with_text_size(theme::TEXT_SIZE_SMALL)
-        .padding(Insets::new(0.0, theme::grid(2.0), 0.0, theme::grid(1.0)))
+        .with_text_alignment(TextAlignment::Center)
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .lens(Artist::name)
+        .fix_width(size);
+    Flex::column()
+        .with_child(artist_image)
+        .with_spacer(theme::grid(0.5))
+        .with_child(artist_label)
+        .padding(theme::grid(0.5))
+        .link()
+        .rounded(theme::BUTTON_BORDER_RADIUS)
+        .on_click(|ctx, artist, _| {
+            ctx.submit_command(cmd::NAVIGATE.with(Nav::ArtistDetail(artist.link())));
+        })
+        .context_menu(|artist| artist_menu(&artist.link()))
 }
 
 fn artist_menu(artist: &ArtistLink) -> Menu<AppState> {
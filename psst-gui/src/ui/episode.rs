@@ -1,13 +1,16 @@
 use std::sync::Arc;
 
 use druid::{
+    im::{vector, Vector},
     widget::{CrossAxisAlignment, Flex, Label, LineBreaking},
     LensExt, LocalizedString, Menu, MenuItem, Size, Widget, WidgetExt,
 };
 
 use crate::{
     cmd,
-    data::{AppState, Episode, Library, Nav},
+    data::{
+        AppState, Episode, EpisodeDownload, Library, Nav, Playable, PlaybackOrigin, PlaybackPayload,
+    },
     widget::{FadeOut, MyWidgetExt, RemoteImage},
 };
 
@@ -40,6 +43,20 @@ pub fn playable_widget() -> impl Widget<PlayRow<Arc<Episode>>> {
 
     let is_playing = playable::is_playing_marker_widget().lens(PlayRow::is_playing);
 
+    let download = Label::dynamic(|row: &PlayRow<Arc<Episode>>, _| {
+        match row.ctx.downloads.get(&row.item.id) {
+            Some(EpisodeDownload::Queued) => "Queued".to_string(),
+            Some(EpisodeDownload::Downloading { progress }) => {
+                format!("Downloading {:.0}%", progress * 100.0)
+            }
+            Some(EpisodeDownload::Downloaded) => "Downloaded".to_string(),
+            Some(EpisodeDownload::Failed) => "Download failed".to_string(),
+            None => String::new(),
+        }
+    })
+    .with_text_size(theme::TEXT_SIZE_SMALL)
+    .with_text_color(theme::PLACEHOLDER_COLOR);
+
     let duration =
         Label::<Arc<Episode>>::dynamic(|episode, _| utils::as_human(episode.duration).to_string())
             .with_text_size(theme::TEXT_SIZE_SMALL)
@@ -55,6 +72,8 @@ pub fn playable_widget() -> impl Widget<PlayRow<Arc<Episode>>> {
                 .with_default_spacer()
                 .with_flex_child(is_playing, 1.0)
                 .with_default_spacer()
+                .with_child(download)
+                .with_default_spacer()
                 .with_child(duration),
         )
         .with_default_spacer()
@@ -79,6 +98,51 @@ pub fn playable_widget() -> impl Widget<PlayRow<Arc<Episode>>> {
         .context_menu(episode_row_menu)
 }
 
+/// Episode listened to partially, clicking it plays on from the resume point.
+pub fn resume_widget() -> impl Widget<Arc<Episode>> {
+    let cover = rounded_cover_widget(theme::grid(4.0));
+
+    let name = Label::raw()
+        .with_font(theme::UI_FONT_MEDIUM)
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .lens(Episode::name.in_arc());
+
+    let remaining = Label::<Arc<Episode>>::dynamic(|episode, _| {
+        format!(
+            "{} · {} left",
+            episode.show.name,
+            utils::as_hours_and_minutes(episode.remaining())
+        )
+    })
+    .with_text_size(theme::TEXT_SIZE_SMALL)
+    .with_text_color(theme::PLACEHOLDER_COLOR);
+
+    Flex::row()
+        .with_child(cover)
+        .with_default_spacer()
+        .with_flex_child(
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(name)
+                .with_spacer(2.0)
+                .with_child(remaining),
+            1.0,
+        )
+        .padding(theme::grid(1.0))
+        .link()
+        .rounded(theme::BUTTON_BORDER_RADIUS)
+        .on_left_click(|ctx, _, episode, _| {
+            // The playback seeks to the resume point on its own.
+            ctx.submit_command(cmd::PLAY_TRACKS.with(PlaybackPayload {
+                origin: PlaybackOrigin::Show(episode.show.clone()),
+                items: vector![Playable::Episode(episode.clone())],
+                position: 0,
+                start_at: None,
+                then: Vector::new(),
+            }));
+        })
+}
+
 fn cover_widget(size: f64) -> impl Widget<Arc<Episode>> {
     RemoteImage::new(
         utils::placeholder_widget(),
@@ -93,10 +157,18 @@ fn rounded_cover_widget(size: f64) -> impl Widget<Arc<Episode>> {
 }
 
 fn episode_row_menu(row: &PlayRow<Arc<Episode>>) -> Menu<AppState> {
-    episode_menu(&row.item, &row.ctx.library)
+    episode_menu(
+        &row.item,
+        &row.ctx.library,
+        row.ctx.downloads.get(&row.item.id),
+    )
 }
 
-pub fn episode_menu(episode: &Episode, _library: &Arc<Library>) -> Menu<AppState> {
+pub fn episode_menu(
+    episode: &Episode,
+    _library: &Arc<Library>,
+    download: Option<&EpisodeDownload>,
+) -> Menu<AppState> {
     let mut menu = Menu::empty();
 
     menu = menu.entry(
@@ -111,5 +183,46 @@ pub fn episode_menu(episode: &Episode, _library: &Arc<Library>) -> Menu<AppState
         .command(cmd::COPY.with(episode.url())),
     );
 
+    menu = menu.separator();
+
+    match download {
+        Some(EpisodeDownload::Downloaded) => {
+            menu = menu.entry(
+                MenuItem::new(
+                    LocalizedString::new("menu-item-remove-download")
+                        .with_placeholder("Remove Download"),
+                )
+                .command(cmd::REMOVE_EPISODE_DOWNLOAD.with(episode.id)),
+            );
+        }
+        Some(EpisodeDownload::Queued | EpisodeDownload::Downloading { .. }) => {
+            menu = menu.entry(
+                MenuItem::new(
+                    LocalizedString::new("menu-item-cancel-download")
+                        .with_placeholder("Cancel Download"),
+                )
+                .command(cmd::REMOVE_EPISODE_DOWNLOAD.with(episode.id)),
+            );
+        }
+        Some(EpisodeDownload::Failed) => {
+            menu = menu.entry(
+                MenuItem::new(
+                    LocalizedString::new("menu-item-retry-download")
+                        .with_placeholder("Retry Download"),
+                )
+                .command(cmd::DOWNLOAD_EPISODE.with(episode.id)),
+            );
+        }
+        None => {
+            menu = menu.entry(
+                MenuItem::new(
+                    LocalizedString::new("menu-item-download")
+                        .with_placeholder("Download for Offline"),
+                )
+                .command(cmd::DOWNLOAD_EPISODE.with(episode.id)),
+            );
+        }
+    }
+
     menu
 }
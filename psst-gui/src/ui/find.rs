@@ -201,6 +201,16 @@ where
                 }
                 ctx.set_handled();
             }
+            Event::KeyDown(k_e) if k_e.key == KbKey::Enter && data.show => {
+                // Cycle through the matches, keeping the focus in the input.
+                if k_e.mods.shift() {
+                    data.focus_previous();
+                } else {
+                    data.focus_next();
+                }
+                ctx.set_handled();
+                return;
+            }
             Event::KeyDown(k_e) if k_e.key == KbKey::Escape => {
                 data.show = false;
             }
@@ -1,19 +1,42 @@
-use druid::{widget::List, LensExt, Selector, Widget, WidgetExt};
+use std::sync::Arc;
+
+use druid::{
+    im::Vector,
+    widget::{CrossAxisAlignment, Either, Flex, List},
+    LensExt, Selector, Widget, WidgetExt,
+};
 
 use crate::{
-    data::{AppState, Personalized},
+    data::{
+        AlbumLink, AppState, ContinueListening, Ctx, Episode, Personalized, PlayHistory, WithCtx,
+    },
+    error::Error,
     webapi::WebApi,
-    widget::{Async, MyWidgetExt},
+    widget::{Async, Empty, MyWidgetExt},
 };
 
 use super::{
-    playlist,
-    utils::{error_widget, spinner_widget},
+    album, episode, playlist, theme,
+    utils::{error_widget, header_widget, spinner_widget},
 };
 
 pub const LOAD_MADE_FOR_YOU: Selector = Selector::new("app.home.load-made-for-your");
+pub const LOAD_CONTINUE_LISTENING: Selector<PlayHistory> =
+    Selector::new("app.home.load-continue-listening");
+
+/// Saved shows looked through for episodes in progress.
+const MAX_CONTINUE_SHOWS: usize = 10;
+const MAX_CONTINUE_EPISODES: usize = 5;
+const MAX_CONTINUE_ALBUMS: usize = 5;
 
 pub fn home_widget() -> impl Widget<AppState> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(continue_listening_widget())
+        .with_child(made_for_you_widget())
+}
+
+fn made_for_you_widget() -> impl Widget<AppState> {
     Async::new(
         spinner_widget,
         || List::new(playlist::playlist_widget),
@@ -27,3 +50,80 @@ pub fn home_widget() -> impl Widget<AppState> {
         |_, data, r| data.personalized.made_for_you.update(r),
     )
 }
+
+fn continue_listening_widget() -> impl Widget<AppState> {
+    // Nothing to continue is not worth an error on the home page.
+    Async::new(|| Empty, continue_listening_loaded_widget, || Empty)
+        .lens(
+            Ctx::make(
+                AppState::common_ctx,
+                AppState::personalized.then(Personalized::continue_listening),
+            )
+            .then(Ctx::in_promise()),
+        )
+        .on_command_async(
+            LOAD_CONTINUE_LISTENING,
+            |history| load_continue_listening(history.recent_albums(MAX_CONTINUE_ALBUMS)),
+            |_, data, _| data.personalized.continue_listening.defer(()),
+            |_, data, (_, r)| {
+                if let Err(err) = &r {
+                    log::warn!("failed to load continue listening: {}", err);
+                }
+                data.personalized.continue_listening.update(((), r))
+            },
+        )
+}
+
+fn continue_listening_loaded_widget() -> impl Widget<WithCtx<ContinueListening>> {
+    let section = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(header_widget("Continue Listening").padding((theme::grid(1.0), 0.0)))
+        .with_child(
+            List::new(episode::resume_widget).lens(Ctx::data().then(ContinueListening::episodes)),
+        )
+        .with_child(List::new(album::album_widget).lens(Ctx::map(ContinueListening::albums)))
+        // Only needed to tell the playlists below apart from this section.
+        .with_child(header_widget("Made For You").padding((theme::grid(1.0), 0.0)));
+
+    Either::new(
+        |continue_listening: &WithCtx<ContinueListening>, _| continue_listening.data.is_empty(),
+        Empty,
+        section,
+    )
+}
+
+fn load_continue_listening(albums: Vector<AlbumLink>) -> Result<ContinueListening, Error> {
+    let webapi = WebApi::global();
+    let mut episodes: Vec<Arc<Episode>> = Vec::new();
+    // A single show or album failing to load shouldn't hide the rest.
+    for show in webapi.get_saved_shows()?.iter().take(MAX_CONTINUE_SHOWS) {
+        let latest = match webapi.get_latest_show_episodes(&show.id) {
+            Ok(latest) => latest,
+            Err(err) => {
+                log::warn!("failed to load episodes of {:?}: {}", show.id, err);
+                continue;
+            }
+        };
+        episodes.extend(
+            latest
+                .into_iter()
+                .filter(|episode| episode.is_in_progress()),
+        );
+    }
+    episodes.sort_by(|a, b| b.release_date.cmp(&a.release_date));
+    episodes.truncate(MAX_CONTINUE_EPISODES);
+    let albums = albums
+        .iter()
+        .filter_map(|link| match webapi.get_album(&link.id) {
+            Ok(album) => Some(album.data),
+            Err(err) => {
+                log::warn!("failed to load album {:?}: {}", link.id, err);
+                None
+            }
+        })
+        .collect();
+    Ok(ContinueListening {
+        episodes: episodes.into(),
+        albums,
+    })
+}
@@ -1,33 +1,86 @@
 use std::sync::Arc;
 
-use druid::{widget::List, LensExt, Selector, Widget, WidgetExt};
+use druid::{
+    im::Vector,
+    widget::{Button, CrossAxisAlignment, Flex, Label, LineBreaking, List, RadioGroup},
+    LensExt, Selector, Widget, WidgetExt,
+};
+use itertools::Itertools;
 
 use crate::{
     cmd,
     data::{
-        Album, AlbumLink, AppState, Ctx, Library, SavedAlbums, SavedShows, SavedTracks, Show,
-        ShowLink, Track, TrackId,
+        Album, AlbumLink, AppState, Config, Ctx, Library, MostPlayed, Nav, PlaybackPayload,
+        RecentPlay, RecentlyPlayed, RecordedPlay, SavedAlbums, SavedShows, SavedTracks,
+        SavedTracksSort, Show, ShowLink, Track, TrackId, WithCtx,
     },
+    error::Error,
     webapi::WebApi,
     widget::{Async, MyWidgetExt},
 };
 
-use super::{album, playable, show, track, utils};
+use super::{album, playable, show, theme, track, utils};
 
 pub const LOAD_TRACKS: Selector = Selector::new("app.library.load-tracks");
 pub const LOAD_ALBUMS: Selector = Selector::new("app.library.load-albums");
 pub const LOAD_SHOWS: Selector = Selector::new("app.library.load-shows");
+pub const LOAD_MOST_PLAYED: Selector = Selector::new("app.library.load-most-played");
+pub const LOAD_RECENTLY_PLAYED: Selector = Selector::new("app.library.load-recently-played");
+
+const SORT_TRACKS: Selector = Selector::new("app.library.sort-tracks");
+const LOAD_MOST_PLAYED_TRACKS: Selector<Vector<TrackId>> =
+    Selector::new("app.library.load-most-played-tracks");
+const RESET_PLAY_COUNTS: Selector = Selector::new("app.library.reset-play-counts");
+const MOST_PLAYED_LIMIT: usize = 50;
+const LOAD_RECENT_PLAYS: Selector<Vector<RecordedPlay>> =
+    Selector::new("app.library.load-recent-plays");
 
 pub const SAVE_TRACK: Selector<Arc<Track>> = Selector::new("app.library.save-track");
 pub const UNSAVE_TRACK: Selector<TrackId> = Selector::new("app.library.unsave-track");
+pub const SAVE_TRACKS: Selector<Vector<Arc<Track>>> = Selector::new("app.library.save-tracks");
+pub const UNSAVE_TRACKS: Selector<Vector<TrackId>> = Selector::new("app.library.unsave-tracks");
 
 pub const SAVE_ALBUM: Selector<Arc<Album>> = Selector::new("app.library.save-album");
 pub const UNSAVE_ALBUM: Selector<AlbumLink> = Selector::new("app.library.unsave-album");
+/// Load the linked album and save it through `SAVE_ALBUM`.
+pub const SAVE_ALBUM_LINK: Selector<AlbumLink> = Selector::new("app.library.save-album-link");
 
 pub const SAVE_SHOW: Selector<Arc<Show>> = Selector::new("app.library.save-show");
 pub const UNSAVE_SHOW: Selector<ShowLink> = Selector::new("app.library.unsave-show");
 
 pub fn saved_tracks_widget() -> impl Widget<AppState> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(saved_tracks_sort_widget())
+        .with_child(saved_tracks_list_widget())
+        .on_command(SORT_TRACKS, |_, _, data| {
+            let sort = data.config.saved_tracks_sort;
+            data.with_library_mut(|library| library.sort_saved_tracks(sort));
+        })
+}
+
+fn saved_tracks_sort_widget() -> impl Widget<AppState> {
+    RadioGroup::row(vec![
+        ("Recently Added", SavedTracksSort::RecentlyAdded),
+        ("Title", SavedTracksSort::Title),
+        ("Artist", SavedTracksSort::Artist),
+        ("Album", SavedTracksSort::Album),
+    ])
+    .lens(AppState::config.then(Config::saved_tracks_sort))
+    .padding((theme::grid(1.0), theme::grid(1.0)))
+    .on_update(|ctx, old_data, data, _| {
+        if !old_data
+            .config
+            .saved_tracks_sort
+            .same(&data.config.saved_tracks_sort)
+        {
+            data.config.save();
+            ctx.submit_command(SORT_TRACKS);
+        }
+    })
+}
+
+fn saved_tracks_list_widget() -> impl Widget<AppState> {
     Async::new(
         utils::spinner_widget,
         || {
@@ -37,10 +90,11 @@ pub fn saved_tracks_widget() -> impl Widget<AppState> {
                         title: true,
                         artist: true,
                         album: true,
+                        play_count: true,
                         ..track::Display::empty()
                     },
                 },
-                cmd::FIND_IN_SAVED_TRACKS,
+                cmd::FIND_IN_TRACKS,
             )
         },
         utils::error_widget,
@@ -54,16 +108,33 @@ pub fn saved_tracks_widget() -> impl Widget<AppState> {
     )
     .on_command_async(
         LOAD_TRACKS,
-        |_| WebApi::global().get_saved_tracks().map(SavedTracks::new),
+        |_| WebApi::global().sync_saved_tracks().map(SavedTracks::new),
         |_, data, _| {
-            data.with_library_mut(|library| {
-                library.saved_tracks.defer_default();
-            });
+            // Keep showing the cached tracks while refreshing.
+            if !data.library.saved_tracks.is_resolved() {
+                data.with_library_mut(|library| {
+                    library.saved_tracks.defer_default();
+                });
+            }
         },
-        |_, data, r| {
-            data.with_library_mut(|library| {
-                library.saved_tracks.update(r);
+        |_, data, (d, r)| {
+            let sort = data.config.saved_tracks_sort;
+            let r = r.map(|mut saved| {
+                saved.sort_by(sort);
+                saved
             });
+            if !data.library.saved_tracks.is_resolved() {
+                data.with_library_mut(|library| {
+                    library.saved_tracks.update((d, r));
+                });
+            } else {
+                match r {
+                    Ok(saved) => data.with_library_mut(|library| {
+                        library.set_saved_tracks(saved);
+                    }),
+                    Err(err) => data.action_failed("Refreshing saved tracks", err),
+                }
+            }
         },
     )
     .on_command_async(
@@ -76,7 +147,7 @@ pub fn saved_tracks_widget() -> impl Widget<AppState> {
         },
         |_, data, (_, r)| {
             if let Err(err) = r {
-                data.error_alert(err);
+                data.action_failed("Saving track", err);
             } else {
                 data.info_alert("Track added to library.")
             }
@@ -92,18 +163,236 @@ pub fn saved_tracks_widget() -> impl Widget<AppState> {
         },
         |_, data, (_, r)| {
             if let Err(err) = r {
-                data.error_alert(err);
+                data.action_failed("Removing track", err);
             } else {
                 data.info_alert("Track removed from library.")
             }
         },
     )
+    .on_command_async(
+        SAVE_TRACKS,
+        |t| {
+            let ids: Vec<String> = t.iter().map(|t| t.id.0.to_base62()).collect();
+            let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+            WebApi::global().save_tracks(&ids)
+        },
+        |_, data, t| {
+            data.with_library_mut(|library| {
+                library.add_tracks(t);
+            });
+        },
+        |_, data, (t, r)| {
+            if let Err(err) = r {
+                data.action_failed("Saving tracks", err);
+            } else {
+                data.info_alert(format!("{} tracks added to library.", t.len()))
+            }
+        },
+    )
+    .on_command_async(
+        UNSAVE_TRACKS,
+        |i| {
+            let ids: Vec<String> = i.iter().map(|i| i.0.to_base62()).collect();
+            let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+            WebApi::global().unsave_tracks(&ids)
+        },
+        |_, data, i| {
+            data.with_library_mut(|library| {
+                library.remove_tracks(i);
+            });
+        },
+        |_, data, (i, r)| {
+            if let Err(err) = r {
+                data.action_failed("Removing tracks", err);
+            } else {
+                data.info_alert(format!("{} tracks removed from library.", i.len()))
+            }
+        },
+    )
+}
+
+pub fn most_played_widget() -> impl Widget<AppState> {
+    let header = Flex::row()
+        .with_flex_child(
+            Label::new("Tracks played most often, counted on this computer.")
+                .with_text_size(theme::TEXT_SIZE_SMALL)
+                .with_text_color(theme::PLACEHOLDER_COLOR)
+                .with_line_break_mode(LineBreaking::WordWrap),
+            1.0,
+        )
+        .with_default_spacer()
+        .with_child(
+            Button::new("Reset Play Counts")
+                .on_click(|ctx, _, _| ctx.submit_command(RESET_PLAY_COUNTS))
+                .disabled_if(|data: &AppState, _| data.common_ctx.play_counts.is_empty()),
+        )
+        .padding((theme::grid(1.0), theme::grid(1.0)));
+
+    let tracks = Async::new(
+        utils::spinner_widget,
+        || {
+            playable::list_widget_with_find(
+                playable::Display {
+                    track: track::Display {
+                        title: true,
+                        artist: true,
+                        album: true,
+                        play_count: true,
+                        ..track::Display::empty()
+                    },
+                },
+                cmd::FIND_IN_TRACKS,
+            )
+        },
+        utils::error_widget,
+    )
+    .lens(Ctx::make(AppState::common_ctx, AppState::most_played).then(Ctx::in_promise()));
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(header)
+        .with_child(tracks)
+        .on_command(LOAD_MOST_PLAYED, |ctx, _, data| {
+            let ids = data.common_ctx.play_counts.most_played(MOST_PLAYED_LIMIT);
+            ctx.submit_command(LOAD_MOST_PLAYED_TRACKS.with(ids.into_iter().collect()));
+        })
+        .on_command_async(
+            LOAD_MOST_PLAYED_TRACKS,
+            |ids| {
+                let ids: Vec<String> = ids.iter().map(|id| id.0.to_base62()).collect();
+                let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+                WebApi::global()
+                    .get_tracks(&ids)
+                    .map(|tracks| MostPlayed { tracks })
+            },
+            |_, data, _| data.most_played.defer_default(),
+            |_, data, (_, r)| data.most_played.resolve_or_reject((), r),
+        )
+        .on_command(RESET_PLAY_COUNTS, |_, _, data| {
+            data.common_ctx_mut().play_counts.clear();
+            data.common_ctx.play_counts.save();
+            data.most_played.resolve(
+                (),
+                MostPlayed {
+                    tracks: Vector::new(),
+                },
+            );
+            data.info_alert("Play counts reset.");
+        })
+}
+
+pub fn recently_played_widget() -> impl Widget<AppState> {
+    let header = Label::new("Tracks played lately, on this computer and on your other devices.")
+        .with_text_size(theme::TEXT_SIZE_SMALL)
+        .with_text_color(theme::PLACEHOLDER_COLOR)
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .padding((theme::grid(1.0), theme::grid(1.0)));
+
+    let recent = Async::new(
+        utils::spinner_widget,
+        recently_played_loaded_widget,
+        utils::error_widget,
+    )
+    .lens(Ctx::make(AppState::common_ctx, AppState::recently_played).then(Ctx::in_promise()));
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(header)
+        .with_child(recent)
+        .on_command(LOAD_RECENTLY_PLAYED, |ctx, _, data| {
+            let plays = data.play_history.plays().clone();
+            ctx.submit_command(LOAD_RECENT_PLAYS.with(plays));
+        })
+        .on_command_async(
+            LOAD_RECENT_PLAYS,
+            load_recently_played,
+            |_, data, _| data.recently_played.defer_default(),
+            |_, data, (_, r)| data.recently_played.resolve_or_reject((), r),
+        )
+}
+
+fn recently_played_loaded_widget() -> impl Widget<WithCtx<RecentlyPlayed>> {
+    let contexts = List::new(|| {
+        Label::dynamic(|nav: &Nav, _| nav.full_title())
+            .with_line_break_mode(LineBreaking::Clip)
+            .padding((theme::grid(1.0), theme::grid(0.5)))
+            .expand_width()
+            .link()
+            .rounded(theme::BUTTON_BORDER_RADIUS)
+            .on_left_click(|ctx, _, nav, _| {
+                ctx.submit_command(cmd::NAVIGATE.with(nav.to_owned()));
+            })
+    })
+    .lens(Ctx::data().then(RecentlyPlayed::contexts));
+
+    let tracks = playable::list_widget_with_find(
+        playable::Display {
+            track: track::Display {
+                title: true,
+                artist: true,
+                album: true,
+                ..track::Display::empty()
+            },
+        },
+        cmd::FIND_IN_TRACKS,
+    );
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(utils::header_widget("Played From").padding((theme::grid(1.0), 0.0)))
+        .with_child(contexts)
+        .with_child(utils::header_widget("Tracks").padding((theme::grid(1.0), 0.0)))
+        .with_child(tracks)
+}
+
+/// Merge the plays recorded here with the ones Spotify knows about.
+fn load_recently_played(local: Vector<RecordedPlay>) -> Result<RecentlyPlayed, Error> {
+    let mut plays = match WebApi::global().get_recently_played() {
+        Ok(plays) => plays,
+        // The local plays are still worth showing.
+        Err(err) if !local.is_empty() => {
+            log::warn!("failed to load recently played tracks: {:?}", err);
+            Vec::new()
+        }
+        Err(err) => return Err(err),
+    };
+    let ids: Vec<String> = local
+        .iter()
+        .map(|play| play.track_id.0.to_base62())
+        .unique()
+        .collect();
+    let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+    let tracks = WebApi::global().get_tracks(&ids)?;
+    for play in local {
+        if let Some(track) = tracks.iter().find(|track| track.id == play.track_id) {
+            plays.push(RecentPlay {
+                track: track.clone(),
+                played_at: play.played_at,
+                context: play.context,
+            });
+        }
+    }
+    Ok(RecentlyPlayed::merge(plays))
 }
 
 pub fn saved_albums_widget() -> impl Widget<AppState> {
     Async::new(
         utils::spinner_widget,
-        || List::new(album::album_widget).lens(Ctx::map(SavedAlbums::albums)),
+        || {
+            Flex::column()
+                .cross_axis_alignment(CrossAxisAlignment::Start)
+                .with_child(
+                    playable::button_widget("Shuffle Albums")
+                        .on_click(|ctx, saved: &mut SavedAlbums, _| {
+                            if let Some(payload) = PlaybackPayload::shuffled_albums(&saved.albums) {
+                                ctx.submit_command(cmd::PLAY_ALBUMS_SHUFFLED.with(payload));
+                            }
+                        })
+                        .padding((theme::grid(1.0), theme::grid(1.0)))
+                        .lens(Ctx::data()),
+                )
+                .with_child(List::new(album::album_widget).lens(Ctx::map(SavedAlbums::albums)))
+        },
         utils::error_widget,
     )
     .lens(
@@ -117,14 +406,26 @@ pub fn saved_albums_widget() -> impl Widget<AppState> {
         LOAD_ALBUMS,
         |_| WebApi::global().get_saved_albums().map(SavedAlbums::new),
         |_, data, _| {
-            data.with_library_mut(|library| {
-                library.saved_albums.defer_default();
-            });
+            // Keep showing the cached albums while refreshing.
+            if !data.library.saved_albums.is_resolved() {
+                data.with_library_mut(|library| {
+                    library.saved_albums.defer_default();
+                });
+            }
         },
-        |_, data, r| {
-            data.with_library_mut(|library| {
-                library.saved_albums.update(r);
-            });
+        |_, data, (d, r)| {
+            if !data.library.saved_albums.is_resolved() {
+                data.with_library_mut(|library| {
+                    library.saved_albums.update((d, r));
+                });
+            } else {
+                match r {
+                    Ok(saved) => data.with_library_mut(|library| {
+                        library.set_saved_albums(saved);
+                    }),
+                    Err(err) => data.action_failed("Refreshing saved albums", err),
+                }
+            }
         },
     )
     .on_command_async(
@@ -135,14 +436,31 @@ pub fn saved_albums_widget() -> impl Widget<AppState> {
                 library.add_album(a);
             });
         },
-        |_, data, (_, r)| {
+        |_, data, (a, r)| {
             if let Err(err) = r {
-                data.error_alert(err);
+                // The album was added right away, take it back out.
+                data.with_library_mut(|library| {
+                    library.remove_album(&a.id);
+                });
+                data.action_failed("Saving album", err);
             } else {
                 data.info_alert("Album added to library.");
             }
         },
     )
+    .on_command_async(
+        SAVE_ALBUM_LINK,
+        |l| WebApi::global().get_album(&l.id),
+        |_, _, _| {},
+        |ctx, data, (_, r)| match r {
+            Ok(album) => {
+                ctx.submit_command(SAVE_ALBUM.with(album.data));
+            }
+            Err(err) => {
+                data.action_failed("Saving album", err);
+            }
+        },
+    )
     .on_command_async(
         UNSAVE_ALBUM,
         |l| WebApi::global().unsave_album(&l.id),
@@ -151,9 +469,12 @@ pub fn saved_albums_widget() -> impl Widget<AppState> {
                 library.remove_album(&l.id);
             });
         },
-        |_, data, (_, r)| {
+        |ctx, data, (_, r)| {
             if let Err(err) = r {
-                data.error_alert(err);
+                // The album was removed right away, but we don't keep it around,
+                // so restore the library from the server.
+                ctx.submit_command(LOAD_ALBUMS);
+                data.action_failed("Removing album", err);
             } else {
                 data.info_alert("Album removed from library.");
             }
@@ -198,7 +519,7 @@ pub fn saved_shows_widget() -> impl Widget<AppState> {
         },
         |_, data, (_, r)| {
             if let Err(err) = r {
-                data.error_alert(err);
+                data.action_failed("Following show", err);
             } else {
                 data.info_alert("Show added to library.");
             }
@@ -214,7 +535,7 @@ pub fn saved_shows_widget() -> impl Widget<AppState> {
         },
         |_, data, (_, r)| {
             if let Err(err) = r {
-                data.error_alert(err);
+                data.action_failed("Unfollowing show", err);
             } else {
                 data.info_alert("Show removed from library.");
             }
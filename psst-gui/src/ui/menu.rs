@@ -13,6 +13,7 @@ pub fn main_menu(_window: Option<WindowId>, _data: &AppState, _env: &Env) -> Men
     }
     .entry(edit_menu())
     .entry(view_menu())
+    .entry(help_menu())
 }
 
 fn mac_app_menu() -> Menu<AppState> {
@@ -78,12 +79,76 @@ fn view_menu() -> Menu<AppState> {
         )
         .entry(
             MenuItem::new(LocalizedString::new("menu-item-search").with_placeholder("Search..."))
-                .command(cmd::SET_FOCUS.to(cmd::WIDGET_SEARCH_INPUT))
+                .command(cmd::FOCUS_SEARCH)
                 .hotkey(SysMods::Cmd, "l"),
         )
+        .entry(
+            MenuItem::new(
+                LocalizedString::new("menu-item-command-palette")
+                    .with_placeholder("Command Palette..."),
+            )
+            .command(cmd::TOGGLE_PALETTE)
+            .hotkey(SysMods::CmdShift, "p"),
+        )
         .entry(
             MenuItem::new(LocalizedString::new("menu-item-find").with_placeholder("Find..."))
                 .command(cmd::TOGGLE_FINDER)
                 .hotkey(SysMods::Cmd, "f"),
         )
+        .entry(
+            MenuItem::new(
+                LocalizedString::new("menu-item-jump-to-playing")
+                    .with_placeholder("Jump to Now Playing"),
+            )
+            .command(cmd::JUMP_TO_PLAYING)
+            .hotkey(SysMods::Cmd, "j"),
+        )
+        .entry(
+            MenuItem::new(
+                LocalizedString::new("menu-item-go-to-playing-album")
+                    .with_placeholder("Go to Playing Album"),
+            )
+            .command(cmd::GO_TO_PLAYING_ALBUM)
+            .hotkey(SysMods::CmdShift, "j"),
+        )
+        .entry(
+            MenuItem::new(
+                LocalizedString::new("menu-item-go-to-playing-artist")
+                    .with_placeholder("Go to Playing Artist"),
+            )
+            .command(cmd::GO_TO_PLAYING_ARTIST)
+            .hotkey(SysMods::CmdShift, "a"),
+        )
+        .entry(
+            MenuItem::new(
+                LocalizedString::new("menu-item-copy-now-playing")
+                    .with_placeholder("Copy Now Playing"),
+            )
+            .command(cmd::COPY_NOW_PLAYING.with(false))
+            .hotkey(SysMods::CmdShift, "c"),
+        )
+        .entry(
+            MenuItem::new(
+                LocalizedString::new("menu-item-copy-now-playing-link")
+                    .with_placeholder("Copy Now Playing with Link"),
+            )
+            .command(cmd::COPY_NOW_PLAYING.with(true)),
+        )
+        .separator()
+        .entry(
+            MenuItem::new(
+                LocalizedString::new("menu-item-work-offline").with_placeholder("Work Offline"),
+            )
+            .command(cmd::TOGGLE_OFFLINE)
+            .selected_if(|data: &AppState, _| data.config.offline),
+        )
+}
+
+fn help_menu() -> Menu<AppState> {
+    Menu::new(LocalizedString::new("menu-help-menu").with_placeholder("Help")).entry(
+        MenuItem::new(
+            LocalizedString::new("menu-item-copy-debug-info").with_placeholder("Copy Debug Info"),
+        )
+        .command(cmd::COPY_DEBUG_INFO),
+    )
 }
@@ -1,16 +1,19 @@
 use std::time::Duration;
 
 use druid::{
+    commands,
     im::Vector,
     lens::Unit,
-    widget::{CrossAxisAlignment, Either, Flex, Label, List, Scroll, Slider, Split, ViewSwitcher},
+    widget::{
+        CrossAxisAlignment, Either, Flex, Label, List, Scroll, Slider, Spinner, Split, ViewSwitcher,
+    },
     Color, Env, Insets, Key, LensExt, Menu, MenuItem, Selector, Widget, WidgetExt, WindowDesc,
 };
 
 use crate::{
     cmd,
-    controller::{AfterDelay, NavController, SessionController},
-    data::{Alert, AlertStyle, AppState, Nav, Playable, Playback, Route},
+    controller::{AfterDelay, NavController, SessionController, ShortcutController},
+    data::{Alert, AlertStyle, AppState, Nav, Playable, Playback, PromiseState, Route},
     widget::{
         icons, icons::SvgIcon, Border, Empty, MyWidgetExt, Overlay, ThemeScope, ViewDispatcher,
     },
@@ -23,6 +26,7 @@ pub mod find;
 pub mod home;
 pub mod library;
 pub mod menu;
+pub mod palette;
 pub mod playable;
 pub mod playback;
 pub mod playlist;
@@ -133,10 +137,14 @@ fn root_widget() -> impl Widget<AppState> {
     let main = Flex::column()
         .cross_axis_alignment(CrossAxisAlignment::Start)
         .with_child(topbar)
-        .with_flex_child(Overlay::bottom(route_widget(), alert_widget()), 1.0)
+        .with_child(palette::palette_widget())
+        .with_child(network_lost_widget())
+        .with_flex_child(Overlay::bottom(content_widget(), overlay_widget()), 1.0)
         .with_child(playback::panel_widget())
         .background(theme::BACKGROUND_LIGHT);
 
+    let main = recommend::play_similar_widget(main);
+
     let split = Split::columns(sidebar, main)
         .split_point(0.2)
         .bar_size(1.0)
@@ -145,13 +153,33 @@ fn root_widget() -> impl Widget<AppState> {
         .solid_bar(true);
 
     ThemeScope::new(split)
-        .controller(SessionController)
+        .controller(SessionController::new())
         .controller(NavController)
+        .controller(ShortcutController)
     // .debug_invalidation()
     // .debug_widget_id()
     // .debug_paint_layout()
 }
 
+fn network_lost_widget() -> impl Widget<AppState> {
+    Either::new(
+        |data: &AppState, _| data.network_lost,
+        Label::new("The network can't be reached, showing saved data until it's back.")
+            .with_text_size(theme::TEXT_SIZE_SMALL)
+            .padding(theme::grid(1.0))
+            .expand_width()
+            .background(theme::GREY_600),
+        Empty,
+    )
+}
+
+fn overlay_widget() -> impl Widget<AppState> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(track::info_widget().padding(theme::grid(1.0)).align_right())
+        .with_child(alert_widget())
+}
+
 fn alert_widget() -> impl Widget<AppState> {
     const BG: Key<Color> = Key::new("app.alert.BG");
     const DISMISS_ALERT: Selector<usize> = Selector::new("app.alert.dismiss");
@@ -192,6 +220,28 @@ fn alert_widget() -> impl Widget<AppState> {
     })
 }
 
+fn content_widget() -> impl Widget<AppState> {
+    Either::new(
+        |state: &AppState, _| state.unauthorized,
+        unauthorized_widget(),
+        route_widget(),
+    )
+}
+
+fn unauthorized_widget() -> impl Widget<AppState> {
+    Flex::column()
+        .with_child(
+            Label::new("Your Spotify credentials were rejected.").with_font(theme::UI_FONT_MEDIUM),
+        )
+        .with_default_spacer()
+        .with_child(
+            Label::new("Log In Again")
+                .link()
+                .on_click(|ctx, _, _| ctx.submit_command(commands::SHOW_PREFERENCES)),
+        )
+        .center()
+}
+
 fn route_widget() -> impl Widget<AppState> {
     ViewDispatcher::new(
         |state: &AppState, _| state.nav.route(),
@@ -199,17 +249,9 @@ fn route_widget() -> impl Widget<AppState> {
             Route::Home => Scroll::new(home::home_widget().padding(theme::grid(1.0)))
                 .vertical()
                 .boxed(),
-            Route::SavedTracks => Flex::column()
-                .with_child(
-                    find::finder_widget(cmd::FIND_IN_SAVED_TRACKS, "Find in Saved Tracks...")
-                        .lens(AppState::finder),
-                )
-                .with_flex_child(
-                    Scroll::new(library::saved_tracks_widget().padding(theme::grid(1.0)))
-                        .vertical(),
-                    1.0,
-                )
-                .boxed(),
+            Route::SavedTracks => {
+                findable_widget(library::saved_tracks_widget(), "Find in Saved Tracks...")
+            }
             Route::SavedAlbums => {
                 Scroll::new(library::saved_albums_widget().padding(theme::grid(1.0)))
                     .vertical()
@@ -220,25 +262,16 @@ fn route_widget() -> impl Widget<AppState> {
                     .vertical()
                     .boxed()
             }
-            Route::SearchResults => Scroll::new(search::results_widget().padding(theme::grid(1.0)))
-                .vertical()
-                .boxed(),
-            Route::AlbumDetail => Scroll::new(album::detail_widget().padding(theme::grid(1.0)))
-                .vertical()
-                .boxed(),
+            Route::SearchResults => {
+                findable_widget(search::results_widget(), "Find in Search Results...")
+            }
+            Route::AlbumDetail => findable_widget(album::detail_widget(), "Find in Album..."),
             Route::ArtistDetail => Scroll::new(artist::detail_widget().padding(theme::grid(1.0)))
                 .vertical()
                 .boxed(),
-            Route::PlaylistDetail => Flex::column()
-                .with_child(
-                    find::finder_widget(cmd::FIND_IN_PLAYLIST, "Find in Playlist...")
-                        .lens(AppState::finder),
-                )
-                .with_flex_child(
-                    Scroll::new(playlist::detail_widget().padding(theme::grid(1.0))).vertical(),
-                    1.0,
-                )
-                .boxed(),
+            Route::PlaylistDetail => {
+                findable_widget(playlist::detail_widget(), "Find in Playlist...")
+            }
             Route::ShowDetail => Scroll::new(show::detail_widget().padding(theme::grid(1.0)))
                 .vertical()
                 .boxed(),
@@ -247,11 +280,33 @@ fn route_widget() -> impl Widget<AppState> {
                     .vertical()
                     .boxed()
             }
+            Route::MostPlayed => {
+                findable_widget(library::most_played_widget(), "Find in Most Played...")
+            }
+            Route::RecentlyPlayed => findable_widget(
+                library::recently_played_widget(),
+                "Find in Recently Played...",
+            ),
         },
     )
     .expand()
 }
 
+/// Scrollable route content with the finder above it, searching through the
+/// track lists of the content.
+fn findable_widget(
+    content: impl Widget<AppState> + 'static,
+    label: &'static str,
+) -> Box<dyn Widget<AppState>> {
+    Flex::column()
+        .with_child(find::finder_widget(cmd::FIND_IN_TRACKS, label).lens(AppState::finder))
+        .with_flex_child(
+            Scroll::new(content.padding(theme::grid(1.0))).vertical(),
+            1.0,
+        )
+        .boxed()
+}
+
 fn sidebar_logo_widget() -> impl Widget<AppState> {
     icons::LOGO
         .scale((29.0, 32.0))
@@ -265,20 +320,38 @@ fn sidebar_menu_widget() -> impl Widget<AppState> {
     Flex::column()
         .with_default_spacer()
         .with_child(sidebar_link_widget("Home", Nav::Home))
-        .with_child(sidebar_link_widget("Tracks", Nav::SavedTracks))
+        .with_child(sidebar_link_with_badge_widget(
+            "Tracks",
+            Nav::SavedTracks,
+            saved_tracks_count_widget(),
+        ))
         .with_child(sidebar_link_widget("Albums", Nav::SavedAlbums))
         .with_child(sidebar_link_widget("Podcasts", Nav::SavedShows))
+        .with_child(sidebar_link_widget("Most Played", Nav::MostPlayed))
+        .with_child(sidebar_link_widget("Recently Played", Nav::RecentlyPlayed))
         .with_child(search::input_widget().padding((theme::grid(1.0), theme::grid(1.0))))
+        .with_child(search::saved_list_widget())
 }
 
 fn sidebar_link_widget(title: &str, link_nav: Nav) -> impl Widget<AppState> {
-    Label::new(title)
+    sidebar_link_with_badge_widget(title, link_nav, Empty)
+}
+
+fn sidebar_link_with_badge_widget(
+    title: &str,
+    link_nav: Nav,
+    badge: impl Widget<AppState> + 'static,
+) -> impl Widget<AppState> {
+    Flex::row()
+        .with_flex_child(Label::new(title).expand_width(), 1.0)
+        .with_child(badge)
         .padding((theme::grid(2.0), theme::grid(1.0)))
         .expand_width()
         .link()
         .env_scope({
             let link_nav = link_nav.clone();
-            move |env, nav: &Nav| {
+            move |env, data: &AppState| {
+                let nav = &data.nav;
                 env.set(
                     theme::LINK_COLD_COLOR,
                     if &link_nav == nav {
@@ -300,7 +373,30 @@ fn sidebar_link_widget(title: &str, link_nav: Nav) -> impl Widget<AppState> {
         .on_click(move |ctx, _, _| {
             ctx.submit_command(cmd::NAVIGATE.with(link_nav.clone()));
         })
-        .lens(AppState::nav)
+}
+
+/// Number of saved tracks, following the tracks getting saved and unsaved.
+fn saved_tracks_count_widget() -> impl Widget<AppState> {
+    ViewSwitcher::new(
+        |data: &AppState, _| data.library.saved_tracks.state(),
+        |state, _, _| match state {
+            PromiseState::Deferred => Spinner::new()
+                .with_color(theme::GREY_400)
+                .fix_size(theme::grid(1.5), theme::grid(1.5))
+                .boxed(),
+            PromiseState::Resolved => Label::dynamic(|data: &AppState, _| {
+                data.library
+                    .saved_tracks
+                    .resolved()
+                    .map_or(0, |saved| saved.tracks.len())
+                    .to_string()
+            })
+            .with_text_size(theme::TEXT_SIZE_SMALL)
+            .with_text_color(theme::PLACEHOLDER_COLOR)
+            .boxed(),
+            PromiseState::Empty | PromiseState::Rejected => Empty.boxed(),
+        },
+    )
 }
 
 fn volume_slider() -> impl Widget<AppState> {
@@ -395,6 +491,8 @@ fn route_icon_widget() -> impl Widget<Nav> {
                 Nav::PlaylistDetail(_) => icon(&icons::PLAYLIST).boxed(),
                 Nav::ShowDetail(_) => icon(&icons::PODCAST).boxed(),
                 Nav::Recommendations(_) => icon(&icons::SEARCH).boxed(),
+                Nav::MostPlayed => Empty.boxed(),
+                Nav::RecentlyPlayed => Empty.boxed(),
             }
         },
     )
@@ -0,0 +1,183 @@
+use druid::{
+    commands,
+    im::Vector,
+    lens::Map,
+    widget::{prelude::*, Controller, Either, Flex, Label, List, Painter, TextBox},
+    Command, KbKey, Selector, WidgetExt,
+};
+
+use crate::{
+    cmd,
+    controller::InputController,
+    data::{AppState, Nav, Palette, PaletteAction, PlaybackState, QueueBehavior},
+    ui::theme,
+    widget::{Empty, MyWidgetExt},
+};
+
+const RUN_ACTION: Selector<PaletteAction> = Selector::new("app.palette.run-action");
+
+/// Matching action, and whether it is the selected one.
+type PaletteRow = (PaletteAction, bool);
+
+pub fn palette_widget() -> impl Widget<AppState> {
+    let input_id = WidgetId::next();
+
+    let input = TextBox::new()
+        .with_placeholder("Type a command...")
+        .controller(InputController::new())
+        .with_id(input_id)
+        .expand_width()
+        .lens(Map::new(
+            |palette: &Palette| palette.query.clone(),
+            |palette: &mut Palette, query| {
+                // Typing changes the matches, start again from the best one.
+                if palette.query != query {
+                    palette.query = query;
+                    palette.selected = 0;
+                }
+            },
+        ));
+
+    let matches = List::new(row_widget).lens(Map::new(
+        |palette: &Palette| {
+            palette
+                .matches()
+                .into_iter()
+                .enumerate()
+                .map(|(index, action)| (action, index == palette.selected))
+                .collect::<Vector<_>>()
+        },
+        |_, _| {},
+    ));
+
+    let palette = Flex::column()
+        .with_child(input)
+        .with_spacer(theme::grid(0.5))
+        .with_child(matches)
+        .padding(theme::grid(1.0))
+        .background(theme::GREY_600);
+
+    Either::new(|data: &Palette, _| data.show, palette, Empty)
+        .lens(AppState::palette)
+        .controller(PaletteController { input_id })
+}
+
+fn row_widget() -> impl Widget<PaletteRow> {
+    Label::dynamic(|(action, _): &PaletteRow, _| action.name().to_string())
+        .with_text_size(theme::TEXT_SIZE_SMALL)
+        .padding(theme::grid(0.5))
+        .expand_width()
+        .background(Painter::new(|ctx, (_, selected): &PaletteRow, env| {
+            if *selected {
+                let bounds = ctx
+                    .size()
+                    .to_rounded_rect(env.get(theme::BUTTON_BORDER_RADIUS));
+                ctx.fill(bounds, &env.get(theme::GREY_500));
+            }
+        }))
+        .link()
+        .on_left_click(|ctx, _, (action, _), _| {
+            ctx.submit_command(RUN_ACTION.with(*action));
+        })
+}
+
+/// Command performing `action`, if there is anything to do.
+fn action_command(action: PaletteAction, data: &AppState) -> Option<Command> {
+    let command = match action {
+        PaletteAction::PlayPause => match data.playback.state {
+            PlaybackState::Playing => cmd::PLAY_PAUSE.into(),
+            PlaybackState::Paused => cmd::PLAY_RESUME.into(),
+            _ => return None,
+        },
+        PaletteAction::Next => cmd::PLAY_NEXT.into(),
+        PaletteAction::Previous => cmd::PLAY_PREVIOUS.into(),
+        PaletteAction::ToggleShuffle => {
+            cmd::PLAY_QUEUE_BEHAVIOR.with(match data.playback.queue_behavior {
+                QueueBehavior::Random => QueueBehavior::Sequential,
+                _ => QueueBehavior::Random,
+            })
+        }
+        PaletteAction::GoToNowPlaying => {
+            let now_playing = data.playback.now_playing.as_ref()?;
+            cmd::NAVIGATE.with(now_playing.origin.to_nav())
+        }
+        PaletteAction::GoHome => cmd::NAVIGATE.with(Nav::Home),
+        PaletteAction::GoToSavedTracks => cmd::NAVIGATE.with(Nav::SavedTracks),
+        PaletteAction::GoToSavedAlbums => cmd::NAVIGATE.with(Nav::SavedAlbums),
+        PaletteAction::GoToSavedShows => cmd::NAVIGATE.with(Nav::SavedShows),
+        PaletteAction::GoToMostPlayed => cmd::NAVIGATE.with(Nav::MostPlayed),
+        PaletteAction::GoToRecentlyPlayed => cmd::NAVIGATE.with(Nav::RecentlyPlayed),
+        PaletteAction::Search => cmd::FOCUS_SEARCH.into(),
+        PaletteAction::OpenPreferences => commands::SHOW_PREFERENCES.into(),
+    };
+    Some(command)
+}
+
+struct PaletteController {
+    input_id: WidgetId,
+}
+
+impl PaletteController {
+    fn run(&self, ctx: &mut EventCtx, data: &mut AppState, action: PaletteAction) {
+        data.palette.show = false;
+        if let Some(command) = action_command(action, data) {
+            ctx.submit_command(command);
+        }
+    }
+}
+
+impl<W> Controller<AppState, W> for PaletteController
+where
+    W: Widget<AppState>,
+{
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(cmd::TOGGLE_PALETTE) => {
+                data.palette.reset();
+                data.palette.show = !data.palette.show;
+                if data.palette.show {
+                    ctx.submit_command(cmd::SET_FOCUS.to(self.input_id));
+                }
+                ctx.set_handled();
+                return;
+            }
+            Event::Command(cmd) if cmd.is(RUN_ACTION) => {
+                self.run(ctx, data, *cmd.get_unchecked(RUN_ACTION));
+                ctx.set_handled();
+                return;
+            }
+            Event::KeyDown(k_e) if data.palette.show => match &k_e.key {
+                KbKey::ArrowUp => {
+                    data.palette.select_previous();
+                    ctx.set_handled();
+                    return;
+                }
+                KbKey::ArrowDown => {
+                    data.palette.select_next();
+                    ctx.set_handled();
+                    return;
+                }
+                KbKey::Enter => {
+                    if let Some(action) = data.palette.selected_action() {
+                        self.run(ctx, data, action);
+                    }
+                    ctx.set_handled();
+                    return;
+                }
+                KbKey::Escape => {
+                    data.palette.show = false;
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        child.event(ctx, event, data, env);
+    }
+}
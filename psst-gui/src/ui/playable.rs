@@ -1,22 +1,25 @@
-use std::{mem, sync::Arc};
+use std::{mem, sync::Arc, time::Duration};
 
 use druid::{
     im::Vector,
     kurbo::Line,
     lens::Map,
     piet::StrokeStyle,
-    widget::{Controller, ControllerHost, List, ListIter, Painter, ViewSwitcher},
-    Data, Env, Event, EventCtx, Lens, RenderContext, Selector, Widget, WidgetExt,
+    widget::{
+        prelude::*, Controller, ControllerHost, Label, List, ListIter, Painter, ViewSwitcher,
+    },
+    KbKey, Lens, Selector, TimerToken, WidgetExt,
 };
 
 use crate::{
     cmd,
     data::{
-        Album, ArtistTracks, CommonCtx, FindQuery, MatchFindQuery, Playable, PlaybackOrigin,
-        PlaybackPayload, PlaylistTracks, Recommendations, SavedTracks, SearchResults, ShowEpisodes,
-        WithCtx,
+        Album, ArtistTracks, CommonCtx, FindQuery, MatchFindQuery, MostPlayed, Playable,
+        PlaybackOrigin, PlaybackPayload, PlaylistTracks, RecentlyPlayed, Recommendations,
+        SavedTracks, SearchResults, SelectedRow, ShowEpisodes, WithCtx,
     },
     ui::theme,
+    widget::MyWidgetExt,
 };
 
 use super::{
@@ -25,6 +28,8 @@ use super::{
     track,
 };
 
+const CLEAR_SELECTION: Selector = Selector::new("app.playable.clear-selection");
+
 #[derive(Copy, Clone)]
 pub struct Display {
     pub track: track::Display,
@@ -34,7 +39,10 @@ pub fn list_widget<T>(display: Display) -> impl Widget<WithCtx<T>>
 where
     T: PlayableIter + Data,
 {
-    ControllerHost::new(List::new(move || playable_widget(display)), PlayController)
+    ControllerHost::new(
+        List::new(move || RowHighlight::new(playable_widget(display))),
+        PlayController,
+    )
 }
 
 pub fn list_widget_with_find<T>(
@@ -45,11 +53,60 @@ where
     T: PlayableIter + Data,
 {
     ControllerHost::new(
-        List::new(move || Findable::new(playable_widget(display), selector)),
+        List::new(move || RowHighlight::new(Findable::new(playable_widget(display), selector))),
         PlayController,
     )
 }
 
+/// Button playing the whole list in order, starting from the first item.
+pub fn play_all_widget<T>() -> impl Widget<T>
+where
+    T: PlayableIter + Data,
+{
+    button_widget("Play All").on_click(|ctx, list: &mut T, _| {
+        ctx.submit_command(cmd::PLAY_TRACKS.with(list.payload(0)));
+    })
+}
+
+/// Button playing the whole list in random order, starting from a random item.
+pub fn shuffle_widget<T>() -> impl Widget<T>
+where
+    T: PlayableIter + Data,
+{
+    button_widget("Shuffle").on_click(|ctx, list: &mut T, _| {
+        ctx.submit_command(cmd::PLAY_SHUFFLED.with(list.payload(0)));
+    })
+}
+
+/// Button queueing the whole list right after the playing item.
+pub fn play_next_widget<T>() -> impl Widget<T>
+where
+    T: PlayableIter + Data,
+{
+    button_widget("Play Next").on_click(|ctx, list: &mut T, _| {
+        ctx.submit_command(cmd::QUEUE_TRACKS_NEXT.with(list.payload(0)));
+    })
+}
+
+/// Button adding the whole list to the end of the queue.
+pub fn add_to_queue_widget<T>() -> impl Widget<T>
+where
+    T: PlayableIter + Data,
+{
+    button_widget("Add to Queue").on_click(|ctx, list: &mut T, _| {
+        ctx.submit_command(cmd::QUEUE_TRACKS.with(list.payload(0)));
+    })
+}
+
+pub fn button_widget<T: Data>(text: &str) -> impl Widget<T> {
+    Label::new(text)
+        .with_font(theme::UI_FONT_MEDIUM)
+        .with_text_size(theme::TEXT_SIZE_SMALL)
+        .padding(theme::grid(0.5))
+        .link()
+        .rounded(theme::BUTTON_BORDER_RADIUS)
+}
+
 fn playable_widget(display: Display) -> impl Widget<PlayRow<Playable>> {
     ViewSwitcher::new(
         |row: &PlayRow<Playable>, _| mem::discriminant(&row.item),
@@ -77,6 +134,95 @@ fn playable_widget(display: Display) -> impl Widget<PlayRow<Playable>> {
     )
 }
 
+/// Outlines the row while it's selected with the keyboard, scrolling it into
+/// view.  Also scrolls to and briefly highlights the row on
+/// `cmd::JUMP_TO_PLAYING`, if it's the one currently playing.
+struct RowHighlight<W> {
+    inner: W,
+    flash_timer: TimerToken,
+}
+
+impl<W> RowHighlight<W> {
+    const FLASH_DURATION: Duration = Duration::from_millis(600);
+
+    fn new(inner: W) -> Self {
+        Self {
+            inner,
+            flash_timer: TimerToken::INVALID,
+        }
+    }
+}
+
+impl<T, W> Widget<PlayRow<T>> for RowHighlight<W>
+where
+    W: Widget<PlayRow<T>>,
+{
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut PlayRow<T>, env: &Env) {
+        match event {
+            Event::Command(cmd) if cmd.is(cmd::JUMP_TO_PLAYING) && data.is_playing => {
+                ctx.scroll_to_view();
+                self.flash_timer = ctx.request_timer(Self::FLASH_DURATION);
+                ctx.request_paint();
+            }
+            Event::Timer(token) if *token == self.flash_timer => {
+                self.flash_timer = TimerToken::INVALID;
+                ctx.request_paint();
+            }
+            _ => {}
+        }
+        self.inner.event(ctx, event, data, env);
+    }
+
+    fn lifecycle(
+        &mut self,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &PlayRow<T>,
+        env: &Env,
+    ) {
+        self.inner.lifecycle(ctx, event, data, env);
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &PlayRow<T>, data: &PlayRow<T>, env: &Env) {
+        if data.is_selected != old_data.is_selected {
+            if data.is_selected {
+                ctx.scroll_to_view();
+            }
+            ctx.request_paint();
+        }
+        self.inner.update(ctx, old_data, data, env);
+    }
+
+    fn layout(
+        &mut self,
+        ctx: &mut LayoutCtx,
+        bc: &BoxConstraints,
+        data: &PlayRow<T>,
+        env: &Env,
+    ) -> Size {
+        self.inner.layout(ctx, bc, data, env)
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &PlayRow<T>, env: &Env) {
+        if self.flash_timer != TimerToken::INVALID {
+            let bounds = ctx
+                .size()
+                .to_rect()
+                .to_rounded_rect(env.get(theme::BUTTON_BORDER_RADIUS));
+            ctx.fill(bounds, &env.get(theme::GREY_500));
+        }
+        self.inner.paint(ctx, data, env);
+        if data.is_selected {
+            let bounds = ctx
+                .size()
+                .to_rect()
+                .inset(-0.5)
+                .to_rounded_rect(env.get(theme::BUTTON_BORDER_RADIUS));
+            ctx.stroke(bounds, &env.get(theme::GREY_400), 1.0);
+        }
+    }
+}
+
 pub fn is_playing_marker_widget() -> impl Widget<bool> {
     Painter::new(|ctx, is_playing, env| {
         const STYLE: StrokeStyle = StrokeStyle::new().dash_pattern(&[1.0, 2.0]);
@@ -99,6 +245,7 @@ pub struct PlayRow<T> {
     pub origin: Arc<PlaybackOrigin>,
     pub position: usize,
     pub is_playing: bool,
+    pub is_selected: bool,
 }
 
 impl<T> PlayRow<T> {
@@ -109,6 +256,7 @@ impl<T> PlayRow<T> {
             origin: self.origin.clone(),
             position: self.position,
             is_playing: self.is_playing,
+            is_selected: self.is_selected,
         }
     }
 }
@@ -134,6 +282,20 @@ pub trait PlayableIter {
     fn origin(&self) -> PlaybackOrigin;
     fn count(&self) -> usize;
     fn for_each(&self, cb: impl FnMut(Playable, usize));
+
+    /// Build a payload queueing all items of the list, starting the playback
+    /// from the item at `position`.
+    fn payload(&self, position: usize) -> PlaybackPayload {
+        let mut items = Vector::new();
+        self.for_each(|item, _| items.push_back(item));
+        PlaybackPayload {
+            items,
+            origin: self.origin(),
+            position,
+            start_at: None,
+            then: Vector::new(),
+        }
+    }
 }
 
 impl PlayableIter for Arc<Album> {
@@ -200,6 +362,38 @@ impl PlayableIter for SavedTracks {
     }
 }
 
+impl PlayableIter for RecentlyPlayed {
+    fn origin(&self) -> PlaybackOrigin {
+        PlaybackOrigin::RecentlyPlayed
+    }
+
+    fn for_each(&self, mut cb: impl FnMut(Playable, usize)) {
+        for (position, track) in self.tracks.iter().enumerate() {
+            cb(Playable::Track(track.to_owned()), position);
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.tracks.len()
+    }
+}
+
+impl PlayableIter for MostPlayed {
+    fn origin(&self) -> PlaybackOrigin {
+        PlaybackOrigin::MostPlayed
+    }
+
+    fn for_each(&self, mut cb: impl FnMut(Playable, usize)) {
+        for (position, track) in self.tracks.iter().enumerate() {
+            cb(Playable::Track(track.to_owned()), position);
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.tracks.len()
+    }
+}
+
 impl PlayableIter for SearchResults {
     fn origin(&self) -> PlaybackOrigin {
         PlaybackOrigin::Search(self.query.clone())
@@ -254,10 +448,12 @@ where
 {
     fn for_each(&self, mut cb: impl FnMut(&PlayRow<Playable>, usize)) {
         let origin = Arc::new(self.data.origin());
+        let selected = self.ctx.selected_position(&origin);
         self.data.for_each(|item, position| {
             cb(
                 &PlayRow {
                     is_playing: self.ctx.is_playing(&item),
+                    is_selected: selected == Some(position),
                     ctx: self.ctx.to_owned(),
                     origin: origin.clone(),
                     item,
@@ -270,10 +466,12 @@ where
 
     fn for_each_mut(&mut self, mut cb: impl FnMut(&mut PlayRow<Playable>, usize)) {
         let origin = Arc::new(self.data.origin());
+        let selected = self.ctx.selected_position(&origin);
         self.data.for_each(|item, position| {
             cb(
                 &mut PlayRow {
                     is_playing: self.ctx.is_playing(&item),
+                    is_selected: selected == Some(position),
                     ctx: self.ctx.to_owned(),
                     origin: origin.clone(),
                     item,
@@ -289,8 +487,52 @@ where
     }
 }
 
+/// Plays the list from the clicked row, and lets the user move a selection
+/// through the list with the arrow keys, Home and End while it's focused,
+/// playing from the selected row on Enter.
 struct PlayController;
 
+impl PlayController {
+    fn select<T: PlayableIter>(data: &mut WithCtx<T>, position: Option<usize>) {
+        let selected_row = position.map(|position| SelectedRow {
+            origin: data.data.origin(),
+            position,
+        });
+        Arc::make_mut(&mut data.ctx).selected_row = selected_row;
+    }
+
+    fn handle_key<T: PlayableIter>(data: &mut WithCtx<T>, key: &KbKey) -> Option<PlaybackPayload> {
+        let count = data.data.count();
+        if count == 0 {
+            return None;
+        }
+        let last = count - 1;
+        let selected = data
+            .ctx
+            .selected_position(&data.data.origin())
+            .map(|position| position.min(last));
+        match key {
+            KbKey::ArrowDown => {
+                Self::select(data, Some(selected.map_or(0, |p| (p + 1).min(last))));
+            }
+            KbKey::ArrowUp => {
+                Self::select(data, Some(selected.map_or(last, |p| p.saturating_sub(1))));
+            }
+            KbKey::Home => {
+                Self::select(data, Some(0));
+            }
+            KbKey::End => {
+                Self::select(data, Some(last));
+            }
+            KbKey::Enter => {
+                return selected.map(|position| data.data.payload(position));
+            }
+            _ => {}
+        }
+        None
+    }
+}
+
 impl<T, W> Controller<WithCtx<T>, W> for PlayController
 where
     T: PlayableIter + Data,
@@ -307,18 +549,105 @@ where
         match event {
             Event::Notification(note) => {
                 if let Some(position) = note.get(cmd::PLAY) {
-                    let mut items = Vector::new();
-                    data.data.for_each(|item, _| items.push_back(item));
-                    let payload = PlaybackPayload {
-                        items,
-                        origin: data.data.origin(),
-                        position: position.to_owned(),
-                    };
+                    let payload = data.data.payload(position.to_owned());
                     ctx.submit_command(cmd::PLAY_TRACKS.with(payload));
                     ctx.set_handled();
                 }
             }
+            Event::MouseDown(_) => {
+                ctx.request_focus();
+                child.event(ctx, event, data, env);
+            }
+            Event::KeyDown(k_e)
+                if ctx.has_focus()
+                    && matches!(
+                        k_e.key,
+                        KbKey::ArrowUp | KbKey::ArrowDown | KbKey::Home | KbKey::End | KbKey::Enter
+                    ) =>
+            {
+                if let Some(payload) = Self::handle_key(data, &k_e.key) {
+                    ctx.submit_command(cmd::PLAY_TRACKS.with(payload));
+                }
+                ctx.set_handled();
+            }
+            Event::KeyDown(k_e) if ctx.has_focus() && k_e.key == KbKey::Escape => {
+                // Hand the focus back, the rest of the keys fall through to the
+                // global shortcuts anyway.
+                ctx.resign_focus();
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(CLEAR_SELECTION) => {
+                if data.ctx.selected_position(&data.data.origin()).is_some() {
+                    Self::select(data, None);
+                }
+                ctx.set_handled();
+            }
             _ => child.event(ctx, event, data, env),
         }
     }
+
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &WithCtx<T>,
+        env: &Env,
+    ) {
+        match event {
+            LifeCycle::BuildFocusChain => {
+                ctx.register_for_focus();
+            }
+            LifeCycle::FocusChanged(false) => {
+                // Only show the selection while the list has the focus.
+                ctx.submit_command(CLEAR_SELECTION.to(ctx.widget_id()));
+            }
+            _ => {}
+        }
+        child.lifecycle(ctx, event, data, env);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::data::Track;
+
+    use super::*;
+
+    #[test]
+    fn payload_plays_from_the_chosen_item() {
+        let saved = SavedTracks {
+            tracks: (0..4).map(Track::for_test).collect(),
+            ..SavedTracks::default()
+        };
+        let payload = saved.payload(2);
+        assert!(matches!(payload.origin, PlaybackOrigin::Library));
+        assert_eq!(payload.position, 2);
+        assert_eq!(payload.start_at, None);
+        assert!(payload.then.is_empty());
+        let ids: Vec<_> = payload.items.iter().map(Playable::id).collect();
+        let expected: Vec<_> = saved.tracks.iter().map(|track| track.id.0).collect();
+        assert_eq!(ids, expected);
+    }
+
+    #[test]
+    fn top_tracks_payload_plays_the_artist() {
+        let top_tracks = ArtistTracks {
+            id: "artist".into(),
+            name: "Artist".into(),
+            tracks: (0..3).map(Track::for_test).collect(),
+        };
+        let payload = top_tracks.payload(0);
+        match &payload.origin {
+            PlaybackOrigin::Artist(link) => {
+                assert_eq!(&*link.id, "artist");
+                assert_eq!(&*link.name, "Artist");
+            }
+            _ => panic!("unexpected origin"),
+        }
+        assert_eq!(payload.position, 0);
+        let ids: Vec<_> = payload.items.iter().map(Playable::id).collect();
+        let expected: Vec<_> = top_tracks.tracks.iter().map(|track| track.id.0).collect();
+        assert_eq!(ids, expected);
+    }
 }
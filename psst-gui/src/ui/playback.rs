@@ -4,8 +4,8 @@ use druid::{
     kurbo::{Affine, BezPath},
     widget::{CrossAxisAlignment, Either, Flex, Label, LineBreaking, Spinner, ViewSwitcher},
     BoxConstraints, Cursor, Data, Env, Event, EventCtx, LayoutCtx, LensExt, LifeCycle,
-    LifeCycleCtx, MouseButton, PaintCtx, Point, Rect, RenderContext, Size, UpdateCtx, Widget,
-    WidgetExt, WidgetPod,
+    LifeCycleCtx, LocalizedString, Menu, MenuItem, MouseButton, PaintCtx, Point, Rect,
+    RenderContext, Size, UpdateCtx, Widget, WidgetExt, WidgetPod,
 };
 use itertools::Itertools;
 
@@ -16,10 +16,13 @@ use crate::{
         AppState, AudioAnalysis, Episode, NowPlaying, Playable, PlayableMatcher, Playback,
         PlaybackOrigin, PlaybackState, QueueBehavior, ShowLink, Track,
     },
-    widget::{icons, icons::SvgIcon, Empty, Maybe, MyWidgetExt, RemoteImage},
+    webapi::WebApi,
+    widget::{icons, icons::SvgIcon, CoverTint, Empty, Maybe, MyWidgetExt, RemoteImage},
 };
 
-use super::{episode, theme, track, utils};
+use super::{artist, episode, library, theme, track, utils};
+
+const COVER_SIZE: f64 = theme::GRID * 8.0;
 
 pub fn panel_widget() -> impl Widget<AppState> {
     let seek_bar = Maybe::or_empty(SeekBar::new).lens(Playback::now_playing);
@@ -29,15 +32,24 @@ pub fn panel_widget() -> impl Widget<AppState> {
         player_widget(),
         Empty,
     );
-    Flex::column()
+    let panel = Flex::column()
         .with_child(seek_bar)
         .with_child(BarLayout::new(item_info, controls))
-        .lens(AppState::playback)
-        .controller(PlaybackController::new())
+        .background(theme::BACKGROUND_LIGHT);
+    // Use the same image as the cover, so it's most likely already cached.
+    CoverTint::new(panel, |playback: &Playback| {
+        playback
+            .now_playing
+            .as_ref()
+            .and_then(|np| np.cover_image_url(COVER_SIZE, COVER_SIZE))
+            .map(|url| url.into())
+    })
+    .lens(AppState::playback)
+    .controller(PlaybackController::new())
 }
 
 fn playing_item_widget() -> impl Widget<NowPlaying> {
-    let cover_art = cover_widget(theme::grid(8.0));
+    let cover_art = cover_widget(COVER_SIZE);
 
     let name = PlayableMatcher::new()
         .track(
@@ -55,12 +67,7 @@ fn playing_item_widget() -> impl Widget<NowPlaying> {
         .lens(NowPlaying::item);
 
     let detail = PlayableMatcher::new()
-        .track(
-            Label::raw()
-                .with_line_break_mode(LineBreaking::Clip)
-                .with_text_size(theme::TEXT_SIZE_SMALL)
-                .lens(Track::lens_artist_name().in_arc()),
-        )
+        .track(artist::small_links_widget().lens(Track::artists.in_arc()))
         .episode(
             Label::raw()
                 .with_line_break_mode(LineBreaking::Clip)
@@ -102,17 +109,39 @@ fn playing_item_widget() -> impl Widget<NowPlaying> {
         )
         .padding(theme::grid(1.0))
         .link()
-        .on_click(|ctx, now_playing, _| {
+        .on_left_click(|ctx, _, now_playing, _| {
             ctx.submit_command(cmd::NAVIGATE.with(now_playing.origin.to_nav()));
         })
         .context_menu(|now_playing| match &now_playing.item {
             Playable::Track(track) => {
-                track::track_menu(track, &now_playing.library, &now_playing.origin)
+                // Blocked tracks get skipped as soon as they start loading.
+                track::track_menu(track, &now_playing.library, &now_playing.origin, false)
+                    .separator()
+                    .entry(save_playing_album_menu_item(now_playing))
+            }
+            Playable::Episode(episode) => {
+                episode::episode_menu(episode, &now_playing.library, None)
             }
-            Playable::Episode(episode) => episode::episode_menu(episode, &now_playing.library),
         })
 }
 
+/// Save the album the playback was started from, disabled when it didn't
+/// start from an album, the album is saved already, or we are offline.
+fn save_playing_album_menu_item(now_playing: &NowPlaying) -> MenuItem<AppState> {
+    let item = MenuItem::new(
+        LocalizedString::new("menu-item-save-playing-album").with_placeholder("Save Playing Album"),
+    );
+    match &now_playing.origin {
+        PlaybackOrigin::Album(link)
+            if !now_playing.library.contains_album_id(&link.id)
+                && !WebApi::global().is_offline() =>
+        {
+            item.command(library::SAVE_ALBUM_LINK.with(link.to_owned()))
+        }
+        _ => item.enabled(false),
+    }
+}
+
 fn cover_widget(size: f64) -> impl Widget<NowPlaying> {
     RemoteImage::new(utils::placeholder_widget(), move |np: &NowPlaying, _| {
         np.cover_image_url(size, size).map(|url| url.into())
@@ -130,6 +159,9 @@ fn playback_origin_icon(origin: &PlaybackOrigin) -> &'static SvgIcon {
         PlaybackOrigin::Show { .. } => &icons::PODCAST,
         PlaybackOrigin::Search { .. } => &icons::SEARCH,
         PlaybackOrigin::Recommendations { .. } => &icons::SEARCH,
+        PlaybackOrigin::MostPlayed => &icons::HEART,
+        PlaybackOrigin::RecentlyPlayed => &icons::HEART,
+        PlaybackOrigin::Remote => &icons::PLAYLIST,
     }
 }
 
@@ -150,6 +182,8 @@ fn player_widget() -> impl Widget<Playback> {
         .with_child(queue_behavior_widget())
         .with_default_spacer()
         .with_child(Maybe::or_empty(durations_widget).lens(Playback::now_playing))
+        .with_default_spacer()
+        .with_child(Maybe::or_empty(bitrate_widget).lens(Playback::now_playing))
         .padding(theme::grid(2.0))
 }
 
@@ -192,24 +226,21 @@ fn queue_behavior_widget() -> impl Widget<Playback> {
         |playback: &Playback, _| playback.queue_behavior,
         |behavior, _, _| {
             faded_button_widget(queue_behavior_icon(behavior))
-                .on_click(|ctx, playback: &mut Playback, _| {
-                    ctx.submit_command(
-                        cmd::PLAY_QUEUE_BEHAVIOR
-                            .with(cycle_queue_behavior(&playback.queue_behavior)),
-                    );
-                })
+                .on_click(|ctx, _, _| ctx.submit_command(cmd::PLAY_CYCLE_QUEUE_BEHAVIOR))
+                .context_menu(|_| queue_menu())
                 .boxed()
         },
     )
 }
 
-fn cycle_queue_behavior(qb: &QueueBehavior) -> QueueBehavior {
-    match qb {
-        QueueBehavior::Sequential => QueueBehavior::Random,
-        QueueBehavior::Random => QueueBehavior::LoopTrack,
-        QueueBehavior::LoopTrack => QueueBehavior::LoopAll,
-        QueueBehavior::LoopAll => QueueBehavior::Sequential,
-    }
+fn queue_menu() -> Menu<AppState> {
+    Menu::empty().entry(
+        MenuItem::new(
+            LocalizedString::new("menu-item-shuffle-remaining")
+                .with_placeholder("Shuffle Upcoming Tracks"),
+        )
+        .command(cmd::PLAY_SHUFFLE_REMAINING),
+    )
 }
 
 fn queue_behavior_icon(qb: &QueueBehavior) -> &'static SvgIcon {
@@ -249,6 +280,17 @@ fn durations_widget() -> impl Widget<NowPlaying> {
     .fix_width(theme::grid(8.0))
 }
 
+fn bitrate_widget() -> impl Widget<NowPlaying> {
+    Label::dynamic(|now_playing: &NowPlaying, _| {
+        now_playing
+            .bitrate
+            .map(|bitrate| format!("{} kbit", bitrate))
+            .unwrap_or_default()
+    })
+    .with_text_size(theme::TEXT_SIZE_SMALL)
+    .with_text_color(theme::PLACEHOLDER_COLOR)
+}
+
 struct BarLayout<T, I, P> {
     item: WidgetPod<T, I>,
     player: WidgetPod<T, P>,
@@ -338,34 +380,57 @@ where
 
 struct SeekBar {
     loudness_path: BezPath,
+    // Fraction of the track the user is currently scrubbing to.  The seek is
+    // committed only after the mouse is released.
+    scrub: Option<f64>,
 }
 
 impl SeekBar {
     fn new() -> Self {
         Self {
             loudness_path: BezPath::new(),
+            scrub: None,
         }
     }
+
+    fn fraction_at(ctx: &EventCtx, pos: Point) -> f64 {
+        (pos.x / ctx.size().width).clamp(0.0, 1.0)
+    }
+
+    fn elapsed_fraction(&self, data: &NowPlaying) -> f64 {
+        self.scrub
+            .unwrap_or_else(|| data.progress.as_secs_f64() / data.item.duration().as_secs_f64())
+    }
 }
 
 impl Widget<NowPlaying> for SeekBar {
-    fn event(&mut self, ctx: &mut EventCtx, event: &Event, _data: &mut NowPlaying, _env: &Env) {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut NowPlaying, _env: &Env) {
         match event {
-            Event::MouseMove(_) => {
+            Event::MouseMove(mouse) => {
                 ctx.set_cursor(&Cursor::Pointer);
+                if ctx.is_active() {
+                    self.scrub = Some(Self::fraction_at(ctx, mouse.pos));
+                    ctx.request_paint();
+                }
             }
             Event::MouseDown(mouse) => {
                 if mouse.button == MouseButton::Left {
                     ctx.set_active(true);
+                    self.scrub = Some(Self::fraction_at(ctx, mouse.pos));
+                    ctx.request_paint();
                 }
             }
             Event::MouseUp(mouse) => {
                 if ctx.is_active() && mouse.button == MouseButton::Left {
-                    if ctx.is_hot() {
-                        let fraction = mouse.pos.x / ctx.size().width;
+                    if let Some(fraction) = self.scrub.take() {
+                        // Optimistically move the play-head, the player will report the
+                        // real position shortly.
+                        data.progress =
+                            Duration::from_secs_f64(data.item.duration().as_secs_f64() * fraction);
                         ctx.submit_command(cmd::PLAY_SEEK.with(fraction));
                     }
                     ctx.set_active(false);
+                    ctx.request_paint();
                 }
             }
             _ => {}
@@ -413,10 +478,11 @@ impl Widget<NowPlaying> for SeekBar {
     }
 
     fn paint(&mut self, ctx: &mut PaintCtx, data: &NowPlaying, env: &Env) {
+        let elapsed_frac = self.elapsed_fraction(data);
         if self.loudness_path.is_empty() {
-            paint_progress_bar(ctx, data, env)
+            paint_progress_bar(ctx, elapsed_frac, env)
         } else {
-            paint_audio_analysis(ctx, data, &self.loudness_path, env)
+            paint_audio_analysis(ctx, elapsed_frac, &self.loudness_path, env)
         }
     }
 }
@@ -482,12 +548,9 @@ fn _compute_loudness_path_from_analysis(
     path
 }
 
-fn paint_audio_analysis(ctx: &mut PaintCtx, data: &NowPlaying, path: &BezPath, env: &Env) {
+fn paint_audio_analysis(ctx: &mut PaintCtx, elapsed_frac: f64, path: &BezPath, env: &Env) {
     let bounds = ctx.size();
 
-    let elapsed_time = data.progress.as_secs_f64();
-    let total_time = data.item.duration().as_secs_f64();
-    let elapsed_frac = elapsed_time / total_time;
     let elapsed_width = bounds.width * elapsed_frac;
     let elapsed = Size::new(elapsed_width, bounds.height).to_rect();
 
@@ -504,10 +567,7 @@ fn paint_audio_analysis(ctx: &mut PaintCtx, data: &NowPlaying, path: &BezPath, e
     });
 }
 
-fn paint_progress_bar(ctx: &mut PaintCtx, data: &NowPlaying, env: &Env) {
-    let elapsed_time = data.progress.as_secs_f64();
-    let total_time = data.item.duration().as_secs_f64();
-
+fn paint_progress_bar(ctx: &mut PaintCtx, elapsed_frac: f64, env: &Env) {
     let (elapsed_color, remaining_color) = if ctx.is_hot() {
         (env.get(theme::GREY_200), env.get(theme::GREY_500))
     } else {
@@ -515,7 +575,6 @@ fn paint_progress_bar(ctx: &mut PaintCtx, data: &NowPlaying, env: &Env) {
     };
     let bounds = ctx.size();
 
-    let elapsed_frac = elapsed_time / total_time;
     let elapsed_width = bounds.width * elapsed_frac;
     let remaining_width = bounds.width - elapsed_width;
     let elapsed = Size::new(elapsed_width, bounds.height).round();
@@ -1,25 +1,41 @@
+use std::{collections::HashSet, fs};
+
 use druid::{
-    widget::{CrossAxisAlignment, Flex, Label, LineBreaking, List},
-    Insets, LensExt, LocalizedString, Menu, MenuItem, Selector, Size, Widget, WidgetExt,
+    commands,
+    widget::{
+        prelude::*, Controller, CrossAxisAlignment, Either, Flex, Label, LineBreaking, List,
+        RadioGroup,
+    },
+    Application, FileDialogOptions, FileSpec, Insets, LensExt, LocalizedString, Menu, MenuItem,
+    Selector, Size, WidgetExt,
 };
 
 use crate::{
     cmd,
     data::{
-        AppState, Ctx, Library, Nav, Playlist, PlaylistAddTrack, PlaylistDetail, PlaylistLink,
-        PlaylistRemoveTrack, PlaylistTracks,
+        AppState, Ctx, Library, Nav, Playlist, PlaylistAddTrack, PlaylistDetail, PlaylistExport,
+        PlaylistExportFormat, PlaylistImport, PlaylistLink, PlaylistRemoveDuplicates,
+        PlaylistRemoveTrack, PlaylistSort, PlaylistTracks, WithCtx,
     },
-    error::Error,
     webapi::WebApi,
-    widget::{Async, MyWidgetExt, RemoteImage},
+    widget::{Async, Empty, MyWidgetExt, RemoteImage},
 };
 
-use super::{playable, theme, track, utils};
+use super::{playable, recommend, theme, track, utils};
 
 pub const LOAD_LIST: Selector = Selector::new("app.playlist.load-list");
 pub const LOAD_DETAIL: Selector<PlaylistLink> = Selector::new("app.playlist.load-detail");
 pub const ADD_TRACK: Selector<PlaylistAddTrack> = Selector::new("app.playlist.add-track");
 pub const REMOVE_TRACK: Selector<PlaylistRemoveTrack> = Selector::new("app.playlist.remove-track");
+pub const IMPORT_FROM_CLIPBOARD: Selector = Selector::new("app.playlist.import-from-clipboard");
+pub const EXPORT: Selector<PlaylistExport> = Selector::new("app.playlist.export");
+
+const IMPORT: Selector<PlaylistImport> = Selector::new("app.playlist.import");
+const IMPORTED_PLAYLIST_NAME: &str = "Imported Playlist";
+const SAVE_EXPORT: Selector<(PlaylistExport, String)> = Selector::new("app.playlist.save-export");
+const SORT_TRACKS: Selector = Selector::new("app.playlist.sort-tracks");
+const REMOVE_DUPLICATES: Selector<PlaylistRemoveDuplicates> =
+    Selector::new("app.playlist.remove-duplicates");
 
 pub fn list_widget() -> impl Widget<AppState> {
     Async::new(
@@ -52,21 +68,13 @@ pub fn list_widget() -> impl Widget<AppState> {
     )
     .on_command_async(
         ADD_TRACK,
-        |d| {
-            WebApi::global().add_track_to_playlist(
-                &d.link.id,
-                &d.track_id
-                    .0
-                    .to_uri()
-                    .ok_or_else(|| Error::WebApiError("Item doesn't have URI".to_string()))?,
-            )
-        },
+        |d| WebApi::global().add_track_to_playlist(&d.link.id, &d.track_id.to_uri()),
         |_, data, d| {
             data.with_library_mut(|library| library.increment_playlist_track_count(&d.link))
         },
         |_, data, (_, r)| {
             if let Err(err) = r {
-                data.error_alert(err);
+                data.action_failed("Adding to playlist", err);
             } else {
                 data.info_alert("Added to playlist.");
             }
@@ -74,21 +82,13 @@ pub fn list_widget() -> impl Widget<AppState> {
     )
     .on_command_async(
         REMOVE_TRACK,
-        |d| {
-            WebApi::global().remove_track_from_playlist(
-                &d.link.id,
-                &d.track_id
-                    .0
-                    .to_uri()
-                    .ok_or_else(|| Error::WebApiError("Item doesn't have URI".to_string()))?,
-            )
-        },
+        |d| WebApi::global().remove_track_from_playlist(&d.link.id, &d.track_id.to_uri()),
         |_, data, d| {
             data.with_library_mut(|library| library.decrement_playlist_track_count(&d.link))
         },
         |e, data, (p, r)| {
             if let Err(err) = r {
-                data.error_alert(err);
+                data.action_failed("Removing from playlist", err);
             } else {
                 data.info_alert("Removed from playlist.");
             }
@@ -96,6 +96,114 @@ pub fn list_widget() -> impl Widget<AppState> {
             e.submit_command(LOAD_DETAIL.with(p.link))
         },
     )
+    .on_command(IMPORT_FROM_CLIPBOARD, |ctx, _, data| {
+        let text = Application::global()
+            .clipboard()
+            .get_string()
+            .unwrap_or_default();
+        let import = PlaylistImport::parse(IMPORTED_PLAYLIST_NAME, &text);
+        if import.track_ids.is_empty() {
+            data.info_alert("No track links found in the clipboard.");
+        } else {
+            ctx.submit_command(IMPORT.with(import));
+        }
+    })
+    .on_command_async(
+        IMPORT,
+        |d| WebApi::global().import_playlist(&d),
+        |_, data, _| data.info_alert("Importing playlist..."),
+        |ctx, data, (d, r)| match r {
+            Ok((playlist, added)) => {
+                for line in &d.invalid_lines {
+                    log::warn!("skipping invalid track link: {:?}", line);
+                }
+                let skipped = d.track_ids.len() - added + d.invalid_lines.len();
+                let link = playlist.link();
+                data.with_library_mut(|library| library.add_playlist(playlist));
+                if skipped > 0 {
+                    data.info_alert(format!("Imported {} tracks, skipped {}.", added, skipped));
+                } else {
+                    data.info_alert(format!("Imported {} tracks.", added));
+                }
+                ctx.submit_command(cmd::NAVIGATE.with(Nav::PlaylistDetail(link)));
+            }
+            Err(err) => {
+                data.action_failed("Importing playlist", err);
+            }
+        },
+    )
+    .on_command_async(
+        EXPORT,
+        // Load all the pages, even if the detail view has the playlist open.
+        |d| WebApi::global().get_playlist_entries(&d.link.id),
+        |_, _, _| {},
+        |ctx, data, (d, r)| match r {
+            Ok(entries) => {
+                let tracks = PlaylistTracks::new(&d.link, entries, PlaylistSort::Manual);
+                let contents = tracks.export(d.format);
+                ctx.submit_command(SAVE_EXPORT.with((d, contents)));
+            }
+            Err(err) => {
+                data.action_failed("Exporting playlist", err);
+            }
+        },
+    )
+    .controller(ExportController { pending: None })
+}
+
+/// Asks where to save an exported playlist, and writes it there.
+struct ExportController {
+    pending: Option<String>,
+}
+
+impl<W> Controller<AppState, W> for ExportController
+where
+    W: Widget<AppState>,
+{
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(SAVE_EXPORT) => {
+                let (export, contents) = cmd.get_unchecked(SAVE_EXPORT);
+                let file_type = match export.format {
+                    PlaylistExportFormat::Uris => FileSpec::new("Track Links", &["txt"]),
+                    PlaylistExportFormat::Csv => FileSpec::new("CSV", &["csv"]),
+                };
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![file_type])
+                    .default_type(file_type)
+                    .default_name(format!(
+                        "{}.{}",
+                        export.link.name,
+                        export.format.extension()
+                    ));
+                self.pending = Some(contents.clone());
+                ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(commands::SAVE_FILE_AS) => {
+                if let Some(contents) = self.pending.take() {
+                    let file = cmd.get_unchecked(commands::SAVE_FILE_AS);
+                    match fs::write(file.path(), contents) {
+                        Ok(_) => data.info_alert("Playlist exported."),
+                        Err(err) => data.action_failed("Exporting playlist", err),
+                    }
+                    ctx.set_handled();
+                }
+            }
+            Event::Command(cmd) if cmd.is(commands::SAVE_PANEL_CANCELLED) => {
+                self.pending = None;
+            }
+            _ => {}
+        }
+        child.event(ctx, event, data, env);
+    }
 }
 
 pub fn playlist_widget() -> impl Widget<Playlist> {
@@ -149,21 +257,8 @@ fn rounded_cover_widget(size: f64) -> impl Widget<Playlist> {
 pub fn detail_widget() -> impl Widget<AppState> {
     Async::new(
         utils::spinner_widget,
-        || {
-            playable::list_widget_with_find(
-                playable::Display {
-                    track: track::Display {
-                        title: true,
-                        artist: true,
-                        album: true,
-                        cover: true,
-                        ..track::Display::empty()
-                    },
-                },
-                cmd::FIND_IN_PLAYLIST,
-            )
-        },
-        utils::error_widget,
+        loaded_detail_widget,
+        utils::retry_error_widget,
     )
     .lens(
         Ctx::make(
@@ -174,17 +269,148 @@ pub fn detail_widget() -> impl Widget<AppState> {
     )
     .on_command_async(
         LOAD_DETAIL,
-        |d| WebApi::global().get_playlist_tracks(&d.id),
+        |d| WebApi::global().get_playlist_entries(&d.id),
         |_, data, d| data.playlist_detail.tracks.defer(d),
         |_, data, (d, r)| {
-            let r = r.map(|tracks| PlaylistTracks {
-                id: d.id.clone(),
-                name: d.name.clone(),
-                tracks,
-            });
+            let sort = data.config.playlist_sort(&d.id);
+            let r = r.map(|entries| PlaylistTracks::new(&d, entries, sort));
             data.playlist_detail.tracks.update((d, r))
         },
     )
+    .on_update(|ctx, old_data, data, _| {
+        let old_tracks = old_data.playlist_detail.tracks.resolved();
+        let tracks = data.playlist_detail.tracks.resolved();
+        if let (Some(old_tracks), Some(tracks)) = (old_tracks, tracks) {
+            if old_tracks.id == tracks.id && old_tracks.sort != tracks.sort {
+                ctx.submit_command(SORT_TRACKS);
+            }
+        }
+    })
+    .on_command(SORT_TRACKS, |_, _, data| {
+        if let Some(tracks) = data.playlist_detail.tracks.resolved_mut() {
+            let sort = tracks.sort;
+            tracks.sort_by(sort);
+            data.config.set_playlist_sort(tracks.id.clone(), sort);
+            data.config.save();
+        }
+    })
+    .on_command_async(
+        REMOVE_DUPLICATES,
+        |d| {
+            let duplicates: Vec<_> = d.duplicates.iter().copied().collect();
+            WebApi::global().remove_playlist_positions(&d.link.id, &duplicates)
+        },
+        |_, data, _| data.info_alert("Removing duplicates..."),
+        |ctx, data, (d, r)| match r {
+            Ok(_) => {
+                let positions: HashSet<usize> =
+                    d.duplicates.iter().map(|(_, position)| *position).collect();
+                if let Some(tracks) = data.playlist_detail.tracks.resolved_mut() {
+                    if tracks.id == d.link.id {
+                        tracks.remove_positions(&positions);
+                    }
+                }
+                data.with_library_mut(|library| {
+                    library.subtract_playlist_track_count(&d.link, positions.len())
+                });
+                data.info_alert(format!("Removed {} duplicate tracks.", positions.len()));
+            }
+            Err(err) => {
+                data.action_failed("Removing duplicates", err);
+                // The playlist might have changed since it was loaded.
+                ctx.submit_command(LOAD_DETAIL.with(d.link));
+            }
+        },
+    )
+    .on_notification(cmd::RETRY, |ctx, _, data| {
+        if let Some(link) = data.playlist_detail.tracks.deferred() {
+            ctx.submit_command(LOAD_DETAIL.with(link.to_owned()));
+        }
+    })
+}
+
+fn loaded_detail_widget() -> impl Widget<WithCtx<PlaylistTracks>> {
+    let playlist_duration = Label::dynamic(|playlist: &PlaylistTracks, _| {
+        format!(
+            "{} songs, {}",
+            playlist.tracks.len(),
+            utils::as_hours_and_minutes(playlist.duration())
+        )
+    })
+    .with_text_size(theme::TEXT_SIZE_SMALL)
+    .with_text_color(theme::PLACEHOLDER_COLOR);
+
+    let playlist_sort = RadioGroup::row(vec![
+        ("Manual", PlaylistSort::Manual),
+        ("Recently Added", PlaylistSort::RecentlyAdded),
+        ("Title", PlaylistSort::Title),
+        ("Artist", PlaylistSort::Artist),
+        ("Album", PlaylistSort::Album),
+    ])
+    .lens(PlaylistTracks::sort);
+
+    let playlist_header = Flex::row()
+        .with_child(playlist_duration)
+        .with_default_spacer()
+        .with_child(playable::shuffle_widget())
+        .with_default_spacer()
+        .with_child(playable::play_next_widget())
+        .with_default_spacer()
+        .with_child(playable::add_to_queue_widget())
+        .with_default_spacer()
+        .with_child(remove_duplicates_widget())
+        .with_child(playlist_sort)
+        .padding((theme::grid(1.0), 0.0))
+        .lens(Ctx::data());
+
+    let playlist_tracks = playable::list_widget_with_find(
+        playable::Display {
+            track: track::Display {
+                title: true,
+                artist: true,
+                album: true,
+                cover: true,
+                play_count: true,
+                ..track::Display::empty()
+            },
+        },
+        cmd::FIND_IN_TRACKS,
+    );
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(playlist_header)
+        .with_default_spacer()
+        .with_child(playlist_tracks)
+}
+
+/// Button removing the repeated tracks, showing how many there are, so the
+/// count is visible before anything gets removed.
+fn remove_duplicates_widget() -> impl Widget<PlaylistTracks> {
+    let button = Label::dynamic(
+        |playlist: &PlaylistTracks, _| match playlist.duplicates().len() {
+            1 => "Remove 1 Duplicate".to_string(),
+            count => format!("Remove {} Duplicates", count),
+        },
+    )
+    .with_font(theme::UI_FONT_MEDIUM)
+    .with_text_size(theme::TEXT_SIZE_SMALL)
+    .padding(theme::grid(0.5))
+    .link()
+    .rounded(theme::BUTTON_BORDER_RADIUS)
+    .on_click(|ctx, playlist: &mut PlaylistTracks, _| {
+        ctx.submit_command(REMOVE_DUPLICATES.with(PlaylistRemoveDuplicates {
+            link: playlist.link(),
+            duplicates: playlist.duplicates(),
+        }));
+    })
+    .padding((0.0, 0.0, theme::grid(1.0), 0.0));
+
+    Either::new(
+        |playlist: &PlaylistTracks, _| !playlist.duplicates().is_empty(),
+        button,
+        Empty,
+    )
 }
 
 fn playlist_menu(playlist: &Playlist) -> Menu<AppState> {
@@ -197,5 +423,52 @@ fn playlist_menu(playlist: &Playlist) -> Menu<AppState> {
         .command(cmd::COPY.with(playlist.url())),
     );
 
+    menu = menu.entry(
+        MenuItem::new(
+            LocalizedString::new("menu-item-play-recommended")
+                .with_placeholder("Play Recommendations"),
+        )
+        .command(recommend::PLAY_FROM_PLAYLIST.with(playlist.link())),
+    );
+
+    menu = menu.entry(
+        MenuItem::new(
+            LocalizedString::new("menu-item-show-blend")
+                .with_placeholder("Blend with My Top Tracks"),
+        )
+        .command(recommend::SHOW_BLEND.with(playlist.link())),
+    );
+
+    menu = menu.entry(
+        MenuItem::new(
+            LocalizedString::new("menu-item-play-playlist-radio")
+                .with_placeholder("Start Playlist Radio"),
+        )
+        .command(recommend::PLAY_PLAYLIST_RADIO.with(playlist.link())),
+    );
+
+    menu = menu.separator();
+
+    menu = menu.entry(
+        MenuItem::new(
+            LocalizedString::new("menu-item-export-links")
+                .with_placeholder("Export Track Links..."),
+        )
+        .command(EXPORT.with(PlaylistExport {
+            link: playlist.link(),
+            format: PlaylistExportFormat::Uris,
+        })),
+    );
+
+    menu = menu.entry(
+        MenuItem::new(
+            LocalizedString::new("menu-item-export-csv").with_placeholder("Export as CSV..."),
+        )
+        .command(EXPORT.with(PlaylistExport {
+            link: playlist.link(),
+            format: PlaylistExportFormat::Csv,
+        })),
+    );
+
     menu
 }
@@ -1,4 +1,7 @@
-use std::thread::{self, JoinHandle};
+use std::{
+    fs,
+    thread::{self, JoinHandle},
+};
 
 use druid::{
     commands,
@@ -6,7 +9,8 @@ use druid::{
         Button, Controller, CrossAxisAlignment, Flex, Label, LineBreaking, MainAxisAlignment,
         RadioGroup, TextBox, ViewSwitcher,
     },
-    Data, Env, Event, EventCtx, LensExt, LifeCycle, LifeCycleCtx, Selector, Widget, WidgetExt,
+    Data, Env, Event, EventCtx, FileDialogOptions, FileSpec, LensExt, LifeCycle, LifeCycleCtx,
+    Selector, Widget, WidgetExt,
 };
 use psst_core::connection::Credentials;
 
@@ -14,8 +18,10 @@ use crate::{
     cmd,
     controller::InputController,
     data::{
-        AppState, AudioQuality, Authentication, Config, Preferences, PreferencesTab, Promise, Theme,
+        AppState, AudioQuality, Authentication, Config, ListDensity, LogLevel, Preferences,
+        PreferencesTab, Promise, ResamplerQuality, ShuffleMode, Theme,
     },
+    log_file::LogFile,
     webapi::WebApi,
     widget::{icons, Async, Border, Checkbox, MyWidgetExt},
 };
@@ -76,17 +82,26 @@ pub fn preferences_widget() -> impl Widget<AppState> {
                 data.config.save();
             }
 
+            if old_data.config.log_level != data.config.log_level {
+                log::set_max_level(data.config.log_level.filter());
+            }
+
             // Propagate some flags further to the state.
             if !old_data
                 .config
                 .show_track_cover
                 .same(&data.config.show_track_cover)
+                || !old_data
+                    .config
+                    .show_play_counts
+                    .same(&data.config.show_play_counts)
             {
                 ctx.submit_command(PROPAGATE_FLAGS);
             }
         })
         .on_command(PROPAGATE_FLAGS, |_, _, data| {
             data.common_ctx_mut().show_track_cover = data.config.show_track_cover;
+            data.common_ctx_mut().show_play_counts = data.config.show_play_counts;
         })
 }
 
@@ -146,6 +161,20 @@ fn general_tab_widget() -> impl Widget<AppState> {
                 .lens(AppState::config.then(Config::theme)),
         );
 
+    col = col.with_spacer(theme::grid(3.0));
+
+    // List density
+    col = col
+        .with_child(Label::new("List density").with_font(theme::UI_FONT_MEDIUM))
+        .with_spacer(theme::grid(2.0))
+        .with_child(
+            RadioGroup::column(vec![
+                ("Comfortable", ListDensity::Comfortable),
+                ("Compact", ListDensity::Compact),
+            ])
+            .lens(AppState::config.then(Config::list_density)),
+        );
+
     col = col.with_spacer(theme::grid(1.5));
 
     // Show track covers
@@ -154,6 +183,14 @@ fn general_tab_widget() -> impl Widget<AppState> {
             .lens(AppState::config.then(Config::show_track_cover)),
     );
 
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Show play counts
+    col = col.with_child(
+        Checkbox::new("Show play counts in track lists")
+            .lens(AppState::config.then(Config::show_play_counts)),
+    );
+
     col = col.with_spacer(theme::grid(3.0));
 
     // Audio quality
@@ -169,7 +206,305 @@ fn general_tab_widget() -> impl Widget<AppState> {
             .lens(AppState::config.then(Config::audio_quality)),
         );
 
-    col
+    col = col.with_spacer(theme::grid(3.0));
+
+    // Download quality
+    col = col
+        .with_child(Label::new("Download quality").with_font(theme::UI_FONT_MEDIUM))
+        .with_spacer(theme::grid(2.0))
+        .with_child(
+            RadioGroup::column(vec![
+                ("Low (96kbit)", AudioQuality::Low),
+                ("Normal (160kbit)", AudioQuality::Normal),
+                ("High (320kbit)", AudioQuality::High),
+            ])
+            .lens(AppState::config.then(Config::download_quality)),
+        );
+
+    col = col.with_spacer(theme::grid(3.0));
+
+    // Resampler quality
+    col = col
+        .with_child(Label::new("Resampling quality").with_font(theme::UI_FONT_MEDIUM))
+        .with_spacer(theme::grid(2.0))
+        .with_child(
+            RadioGroup::column(vec![
+                ("Fast (low-power machines)", ResamplerQuality::Fast),
+                ("Balanced", ResamplerQuality::Balanced),
+                ("Best (more CPU)", ResamplerQuality::Best),
+            ])
+            .lens(AppState::config.then(Config::resampler_quality)),
+        );
+
+    col = col.with_spacer(theme::grid(3.0));
+
+    // Shuffle mode
+    col = col
+        .with_child(Label::new("Shuffle").with_font(theme::UI_FONT_MEDIUM))
+        .with_spacer(theme::grid(2.0))
+        .with_child(
+            RadioGroup::column(vec![
+                ("Uniform", ShuffleMode::Uniform),
+                ("Favor tracks not played lately", ShuffleMode::Weighted),
+            ])
+            .lens(AppState::config.then(Config::shuffle_mode)),
+        );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Skip unavailable tracks
+    col = col.with_child(
+        Checkbox::new("Skip unavailable tracks")
+            .lens(AppState::config.then(Config::skip_unavailable)),
+    );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Search results playback
+    col = col.with_child(
+        Checkbox::new("Keep playing the other search results")
+            .lens(AppState::config.then(Config::search_play_continues)),
+    );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Start minimized
+    col = col.with_child(
+        Checkbox::new("Start minimized").lens(AppState::config.then(Config::start_minimized)),
+    );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Close to tray
+    col = col.with_child(
+        Checkbox::new("Keep playing when the window is closed")
+            .lens(AppState::config.then(Config::close_to_tray)),
+    );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Sleep and wake
+    col = col.with_child(
+        Checkbox::new("Pause after the computer wakes up")
+            .lens(AppState::config.then(Config::pause_on_sleep)),
+    );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Skip episode ads
+    col = col.with_child(
+        Checkbox::new("Skip ads in episodes with chapters")
+            .lens(AppState::config.then(Config::skip_episode_ads)),
+    );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Audio analysis
+    col = col
+        .with_child(
+            Checkbox::new("Load the audio analysis of tracks")
+                .lens(AppState::config.then(Config::fetch_analysis)),
+        )
+        .with_spacer(theme::grid(1.5))
+        .with_child(
+            Checkbox::new("Skip silence at the start of tracks")
+                .lens(AppState::config.then(Config::trim_silence))
+                .disabled_if(|data: &AppState, _| !data.config.fetch_analysis),
+        );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Idle audio device
+    col = col.with_child(
+        Checkbox::new("Release the audio device when idle")
+            .lens(AppState::config.then(Config::release_device_when_idle)),
+    );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Spotify Connect
+    col = col.with_child(
+        Checkbox::new("Show up as a Spotify Connect device")
+            .lens(AppState::config.then(Config::connect_device)),
+    );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Automatic offline mode
+    col = col.with_child(
+        Checkbox::new("Go offline while the network is lost")
+            .lens(AppState::config.then(Config::auto_offline)),
+    );
+
+    col = col.with_spacer(theme::grid(1.5));
+
+    // Response cache
+    col = col.with_child(
+        Checkbox::new("Reuse responses of recent identical requests")
+            .lens(AppState::config.then(Config::response_cache)),
+    );
+
+    col = col.with_spacer(theme::grid(3.0));
+
+    // Log level
+    col = col
+        .with_child(Label::new("Log level").with_font(theme::UI_FONT_MEDIUM))
+        .with_spacer(theme::grid(2.0))
+        .with_child(
+            RadioGroup::row(vec![
+                ("Errors", LogLevel::Error),
+                ("Warnings", LogLevel::Warn),
+                ("Info", LogLevel::Info),
+                ("Debug", LogLevel::Debug),
+                ("Trace", LogLevel::Trace),
+            ])
+            .lens(AppState::config.then(Config::log_level)),
+        )
+        .with_spacer(theme::grid(1.0))
+        .with_child(
+            Label::dynamic(|_, _| {
+                LogFile::path()
+                    .map(|path| format!("Logs are kept in {}", path.to_string_lossy()))
+                    .unwrap_or_default()
+            })
+            .with_text_size(theme::TEXT_SIZE_SMALL)
+            .with_line_break_mode(LineBreaking::WordWrap),
+        );
+
+    col = col.with_spacer(theme::grid(3.0));
+
+    // Settings file
+    col = col
+        .with_child(Label::new("Settings").with_font(theme::UI_FONT_MEDIUM))
+        .with_spacer(theme::grid(2.0))
+        .with_child(
+            Flex::row()
+                .with_child(
+                    Button::new("Export Settings...")
+                        .on_click(|ctx, _, _| ctx.submit_command(SettingsFile::EXPORT)),
+                )
+                .with_default_spacer()
+                .with_child(
+                    Button::new("Import Settings...")
+                        .on_click(|ctx, _, _| ctx.submit_command(SettingsFile::IMPORT)),
+                ),
+        );
+
+    col.controller(SettingsFile::new())
+}
+
+/// Exports the config into a JSON file picked by the user, and imports it
+/// back.
+struct SettingsFile {
+    pending_export: Option<String>,
+    importing: bool,
+}
+
+impl SettingsFile {
+    const EXPORT: Selector = Selector::new("app.preferences.export-settings");
+    const IMPORT: Selector = Selector::new("app.preferences.import-settings");
+
+    fn new() -> Self {
+        Self {
+            pending_export: None,
+            importing: false,
+        }
+    }
+
+    fn file_type() -> FileSpec {
+        FileSpec::new("Settings", &["json"])
+    }
+
+    fn import(ctx: &mut EventCtx, data: &mut AppState, json: &str) {
+        let config = match data.config.import(json) {
+            Ok(config) => config,
+            Err(err) => {
+                data.action_failed("Importing settings", err);
+                return;
+            }
+        };
+        let reconnect = config.proxy_url != data.config.proxy_url
+            || !config
+                .connect_timeout_seconds
+                .same(&data.config.connect_timeout_seconds)
+            || !config
+                .io_timeout_seconds
+                .same(&data.config.io_timeout_seconds);
+        // Saving, re-theming and reconfiguring the playback follow from the
+        // changed config.
+        data.config = config;
+        if reconnect && data.config.has_credentials() {
+            ctx.submit_command(cmd::SESSION_CONNECT);
+        }
+        data.info_alert("Settings imported.");
+    }
+}
+
+impl<W: Widget<AppState>> Controller<AppState, W> for SettingsFile {
+    fn event(
+        &mut self,
+        child: &mut W,
+        ctx: &mut EventCtx,
+        event: &Event,
+        data: &mut AppState,
+        env: &Env,
+    ) {
+        match event {
+            Event::Command(cmd) if cmd.is(Self::EXPORT) => {
+                match data.config.export() {
+                    Ok(json) => {
+                        let options = FileDialogOptions::new()
+                            .allowed_types(vec![Self::file_type()])
+                            .default_type(Self::file_type())
+                            .default_name("psst-settings.json");
+                        self.pending_export = Some(json);
+                        ctx.submit_command(commands::SHOW_SAVE_PANEL.with(options));
+                    }
+                    Err(err) => data.action_failed("Exporting settings", err),
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(Self::IMPORT) => {
+                let options = FileDialogOptions::new()
+                    .allowed_types(vec![Self::file_type()])
+                    .default_type(Self::file_type());
+                self.importing = true;
+                ctx.submit_command(commands::SHOW_OPEN_PANEL.with(options));
+                ctx.set_handled();
+            }
+            Event::Command(cmd)
+                if cmd.is(commands::SAVE_FILE_AS) && self.pending_export.is_some() =>
+            {
+                let json = self.pending_export.take().unwrap();
+                let file = cmd.get_unchecked(commands::SAVE_FILE_AS);
+                match fs::write(file.path(), json) {
+                    Ok(_) => data.info_alert("Settings exported."),
+                    Err(err) => data.action_failed("Exporting settings", err),
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(commands::OPEN_FILE) && self.importing => {
+                self.importing = false;
+                let file = cmd.get_unchecked(commands::OPEN_FILE);
+                match fs::read_to_string(file.path()) {
+                    Ok(json) => Self::import(ctx, data, &json),
+                    Err(err) => data.action_failed("Importing settings", err),
+                }
+                ctx.set_handled();
+            }
+            Event::Command(cmd) if cmd.is(commands::SAVE_PANEL_CANCELLED) => {
+                self.pending_export = None;
+                child.event(ctx, event, data, env);
+            }
+            Event::Command(cmd) if cmd.is(commands::OPEN_PANEL_CANCELLED) => {
+                self.importing = false;
+                child.event(ctx, event, data, env);
+            }
+            _ => {
+                child.event(ctx, event, data, env);
+            }
+        }
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -283,7 +618,10 @@ impl<W: Widget<AppState>> Controller<AppState, W> for Authenticate {
                 data.preferences.auth.result.defer_default();
 
                 // Authenticate in another thread.
-                let config = data.preferences.auth.session_config();
+                let config = data
+                    .preferences
+                    .auth
+                    .session_config(data.config.proxy(), data.config.net_timeouts());
                 let widget_id = ctx.widget_id();
                 let event_sink = ctx.get_external_handle();
                 let thread = thread::spawn(move || {
@@ -1,20 +1,27 @@
-use std::{sync::Arc, time::Duration};
+use std::{collections::HashSet, sync::Arc, time::Duration};
 
 use druid::{
+    im::Vector,
     widget::{CrossAxisAlignment, Flex, Slider},
-    FontDescriptor, FontFamily, LensExt, Selector, Widget, WidgetExt,
+    EventCtx, FontDescriptor, FontFamily, LensExt, Selector, Widget, WidgetExt,
 };
 
 use crate::{
+    cmd,
     data::{
-        AppState, Ctx, Recommend, Recommendations, RecommendationsKnobs, RecommendationsParams,
-        RecommendationsRequest, Toggled, WithCtx,
+        AppState, Ctx, Nav, PlaylistLink, PlaylistRadio, Recommend, Recommendations,
+        RecommendationsKnobs, RecommendationsParams, RecommendationsRequest, Toggled, Track,
+        TrackId, WithCtx,
     },
+    error::Error,
     webapi::WebApi,
     widget::{Async, Checkbox, MyWidgetExt},
 };
 
-use super::{playable, theme, track, utils};
+use super::{
+    playable::{self, PlayableIter},
+    theme, track, utils,
+};
 
 const KNOBS_DEBOUNCE_DELAY: Duration = Duration::from_millis(500);
 
@@ -22,6 +29,169 @@ pub const UPDATE_PARAMS: Selector<RecommendationsParams> =
     Selector::new("app.recommend.update-params");
 pub const LOAD_RESULTS: Selector<Arc<RecommendationsRequest>> =
     Selector::new("app.recommend.load-results");
+pub const PLAY_SIMILAR: Selector<TrackId> = Selector::new("app.recommend.play-similar");
+const LOAD_SIMILAR: Selector<(TrackId, f64)> = Selector::new("app.recommend.load-similar");
+pub const PLAY_FROM_PLAYLIST: Selector<PlaylistLink> =
+    Selector::new("app.recommend.play-from-playlist");
+pub const SHOW_BLEND: Selector<PlaylistLink> = Selector::new("app.recommend.show-blend");
+pub const PLAY_PLAYLIST_RADIO: Selector<PlaylistLink> =
+    Selector::new("app.recommend.play-playlist-radio");
+/// Queue more tracks of the running playlist radio.
+pub const REFILL_RADIO: Selector = Selector::new("app.recommend.refill-radio");
+const LOAD_RADIO_REFILL: Selector<Arc<RecommendationsRequest>> =
+    Selector::new("app.recommend.load-radio-refill");
+
+/// Handle `PLAY_SIMILAR` by loading the audio features of the track and
+/// playing recommendations close to its mood, and `PLAY_FROM_PLAYLIST` by
+/// playing recommendations seeded from a sample of the playlist tracks.
+/// `SHOW_BLEND` shows recommendations blending the top tracks of the user with
+/// the playlist tracks.  `PLAY_PLAYLIST_RADIO` starts a playlist radio, which
+/// `REFILL_RADIO` keeps going.
+pub fn play_similar_widget<W>(widget: W) -> impl Widget<AppState>
+where
+    W: Widget<AppState> + 'static,
+{
+    widget
+        .on_command(PLAY_SIMILAR, |ctx, id, data| {
+            let tolerance = data.config.similar_tolerance;
+            ctx.submit_command(LOAD_SIMILAR.with((*id, tolerance)));
+        })
+        .on_command_async(
+            LOAD_SIMILAR,
+            |(id, tolerance)| load_similar(id, tolerance),
+            |_, _, _| {},
+            |ctx, data, (_, r)| play_recommendations(ctx, data, r, "Loading similar tracks"),
+        )
+        .on_command_async(
+            PLAY_FROM_PLAYLIST,
+            |link| load_from_playlist(&link),
+            |_, _, _| {},
+            |ctx, data, (_, r)| {
+                play_recommendations(ctx, data, r, "Loading playlist recommendations")
+            },
+        )
+        .on_command_async(
+            PLAY_PLAYLIST_RADIO,
+            |link| load_radio(&link),
+            |_, _, _| {},
+            |ctx, data, (link, r)| match r {
+                Ok((_, recommendations)) if recommendations.tracks.is_empty() => {
+                    data.info_alert("No similar tracks found.");
+                }
+                Ok((tracks, recommendations)) => {
+                    data.playlist_radio = Some(PlaylistRadio::new(
+                        link,
+                        tracks,
+                        recommendations.request.clone(),
+                    ));
+                    ctx.submit_command(cmd::PLAY_TRACKS.with(recommendations.payload(0)));
+                }
+                Err(err) => {
+                    data.action_failed("Starting playlist radio", err);
+                }
+            },
+        )
+        .on_command(REFILL_RADIO, |ctx, _, data| {
+            if let Some(radio) = &mut data.playlist_radio {
+                if !radio.refilling {
+                    radio.refilling = true;
+                    ctx.submit_command(LOAD_RADIO_REFILL.with(radio.next_request()));
+                }
+            }
+        })
+        .on_command_async(
+            LOAD_RADIO_REFILL,
+            |request| WebApi::global().get_recommendations(request),
+            |_, _, _| {},
+            |ctx, data, (_, r)| {
+                let radio = match &mut data.playlist_radio {
+                    Some(radio) => radio,
+                    // Stopped while loading.
+                    None => return,
+                };
+                radio.refilling = false;
+                match r {
+                    Ok(mut recommendations) => {
+                        radio.add_request(recommendations.request.clone());
+                        // Don't repeat what is queued already.
+                        let queued: HashSet<_> = data
+                            .playback
+                            .queue
+                            .iter()
+                            .map(|entry| entry.item.id())
+                            .collect();
+                        recommendations
+                            .tracks
+                            .retain(|track| !queued.contains(&track.id.0));
+                        ctx.submit_command(cmd::QUEUE_TRACKS.with(recommendations.payload(0)));
+                    }
+                    Err(err) => {
+                        data.action_failed("Refilling playlist radio", err);
+                    }
+                }
+            },
+        )
+        .on_command_async(
+            SHOW_BLEND,
+            |link| load_blend(&link),
+            |_, _, _| {},
+            |ctx, data, (_, r)| match r {
+                Ok(request) if request.seed_tracks.is_empty() => {
+                    data.info_alert("Nothing to blend, the tracks are all local.");
+                }
+                Ok(request) => {
+                    ctx.submit_command(cmd::NAVIGATE.with(Nav::Recommendations(request)));
+                }
+                Err(err) => {
+                    data.action_failed("Blending with playlist", err);
+                }
+            },
+        )
+}
+
+fn play_recommendations(
+    ctx: &mut EventCtx,
+    data: &mut AppState,
+    result: Result<Recommendations, Error>,
+    action: &str,
+) {
+    match result {
+        Ok(recommendations) if recommendations.tracks.is_empty() => {
+            data.info_alert("No similar tracks found.");
+        }
+        Ok(recommendations) => {
+            ctx.submit_command(cmd::PLAY_TRACKS.with(recommendations.payload(0)));
+        }
+        Err(err) => {
+            data.action_failed(action, err);
+        }
+    }
+}
+
+fn load_similar(id: TrackId, tolerance: f64) -> Result<Recommendations, Error> {
+    let features = WebApi::global().get_audio_features(&id.0.to_base62())?;
+    let request = RecommendationsRequest::similar_to(id, &features.data, tolerance);
+    WebApi::global().get_recommendations(Arc::new(request))
+}
+
+fn load_from_playlist(link: &PlaylistLink) -> Result<Recommendations, Error> {
+    let tracks = WebApi::global().get_playlist_tracks(&link.id)?;
+    let request = RecommendationsRequest::sampled_from(&tracks);
+    WebApi::global().get_recommendations(Arc::new(request))
+}
+
+fn load_radio(link: &PlaylistLink) -> Result<(Vector<Arc<Track>>, Recommendations), Error> {
+    let tracks = WebApi::global().get_playlist_tracks(&link.id)?;
+    let request = RecommendationsRequest::sampled_from(&tracks);
+    let recommendations = WebApi::global().get_recommendations(Arc::new(request))?;
+    Ok((tracks, recommendations))
+}
+
+fn load_blend(link: &PlaylistLink) -> Result<Arc<RecommendationsRequest>, Error> {
+    let mine = WebApi::global().get_user_top_tracks()?;
+    let theirs = WebApi::global().get_playlist_tracks(&link.id)?;
+    Ok(Arc::new(RecommendationsRequest::blended(&mine, &theirs)))
+}
 
 pub fn results_widget() -> impl Widget<AppState> {
     let track_results = Async::new(
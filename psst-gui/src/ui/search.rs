@@ -2,16 +2,17 @@ use std::sync::Arc;
 
 use druid::{
     im::Vector,
-    widget::{CrossAxisAlignment, Either, Flex, Label, LabelText, List, TextBox},
-    Data, LensExt, Selector, Widget, WidgetExt,
+    lens::Map,
+    widget::{CrossAxisAlignment, Either, Flex, Label, LineBreaking, List, TextBox},
+    Insets, LensExt, LocalizedString, Menu, MenuItem, Selector, Widget, WidgetExt,
 };
 
 use crate::{
     cmd,
     controller::InputController,
     data::{
-        Album, AppState, Artist, Ctx, Nav, Playlist, Search, SearchResults, SearchTopic, Show,
-        SpotifyUrl, WithCtx,
+        Album, AppState, Artist, Ctx, Nav, Playlist, SavedSearch, Search, SearchPage,
+        SearchResults, SearchTopic, Show, SpotifyUrl, WithCtx,
     },
     ui::show,
     webapi::WebApi,
@@ -22,6 +23,17 @@ use super::{album, artist, playable, playlist, theme, track, utils};
 
 pub const LOAD_RESULTS: Selector<Arc<str>> = Selector::new("app.search.load-results");
 pub const OPEN_LINK: Selector<SpotifyUrl> = Selector::new("app.search.open-link");
+const LOAD_FIRST_PAGE: Selector<(Arc<str>, usize)> = Selector::new("app.search.load-first-page");
+const SHOW_MORE: Selector<SearchTopic> = Selector::new("app.search.show-more");
+const LOAD_MORE: Selector<SearchPage> = Selector::new("app.search.load-more");
+const SAVE_SEARCH: Selector<Arc<str>> = Selector::new("app.search.save");
+const OPEN_SAVED_SEARCH: Selector<Arc<str>> = Selector::new("app.search.open-saved");
+const RENAME_SAVED_SEARCH: Selector<SavedSearch> = Selector::new("app.search.rename-saved");
+const FINISH_RENAME: Selector = Selector::new("app.search.finish-rename");
+const REMOVE_SAVED_SEARCH: Selector<Arc<str>> = Selector::new("app.search.remove-saved");
+
+/// Saved search in the sidebar, together with the one being renamed.
+type SavedSearchRow = (Option<SavedSearch>, SavedSearch);
 
 pub fn input_widget() -> impl Widget<AppState> {
     TextBox::new()
@@ -34,10 +46,152 @@ pub fn input_widget() -> impl Widget<AppState> {
         }))
         .with_id(cmd::WIDGET_SEARCH_INPUT)
         .expand_width()
-        .lens(AppState::search.then(Search::input))
+        .lens(Map::new(
+            |data: &AppState| data.search.input.clone(),
+            |data: &mut AppState, input| data.set_search_input(input),
+        ))
+        .on_command(cmd::FOCUS_SEARCH, |ctx, _, data| {
+            let query = data.search.input.trim();
+            let showing_query = matches!(&data.nav, Nav::SearchResults(shown) if &**shown == query);
+            if !query.is_empty() && !showing_query {
+                ctx.submit_command(cmd::NAVIGATE.with(Nav::SearchResults(query.into())));
+            }
+            // The text box selects all of its text when focused by other means
+            // than a click, so typing replaces the previous query.
+            ctx.submit_command(cmd::SET_FOCUS.to(cmd::WIDGET_SEARCH_INPUT));
+        })
+}
+
+pub fn saved_list_widget() -> impl Widget<AppState> {
+    List::new(saved_search_widget)
+        .lens(Map::new(
+            |data: &AppState| {
+                (
+                    data.search.renaming.clone(),
+                    data.config.saved_searches.clone(),
+                )
+            },
+            |data: &mut AppState, (renaming, _)| {
+                data.search.renaming = renaming;
+            },
+        ))
+        .on_command(SAVE_SEARCH, |_, query, data| {
+            if data.add_saved_search(query.clone()) {
+                data.info_alert("Search saved.");
+            } else {
+                data.info_alert("Search is saved already.");
+            }
+        })
+        .on_command(OPEN_SAVED_SEARCH, |ctx, query, data| {
+            // Always load fresh results.
+            data.set_search_input(query.to_string());
+            data.search.results.clear();
+            ctx.submit_command(cmd::NAVIGATE.with(Nav::SearchResults(query.clone())));
+        })
+        .on_command(RENAME_SAVED_SEARCH, |_, saved, data| {
+            data.search.renaming = Some(saved.clone());
+        })
+        .on_command(FINISH_RENAME, |_, _, data| {
+            if let Some(saved) = data.search.renaming.take() {
+                data.rename_saved_search(&saved.query, saved.name);
+            }
+        })
+        .on_command(REMOVE_SAVED_SEARCH, |_, query, data| {
+            data.remove_saved_search(query);
+        })
+}
+
+fn saved_search_widget() -> impl Widget<SavedSearchRow> {
+    let label = Label::dynamic(|(_, saved): &SavedSearchRow, _| saved.name.to_string())
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .with_text_size(theme::TEXT_SIZE_SMALL)
+        .expand_width()
+        .padding(Insets::uniform_xy(theme::grid(2.0), theme::grid(0.6)))
+        .link()
+        .on_click(|ctx, (_, saved): &mut SavedSearchRow, _| {
+            ctx.submit_command(OPEN_SAVED_SEARCH.with(saved.query.clone()));
+        })
+        .context_menu(|(_, saved): &SavedSearchRow| saved_search_menu(saved));
+
+    let input = TextBox::new()
+        .controller(InputController::new().on_submit(|ctx, _, _| {
+            ctx.submit_command(FINISH_RENAME);
+        }))
+        .expand_width()
+        .padding(Insets::uniform_xy(theme::grid(1.0), theme::grid(0.3)))
+        .lens(Map::new(
+            |(renaming, _): &SavedSearchRow| {
+                renaming
+                    .as_ref()
+                    .map(|renaming| renaming.name.to_string())
+                    .unwrap_or_default()
+            },
+            |(renaming, _): &mut SavedSearchRow, name: String| {
+                if let Some(renaming) = renaming {
+                    renaming.name = name.into();
+                }
+            },
+        ));
+
+    Either::new(
+        |(renaming, saved): &SavedSearchRow, _| matches!(renaming, Some(renaming) if renaming.query == saved.query),
+        input,
+        label,
+    )
+}
+
+fn saved_search_menu(saved: &SavedSearch) -> Menu<AppState> {
+    let mut menu = Menu::empty();
+
+    menu = menu.entry(
+        MenuItem::new(LocalizedString::new("menu-item-rename-search").with_placeholder("Rename"))
+            .command(RENAME_SAVED_SEARCH.with(saved.clone())),
+    );
+
+    menu = menu.entry(
+        MenuItem::new(
+            LocalizedString::new("menu-item-remove-search").with_placeholder("Remove Saved Search"),
+        )
+        .command(REMOVE_SAVED_SEARCH.with(saved.query.clone())),
+    );
+
+    menu
 }
 
 pub fn results_widget() -> impl Widget<AppState> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Fill)
+        .with_child(library_results_widget())
+        .with_child(network_results_widget())
+}
+
+/// Library items matching the query, shown while the search results load.
+fn library_results_widget() -> impl Widget<AppState> {
+    Either::new(
+        |results: &WithCtx<SearchResults>, _| results.data.is_empty(),
+        Empty,
+        Flex::column()
+            .cross_axis_alignment(CrossAxisAlignment::Fill)
+            .with_child(
+                Label::new("In Your Library")
+                    .with_font(theme::UI_FONT_MEDIUM)
+                    .with_text_size(theme::TEXT_SIZE_LARGE)
+                    .padding((0.0, theme::grid(1.0))),
+            )
+            .with_child(artist_results_widget())
+            .with_child(album_results_widget())
+            .with_child(track_results_widget())
+            .with_child(playlist_results_widget())
+            .with_child(show_results_widget())
+            .padding((0.0, 0.0, 0.0, theme::grid(2.0))),
+    )
+    .lens(Ctx::make(
+        AppState::common_ctx,
+        AppState::search.then(Search::library_matches),
+    ))
+}
+
+fn network_results_widget() -> impl Widget<AppState> {
     Async::new(
         utils::spinner_widget,
         loaded_results_widget,
@@ -47,20 +201,65 @@ pub fn results_widget() -> impl Widget<AppState> {
         Ctx::make(AppState::common_ctx, AppState::search.then(Search::results))
             .then(Ctx::in_promise()),
     )
+    .on_command(LOAD_RESULTS, |ctx, query, data| {
+        ctx.submit_command(LOAD_FIRST_PAGE.with((query.clone(), data.config.search_limit)));
+    })
     .on_command_async(
-        LOAD_RESULTS,
-        |q| WebApi::global().search(&q, SearchTopic::all()),
-        |_, data, q| data.search.results.defer(q),
-        |_, data, r| data.search.results.update(r),
+        LOAD_FIRST_PAGE,
+        |(q, limit)| WebApi::global().search(&q, SearchTopic::all(), limit, 0),
+        |_, data, (q, _)| data.search.results.defer(q),
+        |_, data, ((q, _), r)| {
+            // Items found in the library are listed above already.
+            let r = r.map(|mut results| {
+                results.remove_known(&data.search.library_matches);
+                results
+            });
+            data.search.results.update((q, r))
+        },
+    )
+    .on_command(SHOW_MORE, |ctx, topic, data| {
+        if let Some(results) = data.search.results.resolved() {
+            ctx.submit_command(LOAD_MORE.with(SearchPage {
+                query: results.query.clone(),
+                topic: *topic,
+                offset: results.count(*topic),
+                limit: data.config.search_limit,
+            }));
+        }
+    })
+    .on_command_async(
+        LOAD_MORE,
+        |p| WebApi::global().search(&p.query, &[p.topic], p.limit, p.offset),
+        |_, _, _| {},
+        |_, data, (p, r)| match r {
+            Ok(mut more) => {
+                if let Some(results) = data.search.results.resolved_mut() {
+                    // Ignore pages of a query we're not showing anymore.
+                    if results.query == p.query {
+                        // Skipping the library items shifts the offsets, so
+                        // pages can overlap.
+                        more.remove_known(&data.search.library_matches);
+                        more.remove_known(results);
+                        results.append(p.topic, more);
+                    }
+                }
+            }
+            Err(err) => {
+                data.action_failed("Loading more search results", err);
+            }
+        },
     )
     .on_command_async(
         OPEN_LINK,
         |l| WebApi::global().load_spotify_link(&l),
         |_, data, l| data.search.results.defer(l.id()),
         |ctx, data, (l, r)| match r {
-            Ok(nav) => {
+            Ok((nav, payload)) => {
                 data.search.results.clear();
                 ctx.submit_command(cmd::NAVIGATE.with(nav));
+                if let Some(payload) = payload {
+                    ctx.submit_command(cmd::PLAY_TRACKS.with(payload));
+                }
             }
             Err(err) => {
                 data.search.results.reject(l.id(), err);
@@ -71,13 +270,7 @@ pub fn results_widget() -> impl Widget<AppState> {
 
 fn loaded_results_widget() -> impl Widget<WithCtx<SearchResults>> {
     Either::new(
-        |results: &WithCtx<SearchResults>, _| {
-            results.data.artists.is_empty()
-                && results.data.albums.is_empty()
-                && results.data.tracks.is_empty()
-                && results.data.playlists.is_empty()
-                && results.data.shows.is_empty()
-        },
+        |results: &WithCtx<SearchResults>, _| results.data.is_empty(),
         Label::new("No results")
             .with_text_size(theme::TEXT_SIZE_LARGE)
             .with_text_color(theme::PLACEHOLDER_COLOR)
@@ -85,11 +278,17 @@ fn loaded_results_widget() -> impl Widget<WithCtx<SearchResults>> {
             .center(),
         Flex::column()
             .cross_axis_alignment(CrossAxisAlignment::Fill)
+            .with_child(save_search_widget())
             .with_child(artist_results_widget())
+            .with_child(show_more_widget(SearchTopic::Artist))
             .with_child(album_results_widget())
+            .with_child(show_more_widget(SearchTopic::Album))
             .with_child(track_results_widget())
+            .with_child(show_more_widget(SearchTopic::Track))
             .with_child(playlist_results_widget())
-            .with_child(show_results_widget()),
+            .with_child(show_more_widget(SearchTopic::Playlist))
+            .with_child(show_results_widget())
+            .with_child(show_more_widget(SearchTopic::Show)),
     )
 }
 
@@ -98,7 +297,7 @@ fn artist_results_widget() -> impl Widget<WithCtx<SearchResults>> {
         |artists: &Vector<Artist>, _| artists.is_empty(),
         Empty,
         Flex::column()
-            .with_child(header_widget("Artists"))
+            .with_child(utils::header_widget("Artists"))
             .with_child(List::new(artist::artist_widget)),
     )
     .lens(Ctx::data().then(SearchResults::artists))
@@ -109,7 +308,7 @@ fn album_results_widget() -> impl Widget<WithCtx<SearchResults>> {
         |albums: &WithCtx<Vector<Arc<Album>>>, _| albums.data.is_empty(),
         Empty,
         Flex::column()
-            .with_child(header_widget("Albums"))
+            .with_child(utils::header_widget("Albums"))
             .with_child(List::new(album::album_widget)),
     )
     .lens(Ctx::map(SearchResults::albums))
@@ -120,16 +319,19 @@ fn track_results_widget() -> impl Widget<WithCtx<SearchResults>> {
         |results: &WithCtx<SearchResults>, _| results.data.tracks.is_empty(),
         Empty,
         Flex::column()
-            .with_child(header_widget("Tracks"))
-            .with_child(playable::list_widget(playable::Display {
-                track: track::Display {
-                    title: true,
-                    artist: true,
-                    album: true,
-                    cover: true,
-                    ..track::Display::empty()
+            .with_child(utils::header_widget("Tracks"))
+            .with_child(playable::list_widget_with_find(
+                playable::Display {
+                    track: track::Display {
+                        title: true,
+                        artist: true,
+                        album: true,
+                        cover: true,
+                        ..track::Display::empty()
+                    },
                 },
-            })),
+                cmd::FIND_IN_TRACKS,
+            )),
     )
 }
 
@@ -138,7 +340,7 @@ fn playlist_results_widget() -> impl Widget<WithCtx<SearchResults>> {
         |playlists: &Vector<Playlist>, _| playlists.is_empty(),
         Empty,
         Flex::column()
-            .with_child(header_widget("Playlists"))
+            .with_child(utils::header_widget("Playlists"))
             .with_child(List::new(playlist::playlist_widget)),
     )
     .lens(Ctx::data().then(SearchResults::playlists))
@@ -149,16 +351,33 @@ fn show_results_widget() -> impl Widget<WithCtx<SearchResults>> {
         |shows: &WithCtx<Vector<Arc<Show>>>, _| shows.data.is_empty(),
         Empty,
         Flex::column()
-            .with_child(header_widget("Podcasts"))
+            .with_child(utils::header_widget("Podcasts"))
             .with_child(List::new(show::show_widget)),
     )
     .lens(Ctx::map(SearchResults::shows))
 }
 
-fn header_widget<T: Data>(text: impl Into<LabelText<T>>) -> impl Widget<T> {
-    Label::new(text)
-        .with_font(theme::UI_FONT_MEDIUM)
-        .with_text_color(theme::PLACEHOLDER_COLOR)
+fn save_search_widget() -> impl Widget<WithCtx<SearchResults>> {
+    Label::new("Save Search")
         .with_text_size(theme::TEXT_SIZE_SMALL)
-        .padding((0.0, theme::grid(2.0), 0.0, theme::grid(1.0)))
+        .padding(theme::grid(1.0))
+        .link()
+        .rounded(theme::BUTTON_BORDER_RADIUS)
+        .on_click(|ctx, results: &mut WithCtx<SearchResults>, _| {
+            ctx.submit_command(SAVE_SEARCH.with(results.data.query.clone()));
+        })
+        .align_left()
+}
+
+fn show_more_widget(topic: SearchTopic) -> impl Widget<WithCtx<SearchResults>> {
+    Either::new(
+        move |results: &WithCtx<SearchResults>, _| results.data.topics_with_more.contains(&topic),
+        Label::new("Show More")
+            .with_text_size(theme::TEXT_SIZE_SMALL)
+            .padding(theme::grid(1.0))
+            .link()
+            .rounded(theme::BUTTON_BORDER_RADIUS)
+            .on_click(move |ctx, _, _| ctx.submit_command(SHOW_MORE.with(topic))),
+        Empty,
+    )
 }
@@ -19,31 +19,50 @@ pub const LOAD_DETAIL: Selector<ShowLink> = Selector::new("app.show.load-detail"
 pub fn detail_widget() -> impl Widget<AppState> {
     Flex::column()
         .cross_axis_alignment(CrossAxisAlignment::Start)
-        // .with_child(async_info_widget())
-        // .with_default_spacer()
+        .with_child(async_info_widget())
+        .with_default_spacer()
         .with_child(async_episodes_widget())
 }
 
-// fn async_info_widget() -> impl Widget<AppState> {
-//     Async::new(utils::spinner_widget, info_widget, utils::error_widget)
-//         .lens(
-//             Ctx::make(
-//                 AppState::common_ctx,
-//                 AppState::show_detail.then(ShowDetail::show),
-//             )
-//             .then(Ctx::in_promise()),
-//         )
-//         .on_command_async(
-//             LOAD_DETAIL,
-//             |d| WebApi::global().get_show(&d.id),
-//             |_, data, d| data.show_detail.show.defer(d),
-//             |_, data, (d, r)| data.show_detail.show.update((d, r)),
-//         )
-// }
-
-// fn info_widget() -> impl Widget<WithCtx<Arc<Show>>> {
-//     Label::raw().lens(Ctx::data().then(Show::description.in_arc()))
-// }
+fn async_info_widget() -> impl Widget<AppState> {
+    Async::new(
+        utils::spinner_widget,
+        info_widget,
+        utils::retry_error_widget,
+    )
+    .lens(AppState::show_detail.then(ShowDetail::show))
+    .on_command_async(
+        LOAD_DETAIL,
+        |d| WebApi::global().get_show(&d.id),
+        |_, data, d| data.show_detail.show.defer(d),
+        |_, data, (d, r)| data.show_detail.show.update((d, r)),
+    )
+    .on_notification(cmd::RETRY, |ctx, _, data| {
+        if let Some(link) = data.show_detail.show.deferred() {
+            ctx.submit_command(LOAD_DETAIL.with(link.to_owned()));
+        }
+    })
+}
+
+fn info_widget() -> impl Widget<Arc<Show>> {
+    let description = Label::raw()
+        .with_line_break_mode(LineBreaking::WordWrap)
+        .lens(Show::description.in_arc());
+
+    let episode_count = Label::dynamic(|show: &Arc<Show>, _| match show.total_episodes {
+        1 => "1 episode".to_string(),
+        n => format!("{} episodes", n),
+    })
+    .with_text_size(theme::TEXT_SIZE_SMALL)
+    .with_text_color(theme::PLACEHOLDER_COLOR);
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(description)
+        .with_default_spacer()
+        .with_child(episode_count)
+        .padding(theme::grid(1.0))
+}
 
 fn async_episodes_widget() -> impl Widget<AppState> {
     Async::new(
@@ -53,7 +72,7 @@ fn async_episodes_widget() -> impl Widget<AppState> {
                 track: track::Display::empty(),
             })
         },
-        utils::error_widget,
+        utils::retry_error_widget,
     )
     .lens(
         Ctx::make(
@@ -74,6 +93,11 @@ fn async_episodes_widget() -> impl Widget<AppState> {
             data.show_detail.episodes.update((d, r))
         },
     )
+    .on_notification(cmd::RETRY, |ctx, _, data| {
+        if let Some(link) = data.show_detail.episodes.deferred() {
+            ctx.submit_command(LOAD_DETAIL.with(link.to_owned()));
+        }
+    })
 }
 
 pub fn show_widget() -> impl Widget<WithCtx<Arc<Show>>> {
@@ -135,6 +159,11 @@ fn show_menu(show: &Arc<Show>, library: &Arc<Library>) -> Menu<AppState> {
         .command(cmd::COPY.with(show.link().url())),
     );
 
+    // Editing the library needs the network.
+    if WebApi::global().is_offline() {
+        return menu;
+    }
+
     menu = menu.separator();
 
     if library.contains_show(show) {
@@ -1,8 +1,11 @@
-use druid::{Color, Env, FontDescriptor, FontFamily, FontWeight, Insets, Key, Size};
+use druid::{
+    piet::ImageFormat, Color, Env, FontDescriptor, FontFamily, FontWeight, ImageBuf, Insets, Key,
+    Size,
+};
 
 pub use druid::theme::*;
 
-use crate::data::{AppState, Theme};
+use crate::data::{AppState, ListDensity, Theme};
 
 pub fn grid(m: f64) -> f64 {
     GRID * m
@@ -41,6 +44,9 @@ pub const LINK_HOT_COLOR: Key<Color> = Key::new("app.link-hot-color");
 pub const LINK_ACTIVE_COLOR: Key<Color> = Key::new("app.link-active-color");
 pub const LINK_COLD_COLOR: Key<Color> = Key::new("app.link-cold-color");
 
+pub const TRACK_ROW_INSETS: Key<Insets> = Key::new("app.track-row-insets");
+pub const TRACK_ROW_SHOW_ALBUM: Key<bool> = Key::new("app.track-row-show-album");
+
 pub fn setup(env: &mut Env, state: &AppState) {
     match state.config.theme {
         Theme::Light => setup_light_theme(env),
@@ -117,6 +123,17 @@ pub fn setup(env: &mut Env, state: &AppState) {
     env.set(SCROLLBAR_RADIUS, 5.0);
     env.set(SCROLLBAR_EDGE_WIDTH, 1.0);
 
+    match state.config.list_density {
+        ListDensity::Compact => {
+            env.set(TRACK_ROW_INSETS, Insets::uniform_xy(grid(1.0), grid(0.5)));
+            env.set(TRACK_ROW_SHOW_ALBUM, false);
+        }
+        ListDensity::Comfortable => {
+            env.set(TRACK_ROW_INSETS, Insets::uniform(grid(1.0)));
+            env.set(TRACK_ROW_SHOW_ALBUM, true);
+        }
+    }
+
     env.set(WIDGET_PADDING_VERTICAL, grid(0.5));
     env.set(WIDGET_PADDING_HORIZONTAL, grid(1.0));
     env.set(WIDGET_CONTROL_COMPONENT_PADDING, grid(1.0));
@@ -164,3 +181,52 @@ fn setup_dark_theme(env: &mut Env) {
     env.set(LINK_ACTIVE_COLOR, Color::rgba(1.0, 1.0, 1.0, 0.025));
     env.set(LINK_COLD_COLOR, Color::rgba(1.0, 1.0, 1.0, 0.0));
 }
+
+/// Average color of the image, ignoring transparent pixels.
+pub fn dominant_color(image: &ImageBuf) -> Option<Color> {
+    let stride = match image.format() {
+        ImageFormat::Grayscale => 1,
+        ImageFormat::Rgb => 3,
+        _ => 4,
+    };
+    let mut sum = [0u64; 3];
+    let mut count = 0u64;
+    for pixel in image.raw_pixels().chunks_exact(stride) {
+        match pixel {
+            [l] => sum.iter_mut().for_each(|s| *s += *l as u64),
+            [_, _, _, 0] => continue,
+            [r, g, b, ..] => {
+                sum[0] += *r as u64;
+                sum[1] += *g as u64;
+                sum[2] += *b as u64;
+            }
+            _ => continue,
+        }
+        count += 1;
+    }
+    if count == 0 {
+        return None;
+    }
+    let [r, g, b] = sum.map(|s| (s / count) as u8);
+    Some(Color::rgb8(r, g, b))
+}
+
+/// Subtly tint `background` with `color`.  The color is first shifted to the
+/// luminance of the background, so text drawn over the background stays as
+/// readable as before.
+pub fn tint(background: &Color, color: &Color) -> Color {
+    const STRENGTH: f64 = 0.2;
+
+    fn luminance((r, g, b): (f64, f64, f64)) -> f64 {
+        0.2126 * r + 0.7152 * g + 0.0722 * b
+    }
+
+    let (br, bg, bb, ba) = background.as_rgba();
+    let (cr, cg, cb, _) = color.as_rgba();
+    let shift = luminance((br, bg, bb)) - luminance((cr, cg, cb));
+    let mix = |b: f64, c: f64| {
+        let c = (c + shift).clamp(0.0, 1.0);
+        b * (1.0 - STRENGTH) + c * STRENGTH
+    };
+    Color::rgba(mix(br, cr), mix(bg, cg), mix(bb, cb), ba)
+}
@@ -1,27 +1,32 @@
 use std::sync::Arc;
 
 use druid::{
-    widget::{CrossAxisAlignment, Either, Flex, Label, List},
-    LensExt, LocalizedString, Menu, MenuItem, Size, TextAlignment, Widget, WidgetExt,
+    widget::{CrossAxisAlignment, Either, Flex, Label},
+    Data, LensExt, LocalizedString, Menu, MenuItem, Selector, Size, TextAlignment, Widget,
+    WidgetExt,
 };
 
 use crate::{
     cmd,
     data::{
-        AppState, ArtistLink, Library, Nav, PlaybackOrigin, PlaylistAddTrack, PlaylistRemoveTrack,
-        RecommendationsRequest, Track,
+        AppState, AudioFeatures, Cached, Library, Nav, PlaybackOrigin, PlaylistAddTrack,
+        PlaylistRemoveTrack, RecommendationsRequest, Track,
     },
     ui::playlist,
-    widget::{Empty, MyWidgetExt, RemoteImage},
+    webapi::WebApi,
+    widget::{Async, Empty, Maybe, MyWidgetExt, RemoteImage},
 };
 
 use super::{
-    library,
+    artist, library,
     playable::{self, PlayRow},
-    theme,
+    recommend, theme,
     utils::{self, placeholder_widget},
 };
 
+pub const SHOW_TRACK_INFO: Selector<Arc<Track>> = Selector::new("app.track.show-info");
+pub const HIDE_TRACK_INFO: Selector = Selector::new("app.track.hide-info");
+
 #[derive(Copy, Clone)]
 pub struct Display {
     pub number: bool,
@@ -30,6 +35,8 @@ pub struct Display {
     pub album: bool,
     pub cover: bool,
     pub popularity: bool,
+    /// Local play counts, if enabled in the preferences.
+    pub play_count: bool,
 }
 
 impl Display {
@@ -41,6 +48,7 @@ impl Display {
             album: false,
             cover: false,
             popularity: false,
+            play_count: false,
         }
     }
 }
@@ -82,29 +90,48 @@ pub fn playable_widget(display: Display) -> impl Widget<PlayRow<Arc<Track>>> {
             .with_font(theme::UI_FONT_MEDIUM)
             .lens(PlayRow::item.then(Track::name.in_arc()));
         major.add_child(track_name);
+
+        let explicit = Either::new(
+            |track: &Arc<Track>, _| track.explicit,
+            explicit_badge_widget().padding_left(theme::grid(0.5)),
+            Empty,
+        )
+        .lens(PlayRow::item);
+        major.add_child(explicit);
     }
 
     if display.artist {
-        let track_artists = List::new(|| {
-            Label::raw()
-                .with_text_size(theme::TEXT_SIZE_SMALL)
-                .lens(ArtistLink::name)
-        })
-        .horizontal()
-        .with_spacing(theme::grid(0.5))
-        .lens(PlayRow::item.then(Track::artists.in_arc()));
+        let track_artists =
+            artist::small_links_widget().lens(PlayRow::item.then(Track::artists.in_arc()));
         minor.add_child(track_artists);
     }
 
+    let unavailable = Either::new(
+        |track: &Arc<Track>, _| track.is_unavailable(),
+        Label::new("Unavailable in your region")
+            .with_text_size(theme::TEXT_SIZE_SMALL)
+            .with_text_color(theme::PLACEHOLDER_COLOR)
+            .padding_right(theme::grid(1.0)), // Instead of `add_default_spacer`.
+        Empty,
+    )
+    .lens(PlayRow::item);
+    minor.add_child(unavailable);
+
     if display.album {
         let track_album = Label::raw()
             .with_text_size(theme::TEXT_SIZE_SMALL)
             .with_text_color(theme::PLACEHOLDER_COLOR)
             .lens(PlayRow::item.then(Track::lens_album_name().in_arc()));
-        if display.artist {
-            minor.add_default_spacer();
-        }
-        minor.add_child(track_album);
+        let track_album = if display.artist {
+            track_album.padding_left(theme::grid(1.0)).boxed() // Instead of `add_default_spacer`.
+        } else {
+            track_album.boxed()
+        };
+        minor.add_child(Either::new(
+            |_, env| env.get(theme::TRACK_ROW_SHOW_ALBUM),
+            track_album,
+            Empty,
+        ));
     }
 
     let is_playing = playable::is_playing_marker_widget().lens(PlayRow::is_playing);
@@ -122,6 +149,24 @@ pub fn playable_widget(display: Display) -> impl Widget<PlayRow<Arc<Track>>> {
         major.add_child(track_popularity);
     }
 
+    if display.play_count {
+        let track_play_count = Label::dynamic(|row: &PlayRow<Arc<Track>>, _| {
+            match row.ctx.play_counts.get(&row.item.id) {
+                0 => String::new(),
+                1 => "1 play".to_string(),
+                n => format!("{} plays", n),
+            }
+        })
+        .with_text_size(theme::TEXT_SIZE_SMALL)
+        .with_text_color(theme::PLACEHOLDER_COLOR)
+        .padding_left(theme::grid(1.0)); // Instead of `add_default_spacer`.
+        major.add_child(Either::new(
+            |row, _| row.ctx.show_play_counts,
+            track_play_count,
+            Empty,
+        ));
+    }
+
     let track_duration =
         Label::<Arc<Track>>::dynamic(|track, _| utils::as_minutes_and_seconds(track.duration))
             .with_text_size(theme::TEXT_SIZE_SMALL)
@@ -139,14 +184,173 @@ pub fn playable_widget(display: Display) -> impl Widget<PlayRow<Arc<Track>>> {
                 .with_child(minor),
             1.0,
         )
-        .padding(theme::grid(1.0))
+        .padding(theme::TRACK_ROW_INSETS)
+        .env_scope(|env, row: &PlayRow<Arc<Track>>| {
+            if row.ctx.is_track_blocked(&row.item) || row.item.is_unavailable() {
+                env.set(theme::TEXT_COLOR, env.get(theme::PLACEHOLDER_COLOR));
+            }
+        })
         .link()
         .active(|row, _| row.is_playing)
         .rounded(theme::BUTTON_BORDER_RADIUS)
-        .on_click(|ctx, row, _| ctx.submit_notification(cmd::PLAY.with(row.position)))
+        .on_left_click(|ctx, _, row, _| {
+            if !row.item.is_unavailable() {
+                ctx.submit_notification(cmd::PLAY.with(row.position));
+            }
+        })
         .context_menu(track_row_menu)
 }
 
+fn explicit_badge_widget<T: Data>() -> impl Widget<T> {
+    Label::new("E")
+        .with_font(theme::UI_FONT_MEDIUM)
+        .with_text_size(theme::TEXT_SIZE_SMALL)
+        .with_text_color(theme::PLACEHOLDER_COLOR)
+        .padding((theme::grid(0.5), 0.0))
+        .border(theme::PLACEHOLDER_COLOR, 1.0)
+        .rounded(2.0)
+}
+
+pub fn info_widget() -> impl Widget<AppState> {
+    let header = Flex::row()
+        .with_flex_child(
+            Label::new("Track Info")
+                .with_font(theme::UI_FONT_MEDIUM)
+                .expand_width(),
+            1.0,
+        )
+        .with_child(
+            Label::new("Close")
+                .with_text_size(theme::TEXT_SIZE_SMALL)
+                .link()
+                .on_click(|ctx, _, _| ctx.submit_command(HIDE_TRACK_INFO)),
+        );
+
+    let features = Async::new(
+        utils::spinner_widget,
+        loaded_audio_features_widget,
+        utils::error_widget,
+    )
+    .lens(AppState::audio_features);
+
+    let info = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(header)
+        .with_default_spacer()
+        .with_child(Maybe::or_empty(track_details_widget).lens(AppState::track_info))
+        .with_default_spacer()
+        .with_child(features)
+        .padding(theme::grid(2.0))
+        .background(theme::BACKGROUND_DARK)
+        .rounded(theme::BUTTON_BORDER_RADIUS);
+
+    Either::new(|data: &AppState, _| data.track_info.is_none(), Empty, info)
+        .on_command_async(
+            SHOW_TRACK_INFO,
+            // The audio features are only loaded once the info is shown.
+            |track| WebApi::global().get_audio_features(&track.id.0.to_base62()),
+            |_, data, track| {
+                data.audio_features.defer(track.id);
+                data.track_info = Some(track);
+            },
+            |_, data, (track, r)| data.audio_features.update((track.id, r)),
+        )
+        .on_command(HIDE_TRACK_INFO, |_, _, data| {
+            data.track_info = None;
+            data.audio_features.clear();
+        })
+}
+
+fn info_row_widget<T: Data>(name: &str, value: impl Fn(&T) -> String + 'static) -> impl Widget<T> {
+    Flex::row()
+        .with_child(
+            Label::new(name)
+                .with_text_size(theme::TEXT_SIZE_SMALL)
+                .with_text_color(theme::PLACEHOLDER_COLOR)
+                .fix_width(theme::grid(14.0)),
+        )
+        .with_child(
+            Label::dynamic(move |data, _| value(data)).with_text_size(theme::TEXT_SIZE_SMALL),
+        )
+}
+
+fn track_details_widget() -> impl Widget<Arc<Track>> {
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Label::raw()
+                .with_text_size(theme::TEXT_SIZE_SMALL)
+                .with_font(theme::UI_FONT_MEDIUM)
+                .lens(Track::name.in_arc()),
+        )
+        .with_spacer(theme::grid(0.5))
+        .with_child(info_row_widget("Popularity", |track: &Arc<Track>| {
+            track
+                .popularity
+                .map_or_else(|| "Unknown".to_string(), |p| format!("{}/100", p))
+        }))
+        .with_child(info_row_widget("ISRC", |track: &Arc<Track>| {
+            track.isrc.as_deref().unwrap_or("Unknown").to_string()
+        }))
+        .with_child(info_row_widget("Disc", |track: &Arc<Track>| {
+            track.disc_number.to_string()
+        }))
+        .with_child(info_row_widget("Track", |track: &Arc<Track>| {
+            track.track_number.to_string()
+        }))
+}
+
+fn loaded_audio_features_widget() -> impl Widget<Cached<AudioFeatures>> {
+    let features = Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(info_row_widget("Key", AudioFeatures::key_name))
+        .with_child(info_row_widget("Tempo", |f: &AudioFeatures| {
+            format!("{:.0} BPM", f.tempo)
+        }))
+        .with_child(info_row_widget("Time Signature", |f: &AudioFeatures| {
+            format!("{}/4", f.time_signature)
+        }))
+        .with_child(info_row_widget("Loudness", |f: &AudioFeatures| {
+            format!("{:.1} dB", f.loudness)
+        }))
+        .with_child(info_row_widget("Energy", |f: &AudioFeatures| {
+            percent(f.energy)
+        }))
+        .with_child(info_row_widget("Danceability", |f: &AudioFeatures| {
+            percent(f.danceability)
+        }))
+        .with_child(info_row_widget("Valence", |f: &AudioFeatures| {
+            percent(f.valence)
+        }))
+        .with_child(info_row_widget("Acousticness", |f: &AudioFeatures| {
+            percent(f.acousticness)
+        }))
+        .with_child(info_row_widget("Instrumentalness", |f: &AudioFeatures| {
+            percent(f.instrumentalness)
+        }))
+        .with_child(info_row_widget("Liveness", |f: &AudioFeatures| {
+            percent(f.liveness)
+        }))
+        .with_child(info_row_widget("Speechiness", |f: &AudioFeatures| {
+            percent(f.speechiness)
+        }))
+        .lens(Cached::data);
+
+    Flex::column()
+        .cross_axis_alignment(CrossAxisAlignment::Start)
+        .with_child(
+            Label::new("Audio Features")
+                .with_text_size(theme::TEXT_SIZE_SMALL)
+                .with_font(theme::UI_FONT_MEDIUM),
+        )
+        .with_spacer(theme::grid(0.5))
+        .with_child(features)
+}
+
+fn percent(value: f64) -> String {
+    format!("{:.0}%", value * 100.0)
+}
+
 fn cover_widget(size: f64) -> impl Widget<Arc<Track>> {
     RemoteImage::new(placeholder_widget(), move |track: &Arc<Track>, _| {
         track
@@ -179,13 +383,19 @@ fn popularity_stars(popularity: u32) -> String {
 }
 
 fn track_row_menu(row: &PlayRow<Arc<Track>>) -> Menu<AppState> {
-    track_menu(&row.item, &row.ctx.library, &row.origin)
+    track_menu(
+        &row.item,
+        &row.ctx.library,
+        &row.origin,
+        row.ctx.is_track_blocked(&row.item),
+    )
 }
 
 pub fn track_menu(
     track: &Arc<Track>,
     library: &Library,
     origin: &PlaybackOrigin,
+    is_blocked: bool,
 ) -> Menu<AppState> {
     let mut menu = Menu::empty();
 
@@ -222,6 +432,20 @@ pub fn track_menu(
         )))),
     );
 
+    menu = menu.entry(
+        MenuItem::new(
+            LocalizedString::new("menu-item-play-similar").with_placeholder("Play Similar Mood"),
+        )
+        .command(recommend::PLAY_SIMILAR.with(track.id)),
+    );
+
+    menu = menu.entry(
+        MenuItem::new(
+            LocalizedString::new("menu-item-show-track-info").with_placeholder("Track Info"),
+        )
+        .command(SHOW_TRACK_INFO.with(track.clone())),
+    );
+
     menu = menu.entry(
         MenuItem::new(
             LocalizedString::new("menu-item-copy-link").with_placeholder("Copy Link to Track"),
@@ -229,6 +453,23 @@ pub fn track_menu(
         .command(cmd::COPY.with(track.url())),
     );
 
+    menu = menu.entry(if is_blocked {
+        MenuItem::new(
+            LocalizedString::new("menu-item-unblock-track").with_placeholder("Unblock Track"),
+        )
+        .command(cmd::TOGGLE_BLOCKED_TRACK.with(track.id))
+    } else {
+        MenuItem::new(
+            LocalizedString::new("menu-item-block-track").with_placeholder("Don't Play This Track"),
+        )
+        .command(cmd::TOGGLE_BLOCKED_TRACK.with(track.id))
+    });
+
+    // Editing the library and the playlists needs the network.
+    if WebApi::global().is_offline() {
+        return menu;
+    }
+
     menu = menu.separator();
 
     if library.contains_track(track) {
@@ -1,30 +1,40 @@
 use druid::{
     commands,
-    widget::{Either, Flex, Label},
-    LensExt, Selector, Widget, WidgetExt,
+    widget::{Flex, Label},
+    Application, LensExt, LocalizedString, Menu, MenuItem, Selector, Widget, WidgetExt,
 };
 
 use crate::{
-    data::{AppState, Library, UserProfile},
+    cmd,
+    data::{AppState, IsrcImport, IsrcMatches, Library, UserProfile},
+    error::Error,
     webapi::WebApi,
     widget::{Async, Empty, MyWidgetExt},
 };
 
-use super::theme;
+use super::{playlist, theme};
 
 pub const LOAD_PROFILE: Selector = Selector::new("app.user.load-profile");
 
+const IMPORT_LIKED_FROM_CLIPBOARD: Selector = Selector::new("app.user.import-liked-from-clipboard");
+const IMPORT_LIKED: Selector<IsrcImport> = Selector::new("app.user.import-liked");
+
 pub fn user_widget() -> impl Widget<AppState> {
-    let is_connected = Either::new(
-        // TODO: Avoid the locking here.
-        |state: &AppState, _| state.session.is_connected(),
-        Label::new("Connected")
-            .with_text_color(theme::PLACEHOLDER_COLOR)
-            .with_text_size(theme::TEXT_SIZE_SMALL),
-        Label::new("Disconnected")
-            .with_text_color(theme::PLACEHOLDER_COLOR)
-            .with_text_size(theme::TEXT_SIZE_SMALL),
-    );
+    let is_connected = Label::dynamic(|state: &AppState, _| {
+        let status = if state.config.offline {
+            "Offline"
+        } else if state.network_lost {
+            "No Network"
+        } else if state.session.is_connected() {
+            // TODO: Avoid the locking here.
+            "Connected"
+        } else {
+            "Disconnected"
+        };
+        status.to_string()
+    })
+    .with_text_color(theme::PLACEHOLDER_COLOR)
+    .with_text_size(theme::TEXT_SIZE_SMALL);
 
     let user_profile = Async::new(
         || Empty,
@@ -49,6 +59,82 @@ pub fn user_widget() -> impl Widget<AppState> {
         .with_child(user_profile)
         .padding((theme::grid(2.0), theme::grid(1.5)))
         .expand_width()
+        .on_command(IMPORT_LIKED_FROM_CLIPBOARD, |ctx, _, data| {
+            let text = Application::global()
+                .clipboard()
+                .get_string()
+                .unwrap_or_default();
+            let import = IsrcImport::parse(&text);
+            if import.isrcs.is_empty() {
+                data.info_alert("No ISRCs found in the clipboard.");
+            } else {
+                ctx.submit_command(IMPORT_LIKED.with(import));
+            }
+        })
+        .on_command_async(
+            IMPORT_LIKED,
+            |d| -> Result<IsrcMatches, Error> {
+                let isrcs: Vec<_> = d.isrcs.iter().cloned().collect();
+                let matches = WebApi::global().match_isrcs(&isrcs)?;
+                let ids: Vec<String> = matches.tracks.iter().map(|t| t.id.0.to_base62()).collect();
+                let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+                WebApi::global().save_tracks(&ids)?;
+                Ok(matches)
+            },
+            |_, data, _| data.info_alert("Importing liked songs..."),
+            |_, data, (d, r)| match r {
+                Ok(matches) => {
+                    for line in &d.invalid_lines {
+                        log::warn!("skipping invalid ISRC: {:?}", line);
+                    }
+                    for isrc in &matches.unmatched {
+                        log::warn!("no track found for ISRC {}", isrc);
+                    }
+                    let added = matches.tracks.len();
+                    let skipped = matches.unmatched.len() + d.invalid_lines.len();
+                    data.with_library_mut(|library| library.add_tracks(matches.tracks));
+                    if skipped > 0 {
+                        data.info_alert(format!("Saved {} tracks, {} not found.", added, skipped));
+                    } else {
+                        data.info_alert(format!("Saved {} tracks.", added));
+                    }
+                }
+                Err(err) => data.action_failed("Importing liked songs", err),
+            },
+        )
         .link()
         .on_click(|ctx, _, _| ctx.submit_command(commands::SHOW_PREFERENCES))
+        .context_menu(user_menu)
+}
+
+fn user_menu(state: &AppState) -> Menu<AppState> {
+    Menu::empty()
+        .entry(
+            MenuItem::new(if state.config.offline {
+                LocalizedString::new("menu-item-go-online").with_placeholder("Go Online")
+            } else {
+                LocalizedString::new("menu-item-work-offline").with_placeholder("Work Offline")
+            })
+            .command(cmd::TOGGLE_OFFLINE),
+        )
+        .entry(
+            MenuItem::new(
+                LocalizedString::new("menu-item-import-playlist")
+                    .with_placeholder("Import Playlist from Clipboard"),
+            )
+            .command(playlist::IMPORT_FROM_CLIPBOARD),
+        )
+        .entry(
+            MenuItem::new(
+                LocalizedString::new("menu-item-import-liked")
+                    .with_placeholder("Import Liked Songs from Clipboard (ISRCs)"),
+            )
+            .command(IMPORT_LIKED_FROM_CLIPBOARD),
+        )
+        .separator()
+        .entry(
+            // With `close_to_tray`, closing the window doesn't quit, so offer it here.
+            MenuItem::new(LocalizedString::new("menu-item-quit").with_placeholder("Quit Psst"))
+                .command(commands::QUIT_APP),
+        )
 }
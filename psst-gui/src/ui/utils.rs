@@ -2,12 +2,16 @@ use std::{f64::consts::PI, time::Duration};
 
 use druid::{
     kurbo::Circle,
-    widget::{prelude::*, CrossAxisAlignment, Flex, Label, SizedBox},
+    widget::{prelude::*, Controller, CrossAxisAlignment, Flex, Label, LabelText, SizedBox},
     Data, Vec2, Widget, WidgetExt,
 };
 use time_humanize::HumanTime;
 
-use crate::{error::Error, widget::icons};
+use crate::{
+    cmd,
+    error::Error,
+    widget::{icons, MyWidgetExt},
+};
 
 use super::theme;
 
@@ -80,6 +84,51 @@ pub fn spinner_widget<T: Data>() -> impl Widget<T> {
 }
 
 pub fn error_widget() -> impl Widget<Error> {
+    error_message_widget(Flex::column())
+}
+
+/// Like `error_widget`, but with a "Retry" link that submits `cmd::RETRY` as a
+/// notification.  Wrap the view with `on_notification(cmd::RETRY, ..)` to
+/// re-submit the original load command from the rejected promise.
+pub fn retry_error_widget() -> impl Widget<Error> {
+    let retry = Label::new("Retry")
+        .with_font(theme::UI_FONT_MEDIUM)
+        .padding((0.0, theme::grid(0.5)))
+        .link()
+        .on_click(|ctx, _, _| ctx.submit_notification(cmd::RETRY));
+    error_message_widget(Flex::column().with_child(retry))
+}
+
+/// Small, dimmed title above a section of a page.
+pub fn header_widget<T: Data>(text: impl Into<LabelText<T>>) -> impl Widget<T> {
+    Label::new(text)
+        .with_font(theme::UI_FONT_MEDIUM)
+        .with_text_color(theme::PLACEHOLDER_COLOR)
+        .with_text_size(theme::TEXT_SIZE_SMALL)
+        .padding((0.0, theme::grid(2.0), 0.0, theme::grid(1.0)))
+}
+
+/// Reports rejected credentials to the `SessionController`, so the user gets
+/// asked to log in again.
+struct ReportUnauthorized;
+
+impl<W: Widget<Error>> Controller<Error, W> for ReportUnauthorized {
+    fn lifecycle(
+        &mut self,
+        child: &mut W,
+        ctx: &mut LifeCycleCtx,
+        event: &LifeCycle,
+        data: &Error,
+        env: &Env,
+    ) {
+        if let (LifeCycle::WidgetAdded, Error::Unauthorized) = (event, data) {
+            ctx.submit_command(cmd::SESSION_UNAUTHORIZED);
+        }
+        child.lifecycle(ctx, event, data, env)
+    }
+}
+
+fn error_message_widget(actions: Flex<Error>) -> impl Widget<Error> {
     let icon = icons::ERROR
         .scale((theme::grid(3.0), theme::grid(3.0)))
         .with_color(theme::PLACEHOLDER_COLOR);
@@ -94,13 +143,15 @@ pub fn error_widget() -> impl Widget<Error> {
             Label::dynamic(|err: &Error, _| err.to_string())
                 .with_text_size(theme::TEXT_SIZE_SMALL)
                 .with_text_color(theme::PLACEHOLDER_COLOR),
-        );
+        )
+        .with_child(actions.cross_axis_alignment(CrossAxisAlignment::Start));
     Flex::row()
         .with_child(icon)
         .with_default_spacer()
         .with_child(error)
         .padding((0.0, theme::grid(6.0)))
         .center()
+        .controller(ReportUnauthorized)
 }
 
 pub fn as_minutes_and_seconds(dur: Duration) -> String {
@@ -109,6 +160,16 @@ pub fn as_minutes_and_seconds(dur: Duration) -> String {
     format!("{}∶{:02}", minutes, seconds)
 }
 
+pub fn as_hours_and_minutes(dur: Duration) -> String {
+    let hours = dur.as_secs() / 3600;
+    let minutes = dur.as_secs() % 3600 / 60;
+    if hours > 0 {
+        format!("{} hr {} min", hours, minutes)
+    } else {
+        format!("{} min", minutes)
+    }
+}
+
 pub fn as_human(dur: Duration) -> String {
     HumanTime::from(dur).to_text_en(
         time_humanize::Accuracy::Rough,
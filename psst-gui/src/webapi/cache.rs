@@ -1,7 +1,11 @@
 use std::{
     fs::{self, File},
     path::PathBuf,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
 };
 
 use druid::ImageBuf;
@@ -12,15 +16,58 @@ use psst_core::cache::mkdir_if_not_exists;
 pub struct WebApiCache {
     base: Option<PathBuf>,
     images: Mutex<LruCache<Arc<str>, ImageBuf>>,
+    /// Bodies of recent GET responses, keyed by the request URL, so quickly
+    /// navigating back and forth doesn't send the same requests again.
+    responses: Mutex<LruCache<String, (Instant, Arc<[u8]>)>>,
+    responses_enabled: AtomicBool,
 }
 
 impl WebApiCache {
+    /// How long a response is reused for identical requests.
+    const RESPONSE_TTL: Duration = Duration::from_secs(5);
+
     pub fn new(base: Option<PathBuf>) -> Self {
         const IMAGE_CACHE_SIZE: usize = 256;
+        const RESPONSE_CACHE_SIZE: usize = 64;
         Self {
             base,
             images: Mutex::new(LruCache::new(IMAGE_CACHE_SIZE)),
+            responses: Mutex::new(LruCache::new(RESPONSE_CACHE_SIZE)),
+            responses_enabled: AtomicBool::new(true),
+        }
+    }
+
+    pub fn set_responses_enabled(&self, enabled: bool) {
+        self.responses_enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            self.clear_responses();
+        }
+    }
+
+    pub fn get_response(&self, url: &str) -> Option<Arc<[u8]>> {
+        if !self.responses_enabled.load(Ordering::Relaxed) {
+            return None;
         }
+        let mut responses = self.responses.lock();
+        let fresh = responses
+            .get_mut(url)
+            .filter(|(cached_at, _)| cached_at.elapsed() < Self::RESPONSE_TTL)
+            .map(|(_, body)| body.clone());
+        if fresh.is_none() {
+            responses.remove(url);
+        }
+        fresh
+    }
+
+    pub fn set_response(&self, url: String, body: Arc<[u8]>) {
+        if self.responses_enabled.load(Ordering::Relaxed) {
+            self.responses.lock().insert(url, (Instant::now(), body));
+        }
+    }
+
+    /// Forget the recent responses, they might be outdated after a change.
+    pub fn clear_responses(&self) {
+        self.responses.lock().clear();
     }
 
     pub fn get_image(&self, uri: &Arc<str>) -> Option<ImageBuf> {
@@ -1,14 +1,18 @@
 use std::{
+    collections::{HashMap, HashSet},
     fmt::Display,
     io::{self, Read},
     path::PathBuf,
-    sync::Arc,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
     thread,
     time::Duration,
 };
 
 use druid::{
-    im::Vector,
+    im::{self, Vector},
     image::{self, ImageFormat},
     Data, ImageBuf,
 };
@@ -16,17 +20,21 @@ use itertools::Itertools;
 use once_cell::sync::OnceCell;
 use parking_lot::Mutex;
 use psst_core::{
+    item_id::{ItemId, ItemIdType},
     session::{access_token::TokenProvider, SessionService},
-    util::default_ureq_agent_builder,
+    util::{default_ureq_agent_builder, NetTimeouts},
 };
 use serde::{de::DeserializeOwned, Deserialize};
+use time::OffsetDateTime;
 use ureq::{Agent, Request, Response};
 
 use crate::{
     data::{
-        Album, AlbumType, Artist, ArtistAlbums, AudioAnalysis, Cached, Episode, EpisodeId,
-        EpisodeLink, Nav, Page, Playlist, Range, Recommendations, RecommendationsRequest,
-        SearchResults, SearchTopic, Show, SpotifyUrl, Track, UserProfile,
+        Album, AlbumSort, AlbumType, Artist, ArtistAlbums, AudioAnalysis, AudioFeatures, Cached,
+        Episode, EpisodeId, EpisodeLink, IsrcMatches, Nav, Page, Playable, PlaybackOrigin,
+        PlaybackPayload, Playlist, PlaylistEntry, PlaylistImport, PlaylistLink, Range, RecentPlay,
+        Recommendations, RecommendationsRequest, SavedTrack, SearchResults, SearchTopic, Show,
+        SpotifyUrl, Track, TrackId, UserProfile,
     },
     error::Error,
 };
@@ -39,38 +47,70 @@ pub struct WebApi {
     cache: WebApiCache,
     token_provider: TokenProvider,
     local_track_manager: Mutex<LocalTrackManager>,
+    offline: AtomicBool,
 }
 
 impl WebApi {
     pub fn new(
         session: SessionService,
         proxy_url: Option<&str>,
+        timeouts: NetTimeouts,
         cache_base: Option<PathBuf>,
     ) -> Self {
-        let agent = default_ureq_agent_builder(proxy_url).unwrap().build();
+        let agent = default_ureq_agent_builder(proxy_url, timeouts)
+            .unwrap()
+            .build();
         Self {
             session,
             agent,
             cache: WebApiCache::new(cache_base),
             token_provider: TokenProvider::new(),
             local_track_manager: Mutex::new(LocalTrackManager::new()),
+            offline: AtomicBool::new(false),
         }
     }
 
+    /// In offline mode, only cached responses are served and all other
+    /// requests fail with `Error::Offline`, without touching the network.
+    pub fn set_offline(&self, offline: bool) {
+        self.offline.store(offline, Ordering::Relaxed);
+    }
+
+    pub fn is_offline(&self) -> bool {
+        self.offline.load(Ordering::Relaxed)
+    }
+
+    /// Reuse the responses of identical GET requests sent within a few
+    /// seconds.
+    pub fn set_response_cache(&self, enabled: bool) {
+        self.cache.set_responses_enabled(enabled);
+    }
+
     fn access_token(&self) -> Result<String, Error> {
         let token = self
             .token_provider
             .get(&self.session)
-            .map_err(|err| Error::WebApiError(err.to_string()))?;
+            .map_err(|err| match err {
+                psst_core::error::Error::AuthFailed { .. } => Error::Unauthorized,
+                err => Error::WebApiError(err.to_string()),
+            })?;
         Ok(token.token)
     }
 
     fn request(&self, method: &str, path: impl Display) -> Result<Request, Error> {
-        let token = self.access_token()?;
         let request = self
             .agent
-            .request(method, &format!("https://api.spotify.com/{}", path))
-            .set("Authorization", &format!("Bearer {}", &token));
+            .request(method, &format!("https://api.spotify.com/{}", path));
+        if method != "GET" {
+            // Recent responses might not reflect this change anymore.
+            self.cache.clear_responses();
+        }
+        if self.is_offline() {
+            // The request is never going to be sent, avoid fetching the access token.
+            return Ok(request);
+        }
+        let token = self.access_token()?;
+        let request = request.set("Authorization", &format!("Bearer {}", &token));
         Ok(request)
     }
 
@@ -90,7 +130,10 @@ impl WebApi {
         self.request("DELETE", path)
     }
 
-    fn with_retry(f: impl Fn() -> Result<Response, Error>) -> Result<Response, Error> {
+    fn with_retry(&self, f: impl Fn() -> Result<Response, Error>) -> Result<Response, Error> {
+        if self.is_offline() {
+            return Err(Error::Offline);
+        }
         loop {
             let response = f()?;
             match response.status() {
@@ -111,18 +154,32 @@ impl WebApi {
     /// Send a request with a empty JSON object, throw away the response body.
     /// Use for POST/PUT/DELETE requests.
     fn send_empty_json(&self, request: Request) -> Result<(), Error> {
-        let _response = Self::with_retry(|| Ok(request.clone().send_string("{}")?))?;
+        let _response = self.with_retry(|| Ok(request.clone().send_string("{}")?))?;
         Ok(())
     }
 
     /// Send a request and return the deserialized JSON body.  Use for GET
     /// requests.
     fn load<T: DeserializeOwned>(&self, request: Request) -> Result<T, Error> {
-        let response = Self::with_retry(|| Ok(request.clone().call()?))?;
-        let result = response.into_json()?;
+        let url = (request.method() == "GET").then(|| request.url().to_owned());
+        if let Some(body) = url.as_deref().and_then(|url| self.cache.get_response(url)) {
+            return Ok(serde_json::from_slice(&body)?);
+        }
+        let response = self.with_retry(|| Ok(request.clone().call()?))?;
+        let body = Self::read_body(response)?;
+        let result = serde_json::from_slice(&body)?;
+        if let Some(url) = url {
+            self.cache.set_response(url, body.into());
+        }
         Ok(result)
     }
 
+    fn read_body(response: Response) -> Result<Vec<u8>, Error> {
+        let mut body = Vec::new();
+        response.into_reader().read_to_end(&mut body)?;
+        Ok(body)
+    }
+
     /// Send a request using `self.load()`, but only if it isn't already present
     /// in cache.
     fn load_cached<T: Data + DeserializeOwned>(
@@ -136,13 +193,8 @@ impl WebApi {
             let value = serde_json::from_reader(file)?;
             Ok(Cached::new(value, cached_at))
         } else {
-            let response = Self::with_retry(|| Ok(request.clone().call()?))?;
-            let body = {
-                let mut reader = response.into_reader();
-                let mut body = Vec::new();
-                reader.read_to_end(&mut body)?;
-                body
-            };
+            let response = self.with_retry(|| Ok(request.clone().call()?))?;
+            let body = Self::read_body(response)?;
             let value = serde_json::from_slice(&body)?;
             self.cache.set(bucket, key, &body);
             Ok(Cached::fresh(value))
@@ -200,6 +252,99 @@ impl WebApi {
         Ok(results)
     }
 
+    /// Load a paginated result set like `load_all_pages`, and keep the raw
+    /// items in cache, so they can be served by `get_cached_pages` later.
+    fn load_all_pages_and_cache<T: DeserializeOwned + Clone>(
+        &self,
+        request: Request,
+        bucket: &str,
+        key: &str,
+    ) -> Result<Vector<T>, Error> {
+        let items: Vec<serde_json::Value> = self.load_all_pages(request)?.into_iter().collect();
+        self.cache.set(bucket, key, &serde_json::to_vec(&items)?);
+        let results = items
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()?;
+        Ok(results)
+    }
+
+    /// Load only the first page of a result set ordered from the newest item,
+    /// and prepend its items to the list saved through
+    /// `load_all_pages_and_cache`, up to the first already known item.  Items
+    /// are identified by a JSON pointer, `id_pointer`.  Returns `None` if
+    /// nothing is cached, the page doesn't overlap with the cached items, or
+    /// the merged list doesn't match the total count, e.g. after removals.
+    fn sync_cached_pages<T: DeserializeOwned + Clone>(
+        &self,
+        request: Request,
+        bucket: &str,
+        key: &str,
+        id_pointer: &str,
+    ) -> Result<Option<Vector<T>>, Error> {
+        let cached: Vec<serde_json::Value> = match self.cache.get(bucket, key) {
+            Some(file) => match serde_json::from_reader(file) {
+                Ok(cached) => cached,
+                Err(_) => return Ok(None),
+            },
+            None => return Ok(None),
+        };
+        let known: HashSet<&str> = cached
+            .iter()
+            .filter_map(|item| item.pointer(id_pointer)?.as_str())
+            .collect();
+
+        let page: Page<serde_json::Value> = self.load(request.query("limit", "50"))?;
+        let mut merged: Vec<serde_json::Value> = page
+            .items
+            .into_iter()
+            .take_while(|item| {
+                item.pointer(id_pointer)
+                    .and_then(|id| id.as_str())
+                    .map_or(false, |id| !known.contains(id))
+            })
+            .collect();
+        let overlaps = merged.len() < page.limit.min(page.total);
+        merged.extend(cached.iter().cloned());
+        if !overlaps || merged.len() != page.total {
+            log::info!("cached {} out of sync, reloading", key);
+            return Ok(None);
+        }
+
+        self.cache.set(bucket, key, &serde_json::to_vec(&merged)?);
+        let results = merged
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()?;
+        Ok(Some(results))
+    }
+
+    /// Items saved through `load_all_pages_and_cache`, unless they are older
+    /// than a day.  Meant to be shown while a fresh result set is loading.
+    fn get_cached_pages<T: DeserializeOwned + Clone>(
+        &self,
+        bucket: &str,
+        key: &str,
+    ) -> Option<Vector<T>> {
+        const CACHED_PAGES_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+        let file = self.cache.get(bucket, key)?;
+        let cached_at = file.metadata().ok()?.modified().ok()?;
+        if cached_at
+            .elapsed()
+            .map_or(true, |age| age > CACHED_PAGES_TTL)
+        {
+            return None;
+        }
+        match serde_json::from_reader::<_, Vec<T>>(file) {
+            Ok(items) => Some(items.into_iter().collect()),
+            Err(err) => {
+                log::error!("failed to read cached pages: {}", err);
+                None
+            }
+        }
+    }
+
     /// Load local track files from the official client's database.
     pub fn load_local_tracks(&self, username: &str) {
         if let Err(err) = self
@@ -212,6 +357,11 @@ impl WebApi {
     }
 }
 
+#[derive(Clone, Deserialize)]
+struct SavedAlbum {
+    album: Arc<Album>,
+}
+
 static GLOBAL_WEBAPI: OnceCell<Arc<WebApi>> = OnceCell::new();
 
 /// Global instance.
@@ -235,6 +385,86 @@ impl WebApi {
         let result = self.load(request)?;
         Ok(result)
     }
+
+    // https://developer.spotify.com/documentation/web-api/reference/#/operations/get-users-top-artists-and-tracks
+    pub fn get_user_top_tracks(&self) -> Result<Vector<Arc<Track>>, Error> {
+        let request = self.get("v1/me/top/tracks")?.query("limit", "50");
+        let result: Page<Arc<Track>> = self.load(request)?;
+        Ok(result.items)
+    }
+
+    // https://developer.spotify.com/documentation/web-api/reference/#/operations/get-recently-played
+    pub fn get_recently_played(&self) -> Result<Vec<RecentPlay>, Error> {
+        // The history is paged by cursors, not by offsets like `Page`.
+        #[derive(Deserialize)]
+        struct PlayHistoryPage {
+            items: Vec<PlayHistory>,
+        }
+
+        #[derive(Deserialize)]
+        struct PlayHistory {
+            track: Arc<Track>,
+            #[serde(deserialize_with = "crate::data::utils::deserialize_datetime_option")]
+            played_at: Option<OffsetDateTime>,
+            context: Option<Context>,
+        }
+
+        #[derive(Deserialize)]
+        struct Context {
+            uri: String,
+        }
+
+        let request = self
+            .get("v1/me/player/recently-played")?
+            .query("limit", "50");
+        let result: PlayHistoryPage = self.load(request)?;
+
+        let mut playlists: Vec<PlaylistLink> = Vec::new();
+        let mut plays = Vec::new();
+        for item in result.items {
+            let played_at = match item.played_at {
+                Some(played_at) => played_at.unix_timestamp(),
+                None => continue,
+            };
+            // Context URIs look like `spotify:album:<id>`.
+            let context = item.context.as_ref().and_then(|context| {
+                let (kind, id) = context.uri.strip_prefix("spotify:")?.split_once(':')?;
+                match kind {
+                    "album" => item
+                        .track
+                        .album
+                        .clone()
+                        .filter(|album| &*album.id == id)
+                        .map(Nav::AlbumDetail),
+                    "artist" => item
+                        .track
+                        .artists
+                        .iter()
+                        .find(|artist| &*artist.id == id)
+                        .cloned()
+                        .map(Nav::ArtistDetail),
+                    "playlist" => {
+                        let link = match playlists.iter().find(|link| &*link.id == id) {
+                            Some(link) => link.clone(),
+                            None => {
+                                let link = self.get_playlist_link(id).ok()?;
+                                playlists.push(link.clone());
+                                link
+                            }
+                        };
+                        Some(Nav::PlaylistDetail(link))
+                    }
+                    _ => None,
+                }
+            });
+            plays.push(RecentPlay {
+                track: item.track,
+                played_at,
+                context,
+            });
+        }
+        Ok(plays)
+    }
 }
 
 /// Artist endpoints.
@@ -258,6 +488,7 @@ impl WebApi {
             singles: Vector::new(),
             compilations: Vector::new(),
             appears_on: Vector::new(),
+            sort: AlbumSort::default(),
         };
         for album in result {
             match album.album_type {
@@ -307,6 +538,17 @@ impl WebApi {
         let result = self.load_cached(request, "album", id)?;
         Ok(result)
     }
+
+    /// Load the album into the cache, so a following `get_album` call is
+    /// served without a network round-trip.
+    pub fn prefetch_album(&self, id: &str) {
+        if self.cache.get("album", id).is_some() {
+            return;
+        }
+        if let Err(err) = self.get_album(id) {
+            log::debug!("failed to prefetch album {}: {:?}", id, err);
+        }
+    }
 }
 
 /// Show endpoints. (Podcasts)
@@ -338,6 +580,19 @@ impl WebApi {
         Ok(result.episodes)
     }
 
+    // https://developer.spotify.com/documentation/web-api/reference/#/operations/get-a-shows-episodes
+    /// Only the first page of episodes of a show, the latest ones.
+    pub fn get_latest_show_episodes(&self, id: &str) -> Result<Vector<Arc<Episode>>, Error> {
+        let request = self
+            .get(format!("v1/shows/{}/episodes", id))?
+            .query("market", "from_token");
+        let page: Page<EpisodeLink> = self.load(request)?;
+        if page.items.is_empty() {
+            return Ok(Vector::new());
+        }
+        self.get_episodes(page.items.into_iter().map(|link| link.id))
+    }
+
     // https://developer.spotify.com/documentation/web-api/reference/#/operations/get-a-shows-episodes
     pub fn get_show_episodes(&self, id: &str) -> Result<Vector<Arc<Episode>>, Error> {
         let request = self
@@ -368,26 +623,78 @@ impl WebApi {
         let result = self.load(request)?;
         Ok(result)
     }
+
+    // https://developer.spotify.com/documentation/web-api/reference/#/operations/get-several-tracks
+    pub fn get_tracks(&self, ids: &[&str]) -> Result<Vector<Arc<Track>>, Error> {
+        #[derive(Deserialize)]
+        struct Tracks {
+            // Unknown IDs come back as `null`.
+            tracks: Vec<Option<Arc<Track>>>,
+        }
+
+        let mut tracks = Vector::new();
+        for chunk in Self::id_chunks(ids) {
+            let request = self.get("v1/tracks")?.query("ids", &chunk);
+            let result: Tracks = self.load(request)?;
+            tracks.extend(result.tracks.into_iter().flatten());
+        }
+        Ok(tracks)
+    }
+
+    /// Tracks and episodes of `ids`, in the same order.  Unknown IDs are left
+    /// out.
+    pub fn get_playables(&self, ids: &[ItemId]) -> Result<Vector<Playable>, Error> {
+        let track_ids: Vec<String> = ids
+            .iter()
+            .filter(|id| id.id_type == ItemIdType::Track)
+            .map(|id| id.to_base62())
+            .collect();
+        let track_ids: Vec<&str> = track_ids.iter().map(String::as_str).collect();
+        let episode_ids: Vec<EpisodeId> = ids
+            .iter()
+            .filter(|id| id.id_type == ItemIdType::Podcast)
+            .map(|&id| EpisodeId(id))
+            .collect();
+
+        let mut playables = HashMap::new();
+        for track in self.get_tracks(&track_ids)? {
+            playables.insert(track.id.0, Playable::Track(track));
+        }
+        if !episode_ids.is_empty() {
+            for episode in self.get_episodes(episode_ids)? {
+                playables.insert(episode.id.0, Playable::Episode(episode));
+            }
+        }
+        Ok(ids
+            .iter()
+            .filter_map(|id| playables.get(id).cloned())
+            .collect())
+    }
 }
 
 /// Library endpoints.
 impl WebApi {
     // https://developer.spotify.com/documentation/web-api/reference/library/get-users-saved-albums/
     pub fn get_saved_albums(&self) -> Result<Vector<Arc<Album>>, Error> {
-        #[derive(Clone, Deserialize)]
-        struct SavedAlbum {
-            album: Arc<Album>,
-        }
-
         let request = self.get("v1/me/albums")?.query("market", "from_token");
 
         Ok(self
-            .load_all_pages(request)?
+            .load_all_pages_and_cache(request, "library", "saved-albums")?
             .into_iter()
             .map(|item: SavedAlbum| item.album)
             .collect())
     }
 
+    /// Saved albums from the last `get_saved_albums()` call, if recent enough.
+    pub fn get_cached_saved_albums(&self) -> Option<Vector<Arc<Album>>> {
+        Some(
+            self.get_cached_pages("library", "saved-albums")?
+                .into_iter()
+                .map(|item: SavedAlbum| item.album)
+                .collect(),
+        )
+    }
+
     // https://developer.spotify.com/documentation/web-api/reference/library/save-albums-user/
     pub fn save_album(&self, id: &str) -> Result<(), Error> {
         let request = self.put("v1/me/albums")?.query("ids", id);
@@ -403,19 +710,27 @@ impl WebApi {
     }
 
     // https://developer.spotify.com/documentation/web-api/reference/library/get-users-saved-tracks/
-    pub fn get_saved_tracks(&self) -> Result<Vector<Arc<Track>>, Error> {
-        #[derive(Clone, Deserialize)]
-        struct SavedTrack {
-            track: Arc<Track>,
-        }
+    pub fn get_saved_tracks(&self) -> Result<Vector<SavedTrack>, Error> {
+        let request = self.get("v1/me/tracks")?.query("market", "from_token");
 
+        self.load_all_pages_and_cache(request, "library", "saved-tracks")
+    }
+
+    /// Like `get_saved_tracks`, but if the saved tracks are cached, fetch only
+    /// the most recently saved ones and merge them in front of the cached list.
+    /// Falls back to a full reload if the lists don't line up.
+    pub fn sync_saved_tracks(&self) -> Result<Vector<SavedTrack>, Error> {
         let request = self.get("v1/me/tracks")?.query("market", "from_token");
 
-        Ok(self
-            .load_all_pages(request)?
-            .into_iter()
-            .map(|item: SavedTrack| item.track)
-            .collect())
+        match self.sync_cached_pages(request, "library", "saved-tracks", "/track/id")? {
+            Some(items) => Ok(items),
+            None => self.get_saved_tracks(),
+        }
+    }
+
+    /// Saved tracks from the last `get_saved_tracks()` call, if recent enough.
+    pub fn get_cached_saved_tracks(&self) -> Option<Vector<SavedTrack>> {
+        self.get_cached_pages("library", "saved-tracks")
     }
 
     // https://developer.spotify.com/documentation/web-api/reference/#/operations/get-users-saved-shows
@@ -448,6 +763,37 @@ impl WebApi {
         Ok(())
     }
 
+    // https://developer.spotify.com/documentation/web-api/reference/library/save-tracks-user/
+    pub fn save_tracks(&self, ids: &[&str]) -> Result<(), Error> {
+        for chunk in Self::id_chunks(ids) {
+            let request = self.put("v1/me/tracks")?.query("ids", &chunk);
+            self.send_empty_json(request)?;
+        }
+        Ok(())
+    }
+
+    // https://developer.spotify.com/documentation/web-api/reference/library/remove-tracks-user/
+    pub fn unsave_tracks(&self, ids: &[&str]) -> Result<(), Error> {
+        for chunk in Self::id_chunks(ids) {
+            let request = self.delete("v1/me/tracks")?.query("ids", &chunk);
+            self.send_empty_json(request)?;
+        }
+        Ok(())
+    }
+
+    /// Split `ids` into comma-separated lists of at most 50 unique IDs, the
+    /// limit of the library endpoints.
+    fn id_chunks(ids: &[&str]) -> Vec<String> {
+        const MAX_IDS_PER_REQUEST: usize = 50;
+
+        ids.iter()
+            .unique()
+            .chunks(MAX_IDS_PER_REQUEST)
+            .into_iter()
+            .map(|mut chunk| chunk.join(","))
+            .collect()
+    }
+
     // https://developer.spotify.com/documentation/web-api/reference/#/operations/save-shows-user
     pub fn save_show(&self, id: &str) -> Result<(), Error> {
         let request = self.put("v1/me/shows")?.query("ids", id);
@@ -497,12 +843,30 @@ impl WebApi {
         Ok(result)
     }
 
+    pub fn get_playlist_link(&self, id: &str) -> Result<PlaylistLink, Error> {
+        let request = self
+            .get(format!("v1/playlists/{}", id))?
+            .query("fields", "id,name");
+        let result = self.load(request)?;
+        Ok(result)
+    }
+
     // https://developer.spotify.com/documentation/web-api/reference/#endpoint-get-playlists-tracks
     pub fn get_playlist_tracks(&self, id: &str) -> Result<Vector<Arc<Track>>, Error> {
+        let entries = self.get_playlist_entries(id)?;
+        Ok(entries.into_iter().map(|entry| entry.track).collect())
+    }
+
+    pub fn get_playlist_entries(&self, id: &str) -> Result<Vector<PlaylistEntry>, Error> {
         #[derive(Clone, Deserialize)]
         struct PlaylistItem {
             is_local: bool,
             track: OptionalTrack,
+            #[serde(
+                default,
+                deserialize_with = "crate::data::utils::deserialize_datetime_option"
+            )]
+            added_at: Option<OffsetDateTime>,
         }
 
         // Spotify API likes to return _really_ bogus data for local tracks. Much better
@@ -525,19 +889,69 @@ impl WebApi {
 
         Ok(result
             .into_iter()
-            .filter_map(|item| match item {
-                PlaylistItem {
-                    track: OptionalTrack::Track(track),
-                    ..
-                } => Some(track),
-                PlaylistItem {
-                    track: OptionalTrack::Json(track),
-                    ..
-                } => local_track_manager.find_local_track(track),
+            .filter_map(|item| {
+                let track = match item.track {
+                    OptionalTrack::Track(track) => Some(track),
+                    OptionalTrack::Json(track) => local_track_manager.find_local_track(track),
+                }?;
+                Some(PlaylistEntry {
+                    track,
+                    added_at: item.added_at,
+                })
             })
             .collect())
     }
 
+    // https://developer.spotify.com/documentation/web-api/reference/#/operations/create-playlist
+    pub fn create_playlist(&self, name: &str) -> Result<Playlist, Error> {
+        let request = self.post("v1/me/playlists")?;
+        let body = ureq::json!({
+            "name": name,
+            "public": false,
+        });
+        let response = self.with_retry(|| Ok(request.clone().send_json(body.clone())?))?;
+        let result = response.into_json()?;
+        Ok(result)
+    }
+
+    // https://developer.spotify.com/documentation/web-api/reference/#endpoint-add-tracks-to-playlist
+    pub fn add_tracks_to_playlist(&self, playlist_id: &str, uris: &[String]) -> Result<(), Error> {
+        const MAX_URIS_PER_REQUEST: usize = 100;
+
+        for chunk in uris.chunks(MAX_URIS_PER_REQUEST) {
+            let request = self.post(format!("v1/playlists/{}/tracks", playlist_id))?;
+            let body = ureq::json!({ "uris": chunk });
+            self.with_retry(|| Ok(request.clone().send_json(body.clone())?))?;
+        }
+        Ok(())
+    }
+
+    /// Create a new playlist with the tracks of `import` that exist, in the
+    /// same order.  Returns the playlist with the number of tracks added.
+    pub fn import_playlist(&self, import: &PlaylistImport) -> Result<(Playlist, usize), Error> {
+        let ids = import
+            .track_ids
+            .iter()
+            .map(|id| id.0.to_base62())
+            .collect_vec();
+        let found: HashSet<TrackId> = self
+            .get_tracks(&ids.iter().map(String::as_str).collect_vec())?
+            .iter()
+            .map(|track| track.id)
+            .collect();
+        let uris = import
+            .track_ids
+            .iter()
+            .filter(|id| found.contains(*id))
+            .map(TrackId::to_uri)
+            .collect_vec();
+
+        let mut playlist = self.create_playlist(&import.name)?;
+        self.add_tracks_to_playlist(&playlist.id, &uris)?;
+        playlist.track_count = uris.len();
+        Ok((playlist, uris.len()))
+    }
+
     // https://developer.spotify.com/documentation/web-api/reference/#endpoint-add-tracks-to-playlist
     pub fn add_track_to_playlist(&self, playlist_id: &str, track_uri: &str) -> Result<(), Error> {
         let request = self
@@ -562,12 +976,61 @@ impl WebApi {
 
         Ok(())
     }
+
+    /// Remove the tracks at the given positions of the current playlist
+    /// snapshot.  Spotify checks each position still holds its track.
+    pub fn remove_playlist_positions(
+        &self,
+        playlist_id: &str,
+        tracks: &[(TrackId, usize)],
+    ) -> Result<(), Error> {
+        // Spotify removes at most 100 tracks per request.
+        const MAX_TRACKS: usize = 100;
+
+        #[derive(Deserialize)]
+        struct Snapshot {
+            snapshot_id: String,
+        }
+
+        let request = self
+            .get(format!("v1/playlists/{}", playlist_id))?
+            .query("fields", "snapshot_id");
+        let snapshot: Snapshot = self.load(request)?;
+
+        let positions = tracks
+            .iter()
+            .map(|(id, position)| (id.to_uri(), *position))
+            .into_group_map();
+        // All the requests refer to the same snapshot, so the positions stay
+        // valid after the first removal.
+        for chunk in &positions.into_iter().chunks(MAX_TRACKS) {
+            let tracks = chunk
+                .map(|(uri, positions)| ureq::json!({ "uri": uri, "positions": positions }))
+                .collect_vec();
+            self.delete(&format!("v1/playlists/{}/tracks", playlist_id))?
+                .send_json(ureq::json!({
+                    "tracks": tracks,
+                    "snapshot_id": snapshot.snapshot_id,
+                }))?;
+        }
+
+        Ok(())
+    }
 }
 
 /// Search endpoints.
 impl WebApi {
     // https://developer.spotify.com/documentation/web-api/reference/search/
-    pub fn search(&self, query: &str, topics: &[SearchTopic]) -> Result<SearchResults, Error> {
+    pub fn search(
+        &self,
+        query: &str,
+        topics: &[SearchTopic],
+        limit: usize,
+        offset: usize,
+    ) -> Result<SearchResults, Error> {
+        // Spotify returns at most 50 results of each topic.
+        const MAX_LIMIT: usize = 50;
+
         #[derive(Deserialize)]
         struct ApiSearchResults {
             artists: Option<Page<Artist>>,
@@ -582,14 +1045,30 @@ impl WebApi {
             .get("v1/search")?
             .query("q", query)
             .query("type", &topics)
+            .query("limit", &limit.clamp(1, MAX_LIMIT).to_string())
+            .query("offset", &offset.to_string())
             .query("marker", "from_token");
         let result: ApiSearchResults = self.load(request)?;
 
-        let artists = result.artists.map_or_else(Vector::new, |page| page.items);
-        let albums = result.albums.map_or_else(Vector::new, |page| page.items);
-        let tracks = result.tracks.map_or_else(Vector::new, |page| page.items);
-        let playlists = result.playlists.map_or_else(Vector::new, |page| page.items);
-        let shows = result.shows.map_or_else(Vector::new, |page| page.items);
+        fn items<T: Clone>(
+            topic: SearchTopic,
+            page: Option<Page<T>>,
+            topics_with_more: &mut im::HashSet<SearchTopic>,
+        ) -> Vector<T> {
+            page.map_or_else(Vector::new, |page| {
+                if page.offset + page.items.len() < page.total {
+                    topics_with_more.insert(topic);
+                }
+                page.items
+            })
+        }
+
+        let mut more = im::HashSet::new();
+        let artists = items(SearchTopic::Artist, result.artists, &mut more);
+        let albums = items(SearchTopic::Album, result.albums, &mut more);
+        let tracks = items(SearchTopic::Track, result.tracks, &mut more);
+        let playlists = items(SearchTopic::Playlist, result.playlists, &mut more);
+        let shows = items(SearchTopic::Show, result.shows, &mut more);
         Ok(SearchResults {
             query: query.into(),
             artists,
@@ -597,23 +1076,71 @@ impl WebApi {
             tracks,
             playlists,
             shows,
+            topics_with_more: more,
         })
     }
 
-    pub fn load_spotify_link(&self, link: &SpotifyUrl) -> Result<Nav, Error> {
+    /// Look up the tracks of `isrcs` with the `isrc:` search qualifier.  One
+    /// recording is often released several times, the playable release with
+    /// the highest popularity is picked.
+    pub fn match_isrcs(&self, isrcs: &[Arc<str>]) -> Result<IsrcMatches, Error> {
+        const RESULTS_PER_ISRC: usize = 10;
+
+        let mut matches = IsrcMatches {
+            tracks: Vector::new(),
+            unmatched: Vector::new(),
+        };
+        for isrc in isrcs {
+            let query = format!("isrc:{}", isrc);
+            let results = self.search(&query, &[SearchTopic::Track], RESULTS_PER_ISRC, 0)?;
+            // `max_by_key` returns the last of equal elements, prefer the
+            // first result on ties.
+            let best = results
+                .tracks
+                .iter()
+                .rev()
+                .max_by_key(|track| (!track.is_unavailable(), track.popularity.unwrap_or(0)));
+            match best {
+                Some(track) => matches.tracks.push_back(track.clone()),
+                None => matches.unmatched.push_back(isrc.clone()),
+            }
+        }
+        Ok(matches)
+    }
+
+    /// Resolve a link into a route to navigate to, and for timestamped track
+    /// links, also a payload playing the track from the given position.
+    pub fn load_spotify_link(
+        &self,
+        link: &SpotifyUrl,
+    ) -> Result<(Nav, Option<PlaybackPayload>), Error> {
+        if let SpotifyUrl::Track(id, Some(start_at)) = link {
+            let track = self.get_track(id)?;
+            let album = track.album.clone().ok_or_else(|| {
+                Error::WebApiError("Track was found but has no album".to_string())
+            })?;
+            let payload = PlaybackPayload {
+                origin: PlaybackOrigin::Album(album.clone()),
+                items: Vector::unit(Playable::Track(track)),
+                position: 0,
+                start_at: Some(*start_at),
+                then: Vector::new(),
+            };
+            return Ok((Nav::AlbumDetail(album), Some(payload)));
+        }
         let nav = match link {
             SpotifyUrl::Playlist(id) => Nav::PlaylistDetail(self.get_playlist(id)?.link()),
             SpotifyUrl::Artist(id) => Nav::ArtistDetail(self.get_artist(id)?.link()),
             SpotifyUrl::Album(id) => Nav::AlbumDetail(self.get_album(id)?.data.link()),
             SpotifyUrl::Show(id) => Nav::AlbumDetail(self.get_album(id)?.data.link()),
-            SpotifyUrl::Track(id) => Nav::AlbumDetail(
+            SpotifyUrl::Track(id, _) => Nav::AlbumDetail(
                 // TODO: We should highlight the exact track in the album.
                 self.get_track(id)?.album.clone().ok_or_else(|| {
                     Error::WebApiError("Track was found but has no album".to_string())
                 })?,
             ),
         };
-        Ok(nav)
+        Ok((nav, None))
     }
 }
 
@@ -673,8 +1200,15 @@ impl WebApi {
 
 /// Track endpoints.
 impl WebApi {
+    // https://developer.spotify.com/documentation/web-api/reference/#/operations/get-audio-features
+    pub fn get_audio_features(&self, track_id: &str) -> Result<Cached<AudioFeatures>, Error> {
+        let request = self.get(format!("v1/audio-features/{}", track_id))?;
+        let result = self.load_cached(request, "audio-features", track_id)?;
+        Ok(result)
+    }
+
     // https://developer.spotify.com/documentation/web-api/reference/tracks/get-audio-analysis/
-    pub fn _get_audio_analysis(&self, track_id: &str) -> Result<AudioAnalysis, Error> {
+    pub fn get_audio_analysis(&self, track_id: &str) -> Result<AudioAnalysis, Error> {
         let request = self.get(format!("v1/audio-analysis/{}", track_id))?;
         let result = self.load_cached(request, "audio-analysis", track_id)?;
         Ok(result.data)
@@ -688,6 +1222,9 @@ impl WebApi {
     }
 
     pub fn get_image(&self, uri: Arc<str>) -> Result<ImageBuf, Error> {
+        if self.is_offline() {
+            return Err(Error::Offline);
+        }
         let response = self.agent.get(&uri).call()?;
         let format = match response.content_type() {
             "image/jpeg" => Some(ImageFormat::Jpeg),
@@ -715,7 +1252,10 @@ impl From<io::Error> for Error {
 
 impl From<ureq::Error> for Error {
     fn from(err: ureq::Error) -> Self {
-        Error::WebApiError(err.to_string())
+        match err {
+            ureq::Error::Status(401, _) => Error::Unauthorized,
+            err => Error::WebApiError(err.to_string()),
+        }
     }
 }
 
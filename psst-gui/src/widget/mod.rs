@@ -26,11 +26,13 @@ pub use maybe::Maybe;
 pub use overlay::{Overlay, OverlayPosition};
 pub use promise::Async;
 pub use remote_image::RemoteImage;
-pub use theme::ThemeScope;
+pub use theme::{CoverTint, ThemeScope};
 pub use utils::{Border, Clip, FadeOut, Logger};
 
 use crate::{
-    controller::{ExClick, OnCommand, OnCommandAsync, OnDebounce, OnUpdate},
+    controller::{
+        ExClick, OnCommand, OnCommandAsync, OnDebounce, OnHover, OnNotification, OnUpdate,
+    },
     data::AppState,
 };
 
@@ -67,6 +69,14 @@ pub trait MyWidgetExt<T: Data>: Widget<T> + Sized + 'static {
         ControllerHost::new(self, OnDebounce::trailing(duration, handler))
     }
 
+    fn on_hover(
+        self,
+        duration: Duration,
+        handler: impl Fn(&mut EventCtx, &mut T, &Env) + 'static,
+    ) -> ControllerHost<Self, OnHover<T>> {
+        ControllerHost::new(self, OnHover::new(duration, handler))
+    }
+
     fn on_update<F>(self, handler: F) -> ControllerHost<Self, OnUpdate<F>>
     where
         F: Fn(&mut UpdateCtx, &T, &T, &Env) + 'static,
@@ -74,6 +84,13 @@ pub trait MyWidgetExt<T: Data>: Widget<T> + Sized + 'static {
         ControllerHost::new(self, OnUpdate::new(handler))
     }
 
+    fn on_left_click(
+        self,
+        func: impl Fn(&mut EventCtx, &MouseEvent, &mut T, &Env) + 'static,
+    ) -> ControllerHost<Self, ExClick<T>> {
+        ControllerHost::new(self, ExClick::new(Some(MouseButton::Left), func))
+    }
+
     fn on_right_click(
         self,
         func: impl Fn(&mut EventCtx, &MouseEvent, &mut T, &Env) + 'static,
@@ -93,6 +110,18 @@ pub trait MyWidgetExt<T: Data>: Widget<T> + Sized + 'static {
         ControllerHost::new(self, OnCommand::new(selector, func))
     }
 
+    fn on_notification<U, F>(
+        self,
+        selector: Selector<U>,
+        func: F,
+    ) -> ControllerHost<Self, OnNotification<U, F>>
+    where
+        U: 'static,
+        F: Fn(&mut EventCtx, &U, &mut T),
+    {
+        ControllerHost::new(self, OnNotification::new(selector, func))
+    }
+
     fn on_command_async<U: Data + Send, V: Data + Send>(
         self,
         selector: Selector<U>,
@@ -1,5 +1,9 @@
+use std::sync::Arc;
+
 use crate::{data::AppState, ui::theme};
-use druid::widget::prelude::*;
+use druid::{widget::prelude::*, Color};
+
+use super::remote_image;
 
 pub struct ThemeScope<W> {
     inner: W,
@@ -36,7 +40,9 @@ impl<W: Widget<AppState>> Widget<AppState> for ThemeScope<W> {
     }
 
     fn update(&mut self, ctx: &mut UpdateCtx, old_data: &AppState, data: &AppState, env: &Env) {
-        if !data.config.theme.same(&old_data.config.theme) {
+        if !data.config.theme.same(&old_data.config.theme)
+            || !data.config.list_density.same(&old_data.config.list_density)
+        {
             self.set_env(data, env);
             ctx.request_layout();
             ctx.request_paint();
@@ -61,3 +67,108 @@ impl<W: Widget<AppState>> Widget<AppState> for ThemeScope<W> {
             .paint(ctx, data, self.cached_env.as_ref().unwrap_or(env));
     }
 }
+
+/// Tints `theme::BACKGROUND_LIGHT` for its content with the dominant color of
+/// the image at the location given by `locator`, e.g. the cover art of the
+/// currently playing track.
+pub struct CoverTint<T, W> {
+    inner: W,
+    locator: Box<dyn Fn(&T) -> Option<Arc<str>>>,
+    location: Option<Arc<str>>,
+    color: Option<Color>,
+    cached_env: Option<Env>,
+}
+
+impl<T, W> CoverTint<T, W> {
+    pub fn new(inner: W, locator: impl Fn(&T) -> Option<Arc<str>> + 'static) -> Self {
+        Self {
+            inner,
+            locator: Box::new(locator),
+            location: None,
+            color: None,
+            cached_env: None,
+        }
+    }
+
+    fn set_env(&mut self, outer_env: &Env) {
+        self.cached_env = self.color.as_ref().map(|color| {
+            let background = theme::tint(&outer_env.get(theme::BACKGROUND_LIGHT), color);
+            outer_env
+                .clone()
+                .adding(theme::BACKGROUND_LIGHT, background)
+        });
+    }
+
+    /// Update the image location from `data`, returning true if it changed.
+    fn relocate(&mut self, data: &T) -> bool {
+        let location = (self.locator)(data);
+        let changed = location != self.location;
+        self.location = location;
+        changed
+    }
+}
+
+impl<T: Data, W: Widget<T>> Widget<T> for CoverTint<T, W> {
+    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut T, env: &Env) {
+        if let Event::Command(cmd) = event {
+            if let Some(payload) = cmd.get(remote_image::PROVIDE_DATA) {
+                if Some(&payload.location) == self.location.as_ref() {
+                    self.color = theme::dominant_color(&payload.image_buf);
+                    self.set_env(env);
+                    ctx.request_paint();
+                    return;
+                }
+            }
+        }
+        self.inner
+            .event(ctx, event, data, self.cached_env.as_ref().unwrap_or(env))
+    }
+
+    fn lifecycle(&mut self, ctx: &mut LifeCycleCtx, event: &LifeCycle, data: &T, env: &Env) {
+        if let LifeCycle::WidgetAdded = &event {
+            if self.relocate(data) {
+                if let Some(location) = self.location.clone() {
+                    ctx.submit_command(
+                        remote_image::REQUEST_DATA
+                            .with(location)
+                            .to(ctx.widget_id()),
+                    );
+                }
+            }
+        }
+        self.inner
+            .lifecycle(ctx, event, data, self.cached_env.as_ref().unwrap_or(env))
+    }
+
+    fn update(&mut self, ctx: &mut UpdateCtx, old_data: &T, data: &T, env: &Env) {
+        if self.relocate(data) {
+            if let Some(location) = self.location.clone() {
+                // Keep the previous tint until the new image arrives.
+                ctx.submit_command(
+                    remote_image::REQUEST_DATA
+                        .with(location)
+                        .to(ctx.widget_id()),
+                );
+            } else {
+                self.color = None;
+                self.set_env(env);
+                ctx.request_paint();
+            }
+        } else if ctx.env_changed() {
+            self.set_env(env);
+            ctx.request_paint();
+        }
+        self.inner
+            .update(ctx, old_data, data, self.cached_env.as_ref().unwrap_or(env));
+    }
+
+    fn layout(&mut self, ctx: &mut LayoutCtx, bc: &BoxConstraints, data: &T, env: &Env) -> Size {
+        self.inner
+            .layout(ctx, bc, data, self.cached_env.as_ref().unwrap_or(env))
+    }
+
+    fn paint(&mut self, ctx: &mut PaintCtx, data: &T, env: &Env) {
+        self.inner
+            .paint(ctx, data, self.cached_env.as_ref().unwrap_or(env));
+    }
+}
@@ -0,0 +1,666 @@
+// Automatically generated rust module for 'spirc.proto' file
+
+#![allow(non_snake_case)]
+#![allow(non_upper_case_globals)]
+#![allow(non_camel_case_types)]
+#![allow(unused_imports)]
+#![allow(unknown_lints)]
+#![allow(clippy::all)]
+#![cfg_attr(rustfmt, rustfmt_skip)]
+
+
+use quick_protobuf::{MessageRead, MessageWrite, BytesReader, Writer, WriterBackend, Result};
+use quick_protobuf::sizeofs::*;
+use super::*;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MessageType {
+    kMessageTypeHello = 1,
+    kMessageTypeGoodbye = 2,
+    kMessageTypeProbe = 3,
+    kMessageTypeNotify = 10,
+    kMessageTypeLoad = 20,
+    kMessageTypePlay = 21,
+    kMessageTypePause = 22,
+    kMessageTypePlayPause = 23,
+    kMessageTypeSeek = 24,
+    kMessageTypePrev = 25,
+    kMessageTypeNext = 26,
+    kMessageTypeVolume = 27,
+    kMessageTypeShuffle = 28,
+    kMessageTypeRepeat = 29,
+    kMessageTypeVolumeDown = 31,
+    kMessageTypeVolumeUp = 32,
+    kMessageTypeReplace = 33,
+    kMessageTypeLogout = 34,
+    kMessageTypeAction = 35,
+    kMessageTypeRename = 36,
+    kMessageTypeUpdateMetadata = 128,
+}
+
+impl Default for MessageType {
+    fn default() -> Self {
+        MessageType::kMessageTypeHello
+    }
+}
+
+impl From<i32> for MessageType {
+    fn from(i: i32) -> Self {
+        match i {
+            1 => MessageType::kMessageTypeHello,
+            2 => MessageType::kMessageTypeGoodbye,
+            3 => MessageType::kMessageTypeProbe,
+            10 => MessageType::kMessageTypeNotify,
+            20 => MessageType::kMessageTypeLoad,
+            21 => MessageType::kMessageTypePlay,
+            22 => MessageType::kMessageTypePause,
+            23 => MessageType::kMessageTypePlayPause,
+            24 => MessageType::kMessageTypeSeek,
+            25 => MessageType::kMessageTypePrev,
+            26 => MessageType::kMessageTypeNext,
+            27 => MessageType::kMessageTypeVolume,
+            28 => MessageType::kMessageTypeShuffle,
+            29 => MessageType::kMessageTypeRepeat,
+            31 => MessageType::kMessageTypeVolumeDown,
+            32 => MessageType::kMessageTypeVolumeUp,
+            33 => MessageType::kMessageTypeReplace,
+            34 => MessageType::kMessageTypeLogout,
+            35 => MessageType::kMessageTypeAction,
+            36 => MessageType::kMessageTypeRename,
+            128 => MessageType::kMessageTypeUpdateMetadata,
+            _ => Self::default(),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for MessageType {
+    fn from(s: &'a str) -> Self {
+        match s {
+            "kMessageTypeHello" => MessageType::kMessageTypeHello,
+            "kMessageTypeGoodbye" => MessageType::kMessageTypeGoodbye,
+            "kMessageTypeProbe" => MessageType::kMessageTypeProbe,
+            "kMessageTypeNotify" => MessageType::kMessageTypeNotify,
+            "kMessageTypeLoad" => MessageType::kMessageTypeLoad,
+            "kMessageTypePlay" => MessageType::kMessageTypePlay,
+            "kMessageTypePause" => MessageType::kMessageTypePause,
+            "kMessageTypePlayPause" => MessageType::kMessageTypePlayPause,
+            "kMessageTypeSeek" => MessageType::kMessageTypeSeek,
+            "kMessageTypePrev" => MessageType::kMessageTypePrev,
+            "kMessageTypeNext" => MessageType::kMessageTypeNext,
+            "kMessageTypeVolume" => MessageType::kMessageTypeVolume,
+            "kMessageTypeShuffle" => MessageType::kMessageTypeShuffle,
+            "kMessageTypeRepeat" => MessageType::kMessageTypeRepeat,
+            "kMessageTypeVolumeDown" => MessageType::kMessageTypeVolumeDown,
+            "kMessageTypeVolumeUp" => MessageType::kMessageTypeVolumeUp,
+            "kMessageTypeReplace" => MessageType::kMessageTypeReplace,
+            "kMessageTypeLogout" => MessageType::kMessageTypeLogout,
+            "kMessageTypeAction" => MessageType::kMessageTypeAction,
+            "kMessageTypeRename" => MessageType::kMessageTypeRename,
+            "kMessageTypeUpdateMetadata" => MessageType::kMessageTypeUpdateMetadata,
+            _ => Self::default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CapabilityType {
+    kSupportedContexts = 1,
+    kCanBePlayer = 2,
+    kRestrictToLocal = 3,
+    kDeviceType = 4,
+    kGaiaEqConnectId = 5,
+    kSupportsLogout = 6,
+    kIsObservable = 7,
+    kVolumeSteps = 8,
+    kSupportedTypes = 9,
+    kCommandAcks = 10,
+    kSupportsRename = 11,
+    kHidden = 12,
+    kSupportsPlaylistV2 = 13,
+    kSupportsExternalEpisodes = 14,
+}
+
+impl Default for CapabilityType {
+    fn default() -> Self {
+        CapabilityType::kSupportedContexts
+    }
+}
+
+impl From<i32> for CapabilityType {
+    fn from(i: i32) -> Self {
+        match i {
+            1 => CapabilityType::kSupportedContexts,
+            2 => CapabilityType::kCanBePlayer,
+            3 => CapabilityType::kRestrictToLocal,
+            4 => CapabilityType::kDeviceType,
+            5 => CapabilityType::kGaiaEqConnectId,
+            6 => CapabilityType::kSupportsLogout,
+            7 => CapabilityType::kIsObservable,
+            8 => CapabilityType::kVolumeSteps,
+            9 => CapabilityType::kSupportedTypes,
+            10 => CapabilityType::kCommandAcks,
+            11 => CapabilityType::kSupportsRename,
+            12 => CapabilityType::kHidden,
+            13 => CapabilityType::kSupportsPlaylistV2,
+            14 => CapabilityType::kSupportsExternalEpisodes,
+            _ => Self::default(),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for CapabilityType {
+    fn from(s: &'a str) -> Self {
+        match s {
+            "kSupportedContexts" => CapabilityType::kSupportedContexts,
+            "kCanBePlayer" => CapabilityType::kCanBePlayer,
+            "kRestrictToLocal" => CapabilityType::kRestrictToLocal,
+            "kDeviceType" => CapabilityType::kDeviceType,
+            "kGaiaEqConnectId" => CapabilityType::kGaiaEqConnectId,
+            "kSupportsLogout" => CapabilityType::kSupportsLogout,
+            "kIsObservable" => CapabilityType::kIsObservable,
+            "kVolumeSteps" => CapabilityType::kVolumeSteps,
+            "kSupportedTypes" => CapabilityType::kSupportedTypes,
+            "kCommandAcks" => CapabilityType::kCommandAcks,
+            "kSupportsRename" => CapabilityType::kSupportsRename,
+            "kHidden" => CapabilityType::kHidden,
+            "kSupportsPlaylistV2" => CapabilityType::kSupportsPlaylistV2,
+            "kSupportsExternalEpisodes" => CapabilityType::kSupportsExternalEpisodes,
+            _ => Self::default(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PlayStatus {
+    kPlayStatusStop = 0,
+    kPlayStatusPlay = 1,
+    kPlayStatusPause = 2,
+    kPlayStatusLoading = 3,
+}
+
+impl Default for PlayStatus {
+    fn default() -> Self {
+        PlayStatus::kPlayStatusStop
+    }
+}
+
+impl From<i32> for PlayStatus {
+    fn from(i: i32) -> Self {
+        match i {
+            0 => PlayStatus::kPlayStatusStop,
+            1 => PlayStatus::kPlayStatusPlay,
+            2 => PlayStatus::kPlayStatusPause,
+            3 => PlayStatus::kPlayStatusLoading,
+            _ => Self::default(),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for PlayStatus {
+    fn from(s: &'a str) -> Self {
+        match s {
+            "kPlayStatusStop" => PlayStatus::kPlayStatusStop,
+            "kPlayStatusPlay" => PlayStatus::kPlayStatusPlay,
+            "kPlayStatusPause" => PlayStatus::kPlayStatusPause,
+            "kPlayStatusLoading" => PlayStatus::kPlayStatusLoading,
+            _ => Self::default(),
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Frame {
+    pub version: Option<u32>,
+    pub ident: Option<String>,
+    pub protocol_version: Option<String>,
+    pub seq_nr: Option<u32>,
+    pub typ: Option<spirc::MessageType>,
+    pub device_state: Option<spirc::DeviceState>,
+    pub goodbye: Option<spirc::Goodbye>,
+    pub state: Option<spirc::State>,
+    pub position: Option<u32>,
+    pub volume: Option<u32>,
+    pub state_update_id: Option<i64>,
+    pub recipient: Vec<String>,
+    pub context_player_state: Option<Vec<u8>>,
+    pub new_name: Option<String>,
+    pub metadata: Option<spirc::Metadata>,
+}
+
+impl<'a> MessageRead<'a> for Frame {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(8) => msg.version = Some(r.read_uint32(bytes)?),
+                Ok(18) => msg.ident = Some(r.read_string(bytes)?.to_owned()),
+                Ok(26) => msg.protocol_version = Some(r.read_string(bytes)?.to_owned()),
+                Ok(32) => msg.seq_nr = Some(r.read_uint32(bytes)?),
+                Ok(40) => msg.typ = Some(r.read_enum(bytes)?),
+                Ok(58) => msg.device_state = Some(r.read_message::<spirc::DeviceState>(bytes)?),
+                Ok(90) => msg.goodbye = Some(r.read_message::<spirc::Goodbye>(bytes)?),
+                Ok(98) => msg.state = Some(r.read_message::<spirc::State>(bytes)?),
+                Ok(104) => msg.position = Some(r.read_uint32(bytes)?),
+                Ok(112) => msg.volume = Some(r.read_uint32(bytes)?),
+                Ok(136) => msg.state_update_id = Some(r.read_int64(bytes)?),
+                Ok(146) => msg.recipient.push(r.read_string(bytes)?.to_owned()),
+                Ok(154) => msg.context_player_state = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(162) => msg.new_name = Some(r.read_string(bytes)?.to_owned()),
+                Ok(202) => msg.metadata = Some(r.read_message::<spirc::Metadata>(bytes)?),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for Frame {
+    fn get_size(&self) -> usize {
+        0
+        + self.version.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.ident.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.protocol_version.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.seq_nr.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.typ.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.device_state.as_ref().map_or(0, |m| 1 + sizeof_len((m).get_size()))
+        + self.goodbye.as_ref().map_or(0, |m| 1 + sizeof_len((m).get_size()))
+        + self.state.as_ref().map_or(0, |m| 1 + sizeof_len((m).get_size()))
+        + self.position.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.volume.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.state_update_id.as_ref().map_or(0, |m| 2 + sizeof_varint(*(m) as u64))
+        + self.recipient.iter().map(|s| 2 + sizeof_len((s).len())).sum::<usize>()
+        + self.context_player_state.as_ref().map_or(0, |m| 2 + sizeof_len((m).len()))
+        + self.new_name.as_ref().map_or(0, |m| 2 + sizeof_len((m).len()))
+        + self.metadata.as_ref().map_or(0, |m| 2 + sizeof_len((m).get_size()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.version { w.write_with_tag(8, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.ident { w.write_with_tag(18, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.protocol_version { w.write_with_tag(26, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.seq_nr { w.write_with_tag(32, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.typ { w.write_with_tag(40, |w| w.write_enum(*s as i32))?; }
+        if let Some(ref s) = self.device_state { w.write_with_tag(58, |w| w.write_message(s))?; }
+        if let Some(ref s) = self.goodbye { w.write_with_tag(90, |w| w.write_message(s))?; }
+        if let Some(ref s) = self.state { w.write_with_tag(98, |w| w.write_message(s))?; }
+        if let Some(ref s) = self.position { w.write_with_tag(104, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.volume { w.write_with_tag(112, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.state_update_id { w.write_with_tag(136, |w| w.write_int64(*s))?; }
+        for s in &self.recipient { w.write_with_tag(146, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.context_player_state { w.write_with_tag(154, |w| w.write_bytes(&**s))?; }
+        if let Some(ref s) = self.new_name { w.write_with_tag(162, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.metadata { w.write_with_tag(202, |w| w.write_message(s))?; }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct DeviceState {
+    pub sw_version: Option<String>,
+    pub is_active: Option<bool>,
+    pub can_play: Option<bool>,
+    pub volume: Option<u32>,
+    pub name: Option<String>,
+    pub error_code: Option<u32>,
+    pub became_active_at: Option<i64>,
+    pub error_message: Option<String>,
+    pub capabilities: Vec<spirc::Capability>,
+    pub context_player_error: Option<String>,
+    pub metadata: Vec<spirc::Metadata>,
+}
+
+impl<'a> MessageRead<'a> for DeviceState {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.sw_version = Some(r.read_string(bytes)?.to_owned()),
+                Ok(80) => msg.is_active = Some(r.read_bool(bytes)?),
+                Ok(88) => msg.can_play = Some(r.read_bool(bytes)?),
+                Ok(96) => msg.volume = Some(r.read_uint32(bytes)?),
+                Ok(106) => msg.name = Some(r.read_string(bytes)?.to_owned()),
+                Ok(112) => msg.error_code = Some(r.read_uint32(bytes)?),
+                Ok(120) => msg.became_active_at = Some(r.read_int64(bytes)?),
+                Ok(130) => msg.error_message = Some(r.read_string(bytes)?.to_owned()),
+                Ok(138) => msg.capabilities.push(r.read_message::<spirc::Capability>(bytes)?),
+                Ok(162) => msg.context_player_error = Some(r.read_string(bytes)?.to_owned()),
+                Ok(202) => msg.metadata.push(r.read_message::<spirc::Metadata>(bytes)?),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for DeviceState {
+    fn get_size(&self) -> usize {
+        0
+        + self.sw_version.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.is_active.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.can_play.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.volume.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.name.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.error_code.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.became_active_at.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.error_message.as_ref().map_or(0, |m| 2 + sizeof_len((m).len()))
+        + self.capabilities.iter().map(|s| 2 + sizeof_len((s).get_size())).sum::<usize>()
+        + self.context_player_error.as_ref().map_or(0, |m| 2 + sizeof_len((m).len()))
+        + self.metadata.iter().map(|s| 2 + sizeof_len((s).get_size())).sum::<usize>()
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.sw_version { w.write_with_tag(10, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.is_active { w.write_with_tag(80, |w| w.write_bool(*s))?; }
+        if let Some(ref s) = self.can_play { w.write_with_tag(88, |w| w.write_bool(*s))?; }
+        if let Some(ref s) = self.volume { w.write_with_tag(96, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.name { w.write_with_tag(106, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.error_code { w.write_with_tag(112, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.became_active_at { w.write_with_tag(120, |w| w.write_int64(*s))?; }
+        if let Some(ref s) = self.error_message { w.write_with_tag(130, |w| w.write_string(&**s))?; }
+        for s in &self.capabilities { w.write_with_tag(138, |w| w.write_message(s))?; }
+        if let Some(ref s) = self.context_player_error { w.write_with_tag(162, |w| w.write_string(&**s))?; }
+        for s in &self.metadata { w.write_with_tag(202, |w| w.write_message(s))?; }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Capability {
+    pub typ: Option<spirc::CapabilityType>,
+    pub intValue: Vec<i64>,
+    pub stringValue: Vec<String>,
+}
+
+impl<'a> MessageRead<'a> for Capability {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(8) => msg.typ = Some(r.read_enum(bytes)?),
+                Ok(16) => msg.intValue.push(r.read_int64(bytes)?),
+                Ok(26) => msg.stringValue.push(r.read_string(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for Capability {
+    fn get_size(&self) -> usize {
+        0
+        + self.typ.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.intValue.iter().map(|s| 1 + sizeof_varint(*(s) as u64)).sum::<usize>()
+        + self.stringValue.iter().map(|s| 1 + sizeof_len((s).len())).sum::<usize>()
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.typ { w.write_with_tag(8, |w| w.write_enum(*s as i32))?; }
+        for s in &self.intValue { w.write_with_tag(16, |w| w.write_int64(*s))?; }
+        for s in &self.stringValue { w.write_with_tag(26, |w| w.write_string(&**s))?; }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Goodbye {
+    pub reason: Option<String>,
+}
+
+impl<'a> MessageRead<'a> for Goodbye {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.reason = Some(r.read_string(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for Goodbye {
+    fn get_size(&self) -> usize {
+        0
+        + self.reason.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.reason { w.write_with_tag(10, |w| w.write_string(&**s))?; }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct State {
+    pub context_uri: Option<String>,
+    pub index: Option<u32>,
+    pub position_ms: Option<u32>,
+    pub status: Option<spirc::PlayStatus>,
+    pub position_measured_at: Option<u64>,
+    pub context_description: Option<String>,
+    pub shuffle: Option<bool>,
+    pub repeat: Option<bool>,
+    pub last_command_ident: Option<String>,
+    pub last_command_msgid: Option<u32>,
+    pub playing_from_fallback: Option<bool>,
+    pub row: Option<u32>,
+    pub playing_track_index: Option<u32>,
+    pub track: Vec<spirc::TrackRef>,
+    pub ad: Option<spirc::Ad>,
+}
+
+impl<'a> MessageRead<'a> for State {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(18) => msg.context_uri = Some(r.read_string(bytes)?.to_owned()),
+                Ok(24) => msg.index = Some(r.read_uint32(bytes)?),
+                Ok(32) => msg.position_ms = Some(r.read_uint32(bytes)?),
+                Ok(40) => msg.status = Some(r.read_enum(bytes)?),
+                Ok(56) => msg.position_measured_at = Some(r.read_uint64(bytes)?),
+                Ok(66) => msg.context_description = Some(r.read_string(bytes)?.to_owned()),
+                Ok(104) => msg.shuffle = Some(r.read_bool(bytes)?),
+                Ok(112) => msg.repeat = Some(r.read_bool(bytes)?),
+                Ok(162) => msg.last_command_ident = Some(r.read_string(bytes)?.to_owned()),
+                Ok(168) => msg.last_command_msgid = Some(r.read_uint32(bytes)?),
+                Ok(192) => msg.playing_from_fallback = Some(r.read_bool(bytes)?),
+                Ok(200) => msg.row = Some(r.read_uint32(bytes)?),
+                Ok(208) => msg.playing_track_index = Some(r.read_uint32(bytes)?),
+                Ok(218) => msg.track.push(r.read_message::<spirc::TrackRef>(bytes)?),
+                Ok(226) => msg.ad = Some(r.read_message::<spirc::Ad>(bytes)?),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for State {
+    fn get_size(&self) -> usize {
+        0
+        + self.context_uri.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.index.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.position_ms.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.status.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.position_measured_at.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.context_description.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.shuffle.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.repeat.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.last_command_ident.as_ref().map_or(0, |m| 2 + sizeof_len((m).len()))
+        + self.last_command_msgid.as_ref().map_or(0, |m| 2 + sizeof_varint(*(m) as u64))
+        + self.playing_from_fallback.as_ref().map_or(0, |m| 2 + sizeof_varint(*(m) as u64))
+        + self.row.as_ref().map_or(0, |m| 2 + sizeof_varint(*(m) as u64))
+        + self.playing_track_index.as_ref().map_or(0, |m| 2 + sizeof_varint(*(m) as u64))
+        + self.track.iter().map(|s| 2 + sizeof_len((s).get_size())).sum::<usize>()
+        + self.ad.as_ref().map_or(0, |m| 2 + sizeof_len((m).get_size()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.context_uri { w.write_with_tag(18, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.index { w.write_with_tag(24, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.position_ms { w.write_with_tag(32, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.status { w.write_with_tag(40, |w| w.write_enum(*s as i32))?; }
+        if let Some(ref s) = self.position_measured_at { w.write_with_tag(56, |w| w.write_uint64(*s))?; }
+        if let Some(ref s) = self.context_description { w.write_with_tag(66, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.shuffle { w.write_with_tag(104, |w| w.write_bool(*s))?; }
+        if let Some(ref s) = self.repeat { w.write_with_tag(112, |w| w.write_bool(*s))?; }
+        if let Some(ref s) = self.last_command_ident { w.write_with_tag(162, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.last_command_msgid { w.write_with_tag(168, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.playing_from_fallback { w.write_with_tag(192, |w| w.write_bool(*s))?; }
+        if let Some(ref s) = self.row { w.write_with_tag(200, |w| w.write_uint32(*s))?; }
+        if let Some(ref s) = self.playing_track_index { w.write_with_tag(208, |w| w.write_uint32(*s))?; }
+        for s in &self.track { w.write_with_tag(218, |w| w.write_message(s))?; }
+        if let Some(ref s) = self.ad { w.write_with_tag(226, |w| w.write_message(s))?; }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct TrackRef {
+    pub gid: Option<Vec<u8>>,
+    pub uri: Option<String>,
+    pub queued: Option<bool>,
+    pub context: Option<String>,
+}
+
+impl<'a> MessageRead<'a> for TrackRef {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.gid = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(18) => msg.uri = Some(r.read_string(bytes)?.to_owned()),
+                Ok(24) => msg.queued = Some(r.read_bool(bytes)?),
+                Ok(34) => msg.context = Some(r.read_string(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for TrackRef {
+    fn get_size(&self) -> usize {
+        0
+        + self.gid.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.uri.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.queued.as_ref().map_or(0, |m| 1 + sizeof_varint(*(m) as u64))
+        + self.context.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.gid { w.write_with_tag(10, |w| w.write_bytes(&**s))?; }
+        if let Some(ref s) = self.uri { w.write_with_tag(18, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.queued { w.write_with_tag(24, |w| w.write_bool(*s))?; }
+        if let Some(ref s) = self.context { w.write_with_tag(34, |w| w.write_string(&**s))?; }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Ad {
+    pub next: Option<i32>,
+    pub ogg_fid: Option<Vec<u8>>,
+    pub image_fid: Option<Vec<u8>>,
+    pub duration: Option<i32>,
+    pub click_url: Option<String>,
+    pub impression_url: Option<String>,
+    pub product: Option<String>,
+    pub advertiser: Option<String>,
+    pub gid: Option<Vec<u8>>,
+}
+
+impl<'a> MessageRead<'a> for Ad {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(8) => msg.next = Some(r.read_int32(bytes)?),
+                Ok(18) => msg.ogg_fid = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(26) => msg.image_fid = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(32) => msg.duration = Some(r.read_int32(bytes)?),
+                Ok(42) => msg.click_url = Some(r.read_string(bytes)?.to_owned()),
+                Ok(50) => msg.impression_url = Some(r.read_string(bytes)?.to_owned()),
+                Ok(58) => msg.product = Some(r.read_string(bytes)?.to_owned()),
+                Ok(66) => msg.advertiser = Some(r.read_string(bytes)?.to_owned()),
+                Ok(74) => msg.gid = Some(r.read_bytes(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for Ad {
+    fn get_size(&self) -> usize {
+        0
+        + self.next.as_ref().map_or(0, |m| 1 + sizeof_int32(*(m)))
+        + self.ogg_fid.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.image_fid.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.duration.as_ref().map_or(0, |m| 1 + sizeof_int32(*(m)))
+        + self.click_url.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.impression_url.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.product.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.advertiser.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.gid.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.next { w.write_with_tag(8, |w| w.write_int32(*s))?; }
+        if let Some(ref s) = self.ogg_fid { w.write_with_tag(18, |w| w.write_bytes(&**s))?; }
+        if let Some(ref s) = self.image_fid { w.write_with_tag(26, |w| w.write_bytes(&**s))?; }
+        if let Some(ref s) = self.duration { w.write_with_tag(32, |w| w.write_int32(*s))?; }
+        if let Some(ref s) = self.click_url { w.write_with_tag(42, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.impression_url { w.write_with_tag(50, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.product { w.write_with_tag(58, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.advertiser { w.write_with_tag(66, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.gid { w.write_with_tag(74, |w| w.write_bytes(&**s))?; }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct Metadata {
+    pub type_pb: Option<String>,
+    pub metadata: Option<String>,
+}
+
+impl<'a> MessageRead<'a> for Metadata {
+    fn from_reader(r: &mut BytesReader, bytes: &'a [u8]) -> Result<Self> {
+        let mut msg = Self::default();
+        while !r.is_eof() {
+            match r.next_tag(bytes) {
+                Ok(10) => msg.type_pb = Some(r.read_string(bytes)?.to_owned()),
+                Ok(18) => msg.metadata = Some(r.read_string(bytes)?.to_owned()),
+                Ok(t) => { r.read_unknown(bytes, t)?; }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(msg)
+    }
+}
+
+impl MessageWrite for Metadata {
+    fn get_size(&self) -> usize {
+        0
+        + self.type_pb.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+        + self.metadata.as_ref().map_or(0, |m| 1 + sizeof_len((m).len()))
+    }
+
+    fn write_message<W: WriterBackend>(&self, w: &mut Writer<W>) -> Result<()> {
+        if let Some(ref s) = self.type_pb { w.write_with_tag(10, |w| w.write_string(&**s))?; }
+        if let Some(ref s) = self.metadata { w.write_with_tag(18, |w| w.write_string(&**s))?; }
+        Ok(())
+    }
+}
+